@@ -0,0 +1,89 @@
+//! Times parsing a large `conversations.history` response, with and without
+//! copying every string out of the input. Run with `cargo bench`.
+
+use slk::json;
+use slk::response::{self, ConversationsHistoryResponse, FromResponse};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Roughly a 5 MB page of messages, shaped like what Slack sends: rich-text
+/// blocks, reactions, thread metadata, and some text that needs escapes.
+fn history_payload(messages: usize) -> String {
+    let mut body = String::from(r#"{"ok":true,"messages":["#);
+    for i in 0..messages {
+        if i > 0 {
+            body.push(',');
+        }
+        let ts = format!("17706{:05}.{:06}", i, i * 7 % 1_000_000);
+        let text = format!(
+            "Deploy #{} finished in {}s :rocket: see <https://ci.example.com/b/{}|build>",
+            i,
+            i % 60,
+            i
+        );
+        body.push_str(&format!(
+            concat!(
+                r#"{{"client_msg_id":"8c3f{i:08x}-2b1d-4c3e-9a5f-0d1e2f3a4b5c","type":"message","#,
+                r#""text":"{text}","user":"U0{u:07}","ts":"{ts}","team":"T012AB3C4","#,
+                r#""blocks":[{{"type":"rich_text","block_id":"b{i}","elements":[{{"type":"rich_text_section","#,
+                r#""elements":[{{"type":"text","text":"{text}"}},"#,
+                r#"{{"type":"emoji","name":"rocket","unicode":"1f680"}}]}}]}}],"#,
+                r#""reactions":[{{"name":"+1","users":["U0{u:07}","U0{v:07}"],"count":2}}],"#,
+                r#""thread_ts":"{ts}","reply_count":{r},"reply_users_count":2,"latest_reply":"{ts}","#,
+                r#""reply_users":["U0{u:07}","U0{v:07}"],"is_locked":false,"subscribed":false,"#,
+                r#""edited":{{"user":"U0{u:07}","ts":"{ts}"}},"#,
+                r#""attachments":[{{"fallback":"✅ \"passed\" on main\nall checks green","#,
+                r#""color":"36a64f","id":1}}]}}"#,
+            ),
+            i = i,
+            text = text,
+            u = i % 50,
+            v = (i + 1) % 50,
+            ts = ts,
+            r = i % 5,
+        ));
+    }
+    body.push_str(r#"],"has_more":true,"#);
+    body.push_str(r#""response_metadata":{"next_cursor":"bmV4dF90czoxNzcwNjg5ODg3NTY1MjQ5"}}"#);
+    body
+}
+
+/// The median time of `runs` calls to `f`.
+fn time(runs: usize, mut f: impl FnMut()) -> Duration {
+    let mut times: Vec<Duration> = (0..runs)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    times[runs / 2]
+}
+
+const MESSAGES: usize = 5_600;
+const RUNS: usize = 21;
+
+fn main() {
+    let payload = history_payload(MESSAGES);
+    let mb = payload.len() as f64 / 1e6;
+    println!("conversations.history payload: {:.1} MB, {} messages", mb, MESSAGES);
+    let report = |name: &str, median: Duration| {
+        let secs = median.as_secs_f64();
+        println!("{:<28} {:>8.2} ms  {:>7.1} MB/s", name, secs * 1e3, mb / secs);
+    };
+    report("parse (owned strings)", time(RUNS, || drop(black_box(json::parse(&payload).unwrap()))));
+    report("parse_borrowed", time(RUNS, || drop(black_box(json::parse_borrowed(&payload).unwrap()))));
+    // The typed response, read from an owned tree and from a borrowed one.
+    report(
+        "history from owned",
+        time(RUNS, || {
+            let value = json::parse(&payload).unwrap();
+            drop(black_box(ConversationsHistoryResponse::from_response(&value).unwrap()));
+        }),
+    );
+    report(
+        "history from borrowed",
+        time(RUNS, || drop(black_box(response::parse::<ConversationsHistoryResponse>(&payload).unwrap()))),
+    );
+}
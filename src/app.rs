@@ -0,0 +1,5332 @@
+//! The `slk` command line.
+
+use std::collections::HashMap;
+
+use crate::config::{self, ColorMode, OutputFormat, TokenKind};
+use crate::error::{ErrorKind, SlkError};
+use crate::http::{self, Transport};
+use crate::json::{self, JsonValue};
+use crate::response::{
+    self, ChatGetPermalinkResponse, ChatPostMessageResponse, ConversationsHistoryResponse,
+    ConversationsInfoResponse, ConversationsListResponse, ConversationsRepliesResponse, UserInfoResponse,
+    UserLookupResponse,
+};
+use crate::slack_api::SlackClient;
+use crate::{
+    archive, cache, cli, clipboard, completions, compose, debug, hook, info, interrupt, launch, log, message, notify, oauth,
+    query, report, resolve, rtm, search, slack_api, socket_mode, stats, task, trace, url, warn, websocket, wrap,
+};
+
+enum Command {
+    /// `token` is `Some` for `login --token`; an empty string means "read it from stdin".
+    Login { token: Option<String>, bot: bool, no_browser: bool },
+    Logout,
+    ListAliases,
+    Config(ConfigAction),
+    Completions(completions::Shell),
+    /// `__complete channels [<prefix>]`, for the completion scripts.
+    CompleteChannels { prefix: String },
+    ListConversations,
+    /// `channel_id` is an ID or a channel name; `None` when omitted, to fall
+    /// back to `default_channel`.
+    ShowHistory { channel_id: Option<String> },
+    /// Like `ShowHistory`, then keeps polling for new messages.
+    Watch { channel_id: Option<String> },
+    /// The latest messages in each of the user's channels.
+    Recent,
+    /// Reactions and replies to the user's own messages.
+    Activity,
+    /// Copies a channel's messages into the local archive; `channel_id` as
+    /// for `ShowHistory`.
+    Sync { channel_id: Option<String> },
+    /// Looks for messages in the local archive (`--local`); `query` is the
+    /// words to look for, possibly empty when filters are given.
+    Search { query: String },
+    /// Writes a channel's whole history to a JSON file; `channel_id` as for
+    /// `ShowHistory`.
+    Export { channel_id: Option<String> },
+    /// Shows how busy a channel was; `channel_id` as for `ShowHistory`.
+    Stats { channel_id: Option<String> },
+    /// Formats a saved export or API response from `path`.
+    Render { path: String },
+    /// `channel_id` is an ID or a channel name. `host` is the workspace host
+    /// when the thread was given as a URL.
+    ShowThread { channel_id: String, ts: String, host: Option<String> },
+    /// One message, the one at `ts`. `thread_ts` is the thread it is a reply
+    /// in, when it was given as a reply's link; `host` as for `ShowThread`.
+    ShowMessage { channel_id: String, ts: String, thread_ts: Option<String>, host: Option<String> },
+    /// `open --app`: opens a channel (an ID or name), or the message at `ts`
+    /// in it, in the Slack app. `link` is the URL it was given as, if any.
+    OpenApp { channel_id: String, ts: Option<String>, link: Option<String> },
+    /// Shows the profile of `user`, given in any form `--from` takes.
+    ShowUser { user: String },
+    /// Prints the link to the message at `ts`; `channel_id` as for
+    /// `ShowThread`.
+    Permalink { channel_id: String, ts: String },
+    /// `text` is the message, or `-` to read it from stdin.
+    Send { channel_id: String, text: String },
+    /// Like `Send`, in the thread at `ts`; `host` as for `ShowThread`.
+    Reply { channel_id: String, ts: String, host: Option<String>, text: String },
+    /// Posts `text` (or stdin, when `-`) to the incoming webhook at `url`; needs no token.
+    Webhook { url: String, text: String },
+    /// Calls the Web API `method` with `params` and prints the response.
+    Api { method: String, params: Vec<(String, String)> },
+    Help(String),
+    Version,
+}
+
+enum ConfigAction {
+    Get { key: String },
+    Set { key: String, value: String },
+    List,
+}
+
+/// Parses the command line into the command to run, keeping the flags for
+/// the global options.
+fn parse_args(args: Vec<String>) -> Result<(Command, cli::Matches), SlkError> {
+    let matches = cli::parse(&args)?;
+    if matches.flag("help") {
+        return Ok((Command::Help(cli::help(matches.command)), matches));
+    }
+    if matches.flag("version") {
+        return Ok((Command::Version, matches));
+    }
+    let spec = matches.command.ok_or_else(|| SlkError::from(cli::usage(None)))?;
+    let usage = || SlkError::from(cli::usage(Some(spec)));
+    let mut positionals = matches.positionals.iter().cloned();
+    let mut next = || positionals.next();
+    let command = match spec.name {
+        "login" => Command::Login {
+            // A bare `--token` means "read it from stdin".
+            token: matches
+                .flag("token")
+                .then(|| matches.value("token").unwrap_or_default().to_string()),
+            bot: matches.flag("bot"),
+            no_browser: matches.flag("no-browser"),
+        },
+        "logout" => Command::Logout,
+        "alias" => Command::ListAliases,
+        "config" => {
+            let action = match (next().as_deref(), next(), next()) {
+                (Some("get"), Some(key), None) => ConfigAction::Get { key },
+                (Some("set"), Some(key), Some(value)) => ConfigAction::Set { key, value },
+                (Some("list"), None, None) => ConfigAction::List,
+                _ => return Err(usage()),
+            };
+            Command::Config(action)
+        }
+        "completions" => {
+            let shell = next().ok_or_else(usage)?;
+            Command::Completions(completions::Shell::parse(&shell)?)
+        }
+        "__complete" => match next().as_deref() {
+            Some("channels") => Command::CompleteChannels { prefix: next().unwrap_or_default() },
+            _ => return Err(usage()),
+        },
+        "list" => Command::ListConversations,
+        "history" => Command::ShowHistory { channel_id: next().map(channel_arg).transpose()? },
+        "watch" => Command::Watch { channel_id: next().map(channel_arg).transpose()? },
+        "recent" => Command::Recent,
+        "activity" => Command::Activity,
+        "sync" => Command::Sync { channel_id: next().map(channel_arg).transpose()? },
+        "export" => Command::Export { channel_id: next().map(channel_arg).transpose()? },
+        "stats" => Command::Stats { channel_id: next().map(channel_arg).transpose()? },
+        "render" => Command::Render { path: next().ok_or_else(usage)? },
+        "search" => Command::Search { query: std::iter::from_fn(&mut next).collect::<Vec<_>>().join(" ") },
+        "thread" => {
+            let first = next().ok_or_else(usage)?;
+            if url::is_link(&first) {
+                let thread = url::parse_slack_url(&first)?;
+                Command::ShowThread {
+                    channel_id: thread.channel_id,
+                    ts: thread.ts,
+                    host: url::workspace_host(&first).map(String::from),
+                }
+            } else {
+                let ts = next().ok_or_else(usage)?;
+                Command::ShowThread { channel_id: first, ts, host: None }
+            }
+        }
+        "open" if matches.flag("app") => {
+            let target = next().ok_or_else(usage)?;
+            if !url::is_link(&target) {
+                Command::OpenApp { channel_id: target, ts: None, link: None }
+            } else {
+                let (channel_id, ts) = match url::parse_link(&target)? {
+                    url::SlackLink::Channel { channel_id } => (channel_id, None),
+                    url::SlackLink::Message { channel_id, ts, .. } => (channel_id, Some(ts)),
+                };
+                Command::OpenApp { channel_id, ts, link: Some(target) }
+            }
+        }
+        "open" => {
+            let link = next().ok_or_else(usage)?;
+            match url::parse_link(&link)? {
+                url::SlackLink::Channel { channel_id } => {
+                    Command::ShowHistory { channel_id: Some(channel_id) }
+                }
+                // A reply opens the thread it belongs to.
+                url::SlackLink::Message { channel_id, ts, thread_ts } => Command::ShowThread {
+                    channel_id,
+                    ts: thread_ts.unwrap_or(ts),
+                    host: url::workspace_host(&link).map(String::from),
+                },
+            }
+        }
+        "msg" => {
+            let first = next().ok_or_else(usage)?;
+            if !url::is_link(&first) {
+                let ts = next().ok_or_else(usage)?;
+                Command::ShowMessage { channel_id: first, ts, thread_ts: None, host: None }
+            } else {
+                match url::parse_link(&first)? {
+                    url::SlackLink::Message { channel_id, ts, thread_ts } => Command::ShowMessage {
+                        channel_id,
+                        ts,
+                        thread_ts,
+                        host: url::workspace_host(&first).map(String::from),
+                    },
+                    url::SlackLink::Channel { .. } => {
+                        let hint = "use `slk history` for it";
+                        return Err(SlkError::from(format!("'{}' is a channel link; {}", first, hint)));
+                    }
+                }
+            }
+        }
+        "user" => Command::ShowUser { user: next().ok_or_else(usage)? },
+        "permalink" => {
+            let channel_id = next().ok_or_else(usage)?;
+            Command::Permalink { channel_id, ts: next().ok_or_else(usage)? }
+        }
+        "send" => {
+            let channel_id = next().ok_or_else(usage)?;
+            let text = std::iter::from_fn(&mut next).collect::<Vec<_>>().join(" ");
+            // With --blocks the text is optional, as the notification's.
+            if text.is_empty() && matches.value("blocks").is_none() {
+                return Err(usage());
+            }
+            Command::Send { channel_id, text }
+        }
+        "reply" => {
+            let first = next().ok_or_else(usage)?;
+            let (channel_id, ts, host) = if url::is_link(&first) {
+                let thread = url::parse_slack_url(&first)?;
+                (thread.channel_id, thread.ts, url::workspace_host(&first).map(String::from))
+            } else {
+                (first, next().ok_or_else(usage)?, None)
+            };
+            let text = std::iter::from_fn(&mut next).collect::<Vec<_>>().join(" ");
+            if text.is_empty() {
+                return Err(usage());
+            }
+            Command::Reply { channel_id, ts, host, text }
+        }
+        "webhook" => {
+            let url = next().ok_or_else(usage)?;
+            let text = std::iter::from_fn(&mut next).collect::<Vec<_>>().join(" ");
+            if text.is_empty() {
+                return Err(usage());
+            }
+            Command::Webhook { url, text }
+        }
+        "api" => {
+            let method = next().ok_or_else(usage)?;
+            if !method.contains('.') || !method.chars().all(|c| c.is_ascii_alphanumeric() || c == '.') {
+                let hint = "such as conversations.info";
+                return Err(SlkError::from(format!("'{}' is not a Web API method, {}", method, hint)));
+            }
+            let params = std::iter::from_fn(&mut next)
+                .map(|param| match param.split_once('=') {
+                    Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+                    _ => Err(SlkError::from(format!("expected key=value, got '{}'", param))),
+                })
+                .collect::<Result<_, _>>()?;
+            Command::Api { method, params }
+        }
+        _ => unreachable!("every command in cli::COMMANDS is handled"),
+    };
+    if let Some(extra) = next() {
+        return Err(SlkError::from(format!("unexpected argument '{}'\n{}", extra, cli::usage(Some(spec)))));
+    }
+    Ok((command, matches))
+}
+
+/// A channel argument as given, or the channel ID when it is a channel link
+/// (`https://myteam.slack.com/archives/C081VT5GLQH`) pasted from the browser.
+fn channel_arg(arg: String) -> Result<String, SlkError> {
+    if !url::is_link(&arg) {
+        return Ok(arg);
+    }
+    match url::parse_link(&arg)? {
+        url::SlackLink::Channel { channel_id } => Ok(channel_id),
+        url::SlackLink::Message { .. } => {
+            Err(SlkError::from(format!("'{}' is a message link; use `slk open` or `slk thread` for it", arg)))
+        }
+    }
+}
+
+fn resolve_token(
+    transport: &dyn Transport,
+    base_url: &str,
+    kind: TokenKind,
+) -> Result<String, SlkError> {
+    if kind == TokenKind::Bot {
+        if let Ok(token) = std::env::var("SLACK_BOT_TOKEN")
+            && !token.is_empty()
+        {
+            return Ok(token);
+        }
+        return config::load_credentials()?
+            .and_then(|c| c.bot_token)
+            .ok_or(SlkError::new(
+                ErrorKind::Auth,
+                "no bot token found. Set SLACK_BOT_TOKEN or run: slk login --bot",
+            ));
+    }
+
+    if let Ok(token) = std::env::var("SLACK_TOKEN")
+        && !token.is_empty()
+    {
+        return Ok(token);
+    }
+    if let Some(cmd) = config::load_token_cmd()? {
+        return run_token_cmd(&cmd);
+    }
+    if let Some(creds) = config::load_credentials()?
+        && !creds.token.is_empty()
+    {
+        return match &creds.refresh_token {
+            Some(refresh_token) if creds.is_expired(config::now_unix()) => {
+                let (client_id, client_secret) = config::load_client_credentials()?;
+                let refreshed = oauth::refresh_credentials(
+                    transport,
+                    base_url,
+                    &client_id,
+                    &client_secret,
+                    refresh_token,
+                )?;
+                config::save_credentials(&config::Credentials {
+                    bot_token: creds.bot_token.clone(),
+                    identity: creds.identity.clone(),
+                    ..refreshed.clone()
+                })?;
+                Ok(refreshed.token)
+            }
+            _ => Ok(creds.token),
+        };
+    }
+    Err(SlkError::new(
+        ErrorKind::Auth,
+        "no Slack token found. Set SLACK_TOKEN or run: slk login",
+    ))
+}
+
+/// Runs `token_cmd` through the shell and returns the first line it prints.
+/// The command's stderr (e.g. a password prompt) goes straight to the terminal.
+fn run_token_cmd(cmd: &str) -> Result<String, SlkError> {
+    let shell = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let output = std::process::Command::new(shell.0)
+        .arg(shell.1)
+        .arg(cmd)
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .map_err(|e| SlkError::from(format!("failed to run token_cmd '{}': {}", cmd, e)))?;
+    if !output.status.success() {
+        return Err(SlkError::from(format!(
+            "token_cmd '{}' failed ({})",
+            cmd, output.status
+        )));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let token = stdout.lines().next().unwrap_or("").trim();
+    if token.is_empty() {
+        return Err(SlkError::from(format!("token_cmd '{}' printed no token", cmd)));
+    }
+    Ok(token.to_string())
+}
+
+/// Number of messages requested from `conversations.history` without `--limit`.
+const DEFAULT_HISTORY_LIMIT: usize = 200;
+
+/// How results are rendered: config file defaults overridden by CLI flags.
+struct OutputOptions {
+    format: OutputFormat,
+    time_format: String,
+    color: bool,
+    limit: Option<usize>,
+    /// Only show messages by this user ID (`--from`, once resolved).
+    from: Option<String>,
+    /// Only show messages whose text matches (`slk watch --grep`).
+    grep: Option<regex_lite::Regex>,
+    /// Only show messages mentioning this user ID (`slk watch --mention-only`).
+    mentioning: Option<String>,
+    /// Also show reactions, joins, typing and presence (`slk watch --events`).
+    events: bool,
+    /// Raise desktop notifications for new messages (`slk watch --notify`).
+    notify: Option<notify::Notifier>,
+    /// Run a command for each new message (`slk watch --exec`).
+    exec: Option<hook::Hook>,
+    /// Download the shown messages' files (`--save-files`).
+    save_files: Option<SaveFiles>,
+    /// How to show channels in `slk watch`, by ID (`#general`). Watching more
+    /// than one prefixes each message with its channel's name.
+    channel_names: HashMap<String, String>,
+    /// Print only these fields of the JSON output (`--query`).
+    query: Option<query::Query>,
+    /// Follow each message with its link (`--links`).
+    links: Option<MessageLinks>,
+    /// Leave out joins, leaves, topic changes and the like (`--no-system`).
+    no_system: bool,
+    /// The terminal's width to wrap text at, when printing text to one.
+    width: Option<usize>,
+    /// Leave out the time and name of messages that follow one by the same
+    /// person (`--compact`).
+    compact: bool,
+    /// Print how many messages there are instead of the messages (`--count`).
+    count: bool,
+}
+
+/// Where `--links` points: messages in one channel on the workspace at
+/// `workspace_url`.
+struct MessageLinks {
+    workspace_url: String,
+    channel_id: String,
+}
+
+impl MessageLinks {
+    /// Links to the messages of `channel_id` on the token's workspace, which
+    /// `auth.test` names.
+    fn new(api: &SlackClient, channel_id: &str) -> Result<Self, SlkError> {
+        Ok(MessageLinks { workspace_url: verify_token(api)?.url, channel_id: channel_id.to_string() })
+    }
+
+    /// The link to `m`, the way Slack's "Copy link" gives it: a reply's link
+    /// also names its thread.
+    fn link(&self, m: &message::SlackMessage) -> String {
+        let link = url::archive_link(&self.workspace_url, &self.channel_id, Some(&m.ts));
+        match &m.thread_ts {
+            Some(thread_ts) if *thread_ts != m.ts => {
+                format!("{}?thread_ts={}&cid={}", link, thread_ts, self.channel_id)
+            }
+            _ => link,
+        }
+    }
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        OutputOptions {
+            format: OutputFormat::Text,
+            time_format: message::DEFAULT_TIME_FORMAT.to_string(),
+            color: false,
+            limit: None,
+            from: None,
+            grep: None,
+            mentioning: None,
+            events: false,
+            notify: None,
+            exec: None,
+            save_files: None,
+            channel_names: HashMap::new(),
+            query: None,
+            links: None,
+            no_system: false,
+            width: None,
+            compact: false,
+            count: false,
+        }
+    }
+}
+
+impl OutputOptions {
+    /// Reads `--format`, `--time-format`, `--color` and `--limit`, falling back
+    /// to `defaults` for any that are absent.
+    fn from_matches(matches: &cli::Matches, defaults: &config::Defaults) -> Result<Self, SlkError> {
+        use std::io::IsTerminal;
+
+        let format = match matches.value("format") {
+            Some(f) => OutputFormat::parse(f)?,
+            None => defaults.format.unwrap_or(OutputFormat::Text),
+        };
+        let time_format = matches
+            .value("time-format")
+            .map(String::from)
+            .or_else(|| defaults.time_format.clone())
+            .unwrap_or_else(|| message::DEFAULT_TIME_FORMAT.to_string());
+        let color = match matches.value("color") {
+            Some(c) => ColorMode::parse(c)?,
+            None => defaults.color.unwrap_or(ColorMode::Auto),
+        };
+        let color = match color {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+        };
+        let limit = match matches.value("limit") {
+            Some(n) => match n.parse::<usize>() {
+                Ok(n) if n > 0 => Some(n),
+                _ => return Err(SlkError::from(format!("invalid --limit '{}': expected a positive number", n))),
+            },
+            None => defaults.limit,
+        };
+        let query = matches.value("query").map(query::Query::parse).transpose()?;
+        let no_system = matches.flag("no-system");
+        let compact = matches.flag("compact");
+        let count = matches.flag("count");
+        let width = if format == OutputFormat::Text { wrap::terminal_width() } else { None };
+        Ok(OutputOptions {
+            format,
+            time_format,
+            color,
+            limit,
+            query,
+            no_system,
+            width,
+            compact,
+            count,
+            ..OutputOptions::default()
+        })
+    }
+
+    /// Whether `m` passes the `--from`, `--grep`, `--mention-only` and
+    /// `--no-system` filters.
+    fn shows(&self, m: &message::SlackMessage) -> bool {
+        !(self.no_system && m.is_system())
+            && self.from.as_ref().is_none_or(|from| m.user == *from)
+            && self.grep.as_ref().is_none_or(|re| re.is_match(&m.text))
+            && self.mentioning.as_ref().is_none_or(|user_id| notify::mentions(&m.text, user_id))
+    }
+
+    fn channel_name<'a>(&'a self, channel_id: &'a str) -> &'a str {
+        self.channel_names.get(channel_id).map_or(channel_id, String::as_str)
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+fn format_messages(
+    messages: &[message::SlackMessage],
+    user_names: &HashMap<String, String>,
+    options: &OutputOptions,
+) -> String {
+    let messages: Vec<&message::SlackMessage> = messages
+        .iter()
+        .filter(|m| options.shows(m))
+        .take(options.limit.unwrap_or(usize::MAX))
+        .collect();
+    if options.format == OutputFormat::Json {
+        let items = messages.iter().map(|m| message_json(m, user_names, options)).collect();
+        return JsonValue::Array(items).to_string();
+    }
+    messages
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            let previous = i.checked_sub(1).map(|i| messages[i]);
+            message_line_at(m, previous, user_names, options, 0)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `slk history --threads`: `format_messages` with the replies to each shown
+/// thread (in `replies`, by the parent's ts) indented under it, or in a
+/// `replies` array with `--format json`.
+fn format_threads(
+    messages: &[message::SlackMessage],
+    replies: &HashMap<String, Vec<message::SlackMessage>>,
+    user_names: &HashMap<String, String>,
+    options: &OutputOptions,
+) -> String {
+    let messages = messages.iter().filter(|m| options.shows(m)).take(options.limit.unwrap_or(usize::MAX));
+    let no_replies = Vec::new();
+    if options.format == OutputFormat::Json {
+        let items = messages
+            .map(|m| {
+                let mut item = message_json(m, user_names, options);
+                if let Some(replies) = replies.get(&m.ts) {
+                    let replies: Vec<JsonValue> =
+                        replies.iter().map(|r| message_json(r, user_names, options)).collect();
+                    item.set("replies", replies);
+                }
+                item
+            })
+            .collect();
+        return JsonValue::Array(items).to_string();
+    }
+    let mut lines = Vec::new();
+    let mut previous = None;
+    for m in messages {
+        lines.push(message_line_at(m, previous, user_names, options, 0));
+        let replies = replies.get(&m.ts).unwrap_or(&no_replies);
+        let mut previous_reply = None;
+        for r in replies {
+            lines.push(format!("  {}", message_line_at(r, previous_reply, user_names, options, 2)));
+            previous_reply = Some(r);
+        }
+        // Replies shown in between end a --compact group.
+        previous = if replies.is_empty() { Some(m) } else { None };
+    }
+    lines.join("\n")
+}
+
+fn message_json(
+    m: &message::SlackMessage,
+    user_names: &HashMap<String, String>,
+    options: &OutputOptions,
+) -> JsonValue {
+    let mut fields = vec![
+        ("ts".to_string(), JsonValue::String(m.ts.clone())),
+        (
+            "time".to_string(),
+            JsonValue::String(message::format_unix_ts_with(&m.ts, &options.time_format)),
+        ),
+        ("user".to_string(), JsonValue::String(m.user.clone())),
+    ];
+    if let Some(name) = user_names.get(&m.user) {
+        fields.push(("user_name".to_string(), JsonValue::String(name.clone())));
+    }
+    fields.push(("text".to_string(), JsonValue::String(m.text.clone())));
+    if let Some(subtype) = &m.subtype {
+        fields.push(("subtype".to_string(), JsonValue::String(subtype.clone())));
+    }
+    if let Some(thread_ts) = &m.thread_ts {
+        fields.push(("thread_ts".to_string(), JsonValue::String(thread_ts.clone())));
+    }
+    if m.is_broadcast() {
+        fields.push(("broadcast".to_string(), true.into()));
+    }
+    if m.has_replies() {
+        fields.push(("reply_count".to_string(), m.reply_count.into()));
+        fields.push(("latest_reply".to_string(), m.latest_reply.clone().into()));
+    }
+    if let Some(links) = &options.links {
+        fields.push(("permalink".to_string(), JsonValue::String(links.link(m))));
+    }
+    JsonValue::Object(fields)
+}
+
+fn message_line(
+    m: &message::SlackMessage,
+    user_names: &HashMap<String, String>,
+    options: &OutputOptions,
+) -> String {
+    message_line_at(m, None, user_names, options, 0)
+}
+
+/// How far apart (in seconds) messages by the same person can be and still
+/// be grouped by `--compact`, as Slack's client groups them.
+const COMPACT_WINDOW_SECS: f64 = 5.0 * 60.0;
+
+/// Whether `--compact` shows `m` without its time and name, under `previous`:
+/// both are by the same person, minutes apart, and neither is a system message.
+fn continues(
+    previous: Option<&message::SlackMessage>,
+    m: &message::SlackMessage,
+    options: &OutputOptions,
+) -> bool {
+    let Some(previous) = previous.filter(|_| options.compact) else {
+        return false;
+    };
+    let secs = |m: &message::SlackMessage| m.ts.parse::<f64>().unwrap_or(0.0);
+    previous.user == m.user
+        && !previous.is_system()
+        && !m.is_system()
+        && (secs(m) - secs(previous)).abs() < COMPACT_WINDOW_SECS
+}
+
+/// `message_line` for a line the caller starts at `column`, after an indent
+/// or a channel name: wrapped text lines up under the first line's text.
+/// `previous` is the message shown just above, for `--compact`.
+fn message_line_at(
+    m: &message::SlackMessage,
+    previous: Option<&message::SlackMessage>,
+    user_names: &HashMap<String, String>,
+    options: &OutputOptions,
+    column: usize,
+) -> String {
+    let display = match user_names.get(&m.user) {
+        Some(name) => format!("@{}", name),
+        None => m.user.clone(),
+    };
+    let time = message::format_unix_ts_with(&m.ts, &options.time_format);
+    let wrapped = |text: &str| match options.width {
+        Some(width) => {
+            let indent = column + wrap::display_width(&time) + wrap::display_width(&display) + 2;
+            wrap::wrap(text, indent, width)
+        }
+        None => text.to_string(),
+    };
+    let line = match m.subtype.as_deref() {
+        // Dimmed as a whole, and named by its subtype when it has no text.
+        Some(subtype) if m.is_system() => {
+            let text = match m.text.as_str() {
+                "" => format!("({})", subtype.replace('_', " ")),
+                text => wrapped(text),
+            };
+            options.paint("2", &format!("{} {} {}", time, display, text))
+        }
+        _ if continues(previous, m, options) => {
+            let width = wrap::display_width(&time) + wrap::display_width(&display) + 1;
+            format!("{:width$} {}", "", wrapped(&m.text), width = width)
+        }
+        _ => {
+            let text = wrapped(&m.text);
+            format!("{} {} {}", options.paint("2", &time), options.paint("1;36", &display), text)
+        }
+    };
+    let notes = [
+        reply_marker(m, config::now_unix()),
+        broadcast_note(m),
+        options.links.as_ref().map(|links| links.link(m)),
+    ];
+    std::iter::once(line)
+        .chain(notes.into_iter().flatten().map(|note| options.paint("2", &note)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The `↳ 12 replies, last 2 hours ago` note on a thread's parent, as of
+/// `now` (Unix seconds).
+fn reply_marker(m: &message::SlackMessage, now: u64) -> Option<String> {
+    if !m.has_replies() {
+        return None;
+    }
+    let replies = format!("↳ {} {}", m.reply_count, if m.reply_count == 1 { "reply" } else { "replies" });
+    let latest = m.latest_reply.as_deref().and_then(|ts| ts.split('.').next()?.parse::<u64>().ok());
+    Some(match latest {
+        Some(latest) => format!("{}, last {} ago", replies, describe_age(now.saturating_sub(latest))),
+        None => replies,
+    })
+}
+
+/// How much of a thread's parent `broadcast_note` quotes.
+const BROADCAST_ROOT_CHARS: usize = 40;
+
+/// The `↳ replied to thread 1770689887.565249: Deploy is failing` note on a
+/// reply also sent to the channel, naming the thread it belongs to.
+fn broadcast_note(m: &message::SlackMessage) -> Option<String> {
+    let thread_ts = m.thread_ts.as_deref().filter(|_| m.is_broadcast())?;
+    Some(match m.root_text.as_deref().map(|text| notify::snippet(text, BROADCAST_ROOT_CHARS)) {
+        Some(root) if !root.is_empty() => format!("↳ replied to thread {}: {}", thread_ts, root),
+        _ => format!("↳ replied to thread {}", thread_ts),
+    })
+}
+
+/// Messages for `slk watch`, each with the ID of its channel, one per line as
+/// they arrive. JSON output is one object per line rather than an array.
+/// When several channels are watched, lines start with the channel's name.
+fn format_message_stream(
+    messages: &[(&str, message::SlackMessage)],
+    user_names: &HashMap<String, String>,
+    options: &OutputOptions,
+) -> Vec<String> {
+    let width = options.channel_names.values().map(|n| n.chars().count()).max().unwrap_or(0);
+    messages
+        .iter()
+        .filter(|(_, m)| options.shows(m))
+        .map(|(channel, m)| match options.format {
+            OutputFormat::Json => stream_json(channel, m, user_names, options).to_string(),
+            _ if options.channel_names.len() > 1 => {
+                let name = format!("{:width$}", options.channel_name(channel), width = width);
+                let line = message_line_at(m, None, user_names, options, width + 1);
+                format!("{} {}", options.paint("35", &name), line)
+            }
+            _ => message_line(m, user_names, options),
+        })
+        .collect()
+}
+
+/// A line for `slk watch --events` about what someone did at `ts`, dimmed
+/// to set it apart from messages.
+fn format_activity(
+    activity: &socket_mode::Activity,
+    ts: &str,
+    user_names: &HashMap<String, String>,
+    options: &OutputOptions,
+) -> String {
+    let time = message::format_unix_ts_with(ts, &options.time_format);
+    let user_name = user_names.get(&activity.user);
+    if options.format == OutputFormat::Json {
+        let mut fields = vec![
+            ("channel".to_string(), activity.channel.clone().map_or(JsonValue::Null, JsonValue::String)),
+            ("event".to_string(), JsonValue::String(activity.kind.clone())),
+            ("time".to_string(), JsonValue::String(time)),
+            ("user".to_string(), JsonValue::String(activity.user.clone())),
+        ];
+        if let Some(name) = user_name {
+            fields.push(("user_name".to_string(), JsonValue::String(name.clone())));
+        }
+        fields.push(("action".to_string(), JsonValue::String(activity.action.clone())));
+        return JsonValue::Object(fields).to_string();
+    }
+    let who = user_name.map_or(activity.user.clone(), |name| format!("@{}", name));
+    let line = format!("{} {} {}", time, who, activity.action);
+    if options.channel_names.len() > 1 {
+        let width = options.channel_names.values().map(|n| n.chars().count()).max().unwrap_or(0);
+        let channel = activity.channel.as_deref().map_or("", |c| options.channel_name(c));
+        return options.paint("2", &format!("{:width$} {}", channel, line, width = width));
+    }
+    options.paint("2", &line)
+}
+
+/// A streamed message as JSON: `message_json` with the channel ID first.
+fn stream_json(
+    channel: &str,
+    m: &message::SlackMessage,
+    user_names: &HashMap<String, String>,
+    options: &OutputOptions,
+) -> JsonValue {
+    let JsonValue::Object(mut fields) = message_json(m, user_names, options) else {
+        unreachable!("message_json returns an object")
+    };
+    fields.insert(0, ("channel".to_string(), JsonValue::String(channel.to_string())));
+    JsonValue::Object(fields)
+}
+
+/// Looks up the names of the messages' authors that aren't in `names` yet and
+/// adds them to it.
+fn resolve_user_names(
+    api: &SlackClient,
+    messages: &[message::SlackMessage],
+    names: &mut HashMap<String, String>,
+) -> Result<(), SlkError> {
+    resolve_user_ids(api, messages.iter().map(|m| m.user.as_str()), names)
+}
+
+fn resolve_user_ids<'a>(
+    api: &SlackClient,
+    ids: impl IntoIterator<Item = &'a str>,
+    names: &mut HashMap<String, String>,
+) -> Result<(), SlkError> {
+    let mut unique_ids: Vec<&str> = ids
+        .into_iter()
+        .filter(|id| id.starts_with('U') && !names.contains_key(*id))
+        .collect();
+    unique_ids.sort_unstable();
+    unique_ids.dedup();
+
+    let users = api.concurrently(&unique_ids, |api, id| async move {
+        response::parse::<UserInfoResponse>(&api.users_info_async(id).await?)
+    });
+    for (id, user) in unique_ids.iter().zip(users) {
+        match user?.user.shown_name() {
+            "" => return Err(SlkError::from(format!("users.info has no name for {}", id))),
+            name => names.insert(id.to_string(), name.to_string()),
+        };
+    }
+    Ok(())
+}
+
+fn run_login(
+    transport: &dyn Transport,
+    base_url: &str,
+    bot: bool,
+    no_browser: bool,
+) -> Result<String, SlkError> {
+    let (client_id, client_secret) = config::load_client_credentials()?;
+    let timeout = config::load_login_timeout()?;
+    let mut creds = oauth::run_oauth_flow(
+        transport,
+        base_url,
+        &client_id,
+        &client_secret,
+        bot,
+        no_browser,
+        timeout,
+    )?;
+    let identity = verify_token(&SlackClient::new(transport, base_url, &creds.token))?;
+    // A user-only login keeps a previously stored bot token.
+    if creds.bot_token.is_none() {
+        creds.bot_token = config::load_credentials()?.and_then(|c| c.bot_token);
+    }
+    creds.identity = Some(identity.clone());
+    let location = config::save_credentials(&creds)?;
+    let saved = if creds.bot_token.is_some() && bot {
+        "User and bot tokens"
+    } else {
+        "Token"
+    };
+    Ok(format!(
+        "Authenticated as {} on {}. {} saved to {}",
+        identity.user,
+        identity.team,
+        saved,
+        location
+    ))
+}
+
+/// Checks a token with `auth.test`, returning who it belongs to.
+fn verify_token(api: &SlackClient) -> Result<message::AuthIdentity, SlkError> {
+    response::parse(&api.auth_test()?)
+}
+
+fn read_token_from_stdin() -> Result<String, SlkError> {
+    use std::io::{BufRead, IsTerminal};
+
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprint!("Paste your Slack token (xoxp-/xoxb-): ");
+    }
+    let mut line = String::new();
+    stdin
+        .lock()
+        .read_line(&mut line)
+        .map_err(|e| SlkError::from(format!("failed to read token from stdin: {}", e)))?;
+    Ok(line.trim().to_string())
+}
+
+/// The Block Kit blocks in the JSON file at `path`, for `send --blocks`,
+/// checked before anything is sent.
+fn read_blocks(path: &str) -> Result<String, SlkError> {
+    let input = std::fs::read_to_string(path)
+        .map_err(|e| SlkError::from(format!("could not read {}: {}", path, e)))?;
+    compose::blocks_from_json(&input).map_err(|e| SlkError::from(format!("{}: {}", path, e)))
+}
+
+/// `text` with the `@handle`s and `#channel`s it mentions in Slack's syntax,
+/// so that they notify people and link to the channels. Handles and names
+/// are looked up as for `--from` and channel arguments; those not found are
+/// left as they are.
+fn link_mentions(api: &SlackClient, text: &str, user_names: &mut HashMap<String, String>) -> String {
+    let mut found: HashMap<(compose::MentionKind, String), Option<String>> = HashMap::new();
+    compose::link_mentions(text, |mention| {
+        let key = (mention.kind, mention.name.to_string());
+        let linked = found.entry(key).or_insert_with(|| match mention.kind {
+            // Channel arguments and --from take IDs as they are; text doesn't.
+            compose::MentionKind::User if resolve::is_user_id(mention.name) => None,
+            compose::MentionKind::User => {
+                resolve_user(api, mention.name, user_names).ok().map(|id| format!("<@{}>", id))
+            }
+            compose::MentionKind::Channel => {
+                resolve_channel(api, mention.name).ok().map(|id| format!("<#{}|{}>", id, mention.name))
+            }
+        });
+        linked.clone()
+    })
+}
+
+/// The message for `send`/`reply`: `text` itself, or stdin when it is `-`.
+fn message_text(text: String) -> Result<String, SlkError> {
+    use std::io::{IsTerminal, Read};
+
+    if text != "-" {
+        return Ok(text);
+    }
+    let mut stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprintln!("Type the message, then press Ctrl-D:");
+    }
+    let mut input = String::new();
+    stdin
+        .read_to_string(&mut input)
+        .map_err(|e| SlkError::from(format!("failed to read message from stdin: {}", e)))?;
+    compose::message_from_stdin(&input)
+}
+
+fn run_login_with_token(
+    transport: &dyn Transport,
+    base_url: &str,
+    token: &str,
+) -> Result<String, SlkError> {
+    if !token.starts_with("xox") {
+        return Err(SlkError::from(
+            "that doesn't look like a Slack token (expected xoxp-... or xoxb-...)",
+        ));
+    }
+    let identity = verify_token(&SlackClient::new(transport, base_url, token))?;
+    let existing = config::load_credentials()?.unwrap_or_default();
+    let (creds, kind) = if token.starts_with("xoxb-") {
+        let creds = config::Credentials {
+            bot_token: Some(token.to_string()),
+            identity: existing.identity.clone().or(Some(identity.clone())),
+            ..existing
+        };
+        (creds, "Bot token")
+    } else {
+        let creds = config::Credentials {
+            token: token.to_string(),
+            bot_token: existing.bot_token,
+            identity: Some(identity.clone()),
+            ..config::Credentials::default()
+        };
+        (creds, "Token")
+    };
+    let location = config::save_credentials(&creds)?;
+    Ok(format!(
+        "Authenticated as {} on {}. {} saved to {}",
+        identity.user,
+        identity.team,
+        kind,
+        location
+    ))
+}
+
+/// Revokes each stored token, returning a description of what was revoked.
+/// Tokens Slack no longer accepts are reported but do not stop the logout.
+fn revoke_credentials(
+    transport: &dyn Transport,
+    base_url: &str,
+    creds: &config::Credentials,
+) -> Vec<String> {
+    let tokens = [
+        ("user token", Some(creds.token.as_str()).filter(|t| !t.is_empty())),
+        ("bot token", creds.bot_token.as_deref()),
+    ];
+    let mut revoked = Vec::new();
+    for (kind, token) in tokens {
+        let Some(token) = token else { continue };
+        let result = SlackClient::new(transport, base_url, token)
+            .auth_revoke()
+            .and_then(|raw| response::parse::<response::Empty>(&raw));
+        match result {
+            Ok(_) => revoked.push(format!("Revoked {}", kind)),
+            Err(e) => revoked.push(format!("Could not revoke {} ({})", kind, e)),
+        }
+    }
+    revoked
+}
+
+fn run_logout(transport: &dyn Transport, base_url: &str) -> Result<String, SlkError> {
+    let Some(creds) = config::load_credentials()? else {
+        return Ok("Not logged in: no stored credentials".to_string());
+    };
+    let mut lines = revoke_credentials(transport, base_url, &creds);
+    for location in config::delete_credentials()? {
+        lines.push(format!("Removed {}", location));
+    }
+    if std::env::var("SLACK_TOKEN").is_ok_and(|t| !t.is_empty()) {
+        lines.push("Note: SLACK_TOKEN is still set in your environment".to_string());
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Posts `text` to a Slack incoming webhook, which answers a plain-text `ok`
+/// rather than JSON.
+fn run_webhook(transport: &dyn Transport, url: &str, text: &str) -> Result<String, SlkError> {
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        // Not echoed: the URL is the webhook's credential and may end up in CI logs.
+        return Err(SlkError::new(
+            ErrorKind::Usage,
+            "expected an incoming webhook URL (https://hooks.slack.com/services/...)",
+        ));
+    }
+    let body = JsonValue::object([("text", text.into())]).to_string();
+    let response = transport.send(&http::Request::post_json(url, &body))?;
+    if response.status != 200 {
+        return Err(SlkError::from(format!("webhook request failed ({})", response.describe())));
+    }
+    Ok("Sent to the webhook".to_string())
+}
+
+fn run_show_thread(
+    api: &SlackClient,
+    channel_id: &str,
+    ts: &str,
+    options: &OutputOptions,
+    user_names: &mut HashMap<String, String>,
+) -> Result<String, SlkError> {
+    let raw_json = api.conversations_replies(channel_id, ts)?;
+    let json_value = json::parse(&raw_json)?;
+    let messages = message::extract_messages(&json_value)?;
+    if let Some(save) = &options.save_files {
+        save.save(api, &shown_raw_messages(&json_value, options))?;
+    }
+    resolve_user_names(api, &messages, user_names)?;
+    Ok(format_messages(&messages, user_names, options))
+}
+
+/// Every message in a thread, parent first, as the API returned them, with
+/// the names of their authors and of the people they mention looked up.
+fn fetch_report_thread(
+    api: &SlackClient,
+    channel_id: &str,
+    ts: &str,
+    user_names: &mut HashMap<String, String>,
+) -> Result<Vec<JsonValue>, SlkError> {
+    let messages = fetch_all_pages(api.replies_pages(channel_id, ts))?;
+    let parsed: Vec<message::SlackMessage> = messages.iter().map(message::extract_message).collect();
+    let ids = parsed
+        .iter()
+        .flat_map(|m| std::iter::once(m.user.as_str()).chain(report::mentioned_users(&m.text)));
+    resolve_user_ids(api, ids, user_names)?;
+    Ok(messages)
+}
+
+/// Writes `report` to the file `out`, or returns it for `-`.
+fn write_report(out: &str, report: &str, messages: usize) -> Result<String, SlkError> {
+    if out == "-" {
+        return Ok(report.trim_end().to_string());
+    }
+    std::fs::write(out, report).map_err(|e| SlkError::from(format!("failed to write {}: {}", out, e)))?;
+    Ok(format!("Wrote a report on the thread ({} messages) to {}", messages, out))
+}
+
+fn run_send(
+    api: &SlackClient,
+    channel_id: &str,
+    text: &str,
+    post: &slack_api::PostOptions,
+) -> Result<String, SlkError> {
+    let posted: ChatPostMessageResponse = response::parse(&api.chat_post(channel_id, text, post)?)?;
+    let ts = posted.ts;
+    Ok(match post.thread_ts {
+        Some(thread_ts) if post.broadcast => {
+            format!("Replied to thread {} in {} and sent it to the channel (ts {})", thread_ts, channel_id, ts)
+        }
+        Some(thread_ts) => format!("Replied to thread {} in {} (ts {})", thread_ts, channel_id, ts),
+        None => format!("Sent to {} (ts {})", channel_id, ts),
+    })
+}
+
+/// Opens a channel or message in the Slack app through its `slack://` link,
+/// or in the browser when no app handles those links or opening it fails.
+/// `link` is the web link to fall back on, when the target was given as one.
+fn run_open_app(
+    identity: &message::AuthIdentity,
+    channel_id: &str,
+    ts: Option<&str>,
+    link: Option<&str>,
+) -> Result<String, SlkError> {
+    let app_link = url::app_link(&identity.team_id, channel_id, ts);
+    if launch::has_handler("slack") {
+        match launch::open(&app_link) {
+            Ok(()) => return Ok(format!("Opened {} in the Slack app", app_link)),
+            Err(e) => info!("{}; opening it in the browser", e),
+        }
+    } else {
+        info!("No app handles slack:// links; opening it in the browser");
+    }
+    let web_link = match link {
+        Some(link) if link.starts_with("http") => link.to_string(),
+        _ => url::archive_link(&identity.url, channel_id, ts),
+    };
+    launch::open(&web_link)?;
+    Ok(format!("Opened {} in the browser", web_link))
+}
+
+/// `slk msg`: the message at `ts` with up to `context` messages on either
+/// side, newest first like `slk history`. A reply (with `thread_ts`) is
+/// looked up in its thread, and the context is the thread's other messages.
+/// With context, the message itself is marked with `>`.
+fn run_show_message(
+    api: &SlackClient,
+    channel_id: &str,
+    ts: &str,
+    thread_ts: Option<&str>,
+    context: usize,
+    options: &OutputOptions,
+    user_names: &mut HashMap<String, String>,
+) -> Result<String, SlkError> {
+    let not_found = || {
+        let hint = if thread_ts.is_none() { "; for a reply, give its link" } else { "" };
+        SlkError::from(format!("no message at {} in {}{}", ts, channel_id, hint))
+    };
+    let (messages, at) = match thread_ts.filter(|thread_ts| *thread_ts != ts) {
+        Some(thread_ts) => {
+            let thread = fetch_all_pages(api.replies_pages(channel_id, thread_ts))?;
+            let mut thread: Vec<message::SlackMessage> = thread.iter().map(message::extract_message).collect();
+            thread.reverse();
+            let at = thread.iter().position(|m| m.ts == ts).ok_or_else(not_found)?;
+            let start = at.saturating_sub(context);
+            (thread.into_iter().skip(start).take(at - start + context + 1).collect(), at - start)
+        }
+        None => {
+            let until = json::parse(&api.conversations_history_until(channel_id, ts, context + 1)?)?;
+            let mut until = message::extract_messages(&until)?;
+            until.truncate(context + 1);
+            if until.first().is_none_or(|m| m.ts != ts) {
+                return Err(not_found());
+            }
+            let mut after = if context > 0 { history_after(api, channel_id, ts, context)? } else { Vec::new() };
+            let at = after.len();
+            after.extend(until);
+            (after, at)
+        }
+    };
+    resolve_user_names(api, &messages, user_names)?;
+    if options.format == OutputFormat::Json {
+        let items = messages.iter().map(|m| message_json(m, user_names, options)).collect();
+        return Ok(JsonValue::Array(items).to_string());
+    }
+    let lines: Vec<String> = messages
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            let line = message_line_at(m, None, user_names, options, if context == 0 { 0 } else { 2 });
+            match (context, i == at) {
+                (0, _) => line,
+                (_, true) => format!("> {}", line),
+                (_, false) => format!("  {}", line),
+            }
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+/// The link to a message, from `chat.getPermalink`.
+fn run_permalink(api: &SlackClient, channel_id: &str, ts: &str) -> Result<String, SlkError> {
+    let link: ChatGetPermalinkResponse = response::parse(&api.chat_get_permalink(channel_id, ts)?)?;
+    Ok(link.permalink)
+}
+
+/// `--copy`: puts a command's output on the clipboard as well. A clipboard
+/// failure is only a warning, since the output is printed anyway.
+fn copy_output(output: &str) {
+    if output.is_empty() {
+        warn!("--copy: there is no output to copy");
+        return;
+    }
+    match clipboard::copy(output) {
+        Ok(()) => info!("Copied the output to the clipboard"),
+        Err(e) => warn!("could not copy the output: {}", e),
+    }
+}
+
+/// An API response as `--raw` prints it: indented, but otherwise as Slack
+/// sent it. A response with `"ok": false` is an error.
+fn raw_response(raw_json: &str) -> Result<String, SlkError> {
+    let response = json::parse(raw_json)?;
+    message::check_ok(&response)?;
+    Ok(response.pretty())
+}
+
+/// Calls any Web API method, printing the response as Slack sent it (or
+/// indented with `--pretty`). A response with `"ok": false` is an error.
+fn run_api(
+    api: &SlackClient,
+    method: &str,
+    params: &[(String, String)],
+    pretty: bool,
+) -> Result<String, SlkError> {
+    let params: Vec<(&str, &str)> = params.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    let raw = api.call(method, &params)?;
+    if pretty {
+        return raw_response(&raw);
+    }
+    message::check_ok(&json::parse(&raw)?)?;
+    Ok(raw.trim_end().to_string())
+}
+
+/// Every public and private channel the token can see, page by page.
+fn fetch_conversations(api: &SlackClient) -> Result<Vec<message::SlackConversation>, SlkError> {
+    let mut channels = Vec::new();
+    for page in api.conversations_pages() {
+        channels.extend(response::read::<ConversationsListResponse>(&page?)?.channels);
+    }
+    Ok(channels)
+}
+
+/// Maps a channel name (`general` or `#general`) to its ID, looking in the
+/// cached channel list first and refreshing it when the name isn't there.
+/// IDs are returned as they are.
+fn resolve_channel(api: &SlackClient, channel: &str) -> Result<String, SlkError> {
+    if resolve::is_channel_id(channel) {
+        return Ok(channel.to_string());
+    }
+    if let Some(id) = resolve::find_channel(&cache::load_channels(), channel)? {
+        return Ok(id);
+    }
+    info!("#{} is not in the cached channel list; refreshing it", channel.trim_start_matches('#'));
+    let channels = fetch_conversations(api)?;
+    warn_on_cache_error(cache::save_channels(&channels));
+    resolve::find_channel(&channels, channel)?
+        .ok_or_else(|| resolve::unknown_channel(&channels, channel))
+}
+
+/// How `slk watch` shows a channel given on the command line: `#name`, or
+/// the name from the cached channel list for an ID, if it is there.
+fn channel_label(channel: &str) -> String {
+    if !resolve::is_channel_id(channel) {
+        return format!("#{}", channel.trim_start_matches('#'));
+    }
+    cache::load_channels()
+        .into_iter()
+        .find(|c| c.id == channel && !c.name.is_empty())
+        .map_or(channel.to_string(), |c| format!("#{}", c.name))
+}
+
+/// The channels the user is a member of, as (ID, `#name`) pairs.
+fn member_channels(api: &SlackClient) -> Result<Vec<(String, String)>, SlkError> {
+    let mut channels = Vec::new();
+    for page in api.users_conversations_pages() {
+        channels.extend(response::read::<ConversationsListResponse>(&page?)?.channels);
+    }
+    if channels.is_empty() {
+        return Err(SlkError::from("you are not a member of any channels"));
+    }
+    Ok(channels.into_iter().map(|c| (c.id, format!("#{}", c.name))).collect())
+}
+
+/// Maps a user given as `@handle`, display name or email address to their ID.
+/// Names are looked up in the cached user names and then in `users.list`,
+/// whose members are added to the cache; emails go to `users.lookupByEmail`.
+/// IDs are returned as they are.
+fn resolve_user(
+    api: &SlackClient,
+    user: &str,
+    user_names: &mut HashMap<String, String>,
+) -> Result<String, SlkError> {
+    if resolve::is_user_id(user) {
+        return Ok(user.to_string());
+    }
+    if resolve::is_email(user) {
+        let found = response::parse::<UserLookupResponse>(&api.users_lookup_by_email(user)?)
+            .map_err(|e| SlkError::from(format!("no user with email {}: {}", user, e)))?
+            .user;
+        user_names.insert(found.id.clone(), found.shown_name().to_string());
+        return Ok(found.id);
+    }
+    let cached: Vec<message::SlackUser> = user_names
+        .iter()
+        .map(|(id, name)| message::SlackUser {
+            id: id.clone(),
+            display_name: name.clone(),
+            ..message::SlackUser::default()
+        })
+        .collect();
+    if let Some(id) = resolve::find_user(&cached, user)? {
+        return Ok(id);
+    }
+    info!("Looking up '{}' in the user list", user);
+    // Read a page at a time, so a big workspace's list is never parsed whole.
+    let (mut users, mut cursor) = message::extract_users(&api.users_list(None)?)?;
+    while let Some(next) = cursor {
+        let (page, next) = message::extract_users(&api.users_list(Some(&next))?)?;
+        users.extend(page);
+        cursor = next;
+    }
+    for u in &users {
+        user_names.insert(u.id.clone(), u.shown_name().to_string());
+    }
+    resolve::find_user(&users, user)?.ok_or_else(|| resolve::unknown_user(&users, user))
+}
+
+/// `slk user`: a user's profile, from `users.info`.
+fn run_show_user(
+    api: &SlackClient,
+    user: &str,
+    options: &OutputOptions,
+    user_names: &mut HashMap<String, String>,
+) -> Result<String, SlkError> {
+    let id = resolve_user(api, user, user_names)?;
+    let user = response::parse::<UserInfoResponse>(&api.users_info(&id)?)?.user;
+    Ok(format_user(&user, config::now_unix(), options))
+}
+
+/// A profile as `slk user` shows it, with the user's local time at `now`.
+/// Fields the profile leaves empty are left out.
+fn format_user(user: &message::SlackUser, now: u64, options: &OutputOptions) -> String {
+    let local_time = (!user.tz.is_empty()).then(|| {
+        let local = now.saturating_add_signed(user.tz_offset);
+        message::format_unix_ts_with(&local.to_string(), &options.time_format)
+    });
+    if options.format == OutputFormat::Json {
+        return JsonValue::object([
+            ("id", user.id.as_str().into()),
+            ("name", user.name.as_str().into()),
+            ("display_name", user.display_name.as_str().into()),
+            ("real_name", user.real_name.as_str().into()),
+            ("title", user.title.as_str().into()),
+            ("email", user.email.as_str().into()),
+            ("tz", user.tz.as_str().into()),
+            ("tz_label", user.tz_label.as_str().into()),
+            ("tz_offset", user.tz_offset.into()),
+            ("local_time", local_time.into()),
+        ])
+        .to_string();
+    }
+    let time_zone = match user.tz_label.as_str() {
+        "" => user.tz.clone(),
+        label => format!("{} ({})", user.tz, label),
+    };
+    let (sign, offset) = (if user.tz_offset < 0 { '-' } else { '+' }, user.tz_offset.unsigned_abs());
+    let local_time = local_time.map_or(String::new(), |time| {
+        format!("{} (UTC{}{:02}:{:02})", time, sign, offset / 3600, offset % 3600 / 60)
+    });
+    let rows = [
+        ("User", format!("@{} ({})", user.name, user.id)),
+        ("Display name", user.display_name.clone()),
+        ("Real name", user.real_name.clone()),
+        ("Title", user.title.clone()),
+        ("Email", user.email.clone()),
+        ("Time zone", time_zone),
+        ("Local time", local_time),
+    ];
+    rows.iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(label, value)| format!("{:13} {}", format!("{}:", label), value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_conversations(
+    conversations: &[message::SlackConversation],
+    options: &OutputOptions,
+) -> String {
+    let conversations = &conversations[..conversations.len().min(options.limit.unwrap_or(usize::MAX))];
+    if options.format == OutputFormat::Json {
+        let items = conversations
+            .iter()
+            .map(|c| {
+                JsonValue::object([("id", c.id.as_str().into()), ("name", c.name.as_str().into())])
+            })
+            .collect();
+        return JsonValue::Array(items).to_string();
+    }
+    let lines: Vec<String> = conversations
+        .iter()
+        .map(|c| format!("{}\t{}", c.id, c.name))
+        .collect();
+    lines.join("\n")
+}
+
+/// How many messages `slk history` looks at.
+fn history_limit(options: &OutputOptions) -> usize {
+    // `--from` filters afterwards, so look at a full page for it.
+    match options.from {
+        Some(_) => options.limit.unwrap_or(0).max(DEFAULT_HISTORY_LIMIT),
+        None => options.limit.unwrap_or(DEFAULT_HISTORY_LIMIT),
+    }
+}
+
+/// A channel's recent messages, newest first; with `threads`, each thread's
+/// replies under its parent.
+fn run_show_history(
+    api: &SlackClient,
+    channel_id: &str,
+    threads: bool,
+    options: &OutputOptions,
+    user_names: &mut HashMap<String, String>,
+) -> Result<String, SlkError> {
+    let limit = history_limit(options);
+    let raw_json = api.conversations_history(channel_id, limit)?;
+    let json_value = json::parse(&raw_json)?;
+    let messages = message::extract_messages(&json_value)?;
+    if let Some(save) = &options.save_files {
+        save.save(api, &shown_raw_messages(&json_value, options))?;
+    }
+    if options.count {
+        return Ok(format_counts(&shown_raw_messages(&json_value, options), options));
+    }
+    resolve_user_names(api, &messages, user_names)?;
+    if !threads {
+        return Ok(format_messages(&messages, user_names, options));
+    }
+    let parents: Vec<&str> = messages
+        .iter()
+        .filter(|m| options.shows(m))
+        .take(options.limit.unwrap_or(usize::MAX))
+        .filter(|m| m.has_replies())
+        .map(|m| m.ts.as_str())
+        .collect();
+    info!("Fetching the replies to {} threads", parents.len());
+    let threads =
+        api.concurrently(&parents, |api, ts| fetch_all_pages_async(api.replies_pages(channel_id, ts)));
+    let mut replies = HashMap::new();
+    for (ts, thread) in parents.iter().zip(threads) {
+        let thread: Vec<message::SlackMessage> =
+            thread?.iter().map(message::extract_message).filter(|r| r.ts != *ts).collect();
+        resolve_user_names(api, &thread, user_names)?;
+        replies.insert(ts.to_string(), thread);
+    }
+    Ok(format_threads(&messages, &replies, user_names, options))
+}
+
+/// The line text output of `slk history` starts with, so that it says where
+/// it's from when piped or pasted: `#general — 42 members — topic: …`, or the
+/// other person's name for a DM.
+fn channel_header(
+    api: &SlackClient,
+    channel_id: &str,
+    options: &OutputOptions,
+    user_names: &mut HashMap<String, String>,
+) -> Result<String, SlkError> {
+    let info: ConversationsInfoResponse = response::parse(&api.conversations_info(channel_id)?)?;
+    resolve_user_ids(api, info.user.as_deref(), user_names)?;
+    Ok(format_channel_header(&info, user_names, options))
+}
+
+fn format_channel_header(
+    info: &ConversationsInfoResponse,
+    user_names: &HashMap<String, String>,
+    options: &OutputOptions,
+) -> String {
+    let name = match &info.user {
+        Some(user) => format!("@{}", user_names.get(user).unwrap_or(user)),
+        None => format!("#{}", info.name),
+    };
+    let members = info.num_members.map(|n| format!("{} {}", n, if n == 1 { "member" } else { "members" }));
+    let topic = (!info.topic.is_empty()).then(|| format!("topic: {}", info.topic.replace('\n', " ")));
+    let header = std::iter::once(name).chain(members).chain(topic).collect::<Vec<_>>().join(" — ");
+    options.paint("1", &header)
+}
+
+/// Files bigger than this (in MB) aren't downloaded by `--save-files`
+/// unless `--max-file-size` says otherwise.
+const DEFAULT_MAX_FILE_MB: u64 = 100;
+
+/// Where `--save-files` puts the files attached to the shown messages, and
+/// the biggest it downloads.
+struct SaveFiles {
+    dir: std::path::PathBuf,
+    max_bytes: u64,
+}
+
+impl SaveFiles {
+    fn from_matches(matches: &cli::Matches) -> Result<Option<Self>, SlkError> {
+        let Some(dir) = matches.value("save-files") else {
+            return Ok(None);
+        };
+        let max_mb = match matches.value("max-file-size") {
+            Some(s) => s.parse::<u64>().ok().filter(|n| *n > 0).ok_or_else(|| {
+                SlkError::from(format!("invalid --max-file-size '{}': expected a positive number of MB", s))
+            })?,
+            None => DEFAULT_MAX_FILE_MB,
+        };
+        let max_bytes = max_mb.checked_mul(1024 * 1024).ok_or_else(|| {
+            SlkError::from(format!("invalid --max-file-size '{}': too large", max_mb))
+        })?;
+        Ok(Some(SaveFiles { dir: dir.into(), max_bytes }))
+    }
+
+    /// Downloads the files attached to `messages` as `<ts>-<file name>`.
+    /// Files over the size limit, files already saved, and files that fail
+    /// to download are reported and skipped; `-v` adds progress and a summary.
+    fn save(&self, api: &SlackClient, messages: &[&JsonValue]) -> Result<(), SlkError> {
+        let files: Vec<(&str, &JsonValue)> = messages
+            .iter()
+            .flat_map(|m| {
+                let ts = m.get("ts").and_then(|v| v.as_str()).unwrap_or("0");
+                m.get("files").and_then(|v| v.as_array()).into_iter().flatten().map(move |f| (ts, f))
+            })
+            .collect();
+        if files.is_empty() {
+            info!("No files to save in the shown messages.");
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.dir).map_err(|e| {
+            SlkError::from(format!("failed to create directory {}: {}", self.dir.display(), e))
+        })?;
+        let (mut saved, mut bytes) = (0, 0);
+        for (ts, file) in &files {
+            let field = |key: &str| file.get(key).and_then(|v| v.as_str());
+            let name = field("name").or(field("title")).or(field("id")).unwrap_or("file");
+            let Some(url) = field("url_private_download").or(field("url_private")) else {
+                warn!("{} has no download link (it may have been deleted or be external); skipped", name);
+                continue;
+            };
+            let size = file.get("size").and_then(|v| v.as_u64());
+            if size.is_some_and(|size| size > self.max_bytes) {
+                warn!("{} is larger than --max-file-size ({} MB); skipped", name, self.max_bytes / (1024 * 1024));
+                continue;
+            }
+            let path = self.dir.join(format!("{}-{}", ts, file_name(name)));
+            if size.is_some() && std::fs::metadata(&path).ok().map(|m| m.len()) == size {
+                info!("{} is already saved", path.display());
+                continue;
+            }
+            info!("Downloading {}", name);
+            let body = match api.download_file(url, self.max_bytes) {
+                Ok(Some(body)) => body,
+                Ok(None) => {
+                    warn!("{} is larger than --max-file-size; skipped", name);
+                    continue;
+                }
+                Err(e) => {
+                    warn!("could not download {}: {}", name, e);
+                    continue;
+                }
+            };
+            std::fs::write(&path, &body)
+                .map_err(|e| SlkError::from(format!("failed to write {}: {}", path.display(), e)))?;
+            saved += 1;
+            bytes += body.len();
+        }
+        info!("Saved {} of {} files ({} bytes) to {}", saved, files.len(), bytes, self.dir.display());
+        Ok(())
+    }
+}
+
+/// A file name from Slack made safe to create: path separators and other
+/// characters file systems reject become `_`.
+fn file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_control() || r#"/\:*?"<>|"#.contains(c) { '_' } else { c })
+        .collect();
+    match name.trim_matches('.') {
+        "" => "file".to_string(),
+        _ => name,
+    }
+}
+
+/// The raw messages of a history or replies response that `format_messages`
+/// shows: those passing the filters, up to the limit.
+fn shown_raw_messages<'a>(response: &'a JsonValue, options: &OutputOptions) -> Vec<&'a JsonValue> {
+    response
+        .get("messages")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|m| options.shows(&message::extract_message(*m)))
+        .take(options.limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// `slk history --count`: how many `messages` (as the API returned them)
+/// there are, by how many people, and how many threads and files they have.
+fn format_counts(messages: &[&JsonValue], options: &OutputOptions) -> String {
+    let extracted: Vec<message::SlackMessage> =
+        messages.iter().map(|m| message::extract_message(*m)).collect();
+    let users: std::collections::HashSet<&str> = extracted.iter().map(|m| m.user.as_str()).collect();
+    let threads = extracted.iter().filter(|m| m.has_replies()).count();
+    let files: usize = messages.iter().filter_map(|m| m.get("files")?.as_array()).map(Vec::len).sum();
+    let counts = [
+        ("messages", "Messages:", extracted.len()),
+        ("users", "Users:", users.len()),
+        ("threads", "Threads:", threads),
+        ("files", "Files:", files),
+    ];
+    match options.format {
+        OutputFormat::Json => JsonValue::object(counts.map(|(key, _, n)| (key, n.into()))).to_string(),
+        OutputFormat::Csv => {
+            let rows = counts.map(|(key, _, n)| [key.to_string(), String::new(), String::new(), n.to_string()]);
+            stats::csv(&["kind", "key", "name", "count"], &rows)
+        }
+        OutputFormat::Text => {
+            counts.iter().map(|(_, label, n)| format!("{:9} {}", label, n)).collect::<Vec<_>>().join("\n")
+        }
+    }
+}
+
+/// The messages on every page of `pages`, as the API returned them.
+/// The `limit` messages posted next after `ts` in a channel, newest first.
+/// History is read newest first, so the pages after `ts` alone start from
+/// the present: each try bounds them with `latest` as well, doubling the
+/// span from a minute until it holds `limit` messages or reaches the present.
+fn history_after(
+    api: &SlackClient,
+    channel_id: &str,
+    ts: &str,
+    limit: usize,
+) -> Result<Vec<message::SlackMessage>, SlkError> {
+    let (secs, _) = message::ts_key(ts);
+    let now = config::now_unix();
+    let mut span = 60;
+    loop {
+        let latest = secs.saturating_add(span);
+        let mut messages = Vec::new();
+        for m in api.history_pages_between(channel_id, ts, &format!("{}.000000", latest)).messages() {
+            let m = message::extract_message(&m?);
+            if ts_after(&m.ts, ts) {
+                messages.push(m);
+            }
+        }
+        if messages.len() >= limit || latest >= now {
+            messages.sort_by_key(|m| std::cmp::Reverse(message::ts_key(&m.ts)));
+            messages.drain(..messages.len().saturating_sub(limit));
+            return Ok(messages);
+        }
+        span = span.saturating_mul(2);
+    }
+}
+
+fn fetch_all_pages(pages: slack_api::Pages) -> Result<Vec<JsonValue>, SlkError> {
+    task::block_on(fetch_all_pages_async(pages))
+}
+
+async fn fetch_all_pages_async(mut pages: slack_api::Pages<'_>) -> Result<Vec<JsonValue>, SlkError> {
+    let mut messages = Vec::new();
+    while let Some(page) = pages.next_page().await {
+        let page = page?;
+        let page = page
+            .get("messages")
+            .and_then(|v| v.as_array())
+            .ok_or(SlkError::from("missing 'messages' array in response"))?;
+        messages.extend(page.iter().cloned());
+        info!("Fetched {} messages so far", messages.len());
+    }
+    Ok(messages)
+}
+
+/// A channel's messages posted after `oldest` (or its whole history),
+/// newest first.
+fn fetch_history_after(
+    api: &SlackClient,
+    channel_id: &str,
+    oldest: Option<&str>,
+) -> Result<Vec<JsonValue>, SlkError> {
+    fetch_all_pages(api.history_pages(channel_id, oldest))
+}
+
+/// The replies to `parent` if it starts a thread, leaving out the parent.
+fn fetch_replies_to(
+    api: &SlackClient,
+    channel_id: &str,
+    parent: &JsonValue,
+) -> Result<Option<Vec<JsonValue>>, SlkError> {
+    let has_replies = parent.get("reply_count").and_then(|v| v.as_i64()).is_some_and(|n| n > 0);
+    let ts = match parent.get("ts").and_then(|v| v.as_str()) {
+        Some(ts) if has_replies => ts,
+        _ => return Ok(None),
+    };
+    info!("Fetching the replies to {}", ts);
+    let replies = fetch_all_pages(api.replies_pages(channel_id, ts))?;
+    Ok(Some(replies.into_iter().filter(|r| r.get("ts").and_then(|v| v.as_str()) != Some(ts)).collect()))
+}
+
+/// How much `slk export` wrote, for its summary.
+struct ExportSummary {
+    messages: usize,
+    threads: usize,
+    users: usize,
+}
+
+/// The `key` object of an API response, such as the `channel` of
+/// `conversations.info`.
+fn response_object(raw_json: &str, key: &str) -> Result<JsonValue, SlkError> {
+    let response = json::parse(raw_json)?;
+    message::check_ok(&response)?;
+    response
+        .get(key)
+        .cloned()
+        .ok_or_else(|| SlkError::from(format!("missing '{}' in response", key)))
+}
+
+/// A channel's whole history as one JSON document: the channel from
+/// `conversations.info`, its messages oldest first with each thread's
+/// replies in `replies`, and the `users.info` profiles of everyone who
+/// wrote them. Everything is kept as the API returned it.
+fn export_channel(
+    api: &SlackClient,
+    channel_id: &str,
+) -> Result<(JsonValue, ExportSummary), SlkError> {
+    let channel = response_object(&api.conversations_info(channel_id)?, "channel")?;
+    let mut messages = fetch_history_after(api, channel_id, None)?;
+    messages.reverse();
+    let mut authors = std::collections::BTreeSet::new();
+    let mut threads = 0;
+    for m in &mut messages {
+        authors.insert(message::extract_message(m).user);
+        let Some(replies) = fetch_replies_to(api, channel_id, m)? else {
+            continue;
+        };
+        authors.extend(replies.iter().map(|r| message::extract_message(r).user));
+        m.set("replies", replies);
+        threads += 1;
+    }
+    let authors: Vec<&String> = authors.iter().filter(|id| resolve::is_user_id(id)).collect();
+    let users = api
+        .concurrently(&authors, |api, id| async move {
+            response_object(&api.users_info_async(id).await?, "user")
+        })
+        .into_iter()
+        .collect::<Result<Vec<_>, SlkError>>()?;
+    let summary = ExportSummary { messages: messages.len(), threads, users: users.len() };
+    let document = JsonValue::object([
+        ("exported_at", config::now_unix().into()),
+        ("channel", channel),
+        ("users", users.into()),
+        ("messages", messages.into()),
+    ]);
+    Ok((document, summary))
+}
+
+/// How many messages `slk recent` shows from each channel, unless `--limit`
+/// says otherwise.
+const DEFAULT_RECENT_LIMIT: usize = 3;
+
+/// A channel's part of `slk recent`: its latest messages (newest first), and
+/// with `--since`, how many are new and whether there are more than fetched.
+struct RecentChannel {
+    id: String,
+    name: String,
+    messages: Vec<message::SlackMessage>,
+    new: Option<(usize, bool)>,
+}
+
+/// `slk recent`: the latest `--limit` messages in each channel the user is a
+/// member of, or with `since`, in those with messages since then, the
+/// channel with the newest message first.
+fn run_recent(
+    api: &SlackClient,
+    since: Option<u64>,
+    options: &OutputOptions,
+    user_names: &mut HashMap<String, String>,
+) -> Result<String, SlkError> {
+    let channels = member_channels(api)?;
+    let limit = options.limit.unwrap_or(DEFAULT_RECENT_LIMIT);
+    let page = history_limit(options);
+    info!("Fetching the latest messages in {} channels", channels.len());
+    let responses = api.concurrently(&channels, |api, (id, _)| async move {
+        match since {
+            Some(since) => api.conversations_history_since_async(id, &since.to_string()).await,
+            None => api.conversations_history_async(id, page).await,
+        }
+    });
+    let mut recent = Vec::new();
+    for ((id, name), response) in channels.into_iter().zip(responses) {
+        let response = json::parse(&response?)?;
+        let mut messages = message::extract_messages(&response)?;
+        messages.retain(|m| options.shows(m));
+        if messages.is_empty() {
+            continue;
+        }
+        let has_more = response.get("has_more").and_then(|v| v.as_bool()).unwrap_or(false);
+        let new = since.map(|_| (messages.len(), has_more));
+        messages.truncate(limit);
+        recent.push(RecentChannel { id, name, messages, new });
+    }
+    recent.sort_by(|a, b| message::ts_key(&b.messages[0].ts).cmp(&message::ts_key(&a.messages[0].ts)));
+    resolve_user_ids(api, recent.iter().flat_map(|c| &c.messages).map(|m| m.user.as_str()), user_names)?;
+    Ok(format_recent(&recent, user_names, options))
+}
+
+/// `slk recent` as a `#channel (3 new)` line over each channel's messages,
+/// or a JSON array of channels with a `messages` array each.
+fn format_recent(
+    recent: &[RecentChannel],
+    user_names: &HashMap<String, String>,
+    options: &OutputOptions,
+) -> String {
+    if options.format == OutputFormat::Json {
+        let items = recent.iter().map(|c| {
+            let mut item = JsonValue::object([
+                ("channel", c.id.as_str().into()),
+                ("name", c.name.trim_start_matches('#').into()),
+            ]);
+            if let Some((new, has_more)) = c.new {
+                item.set("new", new);
+                item.set("has_more", has_more);
+            }
+            let messages = c.messages.iter().map(|m| message_json(m, user_names, options));
+            item.set("messages", messages.collect::<Vec<_>>());
+            item
+        });
+        return JsonValue::Array(items.collect()).to_string();
+    }
+    if recent.is_empty() {
+        return "Nothing new.".to_string();
+    }
+    let mut blocks = Vec::new();
+    for c in recent {
+        let header = match c.new {
+            Some((new, has_more)) => format!("{} ({}{} new)", c.name, new, if has_more { "+" } else { "" }),
+            None => c.name.clone(),
+        };
+        let mut lines = vec![options.paint("1", &header)];
+        let mut previous = None;
+        for m in &c.messages {
+            lines.push(format!("  {}", message_line_at(m, previous, user_names, options, 2)));
+            previous = Some(m);
+        }
+        blocks.push(lines.join("\n"));
+    }
+    blocks.join("\n\n")
+}
+
+/// How far back `slk activity` looks unless `--since` says otherwise.
+const DEFAULT_ACTIVITY_SINCE: &str = "7d";
+
+/// How much of each message `slk activity` quotes.
+const ACTIVITY_CHARS: usize = 60;
+
+/// One of the user's messages that others reacted or replied to, for
+/// `slk activity`: the reactions leave out the user's own, and the replies
+/// are the ones by others.
+struct Engagement {
+    channel: String,
+    name: String,
+    message: message::SlackMessage,
+    reactions: Vec<message::Reaction>,
+    replies: Vec<message::SlackMessage>,
+}
+
+/// `slk activity`: the reactions and replies others left on the messages the
+/// user posted since `since` in the channels they are in, newest first.
+fn run_activity(
+    api: &SlackClient,
+    since: u64,
+    options: &OutputOptions,
+    user_names: &mut HashMap<String, String>,
+) -> Result<String, SlkError> {
+    let me = verify_token(api)?.user_id;
+    let channels = member_channels(api)?;
+    let oldest = since.to_string();
+    info!("Fetching the messages since {} in {} channels", oldest, channels.len());
+    let histories =
+        api.concurrently(&channels, |api, (id, _)| fetch_all_pages_async(api.history_pages(id, Some(&oldest))));
+    let mut mine = Vec::new();
+    for ((id, name), history) in channels.iter().zip(histories) {
+        mine.extend(
+            history?
+                .iter()
+                .map(message::extract_message)
+                .filter(|m| m.user == me && !m.is_system() && !m.is_thread_reply())
+                .map(|m| (id.as_str(), name.as_str(), m)),
+        );
+    }
+    let threads: Vec<(&str, &str)> =
+        mine.iter().filter(|(_, _, m)| m.has_replies()).map(|(id, _, m)| (*id, m.ts.as_str())).collect();
+    info!("Fetching the replies to {} threads", threads.len());
+    let fetched = api.concurrently(&threads, |api, (id, ts)| fetch_all_pages_async(api.replies_pages(id, ts)));
+    let mut replies = HashMap::new();
+    for ((_, ts), thread) in threads.iter().zip(fetched) {
+        let thread: Vec<message::SlackMessage> =
+            thread?.iter().map(message::extract_message).filter(|r| r.ts != *ts && r.user != me).collect();
+        replies.insert(ts.to_string(), thread);
+    }
+    let mut activity: Vec<Engagement> = mine
+        .into_iter()
+        .map(|(channel, name, m)| {
+            let reactions = m
+                .reactions
+                .iter()
+                .filter_map(|r| {
+                    let mut r = r.clone();
+                    if r.users.contains(&me) {
+                        r.users.retain(|u| *u != me);
+                        r.count -= 1;
+                    }
+                    (r.count > 0).then_some(r)
+                })
+                .collect();
+            let replies = replies.remove(&m.ts).unwrap_or_default();
+            Engagement { channel: channel.to_string(), name: name.to_string(), message: m, reactions, replies }
+        })
+        .filter(|e| !e.reactions.is_empty() || !e.replies.is_empty())
+        .collect();
+    activity.sort_by_key(|e| std::cmp::Reverse(message::ts_key(&e.message.ts)));
+    let reactors = activity.iter().flat_map(|e| &e.reactions).flat_map(|r| &r.users);
+    let repliers = activity.iter().flat_map(|e| &e.replies).map(|r| &r.user);
+    resolve_user_ids(api, reactors.chain(repliers).map(String::as_str), user_names)?;
+    Ok(format_activity_summary(&activity, since, user_names, options))
+}
+
+/// `slk activity` as a total, then each message with a line for its
+/// reactions and one for its replies; or a JSON array of the messages, each
+/// with `reactions` and `replies`.
+fn format_activity_summary(
+    activity: &[Engagement],
+    since: u64,
+    user_names: &HashMap<String, String>,
+    options: &OutputOptions,
+) -> String {
+    let who = |user: &String| user_names.get(user).map_or(user.clone(), |name| format!("@{}", name));
+    if options.format == OutputFormat::Json {
+        let items = activity.iter().map(|e| {
+            let mut item = message_json(&e.message, user_names, options);
+            item.set("channel", e.channel.as_str());
+            item.set("channel_name", e.name.trim_start_matches('#'));
+            let reactions = e.reactions.iter().map(|r| {
+                JsonValue::object([
+                    ("name", r.name.as_str().into()),
+                    ("count", r.count.into()),
+                    ("users", r.users.iter().map(|u| u.as_str().into()).collect::<Vec<JsonValue>>().into()),
+                ])
+            });
+            item.set("reactions", reactions.collect::<Vec<_>>());
+            let replies = e.replies.iter().map(|r| message_json(r, user_names, options));
+            item.set("replies", replies.collect::<Vec<_>>());
+            item
+        });
+        return JsonValue::Array(items.collect()).to_string();
+    }
+    if options.format == OutputFormat::Csv {
+        let rows: Vec<[String; 6]> = activity
+            .iter()
+            .map(|e| {
+                let reactions: u64 = e.reactions.iter().map(|r| r.count).sum();
+                [
+                    e.channel.clone(),
+                    e.name.trim_start_matches('#').to_string(),
+                    e.message.ts.clone(),
+                    reactions.to_string(),
+                    e.replies.len().to_string(),
+                    e.message.text.clone(),
+                ]
+            })
+            .collect();
+        return stats::csv(&["channel", "channel_name", "ts", "reactions", "replies", "text"], &rows);
+    }
+    let since = message::format_unix_ts_with(&since.to_string(), "%Y-%m-%d %H:%M");
+    if activity.is_empty() {
+        return format!("No reactions or replies to your messages since {}.", since);
+    }
+    let reactions: u64 = activity.iter().flat_map(|e| &e.reactions).map(|r| r.count).sum();
+    let replies: usize = activity.iter().map(|e| e.replies.len()).sum();
+    let count = |n: usize, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+    let mut lines = vec![format!(
+        "{} and {} on {} since {}",
+        count(reactions as usize, "reaction", "reactions"),
+        count(replies, "reply", "replies"),
+        format!("{} of your messages", activity.len()),
+        since
+    )];
+    for e in activity {
+        let time = message::format_unix_ts_with(&e.message.ts, &options.time_format);
+        let text = notify::snippet(&e.message.text, ACTIVITY_CHARS);
+        lines.push(String::new());
+        lines.push(format!("{} {} {}", options.paint("35", &e.name), options.paint("2", &time), text));
+        if !e.reactions.is_empty() {
+            let counts: Vec<String> = e.reactions.iter().map(|r| format!(":{}: {}", r.name, r.count)).collect();
+            lines.push(format!("  {}", counts.join("  ")));
+        }
+        if let Some(last) = e.replies.last() {
+            let mut repliers: Vec<String> = Vec::new();
+            for name in e.replies.iter().map(|r| who(&r.user)) {
+                if !repliers.contains(&name) {
+                    repliers.push(name);
+                }
+            }
+            let replies = count(e.replies.len(), "reply", "replies");
+            let age = describe_age(config::now_unix().saturating_sub(message::ts_key(&last.ts).0));
+            let note = format!("  ↳ {} from {}, last {} ago", replies, repliers.join(", "), age);
+            lines.push(options.paint("2", &note));
+        }
+    }
+    lines.join("\n")
+}
+
+/// How far back `slk stats` looks unless `--since` says otherwise.
+const DEFAULT_STATS_SINCE: &str = "7d";
+
+/// Which report `slk stats` was asked for; at most one of them.
+fn stats_report(matches: &cli::Matches) -> Result<stats::Report, SlkError> {
+    let series = matches.value("series").map(stats::Interval::parse).transpose()?;
+    let keywords = matches.value("keywords").map(stats::parse_keywords).transpose()?;
+    let reports = [
+        ("leaderboard", matches.flag("leaderboard").then_some(stats::Report::Leaderboard)),
+        ("response-time", matches.flag("response-time").then_some(stats::Report::ResponseTime)),
+        ("unanswered", matches.flag("unanswered").then_some(stats::Report::Unanswered)),
+        ("series", series.map(stats::Report::Series)),
+        ("keywords", keywords.map(stats::Report::Keywords)),
+    ];
+    let mut asked: Vec<_> =
+        reports.into_iter().filter_map(|(flag, report)| report.map(|report| (flag, report))).collect();
+    match asked.len() {
+        0 => Ok(stats::Report::Summary),
+        1 => Ok(asked.remove(0).1),
+        _ => {
+            let flags: Vec<String> = asked.iter().map(|(flag, _)| format!("--{}", flag)).collect();
+            Err(SlkError::from(format!("{} can't be combined", flags.join(" and "))))
+        }
+    }
+}
+
+/// Archived messages split into the channel's own messages and the replies
+/// in their threads, by the parent's ts and oldest first. Replies also sent
+/// to the channel are in both.
+fn split_replies(
+    archived: Vec<JsonValue>,
+) -> (Vec<message::SlackMessage>, HashMap<String, Vec<message::SlackMessage>>) {
+    let (mut messages, mut replies) = (Vec::new(), HashMap::<String, Vec<message::SlackMessage>>::new());
+    for m in archived.iter().map(message::extract_message) {
+        match m.thread_ts.clone().filter(|thread_ts| *thread_ts != m.ts) {
+            Some(thread_ts) if m.is_broadcast() => {
+                replies.entry(thread_ts).or_default().push(m.clone());
+                messages.push(m);
+            }
+            Some(thread_ts) => replies.entry(thread_ts).or_default().push(m),
+            None => messages.push(m),
+        }
+    }
+    for thread in replies.values_mut() {
+        thread.sort_by_key(|r| message::ts_key(&r.ts));
+    }
+    (messages, replies)
+}
+
+/// `slk stats`: the `report` on the channel's activity from `since` up to
+/// `until`, from every page of its history in that range.
+fn run_stats(
+    api: &SlackClient,
+    channel_id: &str,
+    since: u64,
+    until: u64,
+    report: stats::Report,
+    options: &OutputOptions,
+    user_names: &mut HashMap<String, String>,
+) -> Result<String, SlkError> {
+    let messages = fetch_history_after(api, channel_id, Some(&since.to_string()))?;
+    let messages: Vec<message::SlackMessage> = messages.iter().map(message::extract_message).collect();
+    let mut replies = HashMap::new();
+    if report.needs_replies() {
+        let parents: Vec<&str> = messages
+            .iter()
+            .filter(|m| m.has_replies() && message::ts_key(&m.ts).0 < until)
+            .map(|m| m.ts.as_str())
+            .collect();
+        info!("Fetching the replies to {} threads", parents.len());
+        let threads =
+            api.concurrently(&parents, |api, ts| fetch_all_pages_async(api.replies_pages(channel_id, ts)));
+        for (ts, thread) in parents.iter().zip(threads) {
+            let thread = thread?.iter().map(message::extract_message).filter(|r| r.ts != *ts).collect();
+            replies.insert(ts.to_string(), thread);
+        }
+    }
+    let reactors = messages.iter().flat_map(|m| &m.reactions).flat_map(|r| &r.users);
+    resolve_user_ids(api, messages.iter().map(|m| &m.user).chain(reactors).map(String::as_str), user_names)?;
+    let history = stats::History { messages: &messages, replies: &replies, since, until };
+    Ok(stats::report(&history, report, options.format, user_names))
+}
+
+/// Writes the channel's export to `out`, or returns it when there's no
+/// file to write.
+fn run_export(
+    api: &SlackClient,
+    channel_id: &str,
+    label: &str,
+    out: Option<&str>,
+) -> Result<String, SlkError> {
+    let (document, summary) = export_channel(api, channel_id)?;
+    let Some(out) = out else {
+        return Ok(document.pretty());
+    };
+    std::fs::write(out, document.pretty() + "\n")
+        .map_err(|e| SlkError::from(format!("failed to write {}: {}", out, e)))?;
+    Ok(format!(
+        "Exported {} messages ({} threads) and {} users from {} to {}",
+        summary.messages, summary.threads, summary.users, label, out
+    ))
+}
+
+/// How far before the newest archived message `slk sync` looks again for
+/// edits, unless `--edit-window` says otherwise.
+const DEFAULT_EDIT_WINDOW_HOURS: u64 = 24;
+
+/// Where `slk sync` stores messages and how much it fetches again.
+struct SyncOptions<'a> {
+    archive: &'a archive::Archive,
+    /// Seconds before the channel's high-water mark (the newest archived
+    /// message) to fetch again, so recent edits are picked up; `None`
+    /// fetches the whole history.
+    edit_window: Option<u64>,
+}
+
+/// Where a sync of a channel last synced up to `latest` starts: `window`
+/// seconds before it.
+fn sync_start(latest: &str, window: u64) -> String {
+    let (secs, micros) = message::ts_key(latest);
+    match secs.checked_sub(window) {
+        Some(secs) if window > 0 => format!("{}.000000", secs),
+        Some(_) => format!("{}.{:06}", secs, micros),
+        None => "0".to_string(),
+    }
+}
+
+/// Stores the channel's messages, the replies in threads started by them,
+/// and their authors' names in the archive, fetching only what was posted
+/// since the last sync (less the edit window).
+/// `label` is how the channel is shown (`#general`, or its ID).
+fn run_sync(
+    api: &SlackClient,
+    channel_id: &str,
+    label: &str,
+    sync: &SyncOptions,
+    user_names: &mut HashMap<String, String>,
+) -> Result<String, SlkError> {
+    let archive = sync.archive;
+    let oldest = match (archive.channel(channel_id).and_then(|c| c.latest), sync.edit_window) {
+        (Some(latest), Some(window)) => Some(sync_start(&latest, window)),
+        _ => None,
+    };
+    match &oldest {
+        Some(oldest) => info!("Fetching messages in {} since {}", label, oldest),
+        None => info!("Fetching the whole history of {}", label),
+    }
+    let mut messages = fetch_history_after(api, channel_id, oldest.as_deref())?;
+    let mut replies = Vec::new();
+    for m in &messages {
+        replies.extend(fetch_replies_to(api, channel_id, m)?.unwrap_or_default());
+    }
+    messages.extend(replies);
+    let authors: Vec<message::SlackMessage> = messages.iter().map(message::extract_message).collect();
+    resolve_user_names(api, &authors, user_names)?;
+    let names = authors
+        .iter()
+        .filter_map(|m| Some((m.user.clone(), user_names.get(&m.user)?.clone())))
+        .collect();
+    archive.save_users(&names)?;
+    let stored = archive.store_messages(channel_id, messages)?;
+    archive.record_sync(archive::ArchivedChannel {
+        id: channel_id.to_string(),
+        name: label.strip_prefix('#').unwrap_or("").to_string(),
+        synced_at: config::now_unix(),
+        latest: stored.latest,
+    })?;
+    Ok(format!(
+        "Synced {}: {} new messages, {} edited, {} in the archive",
+        label, stored.added, stored.updated, stored.total
+    ))
+}
+
+/// The index `slk export --all` keeps in its output directory.
+const EXPORT_INDEX_FILE: &str = "index.json";
+
+/// Exports every channel the user is a member of into `dir`: one file per
+/// channel under `channels/`, and an index listing them that is updated
+/// after each one. When the index shows an earlier run was interrupted, the
+/// channels it finished are skipped; after a finished run, it starts over.
+fn run_export_all(api: &SlackClient, dir: &std::path::Path) -> Result<String, SlkError> {
+    let index_path = dir.join(EXPORT_INDEX_FILE);
+    let previous = std::fs::read_to_string(&index_path).ok().and_then(|c| json::parse(&c).ok());
+    let resuming = previous.as_ref().is_some_and(|index| index.get("finished_at").is_none());
+    let mut done = match &previous {
+        Some(index) if resuming => index.get("channels").cloned().unwrap_or(JsonValue::Array(Vec::new())),
+        _ => JsonValue::Array(Vec::new()),
+    };
+    let started_at = match &previous {
+        Some(index) if resuming => index.get("started_at").cloned().unwrap_or(JsonValue::Null),
+        _ => config::now_unix().into(),
+    };
+    let index = |channels: &JsonValue, finished: bool| {
+        let mut index = JsonValue::object([("started_at", started_at.clone()), ("channels", channels.clone())]);
+        if finished {
+            index.set("finished_at", config::now_unix());
+        }
+        index.pretty() + "\n"
+    };
+    let count = |done: &JsonValue| done.as_array().map_or(0, Vec::len);
+    let channels = member_channels(api)?;
+    let skipped = count(&done);
+    if resuming {
+        info!("Resuming the export in {}: {} channels already done", dir.display(), skipped);
+    }
+    for (id, label) in &channels {
+        if done.as_array().into_iter().flatten().any(|c| c.get("id").and_then(|v| v.as_str()) == Some(id)) {
+            continue;
+        }
+        info!("Exporting {}", label);
+        let (document, summary) = export_channel(api, id)?;
+        let file = format!("channels/{}.json", id);
+        archive::write(&dir.join(&file), &(document.pretty() + "\n"))?;
+        done.push(JsonValue::object([
+            ("id", id.as_str().into()),
+            ("name", label.trim_start_matches('#').into()),
+            ("file", file.into()),
+            ("messages", summary.messages.into()),
+            ("threads", summary.threads.into()),
+            ("users", summary.users.into()),
+        ]));
+        archive::write(&index_path, &index(&done, false))?;
+    }
+    archive::write(&index_path, &index(&done, true))?;
+    let exported = count(&done) - skipped;
+    Ok(match skipped {
+        0 => format!("Exported {} channels to {}", exported, dir.display()),
+        _ => format!("Exported {} channels to {} ({} more were done before)", exported, dir.display(), skipped),
+    })
+}
+
+/// Shows a saved file the way the command that fetches it would, without
+/// the API or a token.
+fn run_render(path: &str, options: &OutputOptions) -> Result<String, SlkError> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| SlkError::from(format!("failed to read {}: {}", path, e)))?;
+    let document =
+        json::parse(&contents).map_err(|e| SlkError::from(format!("{} is not valid JSON: {}", path, e)))?;
+    render_document(&document, options).map_err(|e| SlkError::from(format!("cannot render {}: {}", path, e)))
+}
+
+/// Formats an `slk export` document, or a saved `conversations.history`,
+/// `conversations.replies` or `conversations.list` response. Saved responses
+/// show authors with the cached user names.
+fn render_document(document: &JsonValue, options: &OutputOptions) -> Result<String, SlkError> {
+    if document.get("channel").is_some() && document.get("users").is_some() {
+        return format_export(document, options);
+    }
+    if document.get("messages").is_some() {
+        let messages = message::extract_messages(document)?;
+        return Ok(format_messages(&messages, &cache::load_users(), options));
+    }
+    if document.get("channels").is_some() {
+        return Ok(format_conversations(&message::extract_conversations(document)?, options));
+    }
+    Err(SlkError::from(
+        "expected an slk export or a saved conversations.history, conversations.replies or conversations.list response",
+    ))
+}
+
+/// An export's messages, oldest first, each followed by its thread's
+/// replies (indented in text output), with the names of its users.
+fn format_export(document: &JsonValue, options: &OutputOptions) -> Result<String, SlkError> {
+    let user_names: HashMap<String, String> = document
+        .get("users")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .map(message::extract_user)
+        .map(|u| (u.id.clone(), u.shown_name().to_string()))
+        .collect();
+    let messages = document
+        .get("messages")
+        .and_then(|v| v.as_array())
+        .ok_or(SlkError::from("missing 'messages' array in export"))?;
+    let no_replies = Vec::new();
+    let shown: Vec<(message::SlackMessage, bool)> = messages
+        .iter()
+        .flat_map(|m| {
+            let replies = m.get("replies").and_then(|v| v.as_array()).unwrap_or(&no_replies);
+            std::iter::once((message::extract_message(m), false))
+                .chain(replies.iter().map(|r| (message::extract_message(r), true)))
+        })
+        .filter(|(m, _)| options.shows(m))
+        .take(options.limit.unwrap_or(usize::MAX))
+        .collect();
+    if options.format == OutputFormat::Json {
+        let items = shown.iter().map(|(m, _)| message_json(m, &user_names, options)).collect();
+        return Ok(JsonValue::Array(items).to_string());
+    }
+    Ok(shown
+        .iter()
+        .enumerate()
+        .map(|(i, (m, reply))| {
+            // A reply doesn't continue its parent, nor a message the replies before it.
+            let previous = i.checked_sub(1).map(|i| &shown[i]).filter(|(_, r)| r == reply).map(|(p, _)| p);
+            let line = message_line_at(m, previous, &user_names, options, if *reply { 2 } else { 0 });
+            if *reply { format!("  {}", line) } else { line }
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Says on stderr that the output comes from the archive, and how old it is.
+fn offline_note(what: &str, channel: &archive::ArchivedChannel, archive_ttl: u64, options: &OutputOptions) {
+    let time = message::format_unix_ts_with(&channel.synced_at.to_string(), &options.time_format);
+    eprintln!("Offline: {} as of the last sync at {} (UTC)", what, time);
+    warn_if_stale(channel, archive_ttl);
+}
+
+/// How long after a sync reading the archive starts warning that it may be
+/// missing messages, unless `archive_ttl` says otherwise.
+const DEFAULT_ARCHIVE_TTL_SECS: u64 = 24 * 60 * 60;
+
+fn warn_if_stale(channel: &archive::ArchivedChannel, archive_ttl: u64) {
+    let age = config::now_unix().saturating_sub(channel.synced_at);
+    if age >= archive_ttl {
+        warn!("#{} was last synced {} ago; run `slk sync {}` to catch up", channel.name, describe_age(age), channel.name);
+    }
+}
+
+/// `90` is "1 minute", `7200` "2 hours" and so on, rounded down.
+fn describe_age(secs: u64) -> String {
+    let (n, unit) = match secs {
+        0..3600 => (secs / 60, "minute"),
+        3600..172800 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day"),
+    };
+    format!("{} {}{}", n, unit, if n == 1 { "" } else { "s" })
+}
+
+/// Whether an archived message is a reply in a thread, rather than in the
+/// channel itself (replies also sent to the channel count as the latter).
+fn is_thread_reply(m: &JsonValue) -> bool {
+    let thread_ts = m.get("thread_ts").and_then(|v| v.as_str());
+    thread_ts.is_some_and(|thread_ts| m.get("ts").and_then(|v| v.as_str()) != Some(thread_ts))
+        && m.get("subtype").and_then(|v| v.as_str()) != Some("thread_broadcast")
+}
+
+/// `slk list --offline`: the synced channels, each with when it was synced.
+fn format_archived_channels(channels: &[archive::ArchivedChannel], options: &OutputOptions) -> String {
+    let channels = &channels[..channels.len().min(options.limit.unwrap_or(usize::MAX))];
+    let synced = |c: &archive::ArchivedChannel| message::format_unix_ts_with(&c.synced_at.to_string(), &options.time_format);
+    if options.format == OutputFormat::Json {
+        let items = channels
+            .iter()
+            .map(|c| {
+                JsonValue::object([
+                    ("id", c.id.as_str().into()),
+                    ("name", c.name.as_str().into()),
+                    ("synced", synced(c).into()),
+                ])
+            })
+            .collect();
+        return JsonValue::Array(items).to_string();
+    }
+    channels
+        .iter()
+        .map(|c| format!("{}\t{}\tsynced {}", c.id, c.name, synced(c)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `slk history --offline`: the channel's archived messages, newest first,
+/// leaving out thread replies as `conversations.history` does.
+fn run_history_offline(
+    archive: &archive::Archive,
+    channel_id: &str,
+    options: &OutputOptions,
+) -> Result<String, SlkError> {
+    let archived = archive.messages(channel_id)?;
+    let raw: Vec<&JsonValue> =
+        archived.iter().rev().filter(|m| !is_thread_reply(m)).take(history_limit(options)).collect();
+    if options.count {
+        let shown: Vec<&JsonValue> = raw
+            .into_iter()
+            .filter(|m| options.shows(&message::extract_message(*m)))
+            .take(options.limit.unwrap_or(usize::MAX))
+            .collect();
+        return Ok(format_counts(&shown, options));
+    }
+    let messages: Vec<message::SlackMessage> = raw.into_iter().map(message::extract_message).collect();
+    Ok(format_messages(&messages, &archive.users(), options))
+}
+
+/// `slk thread --offline`: the thread's parent and archived replies.
+fn run_thread_offline(
+    archive: &archive::Archive,
+    channel_id: &str,
+    ts: &str,
+    options: &OutputOptions,
+) -> Result<String, SlkError> {
+    let messages: Vec<message::SlackMessage> = archive
+        .messages(channel_id)?
+        .iter()
+        .filter(|m| {
+            m.get("ts").and_then(|v| v.as_str()) == Some(ts) || m.get("thread_ts").and_then(|v| v.as_str()) == Some(ts)
+        })
+        .map(message::extract_message)
+        .collect();
+    if messages.is_empty() {
+        return Err(SlkError::from(format!("the thread {} is not in the archive of {}", ts, channel_id)));
+    }
+    Ok(format_messages(&messages, &archive.users(), options))
+}
+
+/// Builds the `slk search --local` query from the command line, resolving
+/// channel and user names against the archive alone so it works offline.
+fn search_query(matches: &cli::Matches, text: &str, archive: &archive::Archive) -> Result<search::Query, SlkError> {
+    let mut query = search::Query::new(text);
+    for channel in matches.values("channel") {
+        query.channels.push(archive.find_channel(channel)?.id);
+    }
+    if let Some(from) = matches.value("from") {
+        query.from = Some(archive.find_user(from)?);
+    }
+    query.since = matches.value("since").map(message::parse_date).transpose()?;
+    // `--until` includes the day it names.
+    query.until = matches.value("until").map(|d| Ok::<_, SlkError>(message::parse_date(d)? + 86400)).transpose()?;
+    Ok(query)
+}
+
+/// Searches the archive and formats the matches, oldest first; with
+/// `--limit`, only the newest ones. Messages from more than one channel
+/// are prefixed with the channel's name.
+fn run_search_local(
+    archive: &archive::Archive,
+    query: &search::Query,
+    options: &mut OutputOptions,
+) -> Result<String, SlkError> {
+    let found = search::search(archive, query)?;
+    let skip = found.len().saturating_sub(options.limit.unwrap_or(usize::MAX));
+    let (channels, messages): (Vec<String>, Vec<message::SlackMessage>) = found.into_iter().skip(skip).unzip();
+    options.channel_names = archive
+        .channels()
+        .into_iter()
+        .filter(|c| channels.contains(&c.id))
+        .map(|c| {
+            let name = if c.name.is_empty() { c.id.clone() } else { format!("#{}", c.name) };
+            (c.id, name)
+        })
+        .collect();
+    let user_names = archive.users();
+    let found: Vec<(&str, message::SlackMessage)> = channels.iter().map(String::as_str).zip(messages).collect();
+    if options.format == OutputFormat::Json {
+        let items = found.iter().map(|(c, m)| stream_json(c, m, &user_names, options)).collect();
+        return Ok(JsonValue::Array(items).to_string());
+    }
+    Ok(format_message_stream(&found, &user_names, options).join("\n"))
+}
+
+/// Seconds between polls in `slk watch` unless `--interval` or
+/// `watch_interval` says otherwise.
+const DEFAULT_WATCH_INTERVAL_SECS: u64 = 5;
+
+/// Recent messages `slk watch` shows before waiting for new ones.
+const WATCH_BACKLOG: usize = 10;
+
+/// The poll interval from `--interval`, then `watch_interval` in the config file.
+fn watch_interval(matches: &cli::Matches, configured: Option<u64>) -> Result<std::time::Duration, SlkError> {
+    let secs = match matches.value("interval") {
+        Some(s) => match s.parse::<u64>() {
+            Ok(n) if n > 0 => n,
+            _ => return Err(SlkError::from(format!("invalid --interval '{}': expected a positive number of seconds", s))),
+        },
+        None => configured.unwrap_or(DEFAULT_WATCH_INTERVAL_SECS),
+    };
+    Ok(std::time::Duration::from_secs(secs))
+}
+
+/// What `slk watch` and `slk thread --follow` poll.
+#[derive(Clone, Copy)]
+enum Feed<'a> {
+    Channel(&'a str),
+    Thread { channel_id: &'a str, ts: &'a str },
+}
+
+impl<'a> Feed<'a> {
+    fn channel_id(&self) -> &'a str {
+        match *self {
+            Feed::Channel(channel_id) | Feed::Thread { channel_id, .. } => channel_id,
+        }
+    }
+}
+
+/// Calls to `conversations.history` per minute that watching several
+/// channels stays under (its rate limit tier allows about 50).
+const HISTORY_CALLS_PER_MINUTE: u32 = 50;
+
+/// Whether Slack timestamp `ts` is later than `than`.
+fn ts_after(ts: &str, than: &str) -> bool {
+    message::ts_key(ts) > message::ts_key(than)
+}
+
+/// Messages in `feed` posted after `oldest` (or the latest `limit` when
+/// there is none), oldest first.
+async fn fetch_new_messages(
+    api: &SlackClient<'_>,
+    feed: Feed<'_>,
+    oldest: Option<&str>,
+    limit: usize,
+) -> Result<Vec<message::SlackMessage>, SlkError> {
+    let mut messages = match (feed, oldest) {
+        (Feed::Channel(channel_id), oldest) => {
+            let mut messages = match oldest {
+                // Every page: after a long gap there can be more than one.
+                Some(oldest) => {
+                    let mut pages = api.history_pages(channel_id, Some(oldest));
+                    let mut messages = Vec::new();
+                    while let Some(page) = pages.next_page().await {
+                        messages.extend(response::read::<ConversationsHistoryResponse>(&page?)?.messages);
+                    }
+                    messages
+                }
+                None => {
+                    let raw_json = api.conversations_history_async(channel_id, limit).await?;
+                    response::parse::<ConversationsHistoryResponse>(&raw_json)?.messages
+                }
+            };
+            // History is newest first.
+            messages.reverse();
+            messages
+        }
+        (Feed::Thread { channel_id, ts }, oldest) => {
+            let raw_json = match oldest {
+                Some(oldest) => api.conversations_replies_since_async(channel_id, ts, oldest).await?,
+                None => api.conversations_replies_async(channel_id, ts).await?,
+            };
+            let mut messages = response::parse::<ConversationsRepliesResponse>(&raw_json)?.messages;
+            messages.drain(..messages.len().saturating_sub(limit));
+            messages
+        }
+    };
+    // Replies always include the parent, whatever `oldest` says.
+    if let Some(oldest) = oldest {
+        messages.retain(|m| ts_after(&m.ts, oldest));
+    }
+    Ok(messages)
+}
+
+/// New messages across all of `feeds`, interleaved oldest first, each with
+/// its channel ID. `cursors` holds the newest timestamp seen in each feed
+/// (`None` until the first fetch, which returns up to `backlog` messages).
+/// The feeds are fetched concurrently, and the authors' names looked up.
+fn fetch_new_across<'a>(
+    api: &SlackClient,
+    feeds: &[Feed<'a>],
+    cursors: &mut [Option<String>],
+    backlog: usize,
+    user_names: &mut HashMap<String, String>,
+) -> Result<Vec<(&'a str, message::SlackMessage)>, SlkError> {
+    let first = cursors.iter().all(Option::is_none);
+    let fetched = api.concurrently(&feeds.iter().zip(cursors.iter()).collect::<Vec<_>>(), |api, (feed, oldest)| {
+        fetch_new_messages(api, **feed, oldest.as_deref(), backlog)
+    });
+    let mut batch = Vec::new();
+    for ((feed, oldest), messages) in feeds.iter().zip(cursors.iter_mut()).zip(fetched) {
+        let messages = messages?;
+        if let Some(last) = messages.last() {
+            *oldest = Some(last.ts.clone());
+        } else if oldest.is_none() {
+            // An empty channel: only what is posted from now on is new.
+            *oldest = Some(format!("{}.000000", config::now_unix()));
+        }
+        batch.extend(messages.into_iter().map(|m| (feed.channel_id(), m)));
+    }
+    batch.sort_by_key(|(_, m)| message::ts_key(&m.ts));
+    if first {
+        batch.drain(..batch.len().saturating_sub(backlog));
+    }
+    resolve_user_ids(api, batch.iter().map(|(_, m)| m.user.as_str()), user_names)?;
+    Ok(batch)
+}
+
+/// Prints a line of `slk watch` output, through `--query` if given.
+fn print_line(line: &str, options: &OutputOptions) {
+    match options.query.as_ref().map(|query| query.apply(line)) {
+        None => println!("{}", line),
+        Some(Ok(picked)) if picked.is_empty() => {}
+        Some(Ok(picked)) => println!("{}", picked),
+        Some(Err(e)) => warn!("{}", e),
+    }
+}
+
+fn print_message_stream(
+    messages: &[(&str, message::SlackMessage)],
+    user_names: &HashMap<String, String>,
+    options: &OutputOptions,
+) {
+    for line in format_message_stream(messages, user_names, options) {
+        print_line(&line, options);
+    }
+    for (channel, m) in messages.iter().filter(|(_, m)| options.shows(m)) {
+        if let Some(notifier) = &options.notify
+            && notifier.wants(m)
+        {
+            let author = user_names.get(&m.user).map_or(m.user.clone(), |name| format!("@{}", name));
+            notifier.notify(m, options.channel_name(channel), &author);
+        }
+        if let Some(hook) = &options.exec
+            && hook.wants(m)
+        {
+            let context = hook::Context {
+                channel_id: channel,
+                channel_name: options.channel_name(channel),
+                user_name: user_names.get(&m.user).map(String::as_str),
+                time: &message::format_unix_ts_with(&m.ts, &options.time_format),
+            };
+            hook.run(m, &context, &stream_json(channel, m, user_names, options).to_string());
+        }
+    }
+}
+
+/// How long a Socket Mode read waits before checking for Ctrl-C.
+const SOCKET_POLL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// How long each check of the RTM connection waits, between Socket Mode reads.
+const RTM_POLL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// The first wait before resuming a failed watch, and the longest (the wait
+/// doubles with each failure in a row, with jitter).
+const RESUME_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+const MAX_RESUME_DELAY: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// How often `--mark-read` moves read markers, at most.
+const MARK_READ_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How `slk watch` and `slk thread --follow` wait for new messages.
+struct Follow<'a> {
+    interval: std::time::Duration,
+    /// Receive messages over Socket Mode instead of polling (`--socket`).
+    socket_mode: Option<socket_mode::SocketMode<'a>>,
+    /// Keep the watched channels marked as read (`--mark-read`).
+    mark_read: bool,
+    /// Looks the token up again after a failure, refreshing it if it expired.
+    token: &'a dyn Fn() -> Result<String, SlkError>,
+}
+
+/// Moves each watched channel's read marker up to the newest message seen,
+/// so the Slack client's unread badge stays clear.
+#[derive(Default)]
+struct ReadMarker {
+    /// The newest timestamp seen in each channel since it was last marked.
+    pending: HashMap<String, String>,
+    marked_at: Option<std::time::Instant>,
+    /// Set once marking has failed (e.g. without the `channels:write` scope).
+    failed: bool,
+}
+
+impl ReadMarker {
+    fn saw(&mut self, messages: &[(&str, message::SlackMessage)]) {
+        for (channel, m) in messages {
+            let newest = self.pending.entry(channel.to_string()).or_default();
+            if newest.is_empty() || ts_after(&m.ts, newest) {
+                *newest = m.ts.clone();
+            }
+        }
+    }
+
+    /// Calls `conversations.mark` for the channels with new messages, unless
+    /// it did so within `MARK_READ_INTERVAL` and `now` is false. A failure is
+    /// reported once and turns marking off rather than ending the watch.
+    fn flush(&mut self, api: &SlackClient, now: bool) {
+        let recent = self.marked_at.is_some_and(|t| t.elapsed() < MARK_READ_INTERVAL);
+        if self.failed || self.pending.is_empty() || (recent && !now) {
+            return;
+        }
+        for (channel, ts) in self.pending.drain() {
+            let result = api
+                .conversations_mark(&channel, &ts)
+                .and_then(|raw| response::parse::<response::Empty>(&raw));
+            if let Err(e) = result {
+                warn!("could not mark {} as read, so --mark-read is off: {}", channel, e);
+                self.failed = true;
+                break;
+            }
+        }
+        self.marked_at = Some(std::time::Instant::now());
+    }
+}
+
+/// A running `slk watch` or `slk thread --follow`: what it follows, and how
+/// far it has got.
+struct Watcher<'a> {
+    api: SlackClient<'a>,
+    feeds: &'a [Feed<'a>],
+    options: &'a OutputOptions,
+    user_names: &'a mut HashMap<String, String>,
+    /// The newest timestamp printed in each feed; `None` before the backlog.
+    cursors: Vec<Option<String>>,
+    marker: Option<ReadMarker>,
+    /// Set once the watch has worked; until then, errors end it rather than
+    /// being retried, since they most likely mean a bad channel or token.
+    live: bool,
+    /// Failures since messages last came through, for the backoff.
+    failures: u32,
+    /// Set once RTM couldn't be opened, so typing and presence stay off.
+    rtm_off: bool,
+}
+
+impl Watcher<'_> {
+    /// Prints the messages posted since the cursors (the backlog, the first
+    /// time), in one timeline.
+    fn catch_up(&mut self, backlog: usize) -> Result<(), SlkError> {
+        let mut cursors = self.cursors.clone();
+        let messages = fetch_new_across(&self.api, self.feeds, &mut cursors, backlog, self.user_names)?;
+        self.cursors = cursors;
+        self.failures = 0;
+        print_message_stream(&messages, self.user_names, self.options);
+        if let Some(marker) = &mut self.marker {
+            marker.saw(&messages);
+        }
+        self.flush_marker(false);
+        Ok(())
+    }
+
+    /// Prints a message delivered over Socket Mode, unless it isn't for a
+    /// watched channel or catching up already printed it.
+    fn deliver(&mut self, channel: &str, message: message::SlackMessage) -> Result<(), SlkError> {
+        let Some(i) = self.feeds.iter().position(|f| f.channel_id() == channel) else {
+            return Ok(());
+        };
+        if self.cursors[i].as_ref().is_some_and(|seen| !ts_after(&message.ts, seen)) {
+            return Ok(());
+        }
+        self.cursors[i] = Some(message.ts.clone());
+        resolve_user_names(&self.api, std::slice::from_ref(&message), self.user_names)?;
+        let messages = [(self.feeds[i].channel_id(), message)];
+        print_message_stream(&messages, self.user_names, self.options);
+        if let Some(marker) = &mut self.marker {
+            marker.saw(&messages);
+        }
+        Ok(())
+    }
+
+    /// Prints what someone did in a watched channel, with `--events`.
+    fn show_activity(&mut self, activity: &socket_mode::Activity) -> Result<(), SlkError> {
+        let watched = activity
+            .channel
+            .as_ref()
+            .is_none_or(|c| self.feeds.iter().any(|f| f.channel_id() == c));
+        let options = self.options;
+        let wanted = options.events
+            && options.grep.is_none()
+            && options.mentioning.is_none()
+            && options.from.as_ref().is_none_or(|from| activity.user == *from);
+        if !watched || !wanted {
+            return Ok(());
+        }
+        resolve_user_ids(&self.api, [activity.user.as_str()], self.user_names)?;
+        let ts = format!("{}.000000", config::now_unix());
+        print_line(&format_activity(activity, &ts, self.user_names, options), options);
+        Ok(())
+    }
+
+    /// Opens the RTM connection that brings typing and presence, with
+    /// `--events`. A token that may not use RTM (any but a classic app's) is
+    /// reported once, and the other events still show.
+    fn connect_rtm(&mut self, client: &http::Client) -> Option<websocket::WebSocket> {
+        if !self.options.events || self.rtm_off {
+            return None;
+        }
+        let opened = rtm::connect(client, &self.api).and_then(|ws| ws.set_read_timeout(RTM_POLL).map(|()| ws));
+        match opened {
+            Ok(ws) => Some(ws),
+            Err(e) => {
+                warn!("not showing typing and presence, which need Slack's RTM API: {}", e);
+                self.rtm_off = true;
+                None
+            }
+        }
+    }
+
+    /// Shows what has come over RTM, after subscribing to the presence of
+    /// everyone seen so far if that grew. A closed or failed connection is
+    /// dropped until Socket Mode next reconnects.
+    fn read_rtm(&mut self, rtm: &mut Option<(websocket::WebSocket, usize)>) -> Result<(), SlkError> {
+        let Some((ws, subscribed)) = rtm else {
+            return Ok(());
+        };
+        if self.user_names.len() != *subscribed {
+            let users = self.user_names.keys().map(String::as_str);
+            if let Err(e) = ws.send_text(&rtm::presence_sub(users)) {
+                warn!("RTM connection failed: {}", e);
+                *rtm = None;
+                return Ok(());
+            }
+            *subscribed = self.user_names.len();
+        }
+        loop {
+            match ws.read() {
+                Ok(None) => return Ok(()),
+                Ok(Some(websocket::Message::Text(text))) if !rtm::is_goodbye(&text) => {
+                    if let Some(activity) = rtm::parse_event(&text)? {
+                        self.show_activity(&activity)?;
+                    }
+                }
+                Ok(Some(_)) => {
+                    info!("RTM connection closed");
+                    *rtm = None;
+                    return Ok(());
+                }
+                Err(e) => {
+                    warn!("RTM connection failed: {}", e);
+                    *rtm = None;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn flush_marker(&mut self, now: bool) {
+        if let Some(marker) = &mut self.marker {
+            marker.flush(&self.api, now);
+        }
+    }
+
+    /// Checks for new messages every `interval` until Ctrl-C.
+    fn poll(&mut self, interval: std::time::Duration, backlog: usize) -> Result<(), SlkError> {
+        loop {
+            if !interrupt::sleep(interval) {
+                return Ok(());
+            }
+            self.catch_up(backlog)?;
+        }
+    }
+
+    /// Receives new messages as Socket Mode events until Ctrl-C, catching up
+    /// on anything missed each time it connects. Reconnects whenever Slack
+    /// closes the connection.
+    fn listen(&mut self, socket_mode: &socket_mode::SocketMode, backlog: usize) -> Result<(), SlkError> {
+        loop {
+            let mut ws = socket_mode.connect(&self.api)?;
+            ws.set_read_timeout(SOCKET_POLL)?;
+            info!("Connected to Socket Mode");
+            self.live = true;
+            self.catch_up(backlog)?;
+            let mut rtm = self.connect_rtm(socket_mode.client).map(|ws| (ws, 0));
+            loop {
+                if interrupt::interrupted() {
+                    ws.close();
+                    if let Some((rtm, _)) = &mut rtm {
+                        rtm.close();
+                    }
+                    return Ok(());
+                }
+                self.read_rtm(&mut rtm)?;
+                let Some(frame) = ws.read()? else {
+                    self.flush_marker(false);
+                    continue;
+                };
+                let websocket::Message::Text(text) = frame else { break };
+                let envelope = socket_mode::parse_envelope(&text)?;
+                if let Some(id) = &envelope.envelope_id {
+                    ws.send_text(&socket_mode::ack(id))?;
+                }
+                if envelope.kind == "disconnect" {
+                    break;
+                }
+                if let Some((channel, message)) = envelope.message {
+                    self.deliver(&channel, message)?;
+                }
+                if let Some(activity) = envelope.activity {
+                    self.show_activity(&activity)?;
+                }
+            }
+            info!("Socket Mode connection closed; reconnecting");
+        }
+    }
+}
+
+/// Prints the latest messages in the channels or thread, then each new one
+/// as it arrives, until Ctrl-C. Several channels are polled in turn, no
+/// faster than their combined rate limit allows. When a check or the Socket
+/// Mode connection fails, the watch waits (doubling the wait each time up to
+/// `MAX_RESUME_DELAY`), fetches a fresh token in case it expired, and
+/// resumes from the last message it printed. It gives up when the token
+/// lacks a scope, or is rejected and no newer one is found.
+fn run_watch(
+    api: &SlackClient,
+    feeds: &[Feed],
+    options: &OutputOptions,
+    follow: &Follow,
+    user_names: &mut HashMap<String, String>,
+) -> Result<String, SlkError> {
+    let _guard = interrupt::Guard::start();
+    let backlog = match feeds {
+        [Feed::Thread { .. }] => options.limit.unwrap_or(usize::MAX),
+        _ => options.limit.unwrap_or(WATCH_BACKLOG),
+    };
+    let budget = std::time::Duration::from_secs(60) * feeds.len() as u32 / HISTORY_CALLS_PER_MINUTE;
+    let interval = if budget > follow.interval && follow.socket_mode.is_none() {
+        info!("Checking {} channels every {:.1}s to stay within the rate limit", feeds.len(), budget.as_secs_f64());
+        budget
+    } else {
+        follow.interval
+    };
+    let mut watcher = Watcher {
+        api: api.clone(),
+        feeds,
+        options,
+        user_names,
+        cursors: vec![None; feeds.len()],
+        marker: follow.mark_read.then(ReadMarker::default),
+        live: false,
+        failures: 0,
+        rtm_off: false,
+    };
+    watcher.catch_up(backlog)?;
+    watcher.live = follow.socket_mode.is_none();
+    let policy = slack_api::RetryPolicy {
+        max_retries: u32::MAX,
+        base_delay: RESUME_DELAY,
+        max_delay: MAX_RESUME_DELAY,
+    };
+    loop {
+        let result = match &follow.socket_mode {
+            Some(socket_mode) => watcher.listen(socket_mode, backlog),
+            None => watcher.poll(interval, backlog),
+        };
+        let e = match result {
+            Ok(()) => break,
+            Err(e) if !watcher.live || e.kind == ErrorKind::MissingScope => return Err(e),
+            Err(e) => e,
+        };
+        let delay = policy.backoff(watcher.failures);
+        watcher.failures = watcher.failures.saturating_add(1);
+        warn!("{}; resuming in {:.1}s", e, delay.as_secs_f64());
+        if !interrupt::sleep(delay) {
+            break;
+        }
+        match (follow.token)() {
+            // Slack rejected the token and there is no newer one: waiting won't help.
+            Ok(token) if e.kind == ErrorKind::Auth && token == watcher.api.token() => return Err(e),
+            Ok(token) => watcher.api = watcher.api.with_token(&token),
+            Err(e) => warn!("could not refresh the token: {}", e),
+        }
+    }
+    watcher.flush_marker(true);
+    Ok(String::new())
+}
+
+/// True when a pasted URL's host differs from the workspace the stored
+/// token was issued for.
+fn workspace_mismatch(identity: &message::AuthIdentity, host: &str) -> bool {
+    match url::workspace_host(&identity.url) {
+        Some(expected) => !expected.eq_ignore_ascii_case(host),
+        None => false,
+    }
+}
+
+/// Warns when a thread URL points at a different workspace than the stored
+/// login. Tokens supplied through the environment are not checked.
+fn warn_on_workspace_mismatch(host: &str, kind: TokenKind) -> Result<(), SlkError> {
+    let env_var = match kind {
+        TokenKind::User => "SLACK_TOKEN",
+        TokenKind::Bot => "SLACK_BOT_TOKEN",
+    };
+    if std::env::var(env_var).is_ok_and(|t| !t.is_empty()) {
+        return Ok(());
+    }
+    if let Some(identity) = config::load_credentials()?.and_then(|c| c.identity)
+        && workspace_mismatch(&identity, host)
+    {
+        warn!(
+            "this URL is for {} but you are logged in to {} ({})",
+            host, identity.team, identity.url
+        );
+    }
+    Ok(())
+}
+
+/// How deep aliases may refer to other aliases.
+const MAX_ALIAS_DEPTH: usize = 10;
+
+/// Replaces a user-defined command with its expansion, following aliases that
+/// refer to other aliases. Arguments around the alias are kept; built-in
+/// commands cannot be shadowed.
+fn expand_alias(
+    mut args: Vec<String>,
+    aliases: &[(String, Vec<String>)],
+) -> Result<Vec<String>, SlkError> {
+    let mut seen: Vec<String> = Vec::new();
+    while let Some(index) = cli::command_index(&args) {
+        let name = args[index].clone();
+        if cli::find_command(&name).is_some() {
+            break;
+        }
+        let Some((_, expansion)) = aliases.iter().find(|(n, _)| *n == name) else {
+            break;
+        };
+        if seen.contains(&name) {
+            seen.push(name);
+            return Err(SlkError::from(format!(
+                "alias loop: {}",
+                seen.join(" -> ")
+            )));
+        }
+        if seen.len() >= MAX_ALIAS_DEPTH {
+            return Err(SlkError::from(format!(
+                "alias '{}' nests more than {} levels deep",
+                seen[0], MAX_ALIAS_DEPTH
+            )));
+        }
+        seen.push(name);
+        args.splice(index..index + 1, expansion.iter().cloned());
+    }
+    Ok(args)
+}
+
+fn run_list_aliases(aliases: &[(String, Vec<String>)]) -> String {
+    if aliases.is_empty() {
+        return "No aliases defined. Add an \"aliases\" table to the config file.".to_string();
+    }
+    aliases
+        .iter()
+        .map(|(name, expansion)| format!("{}\t{}", name, expansion.join(" ")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Strings are shown as-is, everything else as JSON.
+fn setting_to_string(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn run_config(action: ConfigAction) -> Result<String, SlkError> {
+    match action {
+        ConfigAction::Get { key } => config::get_setting(&key)?
+            .map(|value| setting_to_string(&value))
+            .ok_or_else(|| SlkError::from(format!("'{}' is not set", key))),
+        ConfigAction::Set { key, value } => {
+            let path = config::set_setting(&key, &value)?;
+            Ok(format!("Set {} in {}", key, path.display()))
+        }
+        ConfigAction::List => {
+            let settings = config::list_settings()?;
+            if settings.is_empty() {
+                return Ok("No settings in the config file.".to_string());
+            }
+            Ok(settings
+                .iter()
+                .map(|(key, value)| format!("{} = {}", key, setting_to_string(value)))
+                .collect::<Vec<_>>()
+                .join("\n"))
+        }
+    }
+}
+
+/// A cache that can't be written only costs a few extra API calls next time.
+fn warn_on_cache_error<T>(result: Result<T, SlkError>) {
+    if let Err(e) = result {
+        warn!("{}", e);
+    }
+}
+
+/// Runs `f` with the cached user names, saving any it adds.
+fn with_user_cache(
+    f: impl FnOnce(&mut HashMap<String, String>) -> Result<String, SlkError>,
+) -> Result<String, SlkError> {
+    let mut names = cache::load_users();
+    let known = names.len();
+    let output = f(&mut names)?;
+    if names.len() != known {
+        warn_on_cache_error(cache::save_users(&names));
+    }
+    Ok(output)
+}
+
+fn run(args: Vec<String>) -> Result<String, SlkError> {
+    // `slk config` must still work when the config file doesn't load, so the
+    // problem can be fixed with it.
+    let settings = if cli::command_index(&args).is_some_and(|i| args[i] == "config") {
+        config::Config::default()
+    } else {
+        config::load_config()?
+    };
+    let args = expand_alias(args, &settings.aliases)?;
+    let (command, matches) = parse_args(args).map_err(|e| e.or_kind(ErrorKind::Usage))?;
+    let command = match command {
+        Command::Help(text) => return Ok(text),
+        Command::Version => return Ok(cli::version()),
+        Command::Config(action) => return run_config(action),
+        Command::Completions(shell) => return Ok(completions::script(shell)),
+        Command::CompleteChannels { prefix } => {
+            return Ok(completions::channel_candidates(&cache::load_channels(), &prefix).join("\n"));
+        }
+        command => command,
+    };
+    log::set_verbosity(if matches.flag("trace") {
+        log::Level::Debug as usize
+    } else {
+        matches.count("verbose")
+    });
+    let trace = log::enabled(log::Level::Debug);
+    if matches.flag("refresh") {
+        cache::set_ttls(0, 0);
+    } else {
+        cache::set_ttls(
+            settings.users_cache_ttl.unwrap_or(cache::DEFAULT_USERS_TTL_SECS),
+            settings.channels_cache_ttl.unwrap_or(cache::DEFAULT_CHANNELS_TTL_SECS),
+        );
+    }
+    let archive_ttl = settings.archive_ttl.unwrap_or(DEFAULT_ARCHIVE_TTL_SECS);
+    // Bad option values and combinations are usage errors, like bad arguments.
+    let usage = |e: SlkError| e.or_kind(ErrorKind::Usage);
+    let token_kind = match matches.value("as") {
+        Some(kind) => TokenKind::parse(kind).map_err(usage)?,
+        None => TokenKind::User,
+    };
+    let defaults = settings.defaults;
+    let mut options = OutputOptions::from_matches(&matches, &defaults).map_err(usage)?;
+    options.save_files = SaveFiles::from_matches(&matches).map_err(usage)?;
+    if matches.flag("offline") && (options.save_files.is_some() || matches.flag("follow") || matches.flag("report")) {
+        return Err(usage(SlkError::from("--offline can't be combined with --follow, --report or --save-files")));
+    }
+    let raw = matches.flag("raw");
+    if raw && (matches.flag("offline") || matches.flag("follow") || matches.flag("report") || matches.flag("from")) {
+        return Err(usage(SlkError::from("--raw can't be combined with --offline, --follow, --report or --from")));
+    }
+    if matches.flag("app") && (raw || matches.flag("from")) {
+        return Err(usage(SlkError::from("--app can't be combined with --raw or --from")));
+    }
+    if matches.flag("threads") && (raw || matches.flag("offline")) {
+        return Err(usage(SlkError::from("--threads can't be combined with --raw or --offline")));
+    }
+    if matches.flag("links") && (raw || matches.flag("offline") || matches.flag("follow") || matches.flag("report")) {
+        return Err(usage(SlkError::from("--links can't be combined with --raw, --offline, --follow or --report")));
+    }
+    if options.count && (raw || matches.flag("threads") || matches.flag("links")) {
+        return Err(usage(SlkError::from("--count can't be combined with --raw, --threads or --links")));
+    }
+    if options.compact && raw {
+        return Err(usage(SlkError::from("--compact can't be combined with --raw")));
+    }
+    if raw && options.save_files.is_some() {
+        return Err(usage(SlkError::from("--raw can't be combined with --save-files")));
+    }
+    let reports = matches!(command, Command::Stats { .. } | Command::Activity)
+        || (options.count && matches!(command, Command::ShowHistory { .. }));
+    if options.format == OutputFormat::Csv && !reports {
+        return Err(usage(SlkError::from(
+            "--format csv is only supported by slk stats, slk activity and slk history --count",
+        )));
+    }
+    if options.query.is_some() && options.format != OutputFormat::Json && !raw && !matches!(command, Command::Api { .. }) {
+        return Err(usage(SlkError::from("--query works on JSON output; add --format json")));
+    }
+    let client = http::Client::configured()?;
+    let tracing = trace::Tracing::new(&client);
+    let transport: &dyn Transport = if trace { &tracing } else { &client };
+    let base_url = config::load_api_base_url()?;
+    debug!("Using the API at {}", base_url);
+    let token = || resolve_token(transport, &base_url, token_kind);
+    let connect = || token().map(|token| SlackClient::new(transport, &base_url, &token));
+    let output = match command {
+        Command::Login { token: None, bot, no_browser } => {
+            run_login(transport, &base_url, bot, no_browser)
+        }
+        Command::Login { token: Some(token), .. } => {
+            let token = if token.is_empty() {
+                read_token_from_stdin()?
+            } else {
+                token
+            };
+            run_login_with_token(transport, &base_url, &token)
+        }
+        Command::Logout => run_logout(transport, &base_url),
+        Command::Webhook { url, text } => run_webhook(transport, &url, &message_text(text)?),
+        Command::ListAliases => Ok(run_list_aliases(&settings.aliases)),
+        Command::Help(_)
+        | Command::Version
+        | Command::Config(_)
+        | Command::Completions(_)
+        | Command::CompleteChannels { .. } => unreachable!("handled before connecting"),
+        Command::ListConversations if matches.flag("offline") => {
+            let channels = archive::Archive::open()?.channels();
+            if channels.is_empty() {
+                return Err(SlkError::from("nothing has been synced yet; run `slk sync <channel>` first"));
+            }
+            eprintln!("Offline: the channels synced with slk sync");
+            Ok(format_archived_channels(&channels, &options))
+        }
+        Command::ListConversations if raw => raw_response(&connect()?.conversations_list()?),
+        Command::ListConversations => {
+            let conversations = fetch_conversations(&connect()?)?;
+            warn_on_cache_error(cache::save_channels(&conversations));
+            Ok(format_conversations(&conversations, &options))
+        }
+        Command::ShowHistory { channel_id } => {
+            let channel_id = channel_id.or(defaults.default_channel).ok_or_else(|| {
+                usage(SlkError::from(
+                    "usage: slk history <channel> (or set default_channel in the config file)",
+                ))
+            })?;
+            if matches.flag("offline") {
+                let archive = archive::Archive::open()?;
+                let channel = archive.find_channel(&channel_id)?;
+                if let Some(from) = matches.value("from") {
+                    options.from = Some(archive.find_user(from)?);
+                }
+                offline_note(&format!("#{}", channel.name), &channel, archive_ttl, &options);
+                run_history_offline(&archive, &channel.id, &options)
+            } else {
+                let api = connect()?;
+                let channel_id = resolve_channel(&api, &channel_id)?;
+                if raw {
+                    raw_response(&api.conversations_history(&channel_id, history_limit(&options))?)
+                } else {
+                    if matches.flag("links") {
+                        options.links = Some(MessageLinks::new(&api, &channel_id)?);
+                    }
+                    with_user_cache(|names| {
+                        if let Some(from) = matches.value("from") {
+                            options.from = Some(resolve_user(&api, from, names)?);
+                        }
+                        let threads = matches.flag("threads");
+                        let history = run_show_history(&api, &channel_id, threads, &options, names)?;
+                        if options.format != OutputFormat::Text || matches.flag("no-header") {
+                            return Ok(history);
+                        }
+                        let header = channel_header(&api, &channel_id, &options, names)?;
+                        Ok(if history.is_empty() { header } else { format!("{}\n{}", header, history) })
+                    })
+                }
+            }
+        }
+        Command::Watch { channel_id } => {
+            let all_member_channels = matches.flag("all-member-channels");
+            let list = matches.value("channels");
+            if list.is_some() && all_member_channels {
+                return Err(usage(SlkError::from("--channels and --all-member-channels cannot be combined")));
+            }
+            if (list.is_some() || all_member_channels) && channel_id.is_some() {
+                return Err(usage(SlkError::from(
+                    "give either a channel or --channels/--all-member-channels, not both",
+                )));
+            }
+            let channels: Vec<String> = match list {
+                Some(list) => {
+                    let channels: Vec<String> =
+                        list.split(',').map(str::trim).filter(|c| !c.is_empty()).map(String::from).collect();
+                    if channels.is_empty() {
+                        let e = SlkError::from("--channels needs a comma-separated list of channels");
+                        return Err(usage(e));
+                    }
+                    channels
+                }
+                None if all_member_channels => Vec::new(),
+                None => vec![channel_id.or(defaults.default_channel).ok_or_else(|| {
+                    usage(SlkError::from(
+                        "usage: slk watch <channel> (or set default_channel in the config file)",
+                    ))
+                })?],
+            };
+            let interval = watch_interval(&matches, settings.watch_interval).map_err(usage)?;
+            let notify = matches.flag("notify") || matches.flag("keyword");
+            if let Some(pattern) = matches.value("grep") {
+                options.grep = Some(
+                    regex_lite::Regex::new(pattern)
+                        .map_err(|e| usage(SlkError::from(format!("invalid --grep '{}': {}", pattern, e))))?,
+                );
+            }
+            let socket_mode = if matches.flag("socket") {
+                Some(socket_mode::SocketMode { client: &client, app_token: config::load_app_token()? })
+            } else {
+                None
+            };
+            let follow = Follow { interval, socket_mode, mark_read: matches.flag("mark-read"), token: &token };
+            options.events = matches.flag("events");
+            if options.events && follow.socket_mode.is_none() {
+                return Err(usage(SlkError::from("--events needs --socket: polling only sees messages")));
+            }
+            options.exec = matches.value("exec").map(|command| hook::Hook::new(command.to_string()));
+            let api = connect()?;
+            let mut channels = if all_member_channels {
+                member_channels(&api)?
+            } else {
+                channels
+                    .iter()
+                    .map(|c| Ok((resolve_channel(&api, c)?, channel_label(c))))
+                    .collect::<Result<Vec<_>, SlkError>>()?
+            };
+            channels.sort_by(|a, b| a.0.cmp(&b.0));
+            channels.dedup_by(|a, b| a.0 == b.0);
+            let feeds: Vec<Feed> = channels.iter().map(|(id, _)| Feed::Channel(id)).collect();
+            options.channel_names = channels.iter().cloned().collect();
+            with_user_cache(|names| {
+                if let Some(from) = matches.value("from") {
+                    options.from = Some(resolve_user(&api, from, names)?);
+                }
+                if notify || matches.flag("mention-only") {
+                    let user_id = verify_token(&api)?.user_id;
+                    if matches.flag("mention-only") {
+                        options.mentioning = Some(user_id.clone());
+                    }
+                    if notify {
+                        let mut keywords = settings.notify_keywords.clone();
+                        keywords.extend(matches.values("keyword").into_iter().map(String::from));
+                        options.notify = Some(notify::Notifier::new(user_id, keywords));
+                    }
+                }
+                run_watch(&api, &feeds, &options, &follow, names)
+            })
+        }
+        Command::Activity => {
+            let since = matches.value("since").unwrap_or(DEFAULT_ACTIVITY_SINCE);
+            let since = stats::parse_time(since, config::now_unix(), false).map_err(usage)?;
+            let api = connect()?;
+            with_user_cache(|names| run_activity(&api, since, &options, names))
+        }
+        Command::Recent => {
+            let now = config::now_unix();
+            let since = matches.value("since").map(|since| stats::parse_time(since, now, false));
+            let since = since.transpose().map_err(usage)?;
+            let api = connect()?;
+            with_user_cache(|names| run_recent(&api, since, &options, names))
+        }
+        Command::Sync { channel_id } => {
+            let channel = channel_id.or(defaults.default_channel).ok_or_else(|| {
+                usage(SlkError::from(
+                    "usage: slk sync <channel> (or set default_channel in the config file)",
+                ))
+            })?;
+            let api = connect()?;
+            let channel_id = resolve_channel(&api, &channel)?;
+            let edit_window = match matches.value("edit-window") {
+                Some(s) => s.parse::<u64>().map_err(|_| {
+                    usage(SlkError::from(format!("invalid --edit-window '{}': expected a number of hours", s)))
+                })?,
+                None => DEFAULT_EDIT_WINDOW_HOURS,
+            };
+            let archive = archive::Archive::open()?;
+            let sync = SyncOptions {
+                archive: &archive,
+                edit_window: (!matches.flag("full")).then_some(edit_window * 3600),
+            };
+            with_user_cache(|names| {
+                run_sync(&api, &channel_id, &channel_label(&channel), &sync, names)
+            })
+        }
+        Command::Export { channel_id } if matches.flag("all") => {
+            if channel_id.is_some() {
+                return Err(usage(SlkError::from("give either a channel or --all, not both")));
+            }
+            let out = matches
+                .value("out")
+                .ok_or_else(|| usage(SlkError::from("--all needs --out <dir> to write the channels to")))?;
+            run_export_all(&connect()?, std::path::Path::new(out))
+        }
+        Command::Export { channel_id } => {
+            let channel = channel_id.or(defaults.default_channel).ok_or_else(|| {
+                usage(SlkError::from(
+                    "usage: slk export <channel> [--out <file>] (or set default_channel in the config file)",
+                ))
+            })?;
+            let api = connect()?;
+            let channel_id = resolve_channel(&api, &channel)?;
+            run_export(&api, &channel_id, &channel_label(&channel), matches.value("out"))
+        }
+        Command::Stats { channel_id } => {
+            let channel = channel_id.or(defaults.default_channel).ok_or_else(|| {
+                usage(SlkError::from(
+                    "usage: slk stats <channel> (or set default_channel in the config file)",
+                ))
+            })?;
+            let now = config::now_unix();
+            let since = matches.value("since").unwrap_or(DEFAULT_STATS_SINCE);
+            let since = stats::parse_time(since, now, false).map_err(usage)?;
+            let until = matches.value("until").map(|until| stats::parse_time(until, now, true));
+            let until = until.transpose().map_err(usage)?.unwrap_or(now);
+            if since >= until {
+                return Err(usage(SlkError::from("--since must be before --until")));
+            }
+            let report = stats_report(&matches).map_err(usage)?;
+            if matches.flag("offline") {
+                let archive = archive::Archive::open()?;
+                let channel = archive.find_channel(&channel)?;
+                offline_note(&format!("#{}", channel.name), &channel, archive_ttl, &options);
+                let (messages, replies) = split_replies(archive.messages(&channel.id)?);
+                let history = stats::History { messages: &messages, replies: &replies, since, until };
+                Ok(stats::report(&history, report, options.format, &archive.users()))
+            } else {
+                let api = connect()?;
+                let channel_id = resolve_channel(&api, &channel)?;
+                with_user_cache(|names| run_stats(&api, &channel_id, since, until, report, &options, names))
+            }
+        }
+        Command::Render { path } => run_render(&path, &options),
+        Command::Search { query } => {
+            if !matches.flag("local") {
+                return Err(usage(SlkError::from(
+                    "slk search only searches the local archive for now: add --local (and run `slk sync` first)",
+                )));
+            }
+            let archive = archive::Archive::open()?;
+            let query = search_query(&matches, &query, &archive)?;
+            if query.is_empty() {
+                return Err(usage(SlkError::from(cli::usage(cli::find_command("search")))));
+            }
+            for channel in archive.channels() {
+                if query.channels.is_empty() || query.channels.contains(&channel.id) {
+                    warn_if_stale(&channel, archive_ttl);
+                }
+            }
+            run_search_local(&archive, &query, &mut options)
+        }
+        Command::ShowThread { channel_id, ts, .. } if matches.flag("offline") => {
+            let archive = archive::Archive::open()?;
+            let channel = archive.find_channel(&channel_id)?;
+            if let Some(from) = matches.value("from") {
+                options.from = Some(archive.find_user(from)?);
+            }
+            offline_note(&format!("the thread in #{}", channel.name), &channel, archive_ttl, &options);
+            run_thread_offline(&archive, &channel.id, &ts, &options)
+        }
+        Command::ShowThread { channel_id, ts, host } if raw => {
+            if let Some(host) = &host {
+                warn_on_workspace_mismatch(host, token_kind)?;
+            }
+            let api = connect()?;
+            let channel_id = resolve_channel(&api, &channel_id)?;
+            raw_response(&api.conversations_replies(&channel_id, &ts)?)
+        }
+        Command::ShowThread { channel_id, ts, host } => {
+            if let Some(host) = &host {
+                warn_on_workspace_mismatch(host, token_kind)?;
+            }
+            let follow = matches.flag("follow");
+            let interval = watch_interval(&matches, settings.watch_interval).map_err(usage)?;
+            let api = connect()?;
+            let label = channel_label(&channel_id);
+            let channel_id = resolve_channel(&api, &channel_id)?;
+            with_user_cache(|names| {
+                if let Some(out) = matches.value("report") {
+                    let messages = fetch_report_thread(&api, &channel_id, &ts, names)?;
+                    let thread = report::Thread {
+                        channel: &label,
+                        permalink: host.map(|h| format!("https://{}/archives/{}/p{}", h, channel_id, ts.replace('.', ""))),
+                        messages: &messages,
+                        user_names: names,
+                        time_format: &options.time_format,
+                    };
+                    return write_report(out, &report::markdown(&thread), messages.len());
+                }
+                if let Some(from) = matches.value("from") {
+                    options.from = Some(resolve_user(&api, from, names)?);
+                }
+                if matches.flag("links") {
+                    options.links = Some(MessageLinks::new(&api, &channel_id)?);
+                }
+                if follow {
+                    let feeds = [Feed::Thread { channel_id: &channel_id, ts: &ts }];
+                    let follow = Follow { interval, socket_mode: None, mark_read: false, token: &token };
+                    return run_watch(&api, &feeds, &options, &follow, names);
+                }
+                run_show_thread(&api, &channel_id, &ts, &options, names)
+            })
+        }
+        Command::ShowMessage { channel_id, ts, thread_ts, host } => {
+            let context = match matches.value("context") {
+                Some(n) => n.parse::<usize>().map_err(|_| {
+                    usage(SlkError::from(format!("invalid --context '{}': expected a number of messages", n)))
+                })?,
+                None => 0,
+            };
+            if let Some(host) = &host {
+                warn_on_workspace_mismatch(host, token_kind)?;
+            }
+            let api = connect()?;
+            let channel_id = resolve_channel(&api, &channel_id)?;
+            with_user_cache(|names| {
+                run_show_message(&api, &channel_id, &ts, thread_ts.as_deref(), context, &options, names)
+            })
+        }
+        Command::OpenApp { channel_id, ts, link } => {
+            if let Some(host) = link.as_deref().and_then(url::workspace_host) {
+                warn_on_workspace_mismatch(host, token_kind)?;
+            }
+            let api = connect()?;
+            let channel_id = resolve_channel(&api, &channel_id)?;
+            run_open_app(&verify_token(&api)?, &channel_id, ts.as_deref(), link.as_deref())
+        }
+        Command::ShowUser { user } => {
+            let api = connect()?;
+            with_user_cache(|names| run_show_user(&api, &user, &options, names))
+        }
+        Command::Permalink { channel_id, ts } => {
+            let api = connect()?;
+            let channel_id = resolve_channel(&api, &channel_id)?;
+            run_permalink(&api, &channel_id, &ts)
+        }
+        Command::Send { channel_id, text } => {
+            let blocks = matches.value("blocks").map(read_blocks).transpose()?;
+            let text = message_text(text)?;
+            let api = connect()?;
+            let channel_id = resolve_channel(&api, &channel_id)?;
+            with_user_cache(|names| {
+                let text = if matches.flag("no-mentions") { text } else { link_mentions(&api, &text, names) };
+                let post = slack_api::PostOptions { blocks: blocks.as_deref(), ..Default::default() };
+                run_send(&api, &channel_id, &text, &post)
+            })
+        }
+        Command::Reply { channel_id, ts, host, text } => {
+            if let Some(host) = host {
+                warn_on_workspace_mismatch(&host, token_kind)?;
+            }
+            let text = message_text(text)?;
+            let api = connect()?;
+            let channel_id = resolve_channel(&api, &channel_id)?;
+            with_user_cache(|names| {
+                let text = if matches.flag("no-mentions") { text } else { link_mentions(&api, &text, names) };
+                let post = slack_api::PostOptions {
+                    thread_ts: Some(&ts),
+                    broadcast: matches.flag("broadcast"),
+                    ..Default::default()
+                };
+                run_send(&api, &channel_id, &text, &post)
+            })
+        }
+        Command::Api { method, params } => run_api(&connect()?, &method, &params, matches.flag("pretty")),
+    }?;
+    let output = match &options.query {
+        Some(query) if !output.is_empty() => query.apply(&output)?,
+        _ => output,
+    };
+    if matches.flag("copy") {
+        copy_output(&output);
+    }
+    Ok(output)
+}
+
+/// Runs the command given in the process arguments and exits with its status.
+pub fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match run(args) {
+        Ok(output) if output.is_empty() => {}
+        Ok(output) => println!("{}", output),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(e.kind.exit_code());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args_thread_with_url() {
+        let args = vec![
+            "slk".to_string(),
+            "thread".to_string(),
+            "https://myteam.slack.com/archives/C081VT5GLQH/p1770689887565249".to_string(),
+        ];
+        let result = parse_args(args).unwrap().0;
+        match result {
+            Command::ShowThread { channel_id, ts, host } => {
+                assert_eq!(channel_id, "C081VT5GLQH");
+                assert_eq!(ts, "1770689887.565249");
+                assert_eq!(host.as_deref(), Some("myteam.slack.com"));
+            }
+            _ => panic!("expected ShowThread"),
+        }
+    }
+
+    #[test]
+    fn test_parse_args_thread_with_ids() {
+        let args = vec![
+            "slk".to_string(),
+            "thread".to_string(),
+            "C081VT5GLQH".to_string(),
+            "1770689887.565249".to_string(),
+        ];
+        let result = parse_args(args).unwrap().0;
+        match result {
+            Command::ShowThread { channel_id, ts, host } => {
+                assert_eq!(channel_id, "C081VT5GLQH");
+                assert_eq!(ts, "1770689887.565249");
+                assert_eq!(host, None);
+            }
+            _ => panic!("expected ShowThread"),
+        }
+    }
+
+    #[test]
+    fn test_parse_args_thread_missing_args() {
+        let args = vec!["slk".to_string(), "thread".to_string()];
+        assert!(parse_args(args).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_config() {
+        let args = |rest: &[&str]| {
+            let mut args = vec!["slk".to_string(), "config".to_string()];
+            args.extend(rest.iter().map(|s| s.to_string()));
+            args
+        };
+        match parse_args(args(&["set", "limit", "20"])).unwrap().0 {
+            Command::Config(ConfigAction::Set { key, value }) => {
+                assert_eq!(key, "limit");
+                assert_eq!(value, "20");
+            }
+            _ => panic!("expected config set"),
+        }
+        assert!(matches!(
+            parse_args(args(&["get", "limit"])).unwrap().0,
+            Command::Config(ConfigAction::Get { .. })
+        ));
+        assert!(matches!(
+            parse_args(args(&["list"])).unwrap().0,
+            Command::Config(ConfigAction::List)
+        ));
+        assert!(parse_args(args(&["set", "limit"])).is_err());
+        assert!(parse_args(args(&[])).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_unknown_command() {
+        let args = vec!["slk".to_string(), "foo".to_string()];
+        assert!(parse_args(args).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_login() {
+        let args = vec!["slk".to_string(), "login".to_string()];
+        let result = parse_args(args).unwrap().0;
+        assert!(matches!(result, Command::Login { token: None, bot: false, no_browser: false }));
+    }
+
+    #[test]
+    fn test_parse_args_login_with_token() {
+        let args = vec![
+            "slk".to_string(),
+            "login".to_string(),
+            "--token".to_string(),
+            "xoxp-123".to_string(),
+        ];
+        match parse_args(args).unwrap().0 {
+            Command::Login { token, .. } => assert_eq!(token.as_deref(), Some("xoxp-123")),
+            _ => panic!("expected Login"),
+        }
+    }
+
+    #[test]
+    fn test_parse_args_login_with_token_from_stdin() {
+        let args = vec!["slk".to_string(), "login".to_string(), "--token".to_string()];
+        match parse_args(args).unwrap().0 {
+            Command::Login { token, .. } => assert_eq!(token.as_deref(), Some("")),
+            _ => panic!("expected Login"),
+        }
+    }
+
+    #[test]
+    fn test_run_login_with_token_rejects_non_slack_token() {
+        let transport = http::FakeTransport::new();
+        let err =
+            run_login_with_token(&transport, config::DEFAULT_API_BASE_URL, "hunter2").unwrap_err();
+        assert!(err.message.contains("doesn't look like a Slack token"));
+        assert!(transport.requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_run_login_with_token_invalid_auth() {
+        let transport = http::FakeTransport::new()
+            .respond("auth.test", r#"{"ok": false, "error": "invalid_auth"}"#);
+        let err = run_login_with_token(&transport, config::DEFAULT_API_BASE_URL, "xoxp-bad")
+            .unwrap_err();
+        assert!(err.message.contains("invalid_auth"));
+    }
+
+    #[test]
+    fn test_parse_args_list() {
+        let args = vec!["slk".to_string(), "list".to_string()];
+        let result = parse_args(args).unwrap().0;
+        assert!(matches!(result, Command::ListConversations));
+    }
+
+    #[test]
+    fn test_parse_args_history() {
+        let args = vec!["slk".to_string(), "history".to_string(), "C081VT5GLQH".to_string()];
+        let result = parse_args(args).unwrap().0;
+        match result {
+            Command::ShowHistory { channel_id } => {
+                assert_eq!(channel_id.as_deref(), Some("C081VT5GLQH"))
+            }
+            _ => panic!("expected ShowHistory"),
+        }
+    }
+
+    #[test]
+    fn test_parse_args_history_missing_channel_id() {
+        let args = vec!["slk".to_string(), "history".to_string()];
+        let result = parse_args(args).unwrap().0;
+        assert!(matches!(result, Command::ShowHistory { channel_id: None }));
+    }
+
+    #[test]
+    fn test_parse_args_open() {
+        let open = |url: &str| parse_args(["slk", "open", url].map(String::from).into()).unwrap().0;
+        assert!(matches!(
+            open("https://myteam.slack.com/archives/C081VT5GLQH"),
+            Command::ShowHistory { channel_id: Some(id) } if id == "C081VT5GLQH"
+        ));
+        assert!(matches!(
+            open("https://myteam.slack.com/archives/C081VT5GLQH/p1770689887565249"),
+            Command::ShowThread { ts, host: Some(_), .. } if ts == "1770689887.565249"
+        ));
+        assert!(matches!(
+            open("https://myteam.slack.com/archives/C081VT5GLQH/p1770689900000100?thread_ts=1770689887.565249"),
+            Command::ShowThread { ts, .. } if ts == "1770689887.565249"
+        ));
+        let open_app = |target: &str| parse_args(["slk", "open", "--app", target].map(String::from).into()).unwrap().0;
+        assert!(matches!(
+            open_app("#general"),
+            Command::OpenApp { channel_id, ts: None, link: None } if channel_id == "#general"
+        ));
+        assert!(matches!(
+            open_app("https://myteam.slack.com/archives/C081VT5GLQH/p1770689900000100?thread_ts=1770689887.565249"),
+            Command::OpenApp { channel_id, ts: Some(ts), link: Some(_) }
+                if channel_id == "C081VT5GLQH" && ts == "1770689900.000100"
+        ));
+    }
+
+    #[test]
+    fn test_parse_args_channel_url() {
+        let args: Vec<String> =
+            ["slk", "history", "https://myteam.slack.com/archives/C081VT5GLQH"].map(String::from).into();
+        assert!(matches!(
+            parse_args(args).unwrap().0,
+            Command::ShowHistory { channel_id: Some(id) } if id == "C081VT5GLQH"
+        ));
+        let args: Vec<String> =
+            ["slk", "watch", "https://myteam.slack.com/archives/C081VT5GLQH/"].map(String::from).into();
+        assert!(matches!(parse_args(args).unwrap().0, Command::Watch { channel_id: Some(id) } if id == "C081VT5GLQH"));
+        let url = "https://myteam.slack.com/archives/C081VT5GLQH/p1770689887565249";
+        let args: Vec<String> = ["slk", "history", url].map(String::from).into();
+        assert!(parse_args(args).is_err_and(|e| e.message.contains("slk open")));
+    }
+
+    #[test]
+    fn test_parse_args_send_and_reply() {
+        let args: Vec<String> = ["slk", "send", "#general", "hello", "world"].map(String::from).into();
+        match parse_args(args).unwrap().0 {
+            Command::Send { channel_id, text } => {
+                assert_eq!(channel_id, "#general");
+                assert_eq!(text, "hello world");
+            }
+            _ => panic!("expected Send"),
+        }
+        let hook = "https://hooks.slack.com/x";
+        let args: Vec<String> = ["slk", "webhook", hook, "build", "ok"].map(String::from).into();
+        match parse_args(args).unwrap().0 {
+            Command::Webhook { url, text } => assert_eq!((url.as_str(), text.as_str()), (hook, "build ok")),
+            _ => panic!("expected Webhook"),
+        }
+        assert!(parse_args(["slk", "webhook", hook].map(String::from).into()).is_err());
+        let args: Vec<String> = ["slk", "reply", "C1", "1770689887.565249", "-"].map(String::from).into();
+        match parse_args(args).unwrap().0 {
+            Command::Reply { channel_id, ts, host, text } => {
+                assert_eq!((channel_id.as_str(), ts.as_str(), text.as_str()), ("C1", "1770689887.565249", "-"));
+                assert_eq!(host, None);
+            }
+            _ => panic!("expected Reply"),
+        }
+        let url = "https://myteam.slack.com/archives/C081VT5GLQH/p1770689887565249";
+        let args: Vec<String> = ["slk", "reply", url, "-"].map(String::from).into();
+        match parse_args(args).unwrap().0 {
+            Command::Reply { channel_id, ts, host, .. } => {
+                assert_eq!((channel_id.as_str(), ts.as_str()), ("C081VT5GLQH", "1770689887.565249"));
+                assert_eq!(host.as_deref(), Some("myteam.slack.com"));
+            }
+            _ => panic!("expected Reply"),
+        }
+        let args: Vec<String> = ["slk", "send", "#general"].map(String::from).into();
+        assert!(parse_args(args).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_no_args() {
+        let args = vec!["slk".to_string()];
+        assert!(parse_args(args).is_err());
+    }
+
+    #[test]
+    fn test_format_messages_with_resolved_names() {
+        let messages = vec![
+            message::SlackMessage {
+                user: "U081R4ZS5E2".to_string(),
+                text: "Hello, this is a thread".to_string(),
+                ts: "1770689887.565249".to_string(),
+                ..Default::default()
+            },
+            message::SlackMessage {
+                user: "U092X3AB7F1".to_string(),
+                text: "Great thread!".to_string(),
+                ts: "1770689900.000100".to_string(),
+                ..Default::default()
+            },
+        ];
+        let mut user_names = HashMap::new();
+        user_names.insert("U081R4ZS5E2".to_string(), "kanta".to_string());
+        user_names.insert("U092X3AB7F1".to_string(), "taro".to_string());
+        let output = format_messages(&messages, &user_names, &OutputOptions::default());
+        assert_eq!(
+            output,
+            "2026-02-10 02:18:07 @kanta Hello, this is a thread\n2026-02-10 02:18:20 @taro Great thread!"
+        );
+    }
+
+    #[test]
+    fn test_format_messages_unresolved_fallback() {
+        let messages = vec![message::SlackMessage {
+            user: "U081R4ZS5E2".to_string(),
+            text: "Hello".to_string(),
+            ts: "1770689887.565249".to_string(),
+            ..Default::default()
+        }];
+        let user_names = HashMap::new();
+        let output = format_messages(&messages, &user_names, &OutputOptions::default());
+        assert_eq!(output, "2026-02-10 02:18:07 U081R4ZS5E2 Hello");
+    }
+
+    #[test]
+    fn test_format_messages_with_links() {
+        let messages = vec![
+            message::SlackMessage {
+                user: "U1".to_string(),
+                text: "root".to_string(),
+                ts: "1770689887.565249".to_string(),
+                ..Default::default()
+            },
+            message::SlackMessage {
+                user: "U2".to_string(),
+                text: "reply".to_string(),
+                ts: "1770689900.000100".to_string(),
+                thread_ts: Some("1770689887.565249".to_string()),
+                ..Default::default()
+            },
+        ];
+        let links =
+            MessageLinks { workspace_url: "https://myteam.slack.com/".to_string(), channel_id: "C1".to_string() };
+        let mut options = OutputOptions { links: Some(links), ..OutputOptions::default() };
+        assert_eq!(
+            format_messages(&messages, &HashMap::new(), &options),
+            "2026-02-10 02:18:07 U1 root https://myteam.slack.com/archives/C1/p1770689887565249\n\
+             2026-02-10 02:18:20 U2 reply \
+             https://myteam.slack.com/archives/C1/p1770689900000100?thread_ts=1770689887.565249&cid=C1"
+        );
+        options.format = OutputFormat::Json;
+        let output = json::parse(&format_messages(&messages, &HashMap::new(), &options)).unwrap();
+        let JsonValue::Array(items) = output else { panic!("expected an array") };
+        assert_eq!(
+            items[0].get("permalink").and_then(|v| v.as_str()),
+            Some("https://myteam.slack.com/archives/C1/p1770689887565249")
+        );
+    }
+
+    #[test]
+    fn test_reply_marker() {
+        let mut parent = message::SlackMessage {
+            ts: "1770689887.565249".to_string(),
+            thread_ts: Some("1770689887.565249".to_string()),
+            reply_count: 12,
+            latest_reply: Some("1770690000.000100".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(reply_marker(&parent, 1770697200).as_deref(), Some("↳ 12 replies, last 2 hours ago"));
+        parent.reply_count = 1;
+        parent.latest_reply = None;
+        assert_eq!(reply_marker(&parent, 1770697200).as_deref(), Some("↳ 1 reply"));
+        let reply = message::SlackMessage { ts: "1770690000.000100".to_string(), ..parent };
+        assert_eq!(reply_marker(&reply, 1770697200), None);
+        assert_eq!(reply_marker(&message::SlackMessage::default(), 1770697200), None);
+    }
+
+    #[test]
+    fn test_run_show_history_count() {
+        let transport = http::FakeTransport::new().respond(
+            "conversations.history",
+            r#"{"ok": true, "messages": [
+                {"user": "U1", "text": "logs attached", "ts": "1770689900.000100",
+                 "files": [{"id": "F1"}, {"id": "F2"}]},
+                {"user": "U2", "text": "<@U2> has joined the channel", "ts": "1770689890.000100",
+                 "subtype": "channel_join"},
+                {"user": "U1", "text": "deploy?", "ts": "1770689887.565249", "thread_ts": "1770689887.565249",
+                 "reply_count": 3}
+            ]}"#,
+        );
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let mut options = OutputOptions { count: true, ..OutputOptions::default() };
+        let counts = run_show_history(&api, "C1", false, &options, &mut HashMap::new()).unwrap();
+        assert_eq!(counts, "Messages: 3\nUsers:    2\nThreads:  1\nFiles:    2");
+        assert_eq!(transport.requests.lock().unwrap().len(), 1);
+        options.no_system = true;
+        options.format = OutputFormat::Json;
+        let counts = run_show_history(&api, "C1", false, &options, &mut HashMap::new()).unwrap();
+        assert_eq!(counts, r#"{"messages":2,"users":1,"threads":1,"files":2}"#);
+        options.format = OutputFormat::Csv;
+        let counts = run_show_history(&api, "C1", false, &options, &mut HashMap::new()).unwrap();
+        assert_eq!(counts, "kind,key,name,count\nmessages,,,2\nusers,,,1\nthreads,,,1\nfiles,,,2");
+    }
+
+    #[test]
+    fn test_broadcast_replies() {
+        let broadcast = message::SlackMessage {
+            user: "U1".to_string(),
+            text: "rolled back, all green".to_string(),
+            ts: "1770690000.000100".to_string(),
+            thread_ts: Some("1770689887.565249".to_string()),
+            subtype: Some("thread_broadcast".to_string()),
+            root_text: Some("Deploy of the payments service is failing\nwith 502s".to_string()),
+            ..Default::default()
+        };
+        let mut options = OutputOptions { time_format: "%H:%M".to_string(), ..OutputOptions::default() };
+        assert_eq!(
+            message_line(&broadcast, &HashMap::new(), &options),
+            "02:20 U1 rolled back, all green ↳ replied to thread 1770689887.565249: \
+             Deploy of the payments service is failin…"
+        );
+        let no_root = message::SlackMessage { root_text: None, ..broadcast };
+        assert_eq!(broadcast_note(&no_root).as_deref(), Some("↳ replied to thread 1770689887.565249"));
+        options.format = OutputFormat::Json;
+        let json = message_json(&no_root, &HashMap::new(), &options);
+        assert_eq!(json.get("broadcast").and_then(|v| v.as_bool()), Some(true));
+        assert_eq!(json.get("thread_ts").and_then(|v| v.as_str()), Some("1770689887.565249"));
+    }
+
+    #[test]
+    fn test_system_messages() {
+        let messages = vec![
+            message::SlackMessage {
+                user: "U3".to_string(),
+                text: "<@U3> has joined the channel".to_string(),
+                ts: "1770689887.565249".to_string(),
+                subtype: Some("channel_join".to_string()),
+                ..Default::default()
+            },
+            message::SlackMessage {
+                user: "U1".to_string(),
+                ts: "1770689890.000100".to_string(),
+                subtype: Some("pinned_item".to_string()),
+                ..Default::default()
+            },
+            message::SlackMessage {
+                user: "B1".to_string(),
+                text: "deployed".to_string(),
+                ts: "1770689900.000100".to_string(),
+                subtype: Some("bot_message".to_string()),
+                ..Default::default()
+            },
+        ];
+        let mut options = OutputOptions { color: true, time_format: "%H:%M".to_string(), ..OutputOptions::default() };
+        assert_eq!(
+            format_messages(&messages, &HashMap::new(), &options),
+            "\x1b[2m02:18 U3 <@U3> has joined the channel\x1b[0m\n\
+             \x1b[2m02:18 U1 (pinned item)\x1b[0m\n\
+             \x1b[2m02:18\x1b[0m \x1b[1;36mB1\x1b[0m deployed"
+        );
+        options.no_system = true;
+        options.color = false;
+        assert_eq!(format_messages(&messages, &HashMap::new(), &options), "02:18 B1 deployed");
+    }
+
+    #[test]
+    fn test_format_messages_compact() {
+        let message = |user: &str, text: &str, ts: &str| message::SlackMessage {
+            user: user.to_string(),
+            text: text.to_string(),
+            ts: ts.to_string(),
+            ..Default::default()
+        };
+        let messages = vec![
+            message("U1", "deploying now", "1770689887.565249"),
+            message("U1", "done", "1770689950.000100"),
+            message("U1", "all green", "1770690100.000100"),
+            message("U2", "thanks", "1770690110.000100"),
+            message("U2", "one more thing", "1770691000.000100"),
+        ];
+        let mut user_names = HashMap::new();
+        user_names.insert("U1".to_string(), "kanta".to_string());
+        let mut options =
+            OutputOptions { time_format: "%H:%M".to_string(), compact: true, ..OutputOptions::default() };
+        assert_eq!(
+            format_messages(&messages, &user_names, &options),
+            "02:18 @kanta deploying now\n\
+             \x20            done\n\
+             \x20            all green\n\
+             02:21 U2 thanks\n\
+             02:36 U2 one more thing"
+        );
+        let reply = message("U2", "nice", "1770689960.000100");
+        let replies = HashMap::from([("1770689950.000100".to_string(), vec![reply])]);
+        assert_eq!(
+            format_threads(&messages[..3], &replies, &user_names, &options),
+            "02:18 @kanta deploying now\n\
+             \x20            done\n  02:19 U2 nice\n\
+             02:21 @kanta all green"
+        );
+        options.compact = false;
+        assert!(format_messages(&messages, &user_names, &options).contains("\n02:19 @kanta done\n"));
+    }
+
+    #[test]
+    fn test_format_messages_wrapped() {
+        let message = || message::SlackMessage {
+            user: "U1".to_string(),
+            text: "the deploy finished but the canary is reporting elevated error rates\nrolling back"
+                .to_string(),
+            ts: "1770689887.565249".to_string(),
+            ..Default::default()
+        };
+        let messages = vec![message()];
+        let mut user_names = HashMap::new();
+        user_names.insert("U1".to_string(), "kanta".to_string());
+        let options =
+            OutputOptions { time_format: "%H:%M".to_string(), width: Some(40), ..OutputOptions::default() };
+        assert_eq!(
+            format_messages(&messages, &user_names, &options),
+            "02:18 @kanta the deploy finished but the\n\
+             \x20            canary is reporting\n\
+             \x20            elevated error rates\n\
+             \x20            rolling back"
+        );
+        let replies = HashMap::from([("1770689887.565249".to_string(), vec![message()])]);
+        let thread = format_threads(&messages, &replies, &user_names, &options);
+        assert_eq!(thread.lines().nth(4), Some("  02:18 @kanta the deploy finished but"));
+        assert_eq!(thread.lines().nth(5), Some("               the canary is reporting"));
+    }
+
+    #[test]
+    fn test_format_messages_empty() {
+        let messages: Vec<message::SlackMessage> = vec![];
+        let user_names = HashMap::new();
+        assert_eq!(format_messages(&messages, &user_names, &OutputOptions::default()), "");
+    }
+
+    #[test]
+    fn test_run_show_thread_end_to_end() {
+        let transport = http::FakeTransport::new()
+            .respond(
+                "conversations.replies",
+                r#"{"ok": true, "messages": [
+                    {"user": "U081R4ZS5E2", "text": "Hello, this is a thread", "ts": "1770689887.565249"},
+                    {"bot_id": "B123", "text": "beep", "ts": "1770689900.000100"}
+                ]}"#,
+            )
+            .respond(
+                "users.info",
+                r#"{"ok": true, "user": {"name": "kanta", "profile": {"display_name": "kanta"}}}"#,
+            );
+        let output = run_show_thread(
+            &SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test"),
+            "C081VT5GLQH",
+            "1770689887.565249",
+            &OutputOptions::default(),
+            &mut HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            "2026-02-10 02:18:07 @kanta Hello, this is a thread\n2026-02-10 02:18:20 B123 beep"
+        );
+
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].url.contains("channel=C081VT5GLQH&ts=1770689887.565249"));
+        assert!(requests[0]
+            .headers
+            .contains(&("Authorization".to_string(), "Bearer xoxp-test".to_string())));
+    }
+
+    #[test]
+    fn test_run_show_message() {
+        let transport = http::FakeTransport::new()
+            .respond(
+                "conversations.history?inclusive=true",
+                r#"{"ok": true, "messages": [
+                    {"user": "U1", "text": "linked", "ts": "1770689887.565249"},
+                    {"user": "U1", "text": "before", "ts": "1770689800.000100"}
+                ]}"#,
+            )
+            .respond(
+                "conversations.history?oldest=1770689887.565249",
+                r#"{"ok": true, "messages": [
+                    {"user": "U1", "text": "much later", "ts": "1770690000.000100"},
+                    {"user": "U1", "text": "after", "ts": "1770689900.000100"}
+                ]}"#,
+            )
+            .respond(
+                "conversations.replies",
+                r#"{"ok": true, "messages": [
+                    {"user": "U1", "text": "parent", "ts": "1770689887.565249"},
+                    {"user": "U1", "text": "reply", "ts": "1770689900.000100"},
+                    {"user": "U1", "text": "next", "ts": "1770689990.000100"}
+                ]}"#,
+            )
+            .respond("users.info", r#"{"ok": true, "user": {"name": "kanta"}}"#);
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let show = |thread_ts, context| {
+            let options = OutputOptions { time_format: "%H:%M".to_string(), ..OutputOptions::default() };
+            run_show_message(&api, "C1", "1770689887.565249", thread_ts, context, &options, &mut HashMap::new())
+        };
+        assert_eq!(show(None, 0).unwrap(), "02:18 @kanta linked");
+        assert_eq!(
+            show(None, 1).unwrap(),
+            "  02:18 @kanta after\n> 02:18 @kanta linked\n  02:16 @kanta before"
+        );
+        assert_eq!(show(Some("1770689887.565249"), 0).unwrap(), "02:18 @kanta linked");
+        let replies = |context| {
+            let options = OutputOptions { time_format: "%H:%M".to_string(), ..OutputOptions::default() };
+            let ts = "1770689990.000100";
+            run_show_message(&api, "C1", ts, Some("1770689887.565249"), context, &options, &mut HashMap::new())
+        };
+        assert_eq!(replies(0).unwrap(), "02:19 @kanta next");
+        assert_eq!(replies(5).unwrap(), "> 02:19 @kanta next\n  02:18 @kanta reply\n  02:18 @kanta parent");
+        let requests = transport.requests.lock().unwrap();
+        assert!(requests[0].url.ends_with("channel=C1&latest=1770689887.565249&inclusive=true&limit=1"));
+        // The context after the message is bounded on both sides, not every newer message.
+        let history: Vec<&str> =
+            requests.iter().map(|r| r.url.as_str()).filter(|u| u.contains("conversations.history")).collect();
+        assert_eq!(history.len(), 4);
+        assert!(history[2].ends_with("channel=C1&oldest=1770689887.565249&latest=1770689947.000000&limit=200"));
+
+        let transport = http::FakeTransport::new().respond("conversations.history", r#"{"ok": true, "messages": []}"#);
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let err = run_show_message(&api, "C1", "1.2", None, 0, &OutputOptions::default(), &mut HashMap::new());
+        assert!(err.unwrap_err().message.contains("for a reply, give its link"));
+    }
+
+    #[test]
+    fn test_history_after_widens_the_span_until_it_has_enough() {
+        let transport = http::FakeTransport::new()
+            .respond("conversations.history?latest=1770689947.000000", r#"{"ok": true, "messages": []}"#)
+            .respond(
+                "conversations.history?cursor=page2",
+                r#"{"ok": true, "messages": [{"user": "U1", "text": "next", "ts": "1770689950.000100"}]}"#,
+            )
+            .respond(
+                "conversations.history?latest=1770690007.000000",
+                r#"{"ok": true, "messages": [{"user": "U1", "text": "later", "ts": "1770690000.000100"}],
+                    "has_more": true, "response_metadata": {"next_cursor": "page2"}}"#,
+            );
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        // The one nearest the message, on the last page of the second span.
+        let after = history_after(&api, "C1", "1770689887.565249", 1).unwrap();
+        assert_eq!(after.iter().map(|m| m.text.as_str()).collect::<Vec<_>>(), ["next"]);
+        let after = history_after(&api, "C1", "1770689887.565249", 2).unwrap();
+        assert_eq!(after.iter().map(|m| m.text.as_str()).collect::<Vec<_>>(), ["later", "next"]);
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 6);
+        assert!(requests.iter().all(|r| r.url.contains("oldest=1770689887.565249&latest=")));
+    }
+
+    #[test]
+    fn test_run_list_conversations_end_to_end() {
+        let transport = http::FakeTransport::new()
+            .respond(
+                "conversations.list?cursor=page2",
+                r#"{"ok": true, "channels": [{"id": "G3", "name": "secret"}],
+                    "response_metadata": {"next_cursor": ""}}"#,
+            )
+            .respond(
+                "conversations.list",
+                r#"{"ok": true, "channels": [{"id": "C1", "name": "general"}, {"id": "C2", "name": "random"}],
+                    "response_metadata": {"next_cursor": "page2"}}"#,
+            );
+        let conversations =
+            fetch_conversations(&SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test")).unwrap();
+        let output = format_conversations(&conversations, &OutputOptions::default());
+        assert_eq!(output, "C1\tgeneral\nC2\trandom\nG3\tsecret");
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].url.contains("types=public_channel,private_channel"), "{}", requests[0].url);
+    }
+
+    #[test]
+    fn test_member_channels_reads_every_page() {
+        let transport = http::FakeTransport::new()
+            .respond(
+                "users.conversations?cursor=page2",
+                r#"{"ok": true, "channels": [{"id": "C201", "name": "late"}]}"#,
+            )
+            .respond(
+                "users.conversations",
+                r#"{"ok": true, "channels": [{"id": "C1", "name": "general"}],
+                    "response_metadata": {"next_cursor": "page2"}}"#,
+            );
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        assert_eq!(
+            member_channels(&api).unwrap(),
+            [("C1".to_string(), "#general".to_string()), ("C201".to_string(), "#late".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_link_mentions() {
+        let transport = http::FakeTransport::new().respond(
+            "users.list",
+            r#"{"ok": true, "members": [{"id": "U1", "name": "kanta"}, {"id": "U2", "name": "taro"}]}"#,
+        );
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let mut names = HashMap::from([("U2".to_string(), "taro".to_string())]);
+        assert_eq!(
+            link_mentions(&api, "@taro @kanta: ship it? cc @nobody @nobody @here @U5", &mut names),
+            "<@U2> <@U1>: ship it? cc @nobody @nobody <!here> @U5"
+        );
+        // @taro was cached, and @nobody looked up once.
+        assert_eq!(transport.requests.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_resolve_user() {
+        let transport = http::FakeTransport::new()
+            .respond(
+                "users.list?cursor=page2",
+                r#"{"ok": true, "members": [
+                    {"id": "U1", "name": "kanta", "profile": {"display_name": "Kanta"}}
+                ]}"#,
+            )
+            .respond(
+                "users.list",
+                r#"{"ok": true, "members": [{"id": "U2", "name": "taro", "deleted": true}],
+                    "response_metadata": {"next_cursor": "page2"}}"#,
+            )
+            .respond(
+                "users.lookupByEmail",
+                r#"{"ok": true, "user": {"id": "U3", "name": "hanako"}}"#,
+            );
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let mut names = HashMap::new();
+        assert_eq!(resolve_user(&api, "U9", &mut names).unwrap(), "U9");
+        // On the second page of the list.
+        assert_eq!(resolve_user(&api, "@kanta", &mut names).unwrap(), "U1");
+        assert_eq!(names.get("U1").map(String::as_str), Some("Kanta"));
+        assert_eq!(transport.requests.lock().unwrap().len(), 2);
+        // Found in the cache this time.
+        assert_eq!(resolve_user(&api, "kanta", &mut names).unwrap(), "U1");
+        assert!(resolve_user(&api, "@taro", &mut names).is_err());
+        assert_eq!(
+            resolve_user(&api, "hanako@example.com", &mut names).unwrap(),
+            "U3"
+        );
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 5);
+        assert!(requests[4].url.ends_with("users.lookupByEmail?email=hanako%40example.com"));
+    }
+
+    #[test]
+    fn test_run_show_history_api_error() {
+        let transport = http::FakeTransport::new().respond(
+            "conversations.history",
+            r#"{"ok": false, "error": "channel_not_found"}"#,
+        );
+        let err = run_show_history(
+            &SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test"),
+            "C404",
+            false,
+            &OutputOptions::default(),
+            &mut HashMap::new(),
+        )
+        .unwrap_err();
+        assert!(err.message.contains("channel_not_found"));
+    }
+
+    #[test]
+    fn test_run_show_history_threads() {
+        let transport = http::FakeTransport::new()
+            .respond(
+                "conversations.history",
+                r#"{"ok": true, "messages": [
+                    {"user": "U1", "text": "later", "ts": "1770690000.000100"},
+                    {"user": "U1", "text": "incident", "ts": "1770689887.565249",
+                     "thread_ts": "1770689887.565249", "reply_count": 2}
+                ]}"#,
+            )
+            .respond(
+                "conversations.replies",
+                r#"{"ok": true, "messages": [
+                    {"user": "U1", "text": "incident", "ts": "1770689887.565249", "thread_ts": "1770689887.565249"},
+                    {"user": "U2", "text": "looking", "ts": "1770689900.000100", "thread_ts": "1770689887.565249"},
+                    {"user": "U2", "text": "fixed", "ts": "1770689990.000100", "thread_ts": "1770689887.565249"}
+                ]}"#,
+            )
+            .respond("users.info", r#"{"ok": true, "user": {"name": "kanta"}}"#);
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let mut options = OutputOptions { time_format: "%H:%M".to_string(), ..OutputOptions::default() };
+        let output = run_show_history(&api, "C1", true, &options, &mut HashMap::new()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "02:20 @kanta later");
+        assert!(lines[1].starts_with("02:18 @kanta incident ↳ 2 replies"));
+        assert_eq!(&lines[2..], ["  02:18 @kanta looking", "  02:19 @kanta fixed"]);
+        assert!(transport.requests.lock().unwrap().iter().any(|r| r.url.contains("ts=1770689887.565249")));
+
+        options.format = OutputFormat::Json;
+        let output = run_show_history(&api, "C1", true, &options, &mut HashMap::new()).unwrap();
+        let output = json::parse(&output).unwrap();
+        let replies = output.as_array().unwrap()[1].get("replies").and_then(|v| v.as_array()).unwrap();
+        assert_eq!(replies.iter().map(|r| r.get("text").and_then(|v| v.as_str())).collect::<Vec<_>>(), [
+            Some("looking"),
+            Some("fixed")
+        ]);
+    }
+
+    #[test]
+    fn test_fetch_new_messages() {
+        let transport = http::FakeTransport::new()
+            .respond(
+                "conversations.history",
+                r#"{"ok": true, "messages": [
+                    {"user": "U2", "text": "second", "ts": "1770689900.000100"},
+                    {"user": "U1", "text": "first", "ts": "1770689887.565249"}
+                ]}"#,
+            )
+            .respond("users.info", r#"{"ok": true, "user": {"id": "U2", "name": "taro"}}"#);
+        let mut names = HashMap::new();
+        names.insert("U1".to_string(), "kanta".to_string());
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let messages = task::block_on(fetch_new_messages(&api, Feed::Channel("C1"), None, 10)).unwrap();
+        assert_eq!(messages[0].text, "first");
+        resolve_user_names(&api, &messages, &mut names).unwrap();
+        task::block_on(fetch_new_messages(&api, Feed::Channel("C1"), Some("1770689900.000100"), 10)).unwrap();
+        let requests = transport.requests.lock().unwrap();
+        assert!(requests[0].url.ends_with("conversations.history?channel=C1&limit=10"));
+        assert!(requests[1].url.ends_with("users.info?user=U2"));
+        assert!(requests[2].url.ends_with("history?channel=C1&limit=200&oldest=1770689900.000100"));
+
+        let options = OutputOptions {
+            format: OutputFormat::Json,
+            from: Some("U2".to_string()),
+            ..OutputOptions::default()
+        };
+        let messages: Vec<(&str, message::SlackMessage)> = messages.into_iter().map(|m| ("C1", m)).collect();
+        assert_eq!(
+            format_message_stream(&messages, &names, &options),
+            vec![r#"{"channel":"C1","ts":"1770689900.000100","time":"2026-02-10 02:18:20","user":"U2","user_name":"taro","text":"second"}"#]
+        );
+    }
+
+    #[test]
+    fn test_stream_filters() {
+        let message = |user: &str, text: &str| message::SlackMessage {
+            user: user.to_string(),
+            text: text.to_string(),
+            ts: "1770689887.565249".to_string(),
+            ..Default::default()
+        };
+        let options = OutputOptions {
+            grep: Some(regex_lite::Regex::new("(?i)deploy|incident").unwrap()),
+            ..OutputOptions::default()
+        };
+        assert!(options.shows(&message("U2", "Deploy finished")));
+        assert!(!options.shows(&message("U2", "lunch?")));
+        let options = OutputOptions {
+            mentioning: Some("U1".to_string()),
+            from: Some("U2".to_string()),
+            ..options
+        };
+        assert!(options.shows(&message("U2", "<@U1> incident in prod")));
+        assert!(!options.shows(&message("U2", "incident in prod")));
+        assert!(!options.shows(&message("U3", "<@U1> incident in prod")));
+    }
+
+    #[test]
+    fn test_fetch_new_across_channels() {
+        let transport = http::FakeTransport::new().respond(
+            "conversations.history",
+            r#"{"ok": true, "messages": [
+                {"user": "U1", "text": "later", "ts": "1770689900.000100"},
+                {"user": "U1", "text": "earlier", "ts": "1770689887.565249"}
+            ]}"#,
+        );
+        let mut names = HashMap::from([("U1".to_string(), "kanta".to_string())]);
+        let feeds = [Feed::Channel("C1"), Feed::Channel("C2")];
+        let mut cursors = vec![None; 2];
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let messages = fetch_new_across(&api, &feeds, &mut cursors, 3, &mut names).unwrap();
+        // The latest three of both channels, interleaved by time.
+        let order: Vec<(&str, &str)> = messages.iter().map(|(c, m)| (*c, m.text.as_str())).collect();
+        assert_eq!(order, [("C2", "earlier"), ("C1", "later"), ("C2", "later")]);
+        assert_eq!(cursors, [Some("1770689900.000100".to_string()), Some("1770689900.000100".to_string())]);
+        fetch_new_across(&api, &feeds, &mut cursors, 3, &mut names).unwrap();
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 4);
+        assert!(requests.iter().any(|r| r.url.ends_with("channel=C2&limit=200&oldest=1770689900.000100")));
+
+        let options = OutputOptions {
+            channel_names: HashMap::from([
+                ("C1".to_string(), "#general".to_string()),
+                ("C2".to_string(), "#ops".to_string()),
+            ]),
+            ..OutputOptions::default()
+        };
+        assert_eq!(
+            format_message_stream(&messages[..1], &names, &options),
+            vec!["#ops     2026-02-10 02:18:07 @kanta earlier"]
+        );
+    }
+
+    #[test]
+    fn test_fetch_new_across_reads_every_page_of_a_gap() {
+        let transport = http::FakeTransport::new()
+            .respond(
+                "conversations.history?cursor=page2",
+                r#"{"ok": true, "messages": [{"user": "U1", "text": "first", "ts": "1770689801.000100"}]}"#,
+            )
+            .respond(
+                "conversations.history",
+                r#"{"ok": true, "messages": [
+                    {"user": "U1", "text": "third", "ts": "1770689900.000100"},
+                    {"user": "U1", "text": "second", "ts": "1770689850.000100"}
+                ], "has_more": true, "response_metadata": {"next_cursor": "page2"}}"#,
+            );
+        let mut names = HashMap::from([("U1".to_string(), "kanta".to_string())]);
+        let feeds = [Feed::Channel("C1")];
+        let mut cursors = vec![Some("1770689800.000000".to_string())];
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let messages = fetch_new_across(&api, &feeds, &mut cursors, 3, &mut names).unwrap();
+        let texts: Vec<&str> = messages.iter().map(|(_, m)| m.text.as_str()).collect();
+        assert_eq!(texts, ["first", "second", "third"]);
+        assert_eq!(cursors, [Some("1770689900.000100".to_string())]);
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].url.ends_with("oldest=1770689800.000000&cursor=page2"), "{}", requests[1].url);
+    }
+
+    #[test]
+    fn test_sync_start() {
+        assert_eq!(sync_start("1770689900.000100", 3600), "1770686300.000000");
+        assert_eq!(sync_start("1770689900.000100", 0), "1770689900.000100");
+        assert_eq!(sync_start("100.000000", 3600), "0");
+    }
+
+    #[test]
+    fn test_run_sync_pages_through_history() {
+        let transport = http::FakeTransport::new()
+            .respond(
+                "conversations.history?cursor=page2",
+                r#"{"ok": true, "messages": [
+                    {"user": "U2", "text": "first", "ts": "1770689800.000100"}
+                ], "response_metadata": {"next_cursor": ""}}"#,
+            )
+            .respond(
+                "conversations.history",
+                r#"{"ok": true, "messages": [
+                    {"user": "U1", "text": "third", "ts": "1770689900.000100"},
+                    {"user": "U1", "text": "second", "ts": "1770689887.565249"}
+                ], "response_metadata": {"next_cursor": "page2"}}"#,
+            )
+            .respond("users.info", r#"{"ok": true, "user": {"id": "U2", "name": "taro"}}"#);
+        let dir = std::env::temp_dir().join(format!("slk-sync-{}", std::process::id()));
+        let archive = archive::Archive::at(dir.clone());
+        let sync = SyncOptions { archive: &archive, edit_window: Some(3600) };
+        let mut names = HashMap::from([("U1".to_string(), "kanta".to_string())]);
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let output =
+            run_sync(&api, "C1", "#general", &sync, &mut names).unwrap();
+        assert_eq!(output, "Synced #general: 3 new messages, 0 edited, 3 in the archive");
+        assert!(transport.requests.lock().unwrap()[1].url.ends_with("&cursor=page2"));
+        let texts: Vec<String> = archive
+            .messages("C1")
+            .unwrap()
+            .iter()
+            .map(|m| m.get("text").and_then(|v| v.as_str()).unwrap_or("").to_string())
+            .collect();
+        assert_eq!(texts, ["first", "second", "third"]);
+        assert_eq!(archive.users().get("U2").map(String::as_str), Some("taro"));
+        assert_eq!(archive.channels()[0].name, "general");
+
+        assert_eq!(archive.channels()[0].latest.as_deref(), Some("1770689900.000100"));
+
+        // The next sync starts an hour before the newest archived message.
+        let output =
+            run_sync(&api, "C1", "#general", &sync, &mut names).unwrap();
+        assert_eq!(output, "Synced #general: 0 new messages, 0 edited, 3 in the archive");
+        let requests = transport.requests.lock().unwrap();
+        assert!(requests.last().unwrap().url.contains("&oldest=1770686300.000000&"));
+        assert!(!requests[0].url.contains("oldest"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_offline_history_and_thread() {
+        let dir = std::env::temp_dir().join(format!("slk-offline-{}", std::process::id()));
+        let archive = archive::Archive::at(dir.clone());
+        let messages = json::parse(
+            r#"[
+                {"user": "U1", "text": "parent", "ts": "1770689887.565249", "thread_ts": "1770689887.565249"},
+                {"user": "U2", "text": "reply", "ts": "1770689890.000100", "thread_ts": "1770689887.565249"},
+                {"user": "U2", "text": "also sent to the channel", "ts": "1770689895.000100",
+                 "thread_ts": "1770689887.565249", "subtype": "thread_broadcast"},
+                {"user": "U1", "text": "later", "ts": "1770689900.000100"}
+            ]"#,
+        )
+        .unwrap();
+        archive.store_messages("C1", messages.as_array().unwrap().clone()).unwrap();
+        archive.save_users(&HashMap::from([("U1".to_string(), "kanta".to_string())])).unwrap();
+        let options = OutputOptions { time_format: "%H:%M:%S".to_string(), ..OutputOptions::default() };
+
+        let history = run_history_offline(&archive, "C1", &options).unwrap();
+        assert_eq!(
+            history,
+            "02:18:20 @kanta later\n\
+             02:18:15 U2 also sent to the channel ↳ replied to thread 1770689887.565249\n\
+             02:18:07 @kanta parent"
+        );
+        let options = OutputOptions { limit: Some(1), ..options };
+        assert_eq!(run_history_offline(&archive, "C1", &options).unwrap(), "02:18:20 @kanta later");
+
+        let options = OutputOptions { limit: None, ..options };
+        let thread = run_thread_offline(&archive, "C1", "1770689887.565249", &options).unwrap();
+        assert_eq!(thread.lines().count(), 3);
+        assert!(thread.starts_with("02:18:07 @kanta parent\n02:18:10 U2 reply"));
+        assert!(run_thread_offline(&archive, "C1", "1770689900.000200", &options).is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_describe_age() {
+        assert_eq!(describe_age(30), "0 minutes");
+        assert_eq!(describe_age(90), "1 minute");
+        assert_eq!(describe_age(7200), "2 hours");
+        assert_eq!(describe_age(3 * 86400 + 5), "3 days");
+    }
+
+    #[test]
+    fn test_export_channel() {
+        let transport = http::FakeTransport::new()
+            .respond("conversations.info", r#"{"ok": true, "channel": {"id": "C1", "name": "general"}}"#)
+            .respond(
+                "conversations.history",
+                r#"{"ok": true, "messages": [
+                    {"user": "U1", "text": "no replies", "ts": "1770689900.000100"},
+                    {"user": "U1", "text": "parent", "ts": "1770689887.565249", "thread_ts": "1770689887.565249", "reply_count": 1}
+                ]}"#,
+            )
+            .respond(
+                "conversations.replies",
+                r#"{"ok": true, "messages": [
+                    {"user": "U1", "text": "parent", "ts": "1770689887.565249", "thread_ts": "1770689887.565249"},
+                    {"user": "U2", "text": "a reply", "ts": "1770689890.000100", "thread_ts": "1770689887.565249"}
+                ]}"#,
+            )
+            .respond("users.info", r#"{"ok": true, "user": {"id": "U1", "name": "kanta"}}"#);
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let (document, summary) = export_channel(&api, "C1").unwrap();
+        assert_eq!((summary.messages, summary.threads, summary.users), (2, 1, 2));
+        assert_eq!(document.get("channel").and_then(|c| c.get("name")), Some(&JsonValue::String("general".to_string())));
+        let messages = document.get("messages").and_then(|v| v.as_array()).unwrap();
+        let text = |m: &JsonValue| m.get("text").and_then(|v| v.as_str()).unwrap().to_string();
+        assert_eq!(text(&messages[0]), "parent");
+        let replies = messages[0].get("replies").and_then(|v| v.as_array()).unwrap();
+        assert_eq!(replies.iter().map(text).collect::<Vec<_>>(), ["a reply"]);
+        assert!(messages[1].get("replies").is_none());
+        let requests = transport.requests.lock().unwrap();
+        assert!(requests.iter().any(|r| r.url.contains("users.info?user=U2")));
+    }
+
+    #[test]
+    fn test_recent() {
+        let transport = http::FakeTransport::new()
+            .respond(
+                "users.conversations",
+                r#"{"ok": true, "channels": [{"id": "C1", "name": "general"}, {"id": "C2", "name": "ops"},
+                    {"id": "C3", "name": "quiet"}]}"#,
+            )
+            .respond(
+                "conversations.history?channel=C1",
+                r#"{"ok": true, "messages": [{"user": "U1", "text": "lunch?", "ts": "1770600000.000100"}]}"#,
+            )
+            .respond(
+                "conversations.history?channel=C2",
+                r#"{"ok": true, "has_more": true, "messages": [
+                    {"user": "U2", "text": "deploy done", "ts": "1770600300.000100"},
+                    {"user": "U2", "text": "deploying", "ts": "1770600200.000100"}]}"#,
+            )
+            .respond("conversations.history?channel=C3", r#"{"ok": true, "messages": []}"#)
+            .respond("users.info?user=U1", r#"{"ok": true, "user": {"id": "U1", "name": "kanta"}}"#)
+            .respond("users.info?user=U2", r#"{"ok": true, "user": {"id": "U2", "name": "ops-bot"}}"#);
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let options = OutputOptions { time_format: "%H:%M".to_string(), limit: Some(1), ..Default::default() };
+        let output = run_recent(&api, Some(1770595200), &options, &mut HashMap::new()).unwrap();
+        let time = |ts: &str| message::format_unix_ts_with(ts, "%H:%M");
+        assert_eq!(
+            output,
+            format!(
+                "#ops (2+ new)\n  {} @ops-bot deploy done\n\n#general (1 new)\n  {} @kanta lunch?",
+                time("1770600300.000100"),
+                time("1770600000.000100")
+            )
+        );
+        assert!(transport.requests.lock().unwrap().iter().any(|r| r.url.contains("C1&oldest=1770595200")));
+
+        let options = OutputOptions { format: OutputFormat::Json, ..options };
+        let output = run_recent(&api, None, &options, &mut HashMap::new()).unwrap();
+        assert!(output.starts_with(r#"[{"channel":"C2","name":"ops","messages":[{"ts":"1770600300.000100","#));
+    }
+
+    #[test]
+    fn test_activity() {
+        let transport = http::FakeTransport::new()
+            .respond(
+                "auth.test",
+                r#"{"ok": true, "user": "kanta", "user_id": "U1", "team": "My Team", "team_id": "T1",
+                    "url": "https://myteam.slack.com/"}"#,
+            )
+            .respond("users.conversations", r#"{"ok": true, "channels": [{"id": "C1", "name": "general"}]}"#)
+            .respond(
+                "conversations.history",
+                r#"{"ok": true, "messages": [
+                    {"user": "U1", "text": "shipped v2", "ts": "1770600300.000100", "reply_count": 3,
+                     "thread_ts": "1770600300.000100", "latest_reply": "1770600600.000100",
+                     "reactions": [{"name": "tada", "count": 2, "users": ["U1", "U2"]}]},
+                    {"user": "U2", "text": "nice", "ts": "1770600200.000100",
+                     "reactions": [{"name": "eyes", "count": 1, "users": ["U3"]}]},
+                    {"user": "U1", "text": "anyone?", "ts": "1770600100.000100",
+                     "reactions": [{"name": "eyes", "count": 1, "users": ["U1"]}]}
+                ]}"#,
+            )
+            .respond(
+                "conversations.replies",
+                r#"{"ok": true, "messages": [
+                    {"user": "U1", "text": "shipped v2", "ts": "1770600300.000100"},
+                    {"user": "U3", "text": "great", "ts": "1770600400.000100"},
+                    {"user": "U1", "text": "thanks", "ts": "1770600500.000100"},
+                    {"user": "U3", "text": "really", "ts": "1770600600.000100"}
+                ]}"#,
+            )
+            .respond("users.info?user=U2", r#"{"ok": true, "user": {"id": "U2", "name": "aki"}}"#)
+            .respond("users.info?user=U3", r#"{"ok": true, "user": {"id": "U3", "name": "mio"}}"#);
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let options = OutputOptions { time_format: "%H:%M".to_string(), ..Default::default() };
+        let output = run_activity(&api, 1770595200, &options, &mut HashMap::new()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("1 reaction and 2 replies on 1 of your messages since "), "{}", output);
+        let time = message::format_unix_ts_with("1770600300", "%H:%M");
+        assert_eq!(lines[2], format!("#general {} shipped v2", time));
+        assert_eq!(lines[3], "  :tada: 1");
+        assert!(lines[4].starts_with("  ↳ 2 replies from @mio, last "), "{}", output);
+        assert_eq!(lines.len(), 5);
+
+        let options = OutputOptions { format: OutputFormat::Json, ..options };
+        let output = run_activity(&api, 1770595200, &options, &mut HashMap::new()).unwrap();
+        assert!(output.contains(r#""reactions":[{"name":"tada","count":1,"users":["U2"]}],"replies":[{"#));
+
+        let options = OutputOptions { format: OutputFormat::Csv, ..options };
+        let output = run_activity(&api, 1770595200, &options, &mut HashMap::new()).unwrap();
+        assert_eq!(
+            output,
+            "channel,channel_name,ts,reactions,replies,text\nC1,general,1770600300.000100,1,2,shipped v2"
+        );
+    }
+
+    #[test]
+    fn test_export_all_resumes() {
+        let transport = http::FakeTransport::new()
+            .respond(
+                "users.conversations",
+                r#"{"ok": true, "channels": [{"id": "C1", "name": "general"}, {"id": "C2", "name": "ops"}]}"#,
+            )
+            .respond("conversations.info", r#"{"ok": true, "channel": {"id": "C2", "name": "ops"}}"#)
+            .respond("conversations.history", r#"{"ok": true, "messages": [{"user": "U1", "text": "hi", "ts": "1.0"}]}"#)
+            .respond("users.info", r#"{"ok": true, "user": {"id": "U1", "name": "kanta"}}"#);
+        let dir = std::env::temp_dir().join(format!("slk-export-all-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // An earlier run stopped after #general.
+        std::fs::write(
+            dir.join(EXPORT_INDEX_FILE),
+            r#"{"started_at": 5, "channels": [{"id": "C1", "name": "general", "file": "channels/C1.json"}]}"#,
+        )
+        .unwrap();
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let output = run_export_all(&api, &dir).unwrap();
+        assert!(output.ends_with("(1 more were done before)"), "{}", output);
+        assert!(!transport.requests.lock().unwrap().iter().any(|r| r.url.contains("channel=C1")));
+        assert!(dir.join("channels/C2.json").exists());
+        let index = json::parse(&std::fs::read_to_string(dir.join(EXPORT_INDEX_FILE)).unwrap()).unwrap();
+        assert_eq!(index.get("started_at"), Some(&JsonValue::Int(5)));
+        assert_eq!(index.get("channels").and_then(|v| v.as_array()).map(Vec::len), Some(2));
+        assert!(index.get("finished_at").is_some());
+
+        // A finished export starts over.
+        let output = run_export_all(&api, &dir).unwrap();
+        assert!(output.starts_with("Exported 2 channels"), "{}", output);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_save_files() {
+        let transport = http::FakeTransport::new()
+            .respond("log.txt", "12345")
+            .respond("core", &"x".repeat(2 * 1024 * 1024));
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let response = json::parse(
+            r#"{"ok": true, "messages": [
+                {"user": "U1", "text": "big", "ts": "1770689900.000100",
+                 "files": [
+                    {"name": "dump.bin", "size": 2097152,
+                     "url_private_download": "https://files.slack.com/x/dump.bin"},
+                    {"name": "core", "url_private_download": "https://files.slack.com/x/core"}
+                 ]},
+                {"user": "U2", "text": "log", "ts": "1770689887.565249",
+                 "files": [{"name": "a/log.txt", "size": 5, "url_private_download": "https://files.slack.com/x/log.txt"}]}
+            ]}"#,
+        )
+        .unwrap();
+        let dir = std::env::temp_dir().join(format!("slk-save-files-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let save = SaveFiles { dir: dir.clone(), max_bytes: 1024 * 1024 };
+        let options = OutputOptions::default();
+        save.save(&api, &shown_raw_messages(&response, &options)).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("1770689887.565249-a_log.txt")).unwrap(), "12345");
+        assert!(!dir.join("1770689900.000100-dump.bin").exists());
+        // Without a size, the limit goes with the download instead.
+        assert!(!dir.join("1770689900.000100-core").exists());
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].max_body, Some(1024 * 1024));
+        assert!(requests[1].headers.contains(&("Authorization".to_string(), "Bearer xoxp-test".to_string())));
+        drop(requests);
+
+        // Only the files of the messages that are shown.
+        let options = OutputOptions { from: Some("U1".to_string()), ..OutputOptions::default() };
+        assert_eq!(shown_raw_messages(&response, &options).len(), 1);
+        let too_large = "18446744073709551615";
+        let args: Vec<String> = ["slk", "history", "C1", "--save-files", "d", "--max-file-size", too_large]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let err = SaveFiles::from_matches(&cli::parse(&args).unwrap()).err().unwrap();
+        assert_eq!(err.message, "invalid --max-file-size '18446744073709551615': too large");
+        assert_eq!(file_name("..."), "file");
+        assert_eq!(file_name("report: v2?.pdf"), "report_ v2_.pdf");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_render_document() {
+        let export = json::parse(
+            r#"{"exported_at": 1, "channel": {"id": "C1", "name": "general"},
+            "users": [{"id": "U1", "name": "kanta"}, {"id": "U2", "name": "t", "profile": {"display_name": "taro"}}],
+            "messages": [
+                {"user": "U1", "text": "parent", "ts": "1770689887.565249", "replies": [
+                    {"user": "U2", "text": "a reply", "ts": "1770689890.000100"}
+                ]},
+                {"user": "U1", "text": "later", "ts": "1770689900.000100"}
+            ]}"#,
+        )
+        .unwrap();
+        let options = OutputOptions::default();
+        assert_eq!(
+            render_document(&export, &options).unwrap(),
+            "2026-02-10 02:18:07 @kanta parent\n  2026-02-10 02:18:10 @taro a reply\n2026-02-10 02:18:20 @kanta later"
+        );
+        let options = OutputOptions { limit: Some(2), format: OutputFormat::Json, ..OutputOptions::default() };
+        let rendered = json::parse(&render_document(&export, &options).unwrap()).unwrap();
+        assert_eq!(rendered.as_array().map(Vec::len), Some(2));
+
+        let list = json::parse(r#"{"ok": true, "channels": [{"id": "C1", "name": "general"}]}"#).unwrap();
+        assert_eq!(render_document(&list, &OutputOptions::default()).unwrap(), "C1\tgeneral");
+        let other = json::parse(r#"{"ok": true, "user": {"id": "U1"}}"#).unwrap();
+        assert!(render_document(&other, &OutputOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_fetch_new_replies() {
+        let transport = http::FakeTransport::new().respond(
+            "conversations.replies",
+            r#"{"ok": true, "messages": [
+                {"user": "U1", "text": "parent", "ts": "1770689887.565249"},
+                {"user": "U1", "text": "old reply", "ts": "1770689890.000100"},
+                {"user": "U1", "text": "new reply", "ts": "1770689900.000100"}
+            ]}"#,
+        );
+        let feed = Feed::Thread { channel_id: "C1", ts: "1770689887.565249" };
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let texts = |messages: Vec<message::SlackMessage>| messages.into_iter().map(|m| m.text).collect::<Vec<_>>();
+        let first = task::block_on(fetch_new_messages(&api, feed, None, 2)).unwrap();
+        assert_eq!(texts(first), vec!["old reply", "new reply"]);
+        let next = task::block_on(fetch_new_messages(&api, feed, Some("1770689890.000100"), 2)).unwrap();
+        assert_eq!(texts(next), vec!["new reply"]);
+        let requests = transport.requests.lock().unwrap();
+        assert!(requests[1].url.ends_with("ts=1770689887.565249&oldest=1770689890.000100"));
+        assert!(ts_after("1770689900.000100", "1770689890.999999"));
+        assert!(!ts_after("1770689890.000100", "1770689890.000100"));
+    }
+
+    #[test]
+    fn test_watcher_resumes_without_duplicates() {
+        // History works but replies don't, so a round fails halfway through.
+        let transport = http::FakeTransport::new().respond(
+            "conversations.history",
+            r#"{"ok": true, "messages": [{"user": "U1", "text": "hi", "ts": "1770689900.000100"}]}"#,
+        );
+        let feeds = [Feed::Channel("C1"), Feed::Thread { channel_id: "C2", ts: "1770689887.565249" }];
+        let options = OutputOptions::default();
+        let mut names = HashMap::from([("U1".to_string(), "kanta".to_string())]);
+        let mut watcher = Watcher {
+            api: SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test"),
+            feeds: &feeds,
+            options: &options,
+            user_names: &mut names,
+            cursors: vec![None; 2],
+            marker: None,
+            live: false,
+            failures: 2,
+            rtm_off: false,
+        };
+        assert!(watcher.catch_up(10).is_err());
+        // Nothing was printed, so nothing counts as seen.
+        assert_eq!(watcher.cursors, [None, None]);
+        assert_eq!(watcher.failures, 2);
+
+        watcher.cursors = vec![Some("1770689900.000100".to_string()), None];
+        let message = |ts: &str| message::SlackMessage {
+            user: "U1".to_string(),
+            text: "hi".to_string(),
+            ts: ts.to_string(),
+            ..Default::default()
+        };
+        watcher.deliver("C1", message("1770689900.000100")).unwrap();
+        watcher.deliver("C9", message("1770689999.000100")).unwrap();
+        assert_eq!(watcher.cursors[0].as_deref(), Some("1770689900.000100"));
+        watcher.deliver("C1", message("1770689950.000100")).unwrap();
+        assert_eq!(watcher.cursors[0].as_deref(), Some("1770689950.000100"));
+        assert_eq!(transport.requests.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_format_activity() {
+        let activity = socket_mode::Activity {
+            kind: "reaction_added".to_string(),
+            channel: Some("C1".to_string()),
+            user: "U1".to_string(),
+            action: "reacted :tada:".to_string(),
+        };
+        let names = HashMap::from([("U1".to_string(), "kanta".to_string())]);
+        let ts = "1770689887.000000";
+        let mut options = OutputOptions { color: true, ..OutputOptions::default() };
+        assert_eq!(
+            format_activity(&activity, ts, &names, &options),
+            "\x1b[2m2026-02-10 02:18:07 @kanta reacted :tada:\x1b[0m"
+        );
+        options.format = OutputFormat::Json;
+        assert_eq!(
+            format_activity(&activity, ts, &names, &options),
+            r#"{"channel":"C1","event":"reaction_added","time":"2026-02-10 02:18:07","user":"U1","user_name":"kanta","action":"reacted :tada:"}"#
+        );
+    }
+
+    #[test]
+    fn test_watcher_reads_typing_and_presence_over_rtm() {
+        use std::io::{BufRead, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+            let mut key = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.strip_prefix("Sec-WebSocket-Key: ") {
+                    key = value.trim().to_string();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut out = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                 Sec-WebSocket-Accept: {}\r\n\r\n",
+                websocket::accept_key(&key)
+            )
+            .into_bytes();
+            for event in [r#"{"type":"user_typing","channel":"C1","user":"U1"}"#, r#"{"type":"goodbye"}"#] {
+                out.extend([0x81, event.len() as u8]);
+                out.extend(event.as_bytes());
+            }
+            stream.write_all(&out).unwrap();
+            // The presence subscription, masked: its length is in the second byte.
+            let mut head = [0u8; 2];
+            reader.read_exact(&mut head).unwrap();
+            (head[1] & 0x7f) as usize
+        });
+
+        let transport = http::FakeTransport::new()
+            .respond("rtm.connect", &format!(r#"{{"ok": true, "url": "ws://127.0.0.1:{}/rtm"}}"#, port));
+        let feeds = [Feed::Channel("C1")];
+        let options = OutputOptions { events: true, ..OutputOptions::default() };
+        let mut names = HashMap::from([("U1".to_string(), "kanta".to_string())]);
+        let mut watcher = Watcher {
+            api: SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test"),
+            feeds: &feeds,
+            options: &options,
+            user_names: &mut names,
+            cursors: vec![None],
+            marker: None,
+            live: true,
+            failures: 0,
+            rtm_off: false,
+        };
+        let client = http::Client::new();
+        let mut rtm = watcher.connect_rtm(&client).map(|ws| (ws, 0));
+        assert!(rtm.is_some());
+        watcher.read_rtm(&mut rtm).unwrap();
+        // Subscribed to the one user seen, then dropped after the goodbye.
+        assert!(rtm.is_none());
+        assert_eq!(server.join().unwrap(), rtm::presence_sub(["U1"]).len());
+
+        // A token that may not use RTM turns typing and presence off, once.
+        let transport = http::FakeTransport::new()
+            .respond("rtm.connect", r#"{"ok": false, "error": "not_allowed_token_type"}"#);
+        watcher.api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        assert!(watcher.connect_rtm(&client).is_none());
+        assert!(watcher.rtm_off);
+        assert!(watcher.connect_rtm(&client).is_none());
+        assert_eq!(transport.requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_read_marker() {
+        let transport = http::FakeTransport::new().respond("conversations.mark", r#"{"ok": true}"#);
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let message = |ts: &str| message::SlackMessage { user: "U1".to_string(), ts: ts.to_string(), ..Default::default() };
+        let mut marker = ReadMarker::default();
+        marker.saw(&[("C1", message("1770689900.000100")), ("C1", message("1770689887.565249"))]);
+        marker.flush(&api, false);
+        marker.saw(&[("C1", message("1770689950.000100"))]);
+        // Too soon after the last call, unless asked to mark now.
+        marker.flush(&api, false);
+        assert_eq!(transport.requests.lock().unwrap().len(), 1);
+        marker.flush(&api, true);
+        let requests = transport.requests.lock().unwrap();
+        assert!(requests[0].url.ends_with("conversations.mark"));
+        assert_eq!(requests[0].body, b"channel=C1&ts=1770689900.000100");
+        assert_eq!(requests[1].body, b"channel=C1&ts=1770689950.000100");
+
+        let transport = http::FakeTransport::new()
+            .respond("conversations.mark", r#"{"ok": false, "error": "missing_scope"}"#);
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let mut marker = ReadMarker::default();
+        marker.saw(&[("C1", message("1770689900.000100"))]);
+        marker.flush(&api, true);
+        assert!(marker.failed);
+        marker.saw(&[("C1", message("1770689950.000100"))]);
+        marker.flush(&api, true);
+        assert_eq!(transport.requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_watch_interval() {
+        let matches = |args: &[&str]| cli::parse(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>()).unwrap();
+        let secs = |m: &cli::Matches, configured| watch_interval(m, configured).map(|d| d.as_secs());
+        assert_eq!(secs(&matches(&["slk", "watch"]), None).unwrap(), DEFAULT_WATCH_INTERVAL_SECS);
+        assert_eq!(secs(&matches(&["slk", "watch"]), Some(30)).unwrap(), 30);
+        assert_eq!(secs(&matches(&["slk", "watch", "--interval", "2"]), Some(30)).unwrap(), 2);
+        assert!(secs(&matches(&["slk", "watch", "--interval", "0"]), None).is_err());
+    }
+
+    #[test]
+    fn test_run_api() {
+        let args: Vec<String> = ["slk", "api", "pins.list", "channel=C1", "q=a=b"].map(String::from).into();
+        match parse_args(args).unwrap().0 {
+            Command::Api { method, params } => {
+                assert_eq!(method, "pins.list");
+                let params: Vec<(&str, &str)> = params.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+                assert_eq!(params, [("channel", "C1"), ("q", "a=b")]);
+            }
+            _ => panic!("expected Api"),
+        }
+        let args: Vec<String> = ["slk", "api", "pins.list", "C1"].map(String::from).into();
+        assert!(parse_args(args).is_err());
+        let args: Vec<String> = ["slk", "api", "../oauth.v2.access"].map(String::from).into();
+        assert!(parse_args(args).is_err());
+
+        let transport = http::FakeTransport::new()
+            .respond("pins.list", r#"{"ok": true, "items": []}"#)
+            .respond("bookmarks.list", r#"{"ok": false, "error": "missing_scope"}"#);
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let params = [("channel".to_string(), "C1".to_string())];
+        assert_eq!(run_api(&api, "pins.list", &params, false).unwrap(), r#"{"ok": true, "items": []}"#);
+        assert!(run_api(&api, "pins.list", &params, true).unwrap().contains("\n  \"items\": []"));
+        assert_eq!(raw_response(r#"{"ok": true, "items": []}"#).unwrap(), "{\n  \"ok\": true,\n  \"items\": []\n}");
+        assert!(raw_response(r#"{"ok": false, "error": "channel_not_found"}"#).is_err());
+        let err = run_api(&api, "bookmarks.list", &[], false).unwrap_err();
+        assert!(err.message.contains("missing_scope"));
+        let requests = transport.requests.lock().unwrap();
+        assert!(requests[0].url.ends_with("/pins.list"));
+        assert_eq!(String::from_utf8_lossy(&requests[0].body), "channel=C1");
+    }
+
+    #[test]
+    fn test_run_send() {
+        let transport = http::FakeTransport::new().respond(
+            "chat.postMessage",
+            r#"{"ok": true, "channel": "C1", "ts": "1770689900.000100"}"#,
+        );
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let reply = || slack_api::PostOptions { thread_ts: Some("1770689887.565249"), ..Default::default() };
+        assert_eq!(
+            run_send(&api, "C1", "a & b", &slack_api::PostOptions::default()).unwrap(),
+            "Sent to C1 (ts 1770689900.000100)"
+        );
+        assert_eq!(
+            run_send(&api, "C1", "hi", &reply()).unwrap(),
+            "Replied to thread 1770689887.565249 in C1 (ts 1770689900.000100)"
+        );
+        let blocks = slack_api::PostOptions { blocks: Some(r#"[{"type":"divider"}]"#), ..Default::default() };
+        run_send(&api, "C1", "", &blocks).unwrap();
+        assert_eq!(
+            run_send(&api, "C1", "fixed", &slack_api::PostOptions { broadcast: true, ..reply() }).unwrap(),
+            "Replied to thread 1770689887.565249 in C1 and sent it to the channel (ts 1770689900.000100)"
+        );
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests[0].method, http::Method::Post);
+        assert_eq!(String::from_utf8_lossy(&requests[0].body), "channel=C1&text=a%20%26%20b");
+        assert_eq!(
+            String::from_utf8_lossy(&requests[1].body),
+            "channel=C1&text=hi&thread_ts=1770689887.565249"
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&requests[2].body),
+            "channel=C1&blocks=%5B%7B%22type%22%3A%22divider%22%7D%5D"
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&requests[3].body),
+            "channel=C1&text=fixed&thread_ts=1770689887.565249&reply_broadcast=true"
+        );
+    }
+
+    #[test]
+    fn test_run_webhook() {
+        let transport = http::FakeTransport::new().respond("XXXX", "ok");
+        let url = "https://hooks.slack.com/services/T1/B1/XXXX";
+        assert_eq!(run_webhook(&transport, url, "Deploy \"v2\" done").unwrap(), "Sent to the webhook");
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!((requests[0].method, requests[0].url.as_str()), (http::Method::Post, url));
+        assert!(requests[0].headers.contains(&("Content-Type".to_string(), "application/json".to_string())));
+        assert_eq!(String::from_utf8_lossy(&requests[0].body), r#"{"text":"Deploy \"v2\" done"}"#);
+        drop(requests);
+
+        let err = run_webhook(&transport, "https://hooks.slack.com/services/T1/B1/gone", "hi").unwrap_err();
+        assert!(err.to_string().contains("HTTP 404"), "{}", err);
+        let err = run_webhook(&transport, "hooks.slack.com/services/T1/B1/XXXX", "hi").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Usage);
+    }
+
+    #[test]
+    fn test_run_bad_option_combination_exits_with_usage_status() {
+        let args: Vec<String> = ["slk", "export", "C1", "--all", "--out", "backup"].map(String::from).into();
+        let err = run(args).unwrap_err();
+        assert_eq!(err.message, "give either a channel or --all, not both");
+        assert_eq!(err.kind.exit_code(), 2);
+    }
+
+    #[test]
+    fn test_channel_header() {
+        let transport = http::FakeTransport::new()
+            .respond(
+                "conversations.info?channel=C1",
+                r#"{"ok": true, "channel": {"id": "C1", "name": "general", "num_members": 42,
+                    "topic": {"value": "Announcements\nand news"}}}"#,
+            )
+            .respond("conversations.info?channel=D1", r#"{"ok": true, "channel": {"id": "D1", "user": "U2"}}"#)
+            .respond("users.info", r#"{"ok": true, "user": {"id": "U2", "name": "taro"}}"#);
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let mut names = HashMap::new();
+        let options = OutputOptions::default();
+        assert_eq!(
+            channel_header(&api, "C1", &options, &mut names).unwrap(),
+            "#general — 42 members — topic: Announcements and news"
+        );
+        assert!(transport.requests.lock().unwrap()[0].url.ends_with("&include_num_members=true"));
+        assert_eq!(channel_header(&api, "D1", &options, &mut names).unwrap(), "@taro");
+        let info = ConversationsInfoResponse {
+            id: "C2".to_string(),
+            name: "ops".to_string(),
+            user: None,
+            num_members: Some(1),
+            topic: String::new(),
+        };
+        let options = OutputOptions { color: true, ..OutputOptions::default() };
+        assert_eq!(format_channel_header(&info, &names, &options), "\x1b[1m#ops — 1 member\x1b[0m");
+    }
+
+    #[test]
+    fn test_run_show_user() {
+        let transport = http::FakeTransport::new()
+            .respond(
+                "users.list",
+                r#"{"ok": true, "members": [{"id": "U081R4ZS5E2", "name": "kanta"}]}"#,
+            )
+            .respond(
+                "users.info",
+                r#"{"ok": true, "user": {"id": "U081R4ZS5E2", "name": "kanta", "real_name": "Kanta Otomaeru",
+                    "tz": "Asia/Tokyo", "tz_label": "Japan Standard Time", "tz_offset": 32400,
+                    "profile": {"display_name": "kanta", "title": "SRE", "email": ""}}}"#,
+            );
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        let mut options = OutputOptions { time_format: "%H:%M".to_string(), ..OutputOptions::default() };
+        let user = run_show_user(&api, "@kanta", &options, &mut HashMap::new()).unwrap();
+        assert!(user.starts_with("User:         @kanta (U081R4ZS5E2)\nDisplay name: kanta\n"));
+        assert!(user.contains("\nTitle:        SRE\nTime zone:    Asia/Tokyo (Japan Standard Time)\n"));
+        assert!(user.contains("(UTC+09:00)"));
+        assert!(!user.contains("Email"));
+        assert!(transport.requests.lock().unwrap()[1].url.ends_with("users.info?user=U081R4ZS5E2"));
+
+        let user = message::SlackUser {
+            id: "U1".to_string(),
+            name: "taro".to_string(),
+            tz: "America/St_Johns".to_string(),
+            tz_offset: -9000,
+            ..message::SlackUser::default()
+        };
+        assert_eq!(
+            format_user(&user, 1770689887, &options),
+            "User:         @taro (U1)\nTime zone:    America/St_Johns\nLocal time:   23:48 (UTC-02:30)"
+        );
+        options.format = OutputFormat::Json;
+        let json = json::parse(&format_user(&user, 1770689887, &options)).unwrap();
+        assert_eq!(json.get("local_time").and_then(|v| v.as_str()), Some("23:48"));
+        assert_eq!(json.get("tz_offset").and_then(|v| v.as_i64()), Some(-9000));
+    }
+
+    #[test]
+    fn test_run_permalink() {
+        let transport = http::FakeTransport::new().respond(
+            "chat.getPermalink",
+            r#"{"ok": true, "channel": "C1", "permalink": "https://myteam.slack.com/archives/C1/p1770689887565249"}"#,
+        );
+        let api = SlackClient::new(&transport, config::DEFAULT_API_BASE_URL, "xoxp-test");
+        assert_eq!(
+            run_permalink(&api, "C1", "1770689887.565249").unwrap(),
+            "https://myteam.slack.com/archives/C1/p1770689887565249"
+        );
+        let requests = transport.requests.lock().unwrap();
+        assert!(requests[0].url.ends_with("/chat.getPermalink?channel=C1&message_ts=1770689887.565249"));
+
+        let args = ["slk", "permalink", "#general", "1770689887.565249", "--copy"].map(String::from).to_vec();
+        let (command, matches) = parse_args(args).unwrap();
+        assert!(matches!(command, Command::Permalink { channel_id, ts }
+            if channel_id == "#general" && ts == "1770689887.565249"));
+        assert!(matches.flag("copy"));
+        assert!(parse_args(["slk", "permalink", "general"].map(String::from).to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_login_bot() {
+        let args = vec!["slk".to_string(), "login".to_string(), "--bot".to_string()];
+        assert!(matches!(
+            parse_args(args).unwrap().0,
+            Command::Login { token: None, bot: true, no_browser: false }
+        ));
+    }
+
+    #[test]
+    fn test_parse_args_login_no_browser() {
+        let args = vec![
+            "slk".to_string(),
+            "login".to_string(),
+            "--no-browser".to_string(),
+            "--bot".to_string(),
+        ];
+        assert!(matches!(
+            parse_args(args).unwrap().0,
+            Command::Login { token: None, bot: true, no_browser: true }
+        ));
+    }
+
+    #[test]
+    fn test_parse_args_logout() {
+        let args = vec!["slk".to_string(), "logout".to_string()];
+        assert!(matches!(parse_args(args).unwrap().0, Command::Logout));
+    }
+
+    #[test]
+    fn test_revoke_credentials() {
+        let transport = http::FakeTransport::new()
+            .respond("auth.revoke", r#"{"ok": true, "revoked": true}"#);
+        let creds = config::Credentials {
+            token: "xoxp-user".to_string(),
+            bot_token: Some("xoxb-bot".to_string()),
+            ..config::Credentials::default()
+        };
+        let revoked = revoke_credentials(&transport, config::DEFAULT_API_BASE_URL, &creds);
+        assert_eq!(revoked, vec!["Revoked user token", "Revoked bot token"]);
+
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].headers.iter().any(|(_, v)| v == "Bearer xoxb-bot"));
+    }
+
+    #[test]
+    fn test_revoke_credentials_reports_failure() {
+        let transport = http::FakeTransport::new()
+            .respond("auth.revoke", r#"{"ok": false, "error": "invalid_auth"}"#);
+        let creds = config::Credentials {
+            token: "xoxp-user".to_string(),
+            ..config::Credentials::default()
+        };
+        let revoked = revoke_credentials(&transport, config::DEFAULT_API_BASE_URL, &creds);
+        assert_eq!(revoked.len(), 1);
+        assert!(revoked[0].starts_with("Could not revoke user token"));
+        assert!(revoked[0].contains("invalid_auth"));
+    }
+
+    #[test]
+    fn test_workspace_mismatch() {
+        let identity = message::AuthIdentity {
+            url: "https://myteam.slack.com/".to_string(),
+            ..message::AuthIdentity::default()
+        };
+        assert!(!workspace_mismatch(&identity, "myteam.slack.com"));
+        assert!(workspace_mismatch(&identity, "otherteam.slack.com"));
+        assert!(!workspace_mismatch(&message::AuthIdentity::default(), "otherteam.slack.com"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_token_cmd() {
+        assert_eq!(run_token_cmd("printf 'xoxp-abc\\nextra\\n'").unwrap(), "xoxp-abc");
+        assert!(run_token_cmd("exit 3").unwrap_err().message.contains("failed"));
+        assert!(run_token_cmd("true").unwrap_err().message.contains("no token"));
+    }
+
+    #[test]
+    fn test_output_options_flags_override_defaults() {
+        let defaults = config::Defaults {
+            format: Some(OutputFormat::Json),
+            time_format: Some("%H:%M".to_string()),
+            color: Some(ColorMode::Always),
+            limit: Some(10),
+            ..config::Defaults::default()
+        };
+        let args: Vec<String> = ["slk", "history", "--limit", "3", "--color", "never"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let matches = cli::parse(&args).unwrap();
+        let options = OutputOptions::from_matches(&matches, &defaults).unwrap();
+        assert_eq!(options.format, OutputFormat::Json);
+        assert_eq!(options.time_format, "%H:%M");
+        assert!(!options.color);
+        assert_eq!(options.limit, Some(3));
+
+        let args = vec!["slk".to_string(), "--limit".to_string(), "0".to_string()];
+        let matches = cli::parse(&args).unwrap();
+        assert!(OutputOptions::from_matches(&matches, &defaults).is_err());
+    }
+
+    #[test]
+    fn test_format_messages_options() {
+        let messages = vec![
+            message::SlackMessage {
+                user: "U1".to_string(),
+                text: "first \"quoted\"".to_string(),
+                ts: "1770689887.565249".to_string(),
+                ..Default::default()
+            },
+            message::SlackMessage {
+                user: "U2".to_string(),
+                text: "second".to_string(),
+                ts: "1770689900.000100".to_string(),
+                ..Default::default()
+            },
+        ];
+        let mut user_names = HashMap::new();
+        user_names.insert("U1".to_string(), "kanta".to_string());
+
+        let options = OutputOptions {
+            time_format: "%H:%M".to_string(),
+            color: true,
+            limit: Some(1),
+            ..OutputOptions::default()
+        };
+        assert_eq!(
+            format_messages(&messages, &user_names, &options),
+            "\x1b[2m02:18\x1b[0m \x1b[1;36m@kanta\x1b[0m first \"quoted\""
+        );
+
+        let options = OutputOptions {
+            format: OutputFormat::Json,
+            ..OutputOptions::default()
+        };
+        assert_eq!(
+            format_messages(&messages, &user_names, &options),
+            r#"[{"ts":"1770689887.565249","time":"2026-02-10 02:18:07","user":"U1","user_name":"kanta","text":"first \"quoted\""},{"ts":"1770689900.000100","time":"2026-02-10 02:18:20","user":"U2","text":"second"}]"#
+        );
+    }
+
+    fn strings(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_expand_alias() {
+        let aliases = vec![
+            ("standup".to_string(), strings(&["history", "C0123", "--limit", "20"])),
+            ("su".to_string(), strings(&["standup"])),
+            ("list".to_string(), strings(&["history", "C9"])),
+        ];
+        assert_eq!(
+            expand_alias(strings(&["slk", "su", "--format", "json"]), &aliases).unwrap(),
+            strings(&["slk", "history", "C0123", "--limit", "20", "--format", "json"])
+        );
+        // Built-in commands and unknown words are left alone.
+        assert_eq!(
+            expand_alias(strings(&["slk", "list"]), &aliases).unwrap(),
+            strings(&["slk", "list"])
+        );
+        assert_eq!(
+            expand_alias(strings(&["slk", "nope"]), &aliases).unwrap(),
+            strings(&["slk", "nope"])
+        );
+    }
+
+    #[test]
+    fn test_expand_alias_loop() {
+        let aliases = vec![
+            ("a".to_string(), strings(&["b", "x"])),
+            ("b".to_string(), strings(&["a"])),
+        ];
+        let err = expand_alias(strings(&["slk", "a"]), &aliases).unwrap_err();
+        assert_eq!(err.message, "alias loop: a -> b -> a");
+    }
+}
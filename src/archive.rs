@@ -0,0 +1,309 @@
+use crate::cache;
+use crate::config::cache_dir;
+use crate::error::SlkError;
+use crate::json::{self, JsonValue};
+use crate::message::{SlackConversation, SlackUser};
+use crate::resolve;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CHANNELS_FILE: &str = "channels.json";
+const USERS_FILE: &str = "users.json";
+const MESSAGES_DIR: &str = "messages";
+
+/// The local copy of synced channels that `slk sync` keeps under the cache
+/// directory, so messages can be read and searched without the API:
+///
+/// - `channels.json`: the synced channels and when each was last synced
+/// - `users.json`: user ID to name for the messages' authors
+/// - `messages/<channel ID>.jsonl`: the channel's messages as the API
+///   returned them, one per line, oldest first
+pub struct Archive {
+    dir: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchivedChannel {
+    pub id: String,
+    pub name: String,
+    /// Unix time of the last sync.
+    pub synced_at: u64,
+    /// The `ts` of the newest archived message: the next sync starts there.
+    pub latest: Option<String>,
+}
+
+/// What storing a batch of messages did to a channel's archive.
+#[derive(Debug, PartialEq)]
+pub struct Stored {
+    /// Messages that weren't archived before.
+    pub added: usize,
+    /// Archived messages that came back different, usually because they were edited.
+    pub updated: usize,
+    /// Messages in the channel's archive afterwards.
+    pub total: usize,
+    /// The `ts` of the newest archived message.
+    pub latest: Option<String>,
+}
+
+impl Archive {
+    pub fn open() -> Result<Self, SlkError> {
+        Ok(Archive::at(cache_dir()?.join("archive")))
+    }
+
+    pub fn at(dir: PathBuf) -> Self {
+        Archive { dir }
+    }
+
+    /// The synced channels, by name.
+    pub fn channels(&self) -> Vec<ArchivedChannel> {
+        read(&self.dir.join(CHANNELS_FILE))
+            .map(|v| channels_from_json(&v))
+            .unwrap_or_default()
+    }
+
+    pub fn channel(&self, id: &str) -> Option<ArchivedChannel> {
+        self.channels().into_iter().find(|c| c.id == id)
+    }
+
+    /// The synced channel given on the command line as a name (`general` or
+    /// `#general`) or an ID.
+    pub fn find_channel(&self, channel: &str) -> Result<ArchivedChannel, SlkError> {
+        let channels = self.channels();
+        let id = if resolve::is_channel_id(channel) {
+            Some(channel.to_string())
+        } else {
+            let conversations: Vec<SlackConversation> = channels
+                .iter()
+                .map(|c| SlackConversation { id: c.id.clone(), name: c.name.clone() })
+                .collect();
+            resolve::find_channel(&conversations, channel)?
+        };
+        id.and_then(|id| channels.into_iter().find(|c| c.id == id)).ok_or_else(|| {
+            SlkError::from(format!("{} has not been synced; run `slk sync {}` first", channel, channel))
+        })
+    }
+
+    /// The ID of a user given as an ID, handle or name, looked up in the
+    /// archived names.
+    pub fn find_user(&self, user: &str) -> Result<String, SlkError> {
+        if resolve::is_user_id(user) {
+            return Ok(user.to_string());
+        }
+        let users: Vec<SlackUser> = self
+            .users()
+            .into_iter()
+            .map(|(id, name)| SlackUser { id, display_name: name, ..SlackUser::default() })
+            .collect();
+        resolve::find_user(&users, user)?.ok_or_else(|| resolve::unknown_user(&users, user))
+    }
+
+    /// Adds `channel` to the channel list, replacing its earlier entry.
+    pub fn record_sync(&self, channel: ArchivedChannel) -> Result<(), SlkError> {
+        let mut channels = self.channels();
+        channels.retain(|c| c.id != channel.id);
+        channels.push(channel);
+        channels.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+        write(&self.dir.join(CHANNELS_FILE), &channels_to_json(&channels).to_string())
+    }
+
+    pub fn users(&self) -> HashMap<String, String> {
+        read(&self.dir.join(USERS_FILE))
+            .map(|v| cache::users_from_json(&v))
+            .unwrap_or_default()
+    }
+
+    /// Adds `users` to the archived names, replacing older names for the same IDs.
+    pub fn save_users(&self, users: &HashMap<String, String>) -> Result<(), SlkError> {
+        let mut all = self.users();
+        all.extend(users.iter().map(|(id, name)| (id.clone(), name.clone())));
+        write(&self.dir.join(USERS_FILE), &cache::users_to_json(&all).to_string())
+    }
+
+    fn messages_path(&self, channel_id: &str) -> PathBuf {
+        self.dir.join(MESSAGES_DIR).join(format!("{}.jsonl", channel_id))
+    }
+
+    /// The channel's archived messages, oldest first. A channel that was
+    /// never synced has none.
+    pub fn messages(&self, channel_id: &str) -> Result<Vec<JsonValue>, SlkError> {
+        let path = self.messages_path(channel_id);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(SlkError::from(format!("failed to read {}: {}", path.display(), e))),
+        };
+        contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(i, line)| {
+                json::parse(line).map_err(|e| {
+                    SlkError::from(format!("{} is damaged at line {}: {}", path.display(), i + 1, e))
+                })
+            })
+            .collect()
+    }
+
+    /// Merges `messages` into the channel's archive. A message that is
+    /// already there (the same `ts`) is replaced, so edits are picked up;
+    /// messages without a `ts` are ignored.
+    pub fn store_messages(&self, channel_id: &str, messages: Vec<JsonValue>) -> Result<Stored, SlkError> {
+        let mut by_ts: BTreeMap<(u64, u64), JsonValue> = BTreeMap::new();
+        for m in self.messages(channel_id)? {
+            if let Some(key) = message_key(&m) {
+                by_ts.insert(key, m);
+            }
+        }
+        let known = by_ts.len();
+        let mut updated = 0;
+        for m in messages {
+            if let Some(key) = message_key(&m)
+                && by_ts.insert(key, m.clone()).is_some_and(|old| old != m)
+            {
+                updated += 1;
+            }
+        }
+        let mut contents = String::new();
+        for m in by_ts.values() {
+            contents.push_str(&m.to_string());
+            contents.push('\n');
+        }
+        write(&self.messages_path(channel_id), &contents)?;
+        let latest = by_ts.values().next_back().and_then(|m| m.get("ts")?.as_str()).map(String::from);
+        Ok(Stored { added: by_ts.len() - known, updated, total: by_ts.len(), latest })
+    }
+}
+
+fn message_key(message: &JsonValue) -> Option<(u64, u64)> {
+    message.get("ts").and_then(|v| v.as_str()).map(crate::message::ts_key)
+}
+
+fn read(path: &Path) -> Option<JsonValue> {
+    let contents = fs::read_to_string(path).ok()?;
+    json::parse(&contents).ok()
+}
+
+/// Writes through a temporary file, so an interrupted sync or export leaves
+/// the previous contents in place.
+pub fn write(path: &Path, contents: &str) -> Result<(), SlkError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| {
+            SlkError::from(format!("failed to create directory {}: {}", dir.display(), e))
+        })?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)
+        .and_then(|_| fs::rename(&tmp, path))
+        .map_err(|e| SlkError::from(format!("failed to write {}: {}", path.display(), e)))
+}
+
+fn channels_from_json(value: &JsonValue) -> Vec<ArchivedChannel> {
+    value
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| {
+                    Some(ArchivedChannel {
+                        id: item.get("id")?.as_str()?.to_string(),
+                        name: item.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                        synced_at: item.get("synced_at").and_then(|v| v.as_u64()).unwrap_or(0),
+                        latest: item.get("latest").and_then(|v| v.as_str()).map(String::from),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn channels_to_json(channels: &[ArchivedChannel]) -> JsonValue {
+    JsonValue::Array(
+        channels
+            .iter()
+            .map(|c| {
+                let mut channel = JsonValue::object([
+                    ("id", c.id.as_str().into()),
+                    ("name", c.name.as_str().into()),
+                    ("synced_at", c.synced_at.into()),
+                ]);
+                if let Some(latest) = &c.latest {
+                    channel.set("latest", latest.as_str());
+                }
+                channel
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(name: &str) -> Archive {
+        let dir = std::env::temp_dir().join(format!("slk-archive-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Archive::at(dir)
+    }
+
+    fn message(ts: &str, text: &str) -> JsonValue {
+        json::parse(&format!(r#"{{"user":"U1","text":"{}","ts":"{}"}}"#, text, ts)).unwrap()
+    }
+
+    #[test]
+    fn test_store_messages_merges_by_ts() {
+        let archive = archive("merge");
+        assert!(archive.messages("C1").unwrap().is_empty());
+        let stored = archive
+            .store_messages("C1", vec![message("1700000002.000000", "b"), message("1700000001.000000", "a")])
+            .unwrap();
+        assert_eq!(
+            stored,
+            Stored { added: 2, updated: 0, total: 2, latest: Some("1700000002.000000".to_string()) }
+        );
+        let stored = archive
+            .store_messages(
+                "C1",
+                vec![message("1700000002.000000", "b (edited)"), message("1700000003.000000", "c")],
+            )
+            .unwrap();
+        assert_eq!(
+            stored,
+            Stored { added: 1, updated: 1, total: 3, latest: Some("1700000003.000000".to_string()) }
+        );
+        let texts: Vec<String> = archive
+            .messages("C1")
+            .unwrap()
+            .iter()
+            .map(|m| m.get("text").and_then(|v| v.as_str()).unwrap().to_string())
+            .collect();
+        assert_eq!(texts, ["a", "b (edited)", "c"]);
+        let _ = fs::remove_dir_all(&archive.dir);
+    }
+
+    #[test]
+    fn test_channels_and_users() {
+        let archive = archive("channels");
+        let general = ArchivedChannel {
+            id: "C1".to_string(),
+            name: "general".to_string(),
+            synced_at: 1,
+            latest: Some("1700000001.000000".to_string()),
+        };
+        archive.record_sync(general.clone()).unwrap();
+        archive
+            .record_sync(ArchivedChannel { id: "C2".to_string(), name: "dev".to_string(), synced_at: 2, latest: None })
+            .unwrap();
+        archive.record_sync(ArchivedChannel { synced_at: 3, ..general }).unwrap();
+        let channels = archive.channels();
+        assert_eq!(channels.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), ["C2", "C1"]);
+        assert_eq!(archive.channel("C1").map(|c| c.synced_at), Some(3));
+        assert_eq!(channels[1].latest.as_deref(), Some("1700000001.000000"));
+        assert_eq!(channels[0].latest, None);
+
+        archive.save_users(&HashMap::from([("U1".to_string(), "kanta".to_string())])).unwrap();
+        archive.save_users(&HashMap::from([("U2".to_string(), "taro".to_string())])).unwrap();
+        assert_eq!(archive.users().len(), 2);
+        let _ = fs::remove_dir_all(&archive.dir);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::config::cache_dir;
+use crate::error::SlkError;
+use crate::json::{self, JsonValue};
+use crate::message::SlackConversation;
+use crate::info;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const USERS_FILE: &str = "users.json";
+const CHANNELS_FILE: &str = "channels.json";
+
+/// How long cached user names are trusted, in seconds, unless
+/// `users_cache_ttl` says otherwise.
+pub const DEFAULT_USERS_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+/// The same for the conversation list (`channels_cache_ttl`).
+pub const DEFAULT_CHANNELS_TTL_SECS: u64 = 24 * 60 * 60;
+
+static USERS_TTL: AtomicU64 = AtomicU64::new(DEFAULT_USERS_TTL_SECS);
+static CHANNELS_TTL: AtomicU64 = AtomicU64::new(DEFAULT_CHANNELS_TTL_SECS);
+
+/// Sets how old, in seconds, the user names and the conversation list may
+/// get before they are fetched again. Zero never uses them (`--refresh`).
+pub fn set_ttls(users: u64, channels: u64) {
+    USERS_TTL.store(users, Ordering::Relaxed);
+    CHANNELS_TTL.store(channels, Ordering::Relaxed);
+}
+
+/// Reads a cache file; a missing or unreadable cache is simply empty.
+fn read(name: &str) -> Option<JsonValue> {
+    let path = cache_dir().ok()?.join(name);
+    let contents = fs::read_to_string(path).ok()?;
+    json::parse(&contents).ok()
+}
+
+/// When a cache file was written. Files from before caches expired have no
+/// time, and count as expired.
+fn saved_at(cache: &JsonValue) -> u64 {
+    cache.get("saved_at").and_then(|v| v.as_u64()).unwrap_or(0)
+}
+
+/// The `key` entry of a cache file written less than `ttl` seconds before `now`.
+fn fresh<'a>(cache: &'a JsonValue, key: &str, ttl: u64, now: u64) -> Option<&'a JsonValue> {
+    if now.saturating_sub(saved_at(cache)) >= ttl {
+        return None;
+    }
+    cache.get(key)
+}
+
+/// Reads the `key` entry of a cache file, if it hasn't expired.
+fn read_fresh(name: &str, key: &str, ttl: &AtomicU64) -> Option<JsonValue> {
+    let cache = read(name)?;
+    let entry = fresh(&cache, key, ttl.load(Ordering::Relaxed), crate::config::now_unix()).cloned();
+    if entry.is_none() {
+        info!("The {} cache has expired; fetching again", key);
+    }
+    entry
+}
+
+/// Wraps a cache's contents with the time it was saved.
+fn stamped(key: &str, value: JsonValue, saved_at: u64) -> JsonValue {
+    JsonValue::object([("saved_at", saved_at.into()), (key, value)])
+}
+
+fn write(name: &str, value: &JsonValue) -> Result<PathBuf, SlkError> {
+    let dir = cache_dir()?;
+    fs::create_dir_all(&dir).map_err(|e| {
+        SlkError::from(format!("failed to create directory {}: {}", dir.display(), e))
+    })?;
+    let path = dir.join(name);
+    fs::write(&path, value.to_string())
+        .map_err(|e| SlkError::from(format!("failed to write {}: {}", path.display(), e)))?;
+    Ok(path)
+}
+
+pub fn users_from_json(value: &JsonValue) -> HashMap<String, String> {
+    let JsonValue::Object(pairs) = value else {
+        return HashMap::new();
+    };
+    pairs
+        .iter()
+        .filter_map(|(id, name)| Some((id.clone(), name.as_str()?.to_string())))
+        .collect()
+}
+
+pub fn users_to_json(users: &HashMap<String, String>) -> JsonValue {
+    let mut pairs: Vec<(String, JsonValue)> = users
+        .iter()
+        .map(|(id, name)| (id.clone(), JsonValue::String(name.clone())))
+        .collect();
+    pairs.sort_by(|a, b| a.0.cmp(&b.0));
+    JsonValue::Object(pairs)
+}
+
+fn channels_from_json(value: &JsonValue) -> Vec<SlackConversation> {
+    value
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| {
+                    Some(SlackConversation {
+                        id: item.get("id")?.as_str()?.to_string(),
+                        name: item.get("name")?.as_str()?.to_string(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn channels_to_json(channels: &[SlackConversation]) -> JsonValue {
+    JsonValue::Array(
+        channels
+            .iter()
+            .map(|c| {
+                JsonValue::object([("id", c.id.as_str().into()), ("name", c.name.as_str().into())])
+            })
+            .collect(),
+    )
+}
+
+/// User ID to display name, as last resolved; empty once expired.
+pub fn load_users() -> HashMap<String, String> {
+    read_fresh(USERS_FILE, "users", &USERS_TTL).map(|v| users_from_json(&v)).unwrap_or_default()
+}
+
+/// Saves `users`, which are the loaded names plus any resolved since. Adding
+/// names keeps the file's time, so the older ones still expire on schedule.
+pub fn save_users(users: &HashMap<String, String>) -> Result<PathBuf, SlkError> {
+    let now = crate::config::now_unix();
+    let ttl = USERS_TTL.load(Ordering::Relaxed);
+    let saved_at = read(USERS_FILE)
+        .map(|cache| saved_at(&cache))
+        .filter(|&saved_at| now.saturating_sub(saved_at) < ttl)
+        .unwrap_or(now);
+    write(USERS_FILE, &stamped("users", users_to_json(users), saved_at))
+}
+
+/// The conversation list as of the last `slk list`; empty once expired.
+pub fn load_channels() -> Vec<SlackConversation> {
+    read_fresh(CHANNELS_FILE, "channels", &CHANNELS_TTL).map(|v| channels_from_json(&v)).unwrap_or_default()
+}
+
+pub fn save_channels(channels: &[SlackConversation]) -> Result<PathBuf, SlkError> {
+    write(CHANNELS_FILE, &stamped("channels", channels_to_json(channels), crate::config::now_unix()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_users_round_trip() {
+        let mut users = HashMap::new();
+        users.insert("U2".to_string(), "taro".to_string());
+        users.insert("U1".to_string(), "kanta".to_string());
+        let value = users_to_json(&users);
+        assert_eq!(value.to_string(), r#"{"U1":"kanta","U2":"taro"}"#);
+        assert_eq!(users_from_json(&value), users);
+        assert!(users_from_json(&JsonValue::Array(vec![])).is_empty());
+    }
+
+    #[test]
+    fn test_channels_round_trip() {
+        let channels = vec![SlackConversation {
+            id: "C1".to_string(),
+            name: "general".to_string(),
+        }];
+        let value = channels_to_json(&channels);
+        assert_eq!(value.to_string(), r#"[{"id":"C1","name":"general"}]"#);
+        assert_eq!(channels_from_json(&value), channels);
+        let partial = json::parse(r#"[{"id":"C2"},{"id":"C3","name":"random"}]"#).unwrap();
+        assert_eq!(channels_from_json(&partial).len(), 1);
+    }
+
+    #[test]
+    fn test_fresh() {
+        let cache = stamped("users", json::parse(r#"{"U1":"kanta"}"#).unwrap(), 1000);
+        assert_eq!(cache.to_string(), r#"{"saved_at":1000,"users":{"U1":"kanta"}}"#);
+        assert!(fresh(&cache, "users", 60, 1059).is_some());
+        assert!(fresh(&cache, "users", 60, 1060).is_none());
+        assert!(fresh(&cache, "users", 0, 1000).is_none());
+        // Caches written before they expired have no time.
+        let old = json::parse(r#"{"U1":"kanta"}"#).unwrap();
+        assert!(fresh(&old, "users", u64::MAX, 1000).is_none());
+    }
+}
@@ -0,0 +1,1107 @@
+use crate::error::SlkError;
+
+/// Whether a flag takes a value, and what it is called in usage text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlagValue {
+    None,
+    Required(&'static str),
+    /// Taken from the next argument unless that looks like a flag.
+    Optional(&'static str),
+}
+
+#[derive(Debug)]
+pub struct Flag {
+    /// The long name, without the leading `--`.
+    pub name: &'static str,
+    pub short: Option<char>,
+    pub value: FlagValue,
+    pub help: &'static str,
+}
+
+#[derive(Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// Positional arguments, one entry per way of calling the command.
+    pub forms: &'static [&'static str],
+    /// Shorter names accepted in place of `name` (`ls` for `list`).
+    pub aliases: &'static [&'static str],
+    pub about: &'static str,
+    pub flags: &'static [Flag],
+    /// Left out of help and completion scripts.
+    pub hidden: bool,
+}
+
+/// Flags every command accepts, before or after the command name.
+pub const GLOBAL_FLAGS: &[Flag] = &[
+    Flag {
+        name: "as",
+        short: None,
+        value: FlagValue::Required("user|bot"),
+        help: "Act with the stored user or bot token",
+    },
+    Flag {
+        name: "format",
+        short: None,
+        value: FlagValue::Required("text|json|csv"),
+        help: "Print plain text, JSON, or CSV (slk stats, activity and history --count)",
+    },
+    Flag {
+        name: "time-format",
+        short: None,
+        value: FlagValue::Required("pattern"),
+        help: "strftime-style timestamp layout",
+    },
+    Flag {
+        name: "color",
+        short: None,
+        value: FlagValue::Required("auto|always|never"),
+        help: "Colorize timestamps and names",
+    },
+    Flag {
+        name: "limit",
+        short: None,
+        value: FlagValue::Required("n"),
+        help: "Show at most n messages or conversations",
+    },
+    Flag {
+        name: "query",
+        short: None,
+        value: FlagValue::Required("path"),
+        help: "Print only these fields of the JSON output (e.g. messages[].text)",
+    },
+    Flag {
+        name: "copy",
+        short: None,
+        value: FlagValue::None,
+        help: "Also put the output on the clipboard",
+    },
+    Flag {
+        name: "refresh",
+        short: None,
+        value: FlagValue::None,
+        help: "Fetch user names and channels again instead of using the cache",
+    },
+    Flag {
+        name: "trace",
+        short: None,
+        value: FlagValue::None,
+        help: "Log each API request to stderr",
+    },
+    Flag {
+        name: "verbose",
+        short: Some('v'),
+        value: FlagValue::None,
+        help: "Print progress to stderr (-vv for debugging details)",
+    },
+    Flag {
+        name: "help",
+        short: Some('h'),
+        value: FlagValue::None,
+        help: "Show help",
+    },
+    Flag {
+        name: "version",
+        short: Some('V'),
+        value: FlagValue::None,
+        help: "Print the version",
+    },
+];
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "login",
+        forms: &[""],
+        aliases: &[],
+        about: "Authenticate via OAuth and save the token",
+        flags: &[
+            Flag {
+                name: "bot",
+                short: None,
+                value: FlagValue::None,
+                help: "Also install a bot token",
+            },
+            Flag {
+                name: "no-browser",
+                short: None,
+                value: FlagValue::None,
+                help: "Print the authorize URL instead of opening a browser",
+            },
+            Flag {
+                name: "token",
+                short: None,
+                value: FlagValue::Optional("token"),
+                help: "Save an existing token (read from stdin if omitted)",
+            },
+        ],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "logout",
+        forms: &[""],
+        aliases: &[],
+        about: "Revoke stored tokens and delete the credentials",
+        flags: &[],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "alias",
+        forms: &[""],
+        aliases: &[],
+        about: "List user-defined aliases",
+        flags: &[],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "config",
+        forms: &["get <key>", "set <key> <value>", "list"],
+        aliases: &[],
+        about: "Read or change settings in the config file",
+        flags: &[],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "completions",
+        forms: &["<bash|zsh|fish>"],
+        aliases: &[],
+        about: "Print a shell completion script",
+        flags: &[],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "list",
+        forms: &[""],
+        aliases: &["ls"],
+        about: "List conversations",
+        flags: &[
+            Flag {
+                name: "offline",
+                short: None,
+                value: FlagValue::None,
+                help: "Answer from the archive kept by slk sync instead of the API",
+            },
+            Flag {
+                name: "raw",
+                short: None,
+                value: FlagValue::None,
+                help: "Print the API response as Slack sent it, indented",
+            },
+        ],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "history",
+        forms: &["[<channel>]"],
+        aliases: &["hist"],
+        about: "Show recent messages in a channel",
+        flags: &[
+            Flag {
+                name: "from",
+                short: None,
+                value: FlagValue::Required("user"),
+                help: "Only show messages by this user (@handle, name, email or ID)",
+            },
+            Flag {
+                name: "no-system",
+                short: None,
+                value: FlagValue::None,
+                help: "Hide joins, leaves, topic changes and other system messages",
+            },
+            Flag {
+                name: "compact",
+                short: None,
+                value: FlagValue::None,
+                help: "Group back-to-back messages by the same person under one time and name",
+            },
+            Flag {
+                name: "save-files",
+                short: None,
+                value: FlagValue::Required("dir"),
+                help: "Download the files attached to the shown messages into this directory",
+            },
+            Flag {
+                name: "max-file-size",
+                short: None,
+                value: FlagValue::Required("MB"),
+                help: "Skip files bigger than this with --save-files (default: 100)",
+            },
+            Flag {
+                name: "offline",
+                short: None,
+                value: FlagValue::None,
+                help: "Answer from the archive kept by slk sync instead of the API",
+            },
+            Flag {
+                name: "threads",
+                short: None,
+                value: FlagValue::None,
+                help: "Show the replies to each thread under it",
+            },
+            Flag {
+                name: "links",
+                short: None,
+                value: FlagValue::None,
+                help: "Follow each message with its link into Slack",
+            },
+            Flag {
+                name: "count",
+                short: None,
+                value: FlagValue::None,
+                help: "Print how many messages, people, threads and files there are instead",
+            },
+            Flag {
+                name: "no-header",
+                short: None,
+                value: FlagValue::None,
+                help: "Leave out the line naming the channel, its member count and topic",
+            },
+            Flag {
+                name: "raw",
+                short: None,
+                value: FlagValue::None,
+                help: "Print the API response as Slack sent it, indented",
+            },
+        ],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "thread",
+        forms: &["<channel> <thread-ts>", "<url>"],
+        aliases: &["th"],
+        about: "Display the messages in a thread",
+        flags: &[
+            Flag {
+                name: "from",
+                short: None,
+                value: FlagValue::Required("user"),
+                help: "Only show messages by this user (@handle, name, email or ID)",
+            },
+            Flag {
+                name: "no-system",
+                short: None,
+                value: FlagValue::None,
+                help: "Hide joins, leaves, topic changes and other system messages",
+            },
+            Flag {
+                name: "compact",
+                short: None,
+                value: FlagValue::None,
+                help: "Group back-to-back messages by the same person under one time and name",
+            },
+            Flag {
+                name: "follow",
+                short: Some('f'),
+                value: FlagValue::None,
+                help: "Keep printing new replies as they arrive, until Ctrl-C",
+            },
+            Flag {
+                name: "interval",
+                short: None,
+                value: FlagValue::Required("secs"),
+                help: "Seconds between checks for new replies with --follow (default: 5)",
+            },
+            Flag {
+                name: "report",
+                short: None,
+                value: FlagValue::Required("file.md"),
+                help: "Write the thread up as a Markdown report (participants, timeline, files; - for stdout)",
+            },
+            Flag {
+                name: "save-files",
+                short: None,
+                value: FlagValue::Required("dir"),
+                help: "Download the files attached to the shown messages into this directory",
+            },
+            Flag {
+                name: "max-file-size",
+                short: None,
+                value: FlagValue::Required("MB"),
+                help: "Skip files bigger than this with --save-files (default: 100)",
+            },
+            Flag {
+                name: "offline",
+                short: None,
+                value: FlagValue::None,
+                help: "Answer from the archive kept by slk sync instead of the API",
+            },
+            Flag {
+                name: "links",
+                short: None,
+                value: FlagValue::None,
+                help: "Follow each message with its link into Slack",
+            },
+            Flag {
+                name: "raw",
+                short: None,
+                value: FlagValue::None,
+                help: "Print the API response as Slack sent it, indented",
+            },
+        ],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "watch",
+        forms: &["[<channel>]"],
+        aliases: &[],
+        about: "Print new messages in a channel as they arrive, until Ctrl-C",
+        flags: &[
+            Flag {
+                name: "channels",
+                short: None,
+                value: FlagValue::Required("a,b,c"),
+                help: "Watch several channels in one timeline, each line prefixed with its channel",
+            },
+            Flag {
+                name: "all-member-channels",
+                short: None,
+                value: FlagValue::None,
+                help: "Watch every channel you are a member of",
+            },
+            Flag {
+                name: "grep",
+                short: None,
+                value: FlagValue::Required("regex"),
+                help: "Only show messages whose text matches this regular expression",
+            },
+            Flag {
+                name: "mention-only",
+                short: None,
+                value: FlagValue::None,
+                help: "Only show messages that mention you (or @here/@channel)",
+            },
+            Flag {
+                name: "mark-read",
+                short: None,
+                value: FlagValue::None,
+                help: "Keep the channels marked as read in Slack while watching",
+            },
+            Flag {
+                name: "exec",
+                short: None,
+                value: FlagValue::Required("command"),
+                help: "Run a shell command for each new message (details in SLK_* variables and JSON on stdin)",
+            },
+            Flag {
+                name: "interval",
+                short: None,
+                value: FlagValue::Required("secs"),
+                help: "Seconds between checks for new messages (default: 5)",
+            },
+            Flag {
+                name: "socket",
+                short: None,
+                value: FlagValue::None,
+                help: "Receive messages over Socket Mode instead of polling",
+            },
+            Flag {
+                name: "events",
+                short: None,
+                value: FlagValue::None,
+                help: "With --socket, also show reactions, joins, typing and presence (dimmed)",
+            },
+            Flag {
+                name: "notify",
+                short: None,
+                value: FlagValue::None,
+                help: "Show a desktop notification for messages that mention you",
+            },
+            Flag {
+                name: "keyword",
+                short: None,
+                value: FlagValue::Required("word"),
+                help: "Also notify about messages containing this word (repeatable; implies --notify)",
+            },
+            Flag {
+                name: "from",
+                short: None,
+                value: FlagValue::Required("user"),
+                help: "Only show messages by this user (@handle, name, email or ID)",
+            },
+            Flag {
+                name: "no-system",
+                short: None,
+                value: FlagValue::None,
+                help: "Hide joins, leaves, topic changes and other system messages",
+            },
+        ],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "recent",
+        forms: &[""],
+        aliases: &[],
+        about: "Show the latest messages in every channel you are in, most recent first",
+        flags: &[
+            Flag {
+                name: "since",
+                short: None,
+                value: FlagValue::Required("date|age"),
+                help: "Only channels with messages since this day (YYYY-MM-DD, UTC) or this long ago (12h, 1d)",
+            },
+            Flag {
+                name: "no-system",
+                short: None,
+                value: FlagValue::None,
+                help: "Hide joins, leaves, topic changes and other system messages",
+            },
+        ],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "activity",
+        forms: &[""],
+        aliases: &[],
+        about: "Show the reactions and replies others left on your messages",
+        flags: &[Flag {
+            name: "since",
+            short: None,
+            value: FlagValue::Required("date|age"),
+            help: "Look at your messages since this day (YYYY-MM-DD, UTC) or this long ago (default: 7d)",
+        }],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "sync",
+        forms: &["[<channel>]"],
+        aliases: &[],
+        about: "Copy a channel's messages into the local archive",
+        flags: &[
+            Flag {
+                name: "edit-window",
+                short: None,
+                value: FlagValue::Required("hours"),
+                help: "Fetch this much before the last synced message again, for edits (default: 24)",
+            },
+            Flag {
+                name: "full",
+                short: None,
+                value: FlagValue::None,
+                help: "Fetch the whole history again instead of only what is new",
+            },
+        ],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "export",
+        forms: &["[<channel>]"],
+        aliases: &[],
+        about: "Write a channel's whole history, threads included, as JSON",
+        flags: &[
+            Flag {
+                name: "out",
+                short: Some('o'),
+                value: FlagValue::Required("path"),
+                help: "Write to this file instead of stdout (a directory with --all)",
+            },
+            Flag {
+                name: "all",
+                short: None,
+                value: FlagValue::None,
+                help: "Export every channel you are a member of, resuming an interrupted run",
+            },
+        ],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "stats",
+        forms: &["[<channel>]"],
+        aliases: &[],
+        about: "Show how busy a channel was: messages per day, per person and by hour",
+        flags: &[
+            Flag {
+                name: "since",
+                short: None,
+                value: FlagValue::Required("date|age"),
+                help: "Start at this day (YYYY-MM-DD, UTC) or this long ago (7d, 12h, 2w; default: 7d)",
+            },
+            Flag {
+                name: "until",
+                short: None,
+                value: FlagValue::Required("date|age"),
+                help: "End after this day (YYYY-MM-DD, UTC) or this long ago (default: now)",
+            },
+            Flag {
+                name: "leaderboard",
+                short: None,
+                value: FlagValue::None,
+                help: "Show who posted, reacted and got reactions the most, and the top reactions",
+            },
+            Flag {
+                name: "response-time",
+                short: None,
+                value: FlagValue::None,
+                help: "Show how long messages waited for a reply, and which never got one",
+            },
+            Flag {
+                name: "unanswered",
+                short: None,
+                value: FlagValue::None,
+                help: "List the messages that got no replies",
+            },
+            Flag {
+                name: "series",
+                short: None,
+                value: FlagValue::Required("day|hour"),
+                help: "Show the messages in each day or hour, for plotting (with --format csv)",
+            },
+            Flag {
+                name: "keywords",
+                short: None,
+                value: FlagValue::Required("word,word"),
+                help: "Count the messages mentioning each of these words, per day",
+            },
+            Flag {
+                name: "offline",
+                short: None,
+                value: FlagValue::None,
+                help: "Answer from the archive kept by slk sync instead of the API",
+            },
+        ],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "render",
+        forms: &["<file.json>"],
+        aliases: &[],
+        about: "Show a file from slk export, or a saved API response, without the API",
+        flags: &[Flag {
+            name: "compact",
+            short: None,
+            value: FlagValue::None,
+            help: "Group back-to-back messages by the same person under one time and name",
+        }],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "search",
+        forms: &["[<query>]"],
+        aliases: &[],
+        about: "Search the messages archived by slk sync",
+        flags: &[
+            Flag {
+                name: "local",
+                short: None,
+                value: FlagValue::None,
+                help: "Search the local archive (needed for now)",
+            },
+            Flag {
+                name: "channel",
+                short: None,
+                value: FlagValue::Required("channel"),
+                help: "Only search this channel (repeatable)",
+            },
+            Flag {
+                name: "from",
+                short: None,
+                value: FlagValue::Required("user"),
+                help: "Only show messages by this user (@handle, name or ID)",
+            },
+            Flag {
+                name: "since",
+                short: None,
+                value: FlagValue::Required("YYYY-MM-DD"),
+                help: "Only show messages from this day on (UTC)",
+            },
+            Flag {
+                name: "until",
+                short: None,
+                value: FlagValue::Required("YYYY-MM-DD"),
+                help: "Only show messages up to and including this day (UTC)",
+            },
+        ],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "open",
+        forms: &["<url>"],
+        aliases: &[],
+        about: "Show what a Slack link points at: a channel's history or a thread",
+        flags: &[
+            Flag {
+                name: "app",
+                short: None,
+                value: FlagValue::None,
+                help: "Open the link in the Slack app (or the browser); also takes a channel",
+            },
+            Flag {
+                name: "from",
+                short: None,
+                value: FlagValue::Required("user"),
+                help: "Only show messages by this user (@handle, name, email or ID)",
+            },
+            Flag {
+                name: "no-system",
+                short: None,
+                value: FlagValue::None,
+                help: "Hide joins, leaves, topic changes and other system messages",
+            },
+            Flag {
+                name: "compact",
+                short: None,
+                value: FlagValue::None,
+                help: "Group back-to-back messages by the same person under one time and name",
+            },
+            Flag {
+                name: "links",
+                short: None,
+                value: FlagValue::None,
+                help: "Follow each message with its link into Slack",
+            },
+            Flag {
+                name: "raw",
+                short: None,
+                value: FlagValue::None,
+                help: "Print the API response as Slack sent it, indented",
+            },
+        ],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "msg",
+        forms: &["<url>", "<channel> <ts>"],
+        aliases: &[],
+        about: "Show a single message, optionally with the messages around it",
+        flags: &[Flag {
+            name: "context",
+            short: Some('C'),
+            value: FlagValue::Required("n"),
+            help: "Also show up to n messages before and after it",
+        }],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "user",
+        forms: &["<user>"],
+        aliases: &[],
+        about: "Show a user's profile: names, title, email, time zone and local time",
+        flags: &[],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "permalink",
+        forms: &["<channel> <ts>"],
+        aliases: &[],
+        about: "Print a shareable link to a message",
+        flags: &[],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "send",
+        forms: &["<channel> <text|->"],
+        aliases: &[],
+        about: "Post a message to a channel (- reads it from stdin)",
+        flags: &[
+            Flag {
+                name: "blocks",
+                short: None,
+                value: FlagValue::Required("file.json"),
+                help: "Post the Block Kit blocks in this file; the text, if given, shows in notifications",
+            },
+            Flag {
+                name: "no-mentions",
+                short: None,
+                value: FlagValue::None,
+                help: "Send @handles and #channels as plain text instead of as mentions",
+            },
+        ],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "reply",
+        forms: &["<channel> <thread-ts> <text|->", "<url> <text|->"],
+        aliases: &[],
+        about: "Reply in a thread (- reads the message from stdin)",
+        flags: &[
+            Flag {
+                name: "broadcast",
+                short: None,
+                value: FlagValue::None,
+                help: "Also send the reply to the channel",
+            },
+            Flag {
+                name: "no-mentions",
+                short: None,
+                value: FlagValue::None,
+                help: "Send @handles and #channels as plain text instead of as mentions",
+            },
+        ],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "webhook",
+        forms: &["<url> <text|->"],
+        aliases: &[],
+        about: "Post to an incoming webhook, no token needed (- reads the message from stdin)",
+        flags: &[],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "api",
+        forms: &["<method> [<key=value>...]"],
+        aliases: &[],
+        about: "Call any Web API method and print its JSON response",
+        flags: &[Flag {
+            name: "pretty",
+            short: None,
+            value: FlagValue::None,
+            help: "Indent the JSON",
+        }],
+        hidden: false,
+    },
+    CommandSpec {
+        name: "__complete",
+        forms: &["channels [<prefix>]"],
+        aliases: &[],
+        about: "Print completion candidates (used by the completion scripts)",
+        flags: &[],
+        hidden: true,
+    },
+];
+
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|c| c.name == name || c.aliases.contains(&name))
+}
+
+/// The commands shown in help and offered by the completion scripts.
+pub fn visible_commands() -> impl Iterator<Item = &'static CommandSpec> {
+    COMMANDS.iter().filter(|c| !c.hidden)
+}
+
+/// The result of parsing a command line against the command table.
+#[derive(Debug, Default)]
+pub struct Matches {
+    pub command: Option<&'static CommandSpec>,
+    pub positionals: Vec<String>,
+    /// Flags in the order given, by long name.
+    flags: Vec<(&'static str, Option<String>)>,
+}
+
+impl Matches {
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|(n, _)| *n == name)
+    }
+
+    /// How many times `name` was given (`-vv` counts twice).
+    pub fn count(&self, name: &str) -> usize {
+        self.flags.iter().filter(|(n, _)| *n == name).count()
+    }
+
+    /// The value of the last occurrence of `name`.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|(n, _)| *n == name)
+            .and_then(|(_, v)| v.as_deref())
+    }
+
+    /// The values of every occurrence of `name`, in order.
+    pub fn values(&self, name: &str) -> Vec<&str> {
+        self.flags
+            .iter()
+            .filter(|(n, _)| *n == name)
+            .filter_map(|(_, v)| v.as_deref())
+            .collect()
+    }
+}
+
+fn lookup<'a>(flags: &'a [Flag], long: Option<&str>, short: Option<char>) -> Option<&'a Flag> {
+    flags.iter().find(|f| match (long, short) {
+        (Some(long), _) => f.name == long,
+        (None, Some(short)) => f.short == Some(short),
+        (None, None) => false,
+    })
+}
+
+/// The position of the command name in `args`: the first word that is not a
+/// flag or a global flag's value.
+pub fn command_index(args: &[String]) -> Option<usize> {
+    let mut i = 1;
+    while i < args.len() {
+        let arg = &args[i];
+        if arg == "--" {
+            return None;
+        }
+        let Some(long) = arg.strip_prefix("--") else {
+            if arg.starts_with('-') && arg.len() > 1 {
+                i += 1;
+                continue;
+            }
+            return Some(i);
+        };
+        let takes_value = !long.contains('=')
+            && lookup(GLOBAL_FLAGS, Some(long), None)
+                .is_some_and(|f| matches!(f.value, FlagValue::Required(_)));
+        i += if takes_value { 2 } else { 1 };
+    }
+    None
+}
+
+/// Splits `args` (including the program name) into the command, its
+/// positional arguments and its flags. `--name=value` and `--name value` are
+/// both accepted; everything after `--` is positional.
+pub fn parse(args: &[String]) -> Result<Matches, SlkError> {
+    let mut matches = Matches::default();
+    let mut rest = args.iter().skip(1).peekable();
+    let mut only_positionals = false;
+    while let Some(arg) = rest.next() {
+        if only_positionals || arg == "-" || !arg.starts_with('-') {
+            if matches.command.is_none() && !only_positionals {
+                matches.command = Some(find_command(arg).ok_or_else(|| {
+                    SlkError::from(format!("unknown command '{}'\n{}", arg, usage(None)))
+                })?);
+            } else {
+                matches.positionals.push(arg.clone());
+            }
+            continue;
+        }
+        if arg == "--" {
+            only_positionals = true;
+            continue;
+        }
+        let (long, short, inline) = match arg.strip_prefix("--") {
+            Some(body) => match body.split_once('=') {
+                Some((name, value)) => (Some(name), None, Some(value.to_string())),
+                None => (Some(body), None, None),
+            },
+            None => {
+                let mut chars = arg[1..].chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => (None, Some(c), None),
+                    // A cluster of switches, like `-vv`.
+                    _ => {
+                        for c in arg[1..].chars() {
+                            let flag = lookup(GLOBAL_FLAGS, None, Some(c))
+                                .filter(|f| matches!(f.value, FlagValue::None))
+                                .ok_or_else(|| {
+                                    SlkError::from(format!("unknown option '{}'\n{}", arg, usage(matches.command)))
+                                })?;
+                            matches.flags.push((flag.name, None));
+                        }
+                        continue;
+                    }
+                }
+            }
+        };
+        let command_flags = matches.command.map_or(&[][..], |c| c.flags);
+        let flag = lookup(GLOBAL_FLAGS, long, short)
+            .or_else(|| lookup(command_flags, long, short))
+            .ok_or_else(|| {
+                SlkError::from(format!("unknown option '{}'\n{}", arg, usage(matches.command)))
+            })?;
+        let value = match flag.value {
+            FlagValue::None if inline.is_some() => {
+                return Err(SlkError::from(format!("--{} does not take a value", flag.name)));
+            }
+            FlagValue::None => None,
+            FlagValue::Required(_) => match inline.or_else(|| rest.next().cloned()) {
+                Some(value) => Some(value),
+                None => return Err(SlkError::from(format!("--{} requires a value", flag.name))),
+            },
+            FlagValue::Optional(_) => {
+                inline.or_else(|| rest.next_if(|next| !next.starts_with('-')).cloned())
+            }
+        };
+        matches.flags.push((flag.name, value));
+    }
+    Ok(matches)
+}
+
+pub fn version() -> String {
+    format!("slk {}", env!("CARGO_PKG_VERSION"))
+}
+
+fn flag_synopsis(flag: &Flag) -> String {
+    match flag.value {
+        FlagValue::None => format!("[--{}]", flag.name),
+        FlagValue::Required(v) => format!("[--{} <{}>]", flag.name, v),
+        FlagValue::Optional(v) => format!("[--{} [<{}>]]", flag.name, v),
+    }
+}
+
+fn command_lines(command: &CommandSpec) -> Vec<String> {
+    command
+        .forms
+        .iter()
+        .map(|form| {
+            let mut words = vec![format!("slk {}", command.name)];
+            words.extend(command.flags.iter().map(flag_synopsis));
+            if !form.is_empty() {
+                words.push(form.to_string());
+            }
+            words.join(" ")
+        })
+        .collect()
+}
+
+/// `usage: ...` lines for one command, or for all of them.
+pub fn usage(command: Option<&CommandSpec>) -> String {
+    let lines: Vec<String> = match command {
+        Some(command) => command_lines(command),
+        None => visible_commands().flat_map(command_lines).collect(),
+    };
+    format!("usage: {}", lines.join("\n       "))
+}
+
+fn flag_table(flags: &[Flag]) -> String {
+    let labels: Vec<String> = flags
+        .iter()
+        .map(|f| {
+            let short = f.short.map(|c| format!("-{}, ", c)).unwrap_or_default();
+            match f.value {
+                FlagValue::None => format!("{}--{}", short, f.name),
+                FlagValue::Required(v) => format!("{}--{} <{}>", short, f.name, v),
+                FlagValue::Optional(v) => format!("{}--{} [<{}>]", short, f.name, v),
+            }
+        })
+        .collect();
+    let width = labels.iter().map(String::len).max().unwrap_or(0);
+    labels
+        .iter()
+        .zip(flags)
+        .map(|(label, f)| format!("  {:width$}  {}", label, f.help, width = width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `--help` output for one command, or the overview when `command` is `None`.
+pub fn help(command: Option<&CommandSpec>) -> String {
+    match command {
+        Some(command) => {
+            let mut text = format!("{}\n\n{}\n", command.about, usage(Some(command)));
+            if !command.aliases.is_empty() {
+                text.push_str(&format!("\nAliases: {}\n", command.aliases.join(", ")));
+            }
+            if !command.flags.is_empty() {
+                text.push_str(&format!("\nOptions:\n{}\n", flag_table(command.flags)));
+            }
+            text.push_str(&format!("\nGlobal options:\n{}", flag_table(GLOBAL_FLAGS)));
+            text
+        }
+        None => {
+            let names: Vec<String> = visible_commands()
+                .map(|c| match c.aliases {
+                    [] => c.name.to_string(),
+                    aliases => format!("{} ({})", c.name, aliases.join(", ")),
+                })
+                .collect();
+            let width = names.iter().map(String::len).max().unwrap_or(0);
+            let commands: Vec<String> = names
+                .iter()
+                .zip(visible_commands())
+                .map(|(name, c)| format!("  {:width$}  {}", name, c.about, width = width))
+                .collect();
+            format!(
+                "{} - {}\n\nusage: slk [options] <command> [<args>]\n\nCommands:\n{}\n\nOptions:\n{}\n\nRun 'slk <command> --help' for more about a command.",
+                version(),
+                env!("CARGO_PKG_DESCRIPTION"),
+                commands.join("\n"),
+                flag_table(GLOBAL_FLAGS)
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_flags_anywhere() {
+        let m = parse(&strings(&["slk", "--as", "bot", "history", "C1", "--limit=5", "--trace"]))
+            .unwrap();
+        assert_eq!(m.command.map(|c| c.name), Some("history"));
+        assert_eq!(m.positionals, strings(&["C1"]));
+        assert_eq!(m.value("as"), Some("bot"));
+        assert_eq!(m.value("limit"), Some("5"));
+        assert!(m.flag("trace"));
+        assert!(!m.flag("help"));
+    }
+
+    #[test]
+    fn test_parse_optional_value() {
+        let m = parse(&strings(&["slk", "login", "--token", "--bot"])).unwrap();
+        assert!(m.flag("token"));
+        assert_eq!(m.value("token"), None);
+        assert!(m.flag("bot"));
+        let m = parse(&strings(&["slk", "login", "--token", "xoxp-1"])).unwrap();
+        assert_eq!(m.value("token"), Some("xoxp-1"));
+    }
+
+    #[test]
+    fn test_repeated_values() {
+        let m = parse(&strings(&["slk", "watch", "--keyword", "deploy", "C1", "--keyword=outage"])).unwrap();
+        assert_eq!(m.values("keyword"), ["deploy", "outage"]);
+        assert_eq!(m.value("keyword"), Some("outage"));
+        assert!(m.values("from").is_empty());
+    }
+
+    #[test]
+    fn test_parse_short_cluster() {
+        let m = parse(&strings(&["slk", "-vv", "list", "-v"])).unwrap();
+        assert_eq!(m.count("verbose"), 3);
+        assert!(parse(&strings(&["slk", "-vx", "list"])).unwrap_err().message.starts_with("unknown option '-vx'"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse(&strings(&["slk", "list", "--bogus"])).unwrap_err();
+        assert!(err.message.starts_with("unknown option '--bogus'\nusage: slk list"));
+        // Command flags are only known after the command.
+        assert!(parse(&strings(&["slk", "--bot", "login"])).is_err());
+        assert!(parse(&strings(&["slk", "list", "--as"])).unwrap_err().message.contains("requires a value"));
+        assert!(parse(&strings(&["slk", "list", "--trace=1"])).is_err());
+        assert!(parse(&strings(&["slk", "frobnicate"])).unwrap_err().message.contains("unknown command"));
+    }
+
+    #[test]
+    fn test_parse_double_dash() {
+        let m = parse(&strings(&["slk", "config", "set", "--", "time_format", "--%H"])).unwrap();
+        assert_eq!(m.positionals, strings(&["set", "time_format", "--%H"]));
+        let m = parse(&strings(&["slk", "-h"])).unwrap();
+        assert!(m.command.is_none());
+        assert!(m.flag("help"));
+    }
+
+    #[test]
+    fn test_command_index() {
+        assert_eq!(command_index(&strings(&["slk", "list"])), Some(1));
+        assert_eq!(command_index(&strings(&["slk", "--as", "bot", "--trace", "su"])), Some(4));
+        assert_eq!(command_index(&strings(&["slk", "--format=json", "su"])), Some(2));
+        assert_eq!(command_index(&strings(&["slk", "--trace"])), None);
+    }
+
+    #[test]
+    fn test_command_aliases() {
+        assert_eq!(find_command("ls").map(|c| c.name), Some("list"));
+        assert_eq!(find_command("hist").map(|c| c.name), Some("history"));
+        let m = parse(&strings(&["slk", "th", "C1", "1770689887.565249"])).unwrap();
+        assert_eq!(m.command.map(|c| c.name), Some("thread"));
+        // Every name and alias picks out exactly one command.
+        let mut names: Vec<&str> = COMMANDS.iter().flat_map(|c| c.aliases.iter().copied().chain([c.name])).collect();
+        let count = names.len();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), count);
+        assert!(help(find_command("ls")).contains("\nAliases: ls\n"));
+        assert!(help(None).contains("  list (ls)  "));
+    }
+
+    #[test]
+    fn test_usage_and_help() {
+        assert_eq!(
+            usage(find_command("thread")),
+            "usage: slk thread [--from <user>] [--no-system] [--compact] [--follow] [--interval <secs>] [--report <file.md>] [--save-files <dir>] [--max-file-size <MB>] [--offline] [--links] [--raw] <channel> <thread-ts>\n       slk thread [--from <user>] [--no-system] [--compact] [--follow] [--interval <secs>] [--report <file.md>] [--save-files <dir>] [--max-file-size <MB>] [--offline] [--links] [--raw] <url>"
+        );
+        assert_eq!(
+            usage(find_command("login")),
+            "usage: slk login [--bot] [--no-browser] [--token [<token>]]"
+        );
+        let help = help(find_command("login"));
+        assert!(help.contains("  --token [<token>]  Save an existing token"));
+        assert!(help.contains("-h, --help"));
+    }
+}
@@ -0,0 +1,81 @@
+//! Slack access for other Rust programs: read a channel or thread and post
+//! messages with a token, the way the `slk` commands do.
+//!
+//! Channels are given by ID. The API is reached at `SLK_API_BASE_URL`, then
+//! `api_base_url` in the config file, then `https://slack.com/api`.
+//!
+//! For other API methods, or to keep one connection for several calls,
+//! build a [`SlackClient`] over an [`HttpClient`] (`HttpClient::configured()`
+//! applies the TLS pins from `SLK_TLS_PINS` or the config file, as these
+//! functions do). Its `history_pages` and
+//! `replies_pages` page through a whole channel or thread lazily, as
+//! [`Pages`] or, with `messages()`, one message at a time. Its
+//! `concurrently` overlaps calls to the `_async` methods on the calling
+//! thread.
+
+use crate::config;
+use crate::error::SlkError;
+use crate::message::{SlackConversation, SlackMessage};
+use crate::response::{
+    self, ChatPostMessageResponse, ConversationsHistoryResponse, ConversationsListResponse, ConversationsRepliesResponse,
+};
+
+pub use crate::http::Client as HttpClient;
+pub use crate::slack_api::{Messages, Pages, RetryPolicy, SlackClient};
+
+/// The newest `limit` messages in a channel, newest first.
+pub fn history(token: &str, channel_id: &str, limit: usize) -> Result<Vec<SlackMessage>, SlkError> {
+    let base_url = config::load_api_base_url()?;
+    let http = HttpClient::configured()?;
+    let raw = SlackClient::new(&http, &base_url, token).conversations_history(channel_id, limit)?;
+    Ok(response::parse::<ConversationsHistoryResponse>(&raw)?.messages)
+}
+
+/// A thread's parent message followed by its replies, oldest first.
+pub fn thread(token: &str, channel_id: &str, ts: &str) -> Result<Vec<SlackMessage>, SlkError> {
+    let base_url = config::load_api_base_url()?;
+    let http = HttpClient::configured()?;
+    let raw = SlackClient::new(&http, &base_url, token).conversations_replies(channel_id, ts)?;
+    Ok(response::parse::<ConversationsRepliesResponse>(&raw)?.messages)
+}
+
+/// The conversations the token can see.
+pub fn conversations(token: &str) -> Result<Vec<SlackConversation>, SlkError> {
+    let base_url = config::load_api_base_url()?;
+    let http = HttpClient::configured()?;
+    let api = SlackClient::new(&http, &base_url, token);
+    let mut channels = Vec::new();
+    for page in api.conversations_pages() {
+        channels.extend(response::read::<ConversationsListResponse>(&page?)?.channels);
+    }
+    Ok(channels)
+}
+
+/// Posts `text` to a channel, or as a reply when `thread_ts` is given, and
+/// returns the new message's `ts`.
+pub fn post(token: &str, channel_id: &str, text: &str, thread_ts: Option<&str>) -> Result<String, SlkError> {
+    let base_url = config::load_api_base_url()?;
+    let http = HttpClient::configured()?;
+    let raw = SlackClient::new(&http, &base_url, token).chat_post_message(channel_id, text, thread_ts)?;
+    Ok(response::parse::<ChatPostMessageResponse>(&raw)?.ts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configured_tls_pins_apply_to_library_calls() {
+        // A bad pin next to a good one is an error, not dropped.
+        let pins = "sha256/AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=,sha256/not-a-pin";
+        unsafe { std::env::set_var("SLK_TLS_PINS", pins) };
+        let err = history("xoxp-test", "C1", 1).unwrap_err();
+        assert!(err.to_string().contains("invalid TLS pin 'sha256/not-a-pin'"), "{}", err);
+        assert!(err.to_string().contains("in SLK_TLS_PINS"), "{}", err);
+        assert!(post("xoxp-test", "C1", "hi", None).is_err());
+
+        unsafe { std::env::set_var("SLK_TLS_PINS", "sha256/AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=") };
+        assert!(HttpClient::configured().is_ok());
+        unsafe { std::env::remove_var("SLK_TLS_PINS") };
+    }
+}
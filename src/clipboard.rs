@@ -0,0 +1,67 @@
+//! Copying text to the system clipboard, through `pbcopy` on macOS,
+//! `clip.exe` on Windows (and WSL), and `wl-copy`, `xclip` or `xsel` on
+//! Linux, whichever is installed.
+
+use crate::error::SlkError;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// The clipboard programs to try, in order, with their arguments.
+fn candidates(wayland: bool) -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        return vec![("pbcopy", &[])];
+    }
+    if cfg!(windows) {
+        return vec![("clip.exe", &[])];
+    }
+    let mut candidates: Vec<(&str, &[&str])> = Vec::new();
+    if wayland {
+        candidates.push(("wl-copy", &[]));
+    }
+    candidates.push(("xclip", &["-selection", "clipboard"]));
+    candidates.push(("xsel", &["--clipboard", "--input"]));
+    candidates.push(("clip.exe", &[]));
+    candidates
+}
+
+/// Puts `text` on the clipboard with the first clipboard program found.
+pub fn copy(text: &str) -> Result<(), SlkError> {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    for (program, args) in candidates(wayland) {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(SlkError::from(format!("could not run {}: {}", program, e))),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(text.as_bytes())
+                .map_err(|e| SlkError::from(format!("could not write to {}: {}", program, e)))?;
+        }
+        return match child.wait() {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(SlkError::from(format!("{} exited with {}", program, status))),
+            Err(e) => Err(SlkError::from(format!("{} failed: {}", program, e))),
+        };
+    }
+    Err(SlkError::from("no clipboard program found; install wl-copy, xclip or xsel"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_candidates() {
+        let programs = |wayland| candidates(wayland).into_iter().map(|(p, _)| p).collect::<Vec<_>>();
+        assert_eq!(programs(true), ["wl-copy", "xclip", "xsel", "clip.exe"]);
+        assert_eq!(programs(false), ["xclip", "xsel", "clip.exe"]);
+    }
+}
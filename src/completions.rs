@@ -0,0 +1,371 @@
+use crate::cli::{CommandSpec, Flag, FlagValue, GLOBAL_FLAGS, visible_commands};
+use crate::error::SlkError;
+use crate::message::SlackConversation;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub fn parse(s: &str) -> Result<Self, SlkError> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(SlkError::from(format!(
+                "invalid shell '{}': expected 'bash', 'zsh' or 'fish'",
+                s
+            ))),
+        }
+    }
+}
+
+/// The completion script for `shell`, generated from the command table.
+pub fn script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish(),
+    }
+}
+
+/// The fixed set of values a flag accepts, when its placeholder lists them
+/// (`user|bot`).
+fn choices(flag: &Flag) -> Vec<&'static str> {
+    match flag.value {
+        FlagValue::Required(v) | FlagValue::Optional(v) if v.contains('|') => v.split('|').collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Words that can follow the command name: literal first words of its forms
+/// (`config get`) or the alternatives of a `<a|b>` placeholder.
+fn first_words(command: &CommandSpec) -> Vec<&'static str> {
+    let mut words = Vec::new();
+    for form in command.forms {
+        let Some(word) = form.split_whitespace().next() else {
+            continue;
+        };
+        if let Some(inner) = word.strip_prefix('<').and_then(|w| w.strip_suffix('>')) {
+            if inner.contains('|') {
+                words.extend(inner.split('|'));
+            }
+        } else if !word.starts_with('[') {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// Whether the command's first argument is a channel, completed from the
+/// cached channel list.
+fn takes_channel(command: &CommandSpec) -> bool {
+    command.forms.iter().any(|form| {
+        matches!(form.split_whitespace().next(), Some("<channel>" | "[<channel>]"))
+    })
+}
+
+/// Cached channels whose name (or ID) starts with `prefix`, for
+/// `slk __complete channels`. A leading `#` is kept on the candidates.
+pub fn channel_candidates(channels: &[SlackConversation], prefix: &str) -> Vec<String> {
+    let (hash, name_prefix) = match prefix.strip_prefix('#') {
+        Some(rest) => ("#", rest),
+        None => ("", prefix),
+    };
+    let mut candidates: Vec<String> = channels
+        .iter()
+        .filter(|c| !c.name.is_empty() && c.name.starts_with(name_prefix))
+        .map(|c| format!("{}{}", hash, c.name))
+        .collect();
+    if hash.is_empty() && !prefix.is_empty() {
+        candidates.extend(channels.iter().filter(|c| c.id.starts_with(prefix)).map(|c| c.id.clone()));
+    }
+    candidates.sort();
+    candidates.dedup();
+    candidates
+}
+
+/// The command's name followed by its aliases.
+fn command_names(command: &CommandSpec) -> Vec<&'static str> {
+    let mut names = vec![command.name];
+    names.extend(command.aliases);
+    names
+}
+
+fn long_flags(flags: &[Flag]) -> Vec<String> {
+    flags.iter().map(|f| format!("--{}", f.name)).collect()
+}
+
+fn bash() -> String {
+    let global = long_flags(GLOBAL_FLAGS).join(" ");
+    let value_flags: Vec<String> = GLOBAL_FLAGS
+        .iter()
+        .filter(|f| matches!(f.value, FlagValue::Required(_)))
+        .map(|f| format!("--{}", f.name))
+        .collect();
+
+    let mut value_cases = String::new();
+    for flag in visible_commands().flat_map(|c| c.flags).chain(GLOBAL_FLAGS) {
+        if matches!(flag.value, FlagValue::Required(_)) {
+            value_cases.push_str(&format!(
+                "        --{})\n            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n            return\n            ;;\n",
+                flag.name,
+                choices(flag).join(" ")
+            ));
+        }
+    }
+
+    let names: Vec<&str> = visible_commands().map(|c| c.name).collect();
+    let mut command_cases = format!(
+        "        \"\")\n            COMPREPLY=($(compgen -W \"{} $global\" -- \"$cur\"))\n            ;;\n",
+        names.join(" ")
+    );
+    for command in visible_commands() {
+        let mut words = long_flags(command.flags);
+        words.push("$global".to_string());
+        // Only offer subcommand words right after the command name.
+        let first = first_words(command);
+        let words = if first.is_empty() {
+            words.join(" ")
+        } else {
+            format!(
+                "$([ \"$i\" -eq $((COMP_CWORD - 1)) ] && echo \"{}\") {}",
+                first.join(" "),
+                words.join(" ")
+            )
+        };
+        let complete_words = format!("COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", words);
+        let body = if takes_channel(command) {
+            format!(
+                "if [ \"$i\" -eq $((COMP_CWORD - 1)) ] && [[ \"$cur\" != -* ]]; then\n                COMPREPLY=($(slk __complete channels \"$cur\" 2>/dev/null))\n            else\n                {}\n            fi",
+                complete_words
+            )
+        } else {
+            complete_words
+        };
+        command_cases.push_str(&format!(
+            "        {})\n            {}\n            ;;\n",
+            command_names(command).join("|"),
+            body
+        ));
+    }
+
+    format!(
+        r#"# bash completion for slk
+_slk() {{
+    local cur prev cmd="" i
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    local global="{global}"
+
+    case "$prev" in
+{value_cases}    esac
+
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${{COMP_WORDS[i]}}" in
+            {value_flags}) ((i++)) ;;
+            -*) ;;
+            *) cmd="${{COMP_WORDS[i]}}"; break ;;
+        esac
+    done
+
+    case "$cmd" in
+{command_cases}    esac
+}}
+complete -F _slk slk
+"#,
+        global = global,
+        value_cases = value_cases,
+        value_flags = value_flags.join("|"),
+        command_cases = command_cases,
+    )
+}
+
+/// Quotes `s` for a single-quoted shell string.
+fn single_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn zsh_flag(flag: &Flag) -> String {
+    let help = flag.help.replace('[', "\\[").replace(']', "\\]").replace(':', "\\:");
+    let value = match flag.value {
+        FlagValue::None => String::new(),
+        FlagValue::Required(v) if v.contains('|') => format!(":{}:({})", v, choices(flag).join(" ")),
+        FlagValue::Required(v) => format!(":{}:", v),
+        FlagValue::Optional(v) => format!("::{}:", v),
+    };
+    let long = format!("--{}", flag.name);
+    match flag.short {
+        Some(short) => format!(
+            "'(-{short} {long})'{{-{short},{long}}}{}",
+            single_quote(&format!("[{}]{}", help, value)),
+            short = short,
+            long = long
+        ),
+        None => single_quote(&format!("{}[{}]{}", long, help, value)),
+    }
+}
+
+fn zsh() -> String {
+    let commands: Vec<String> = visible_commands()
+        .map(|c| format!("        {}", single_quote(&format!("{}:{}", c.name, c.about))))
+        .collect();
+    let global: Vec<String> = GLOBAL_FLAGS.iter().map(|f| format!("        {}", zsh_flag(f))).collect();
+    let mut cases = String::new();
+    for command in visible_commands() {
+        let mut specs = vec!["$global".to_string()];
+        specs.extend(command.flags.iter().map(zsh_flag));
+        let first = first_words(command);
+        if !first.is_empty() {
+            specs.push(single_quote(&format!("1:argument:({})", first.join(" "))));
+        } else if takes_channel(command) {
+            specs.push(single_quote(
+                r#"1:channel:{compadd -- ${(f)"$(slk __complete channels "$PREFIX" 2>/dev/null)"}}"#,
+            ));
+        }
+        cases.push_str(&format!(
+            "                {})\n                    _arguments {}\n                    ;;\n",
+            command_names(command).join("|"),
+            specs.join(" ")
+        ));
+    }
+
+    format!(
+        r#"#compdef slk
+
+_slk() {{
+    local -a commands global
+    commands=(
+{commands}
+    )
+    global=(
+{global}
+    )
+    local curcontext="$curcontext" state line
+    _arguments -C $global '1:command:->command' '*::argument:->argument'
+    case $state in
+        command)
+            _describe -t commands 'slk command' commands
+            ;;
+        argument)
+            case $words[1] in
+{cases}            esac
+            ;;
+    esac
+}}
+
+_slk "$@"
+"#,
+        commands = commands.join("\n"),
+        global = global.join("\n"),
+        cases = cases,
+    )
+}
+
+fn fish_flag(condition: Option<&str>, flag: &Flag) -> String {
+    let mut line = String::from("complete -c slk");
+    if let Some(condition) = condition {
+        line.push_str(&format!(" -n {}", single_quote(condition)));
+    }
+    if let Some(short) = flag.short {
+        line.push_str(&format!(" -s {}", short));
+    }
+    line.push_str(&format!(" -l {}", flag.name));
+    if matches!(flag.value, FlagValue::Required(_)) {
+        line.push_str(" -x");
+    }
+    let choices = choices(flag);
+    if !choices.is_empty() {
+        line.push_str(&format!(" -a {}", single_quote(&choices.join(" "))));
+    }
+    line.push_str(&format!(" -d {}", single_quote(flag.help)));
+    line
+}
+
+fn fish() -> String {
+    let mut lines = vec!["# fish completion for slk".to_string(), "complete -c slk -f".to_string()];
+    for command in visible_commands() {
+        lines.push(format!(
+            "complete -c slk -n __fish_use_subcommand -a {} -d {}",
+            command.name,
+            single_quote(command.about)
+        ));
+    }
+    lines.extend(GLOBAL_FLAGS.iter().map(|f| fish_flag(None, f)));
+    for command in visible_commands() {
+        let condition = format!("__fish_seen_subcommand_from {}", command_names(command).join(" "));
+        if takes_channel(command) {
+            lines.push(format!(
+                "complete -c slk -n {} -a '(slk __complete channels (commandline -ct) 2>/dev/null)'",
+                single_quote(&condition)
+            ));
+        }
+        lines.extend(command.flags.iter().map(|f| fish_flag(Some(&condition), f)));
+        let first = first_words(command);
+        if !first.is_empty() {
+            let words = first.join(" ");
+            lines.push(format!(
+                "complete -c slk -n {} -a {}",
+                single_quote(&format!("{}; and not __fish_seen_subcommand_from {}", condition, words)),
+                single_quote(&words)
+            ));
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::find_command;
+
+    #[test]
+    fn test_first_words() {
+        assert_eq!(first_words(find_command("config").unwrap()), vec!["get", "set", "list"]);
+        assert_eq!(first_words(find_command("completions").unwrap()), vec!["bash", "zsh", "fish"]);
+        assert!(first_words(find_command("thread").unwrap()).is_empty());
+        assert!(first_words(find_command("history").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_scripts_cover_the_command_table() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = script(shell);
+            for command in visible_commands() {
+                assert!(script.contains(command.name), "{:?} misses {}", shell, command.name);
+                for flag in command.flags {
+                    assert!(script.contains(flag.name), "{:?} misses --{}", shell, flag.name);
+                }
+            }
+        }
+        assert!(script(Shell::Bash).contains(r#"compgen -W "user bot""#));
+        assert!(script(Shell::Zsh).contains("'--as[Act with the stored user or bot token]:user|bot:(user bot)'"));
+        assert!(script(Shell::Fish).contains("complete -c slk -l as -x -a 'user bot'"));
+        assert!(script(Shell::Bash).contains("        list|ls)\n"));
+        assert!(script(Shell::Fish).contains("__fish_seen_subcommand_from history hist"));
+    }
+
+    #[test]
+    fn test_channel_candidates() {
+        let channels = vec![
+            SlackConversation { id: "C1".to_string(), name: "general".to_string() },
+            SlackConversation { id: "C2".to_string(), name: "gen-ai".to_string() },
+            SlackConversation { id: "C3".to_string(), name: "random".to_string() },
+        ];
+        assert_eq!(channel_candidates(&channels, "#gen"), vec!["#gen-ai", "#general"]);
+        assert_eq!(channel_candidates(&channels, "ra"), vec!["random"]);
+        assert_eq!(channel_candidates(&channels, "C3"), vec!["C3"]);
+        assert_eq!(channel_candidates(&channels, "").len(), 3);
+        assert!(takes_channel(find_command("history").unwrap()));
+        assert!(!takes_channel(find_command("config").unwrap()));
+    }
+
+    #[test]
+    fn test_shell_parse() {
+        assert_eq!(Shell::parse("zsh").unwrap(), Shell::Zsh);
+        assert!(Shell::parse("powershell").is_err());
+    }
+}
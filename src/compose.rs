@@ -0,0 +1,205 @@
+use crate::error::SlkError;
+use crate::json::{self, JsonValue};
+use std::ops::Range;
+
+/// How many blocks Slack takes in one message.
+const MAX_BLOCKS: usize = 50;
+
+/// Whether piped text reads like the output of a command (`git log`, `ls -l`)
+/// rather than prose: several lines, some of them indented or laid out in
+/// columns, and no code block of its own.
+pub fn looks_like_command_output(text: &str) -> bool {
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+    lines.len() > 1
+        && !text.contains("```")
+        && lines
+            .iter()
+            .any(|l| l.starts_with([' ', '\t']) || l.contains('\t') || l.trim().contains("  "))
+}
+
+/// The message to send for text read from stdin: trailing newlines dropped,
+/// and command output wrapped in a code block so Slack keeps its layout.
+pub fn message_from_stdin(input: &str) -> Result<String, SlkError> {
+    let text = input.trim_end_matches(['\n', '\r']);
+    if text.trim().is_empty() {
+        return Err(SlkError::from("nothing to send: stdin was empty"));
+    }
+    if looks_like_command_output(text) {
+        return Ok(format!("```\n{}\n```", text));
+    }
+    Ok(text.to_string())
+}
+
+/// The Block Kit blocks in `input`, as the JSON array `chat.postMessage`
+/// takes: either the array itself, or an object with a `blocks` array as
+/// Block Kit Builder copies it. Each block must be an object with a `type`.
+pub fn blocks_from_json(input: &str) -> Result<String, SlkError> {
+    let value = json::parse(input).map_err(|e| SlkError::from(format!("invalid JSON: {}", e)))?;
+    let blocks = match &value {
+        JsonValue::Array(blocks) => blocks,
+        _ => value
+            .get("blocks")
+            .and_then(|v| v.as_array())
+            .ok_or(SlkError::from("expected an array of blocks, or an object with a \"blocks\" array"))?,
+    };
+    if blocks.is_empty() {
+        return Err(SlkError::from("there are no blocks to send"));
+    }
+    if blocks.len() > MAX_BLOCKS {
+        let too_many = format!("{} blocks is more than the {} Slack allows", blocks.len(), MAX_BLOCKS);
+        return Err(SlkError::from(too_many));
+    }
+    if let Some(i) = blocks.iter().position(|b| b.get("type").and_then(|v| v.as_str()).is_none()) {
+        return Err(SlkError::from(format!("block {} has no \"type\"", i + 1)));
+    }
+    Ok(JsonValue::Array(blocks.clone()).to_string())
+}
+
+/// Whether a mention in outgoing text is of a person or a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MentionKind {
+    User,
+    Channel,
+}
+
+/// An `@name` or `#name` in outgoing text: `name` is without the sigil, and
+/// `range` covers both.
+#[derive(Debug, PartialEq)]
+pub struct Mention<'a> {
+    pub kind: MentionKind,
+    pub name: &'a str,
+    pub range: Range<usize>,
+}
+
+/// The `@handle` and `#channel` mentions in `text`, leaving out code, the
+/// `<…>` mentions and links already in Slack's syntax, email addresses,
+/// URL fragments and `#123` issue numbers.
+pub fn find_mentions(text: &str) -> Vec<Mention<'_>> {
+    let mut mentions = Vec::new();
+    let (mut in_fence, mut in_code, mut in_link) = (false, false, false);
+    let mut previous = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if text[i..].starts_with("```") {
+            in_fence = !in_fence;
+            chars.nth(1);
+            previous = Some('`');
+            continue;
+        }
+        match c {
+            '`' if !in_fence => in_code = !in_code,
+            '<' => in_link = true,
+            '>' => in_link = false,
+            '@' | '#' if !(in_fence || in_code || in_link) && previous.is_none_or(starts_mention) => {
+                let kind = if c == '@' { MentionKind::User } else { MentionKind::Channel };
+                let allowed = |c: char| match kind {
+                    MentionKind::User => c.is_ascii_alphanumeric() || "._-".contains(c),
+                    MentionKind::Channel => c.is_ascii_lowercase() || c.is_ascii_digit() || "_-".contains(c),
+                };
+                let rest = &text[i + 1..];
+                let name = &rest[..rest.find(|c: char| !allowed(c)).unwrap_or(rest.len())];
+                let name = name.trim_end_matches(['.', '-', '_']);
+                let named = match kind {
+                    MentionKind::User => !name.is_empty(),
+                    MentionKind::Channel => name.contains(|c: char| c.is_ascii_lowercase()),
+                };
+                if named {
+                    mentions.push(Mention { kind, name, range: i..i + 1 + name.len() });
+                }
+            }
+            _ => {}
+        }
+        previous = Some(c);
+    }
+    mentions
+}
+
+/// Whether a mention can follow `c`: not within a word, an email address,
+/// a URL or an HTML entity.
+fn starts_mention(c: char) -> bool {
+    !(c.is_alphanumeric() || "_.-/&@#:".contains(c))
+}
+
+/// `text` with its mentions in Slack's syntax: `@here`, `@channel` and
+/// `@everyone` as `<!here>` and so on, and other mentions as `resolve` gives
+/// them, left as they are when it gives `None`.
+pub fn link_mentions(text: &str, mut resolve: impl FnMut(&Mention) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut end = 0;
+    for mention in find_mentions(text) {
+        let special =
+            mention.kind == MentionKind::User && ["here", "channel", "everyone"].contains(&mention.name);
+        let linked = if special { Some(format!("<!{}>", mention.name)) } else { resolve(&mention) };
+        if let Some(linked) = linked {
+            out.push_str(&text[end..mention.range.start]);
+            out.push_str(&linked);
+            end = mention.range.end;
+        }
+    }
+    out.push_str(&text[end..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_looks_like_command_output() {
+        let git_log = "commit 7d89b5d\nAuthor: kanta\n\n    Resolve user handles\n";
+        assert!(looks_like_command_output(git_log));
+        assert!(looks_like_command_output("total 8\n-rw-r--r--  1 kanta  staff  12 a.txt"));
+        assert!(looks_like_command_output("a\tb\nc\td"));
+        assert!(!looks_like_command_output("Deploy finished.\nAll green."));
+        assert!(!looks_like_command_output("    just one indented line"));
+        assert!(!looks_like_command_output("```\n  already fenced\n```"));
+    }
+
+    #[test]
+    fn test_message_from_stdin() {
+        assert_eq!(message_from_stdin("hello\n").unwrap(), "hello");
+        assert_eq!(
+            message_from_stdin("name  size\na.txt  12\n").unwrap(),
+            "```\nname  size\na.txt  12\n```"
+        );
+        assert!(message_from_stdin("\n\n").is_err());
+    }
+    #[test]
+    fn test_blocks_from_json() {
+        let section = r#"{"type": "section", "text": {"type": "mrkdwn", "text": "*Deploy* done"}}"#;
+        let expected = r#"[{"type":"section","text":{"type":"mrkdwn","text":"*Deploy* done"}}]"#;
+        assert_eq!(blocks_from_json(&format!("[{}]", section)).unwrap(), expected);
+        assert_eq!(blocks_from_json(&format!(r#"{{"blocks": [{}]}}"#, section)).unwrap(), expected);
+        assert!(blocks_from_json("[").unwrap_err().to_string().starts_with("invalid JSON"));
+        assert!(blocks_from_json("[]").is_err());
+        assert!(blocks_from_json(r#"{"text": "hi"}"#).is_err());
+        assert_eq!(
+            blocks_from_json(r#"[{"type": "divider"}, {"text": "hi"}]"#).unwrap_err().to_string(),
+            "block 2 has no \"type\""
+        );
+    }
+    #[test]
+    fn test_find_mentions() {
+        let names = |text| find_mentions(text).iter().map(|m| (m.kind, m.name)).collect::<Vec<_>>();
+        use MentionKind::{Channel, User};
+        assert_eq!(names("@alice, see #general."), [(User, "alice"), (Channel, "general")]);
+        assert_eq!(names("(@kanta.m) #ops-alerts!"), [(User, "kanta.m"), (Channel, "ops-alerts")]);
+        assert!(names("mail kanta@example.com about #123 or https://x.test/#top").is_empty());
+        assert!(names("`@alice` and\n```\n#general\n```\n<@U1> <#C1|general> &#39;").is_empty());
+        assert_eq!(find_mentions("hi @bob").first().map(|m| m.range.clone()), Some(3..7));
+    }
+
+    #[test]
+    fn test_link_mentions() {
+        let resolve = |m: &Mention| match (m.kind, m.name) {
+            (MentionKind::User, "alice") => Some("<@U1>".to_string()),
+            (MentionKind::Channel, "general") => Some("<#C1|general>".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            link_mentions("@alice: @here see #general, not #nowhere or @nobody", resolve),
+            "<@U1>: <!here> see <#C1|general>, not #nowhere or @nobody"
+        );
+        assert_eq!(link_mentions("日本語 @aliceさん", resolve), "日本語 <@U1>さん");
+    }
+}
@@ -1,8 +1,89 @@
+//! The error type returned throughout slk.
+
+/// What kind of failure an error is, for callers that need more than the
+/// message, and for the exit status of `slk`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    /// Anything not covered below.
+    Other,
+    /// The command line was wrong: an unknown command, a missing argument or
+    /// a bad option value.
+    Usage,
+    /// There is no token, or Slack rejected it.
+    Auth,
+    /// The token lacks a scope the API method needs.
+    MissingScope,
+    /// Slack kept rate limiting the request.
+    RateLimited,
+    /// Slack couldn't be reached, or failed on its side.
+    Network,
+    /// A response or file wasn't the JSON expected.
+    Parse,
+    /// Slack answered `"ok": false` with this error code.
+    Api { code: String },
+}
+
+impl ErrorKind {
+    /// The kind of failure a Slack error code (`invalid_auth`,
+    /// `missing_scope`, ...) means.
+    pub fn from_api_code(code: &str) -> Self {
+        match code {
+            "not_authed" | "invalid_auth" | "account_inactive" | "token_revoked" | "token_expired"
+            | "no_permission" => ErrorKind::Auth,
+            "missing_scope" => ErrorKind::MissingScope,
+            "ratelimited" => ErrorKind::RateLimited,
+            "internal_error" => ErrorKind::Network,
+            _ => ErrorKind::Api { code: code.to_string() },
+        }
+    }
+
+    /// Whether the same request may succeed later: Slack was unreachable,
+    /// failing, or rate limiting. A bad token or a missing scope won't fix
+    /// itself.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ErrorKind::RateLimited | ErrorKind::Network)
+    }
+
+    /// The process exit status for an error of this kind.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::Auth => 3,
+            ErrorKind::MissingScope => 4,
+            ErrorKind::RateLimited => 5,
+            ErrorKind::Network => 6,
+            ErrorKind::Parse => 7,
+            ErrorKind::Api { .. } => 8,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct SlkError {
+    pub kind: ErrorKind,
     pub message: String,
 }
 
+impl SlkError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        SlkError { kind, message: message.into() }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind.is_retryable()
+    }
+
+    /// The same error, as `kind` instead. Errors that already have a kind
+    /// other than `Other` keep it.
+    pub fn or_kind(self, kind: ErrorKind) -> Self {
+        match self.kind {
+            ErrorKind::Other => SlkError { kind, ..self },
+            _ => self,
+        }
+    }
+}
+
 impl std::fmt::Display for SlkError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.message)
@@ -11,13 +92,14 @@ impl std::fmt::Display for SlkError {
 
 impl From<String> for SlkError {
     fn from(s: String) -> Self {
-        SlkError { message: s }
+        SlkError { kind: ErrorKind::Other, message: s }
     }
 }
 
 impl From<&str> for SlkError {
     fn from(s: &str) -> Self {
         SlkError {
+            kind: ErrorKind::Other,
             message: s.to_string(),
         }
     }
@@ -0,0 +1,105 @@
+use crate::message::SlackMessage;
+use crate::warn;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// A shell command run for each new message in `slk watch --exec`. The
+/// message is passed both as `SLK_*` environment variables and as a line of
+/// JSON on stdin; the command's output goes to the terminal. Messages from
+/// before the hook was created (the backlog) don't run it.
+pub struct Hook {
+    command: String,
+    /// Unix time the hook was created.
+    since: u64,
+}
+
+/// Where a message came from and who wrote it, for the hook's environment.
+pub struct Context<'a> {
+    pub channel_id: &'a str,
+    /// `#general`, or the ID when the name isn't known.
+    pub channel_name: &'a str,
+    pub user_name: Option<&'a str>,
+    pub time: &'a str,
+}
+
+impl Hook {
+    pub fn new(command: String) -> Self {
+        Hook { command, since: crate::config::now_unix() }
+    }
+
+    pub fn wants(&self, message: &SlackMessage) -> bool {
+        let secs = message.ts.split('.').next().and_then(|s| s.parse::<u64>().ok());
+        secs.is_some_and(|secs| secs >= self.since)
+    }
+
+    /// Runs the command for `message` and waits for it. A failure is
+    /// reported but doesn't stop the watch.
+    pub fn run(&self, message: &SlackMessage, context: &Context, json: &str) {
+        let shell = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+        let child = Command::new(shell.0)
+            .arg(shell.1)
+            .arg(&self.command)
+            .envs(env(message, context))
+            .stdin(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                warn!("failed to run --exec command '{}': {}", self.command, e);
+                return;
+            }
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            // A command that ignores stdin may exit before reading it.
+            let _ = writeln!(stdin, "{}", json);
+        }
+        match child.wait() {
+            Ok(status) if status.success() => {}
+            Ok(status) => warn!("--exec command '{}' failed ({})", self.command, status),
+            Err(e) => warn!("--exec command '{}' failed: {}", self.command, e),
+        }
+    }
+}
+
+/// The environment variables describing `message`.
+fn env(message: &SlackMessage, context: &Context) -> Vec<(&'static str, String)> {
+    vec![
+        ("SLK_CHANNEL", context.channel_id.to_string()),
+        ("SLK_CHANNEL_NAME", context.channel_name.to_string()),
+        ("SLK_TS", message.ts.clone()),
+        ("SLK_TIME", context.time.to_string()),
+        ("SLK_USER", message.user.clone()),
+        ("SLK_USER_NAME", context.user_name.unwrap_or("").to_string()),
+        ("SLK_TEXT", message.text.clone()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_and_wants() {
+        let message = SlackMessage {
+            user: "U1".to_string(),
+            text: "deploy done".to_string(),
+            ts: "1770689887.565249".to_string(),
+            ..Default::default()
+        };
+        let context = Context {
+            channel_id: "C1",
+            channel_name: "#general",
+            user_name: Some("kanta"),
+            time: "2026-02-10 02:18:07",
+        };
+        let vars = env(&message, &context);
+        assert!(vars.contains(&("SLK_CHANNEL_NAME", "#general".to_string())));
+        assert!(vars.contains(&("SLK_USER_NAME", "kanta".to_string())));
+        assert!(vars.contains(&("SLK_TEXT", "deploy done".to_string())));
+
+        let hook = Hook { command: "true".to_string(), since: 1770689887 };
+        assert!(hook.wants(&message));
+        let hook = Hook { command: "true".to_string(), since: 1770689888 };
+        assert!(!hook.wants(&message));
+    }
+}
@@ -0,0 +1,1424 @@
+use crate::error::{ErrorKind, SlkError};
+use crate::task::{self, Pending};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::Poll;
+use std::time::Duration;
+
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::server::ParsedCertificate;
+use rustls::{
+    ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    StreamOwned,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const IO_TIMEOUT: Duration = Duration::from_secs(60);
+const USER_AGENT: &str = concat!("slk/", env!("CARGO_PKG_VERSION"));
+const MAX_IDLE_PER_HOST: usize = 4;
+const DESCRIBE_BODY_LIMIT: usize = 200;
+const DIAGNOSTIC_HEADERS: [&str; 2] = ["Retry-After", "x-slack-req-id"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    Get,
+    Post,
+}
+
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Post => "POST",
+        }
+    }
+
+    /// Whether sending the request twice has the same effect as sending it once.
+    pub fn is_idempotent(&self) -> bool {
+        matches!(self, Method::Get)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// The largest response body to read; a bigger one fails with
+    /// [`Error::BodyTooLarge`] as soon as it is known to be.
+    pub max_body: Option<u64>,
+}
+
+impl Request {
+    pub fn get(url: &str) -> Self {
+        Request {
+            method: Method::Get,
+            url: url.to_string(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            max_body: None,
+        }
+    }
+
+    pub fn post_form(url: &str, params: &[(&str, &str)]) -> Self {
+        Request {
+            method: Method::Post,
+            url: url.to_string(),
+            headers: vec![(
+                "Content-Type".to_string(),
+                "application/x-www-form-urlencoded".to_string(),
+            )],
+            body: form_encode(params).into_bytes(),
+            max_body: None,
+        }
+    }
+
+    pub fn post_json(url: &str, body: &str) -> Self {
+        Request {
+            method: Method::Post,
+            url: url.to_string(),
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: body.as_bytes().to_vec(),
+            max_body: None,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn max_body(mut self, bytes: u64) -> Self {
+        self.max_body = Some(bytes);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn text(&self) -> Result<String, SlkError> {
+        String::from_utf8(self.body.clone())
+            .map_err(|e| SlkError::from(format!("invalid UTF-8 in response: {}", e)))
+    }
+
+    /// One-line summary for error messages: status, diagnostic headers, and
+    /// the start of the body.
+    pub fn describe(&self) -> String {
+        let mut out = format!("HTTP {}", self.status);
+        for name in DIAGNOSTIC_HEADERS {
+            if let Some(value) = self.header(name) {
+                out.push_str(&format!(", {}: {}", name, value));
+            }
+        }
+        let body = String::from_utf8_lossy(&self.body);
+        let body = body.trim();
+        if !body.is_empty() {
+            out.push_str(": ");
+            match body.char_indices().nth(DESCRIBE_BODY_LIMIT) {
+                Some((end, _)) => {
+                    out.push_str(&body[..end]);
+                    out.push_str("...");
+                }
+                None => out.push_str(body),
+            }
+        }
+        out
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The request could not be formed (bad URL, unknown scheme).
+    InvalidRequest(String),
+    /// Connecting, reading, or writing failed; usually worth retrying.
+    Io(std::io::Error),
+    /// The server replied with something that is not valid HTTP/1.1.
+    Protocol(String),
+    /// The response body is bigger than the request's `max_body`.
+    BodyTooLarge(u64),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidRequest(msg) => write!(f, "invalid request: {}", msg),
+            Error::Io(e) => write!(f, "network error: {}", e),
+            Error::Protocol(msg) => write!(f, "HTTP protocol error: {}", msg),
+            Error::BodyTooLarge(max) => write!(f, "response body is larger than {} bytes", max),
+        }
+    }
+}
+
+/// Transient failures are network errors, worth retrying; the rest (TLS
+/// verification, malformed HTTP) are not.
+impl From<Error> for SlkError {
+    fn from(e: Error) -> Self {
+        let kind = if e.is_transient() { ErrorKind::Network } else { ErrorKind::Other };
+        SlkError::new(kind, e.to_string())
+    }
+}
+
+impl Error {
+    /// Whether sending the same request again might succeed. TLS verification
+    /// failures (including pin mismatches) surface as `InvalidData` and are not.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Error::Io(e) if e.kind() != std::io::ErrorKind::InvalidData)
+    }
+
+    /// The peer reset or closed the connection mid-exchange.
+    pub fn is_connection_dropped(&self) -> bool {
+        use std::io::ErrorKind;
+        matches!(
+            self,
+            Error::Io(e) if matches!(
+                e.kind(),
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::UnexpectedEof
+            )
+        )
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Where a URL points: the scheme, host and port to connect to, and the
+/// path (with any query) to ask for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub tls: bool,
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+pub fn parse_url(url: &str) -> Result<Target, Error> {
+    let (tls, rest) = if let Some(rest) = url.strip_prefix("https://") {
+        (true, rest)
+    } else if let Some(rest) = url.strip_prefix("http://") {
+        (false, rest)
+    } else {
+        return Err(Error::InvalidRequest(format!("unsupported URL: {}", url)));
+    };
+
+    let (authority, path) = match rest.find(['/', '?']) {
+        Some(i) if rest[i..].starts_with('/') => (&rest[..i], rest[i..].to_string()),
+        Some(i) => (&rest[..i], format!("/{}", &rest[i..])),
+        None => (rest, "/".to_string()),
+    };
+
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse()
+                .map_err(|_| Error::InvalidRequest(format!("invalid port in URL: {}", url)))?;
+            (host, port)
+        }
+        None => (authority, if tls { 443 } else { 80 }),
+    };
+    if host.is_empty() {
+        return Err(Error::InvalidRequest(format!("missing host in URL: {}", url)));
+    }
+
+    Ok(Target {
+        tls,
+        host: host.to_string(),
+        port,
+        path,
+    })
+}
+
+/// The URL a `Location` header points to from `base`: absolute, on the same
+/// scheme (`//host/...`), on the same host (`/path`), or next to the current
+/// path (`name`).
+pub fn resolve_url(base: &str, location: &str) -> Result<String, Error> {
+    if location.contains("://") {
+        return Ok(location.to_string());
+    }
+    let target = parse_url(base)?;
+    let scheme = if target.tls { "https" } else { "http" };
+    if let Some(rest) = location.strip_prefix("//") {
+        return Ok(format!("{}://{}", scheme, rest));
+    }
+    let origin = match (target.tls, target.port) {
+        (true, 443) | (false, 80) => format!("{}://{}", scheme, target.host),
+        (_, port) => format!("{}://{}:{}", scheme, target.host, port),
+    };
+    if location.starts_with('/') {
+        return Ok(format!("{}{}", origin, location));
+    }
+    let path = target.path.split(['?', '#']).next().unwrap_or("/");
+    let dir = &path[..path.rfind('/').map_or(0, |i| i + 1)];
+    Ok(format!("{}{}{}", origin, dir, location))
+}
+
+/// Percent-encodes a string for use in a query string or form body.
+pub fn url_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+pub fn form_encode(params: &[(&str, &str)]) -> String {
+    params
+        .iter()
+        .map(|(k, v)| format!("{}={}", url_encode(k), url_encode(v)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn root_store() -> RootCertStore {
+    RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned())
+}
+
+fn default_tls_config() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            let config = ClientConfig::builder()
+                .with_root_certificates(root_store())
+                .with_no_client_auth();
+            Arc::new(config)
+        })
+        .clone()
+}
+
+/// SHA-256 of a certificate's DER-encoded SubjectPublicKeyInfo, as used by
+/// `sha256/...` pins (HPKP / curl `--pinnedpubkey` format).
+pub fn spki_sha256(cert: &CertificateDer<'_>) -> Option<[u8; 32]> {
+    let parsed = ParsedCertificate::try_from(cert).ok()?;
+    let digest = ring::digest::digest(&ring::digest::SHA256, parsed.subject_public_key_info().as_ref());
+    digest.as_ref().try_into().ok()
+}
+
+/// Performs normal WebPKI verification, then additionally requires that some
+/// certificate in the presented chain matches one of the configured SPKI pins.
+#[derive(Debug)]
+struct PinningVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pins: Vec<[u8; 32]>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        let presented: Vec<[u8; 32]> = std::iter::once(end_entity)
+            .chain(intermediates)
+            .filter_map(spki_sha256)
+            .collect();
+        if presented.iter().any(|hash| self.pins.contains(hash)) {
+            return Ok(verified);
+        }
+        let presented = presented
+            .iter()
+            .map(|hash| format!("sha256/{}", crate::base64::encode(hash)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Err(rustls::Error::General(format!(
+            "certificate pin mismatch for {} (server presented: {})",
+            server_name.to_str(),
+            presented
+        )))
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+fn pinned_tls_config(pins: Vec<[u8; 32]>) -> Result<Arc<ClientConfig>, Error> {
+    let inner = WebPkiServerVerifier::builder(Arc::new(root_store()))
+        .build()
+        .map_err(|e| Error::InvalidRequest(format!("failed to build certificate verifier: {}", e)))?;
+    let config = ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinningVerifier { inner, pins }))
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+fn connect(target: &Target) -> Result<TcpStream, Error> {
+    let addrs = (target.host.as_str(), target.port).to_socket_addrs()?;
+    let mut last_err = None;
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(IO_TIMEOUT))?;
+                stream.set_write_timeout(Some(IO_TIMEOUT))?;
+                return Ok(stream);
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(Error::Io(last_err.unwrap_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("could not resolve {}", target.host),
+        )
+    })))
+}
+
+fn write_request<W: Write>(w: &mut W, target: &Target, request: &Request) -> Result<(), Error> {
+    let default_port = if target.tls { 443 } else { 80 };
+    let host = if target.port == default_port {
+        target.host.clone()
+    } else {
+        format!("{}:{}", target.host, target.port)
+    };
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nAccept-Encoding: identity\r\n",
+        request.method.as_str(),
+        target.path,
+        host,
+        USER_AGENT
+    );
+    for (name, value) in &request.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if request.method == Method::Post || !request.body.is_empty() {
+        head.push_str(&format!("Content-Length: {}\r\n", request.body.len()));
+    }
+    head.push_str("\r\n");
+    w.write_all(head.as_bytes())?;
+    w.write_all(&request.body)?;
+    w.flush()?;
+    Ok(())
+}
+
+fn read_line<R: BufRead>(r: &mut R) -> Result<String, Error> {
+    let mut line = String::new();
+    let n = r.read_line(&mut line)?;
+    if n == 0 {
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "connection closed before response was complete",
+        )));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn read_head<R: BufRead>(r: &mut R) -> Result<(u16, Vec<(String, String)>), Error> {
+    loop {
+        let status_line = read_line(r)?;
+        let status: u16 = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| Error::Protocol(format!("invalid status line: {}", status_line)))?;
+
+        let mut headers = Vec::new();
+        loop {
+            let line = read_line(r)?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some((k, v)) = line.split_once(':') {
+                headers.push((k.trim().to_string(), v.trim().to_string()));
+            }
+        }
+
+        // Interim responses (e.g. "100 Continue") are followed by the real one;
+        // "101 Switching Protocols" hands the connection over instead.
+        if !(100..200).contains(&status) || status == 101 {
+            return Ok((status, headers));
+        }
+    }
+}
+
+fn read_chunked<R: BufRead>(r: &mut R) -> Result<Vec<u8>, Error> {
+    let mut body = Vec::new();
+    loop {
+        let line = read_line(r)?;
+        let size_str = line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_str, 16)
+            .map_err(|_| Error::Protocol(format!("invalid chunk size: {}", line)))?;
+        if size == 0 {
+            // Skip trailers up to the terminating blank line.
+            while !read_line(r)?.is_empty() {}
+            return Ok(body);
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        r.read_exact(&mut body[start..])?;
+        read_line(r)?;
+    }
+}
+
+fn read_response<R: BufRead>(r: &mut R) -> Result<Response, Error> {
+    let (status, headers) = read_head(r)?;
+    let find = |name: &str| {
+        headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    };
+
+    let body = if status == 204 || status == 304 {
+        Vec::new()
+    } else if find("Transfer-Encoding").is_some_and(|v| v.eq_ignore_ascii_case("chunked")) {
+        read_chunked(r)?
+    } else if let Some(len) = find("Content-Length") {
+        let len: usize = len
+            .parse()
+            .map_err(|_| Error::Protocol(format!("invalid Content-Length: {}", len)))?;
+        let mut body = vec![0u8; len];
+        r.read_exact(&mut body)?;
+        body
+    } else {
+        let mut body = Vec::new();
+        match r.read_to_end(&mut body) {
+            Ok(_) => {}
+            // Servers that close without a TLS close_notify still delimit the body by EOF.
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {}
+            Err(e) => return Err(Error::Io(e)),
+        }
+        body
+    };
+
+    Ok(Response {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// Whether the connection can carry another request after this response.
+fn is_reusable(response: &Response) -> bool {
+    let close = response
+        .header("Connection")
+        .is_some_and(|v| v.eq_ignore_ascii_case("close"));
+    let delimited = response.status == 204 || response.status == 304 || is_delimited(response);
+    !close && delimited
+}
+
+/// Something that can carry an HTTP request to a server and bring back the response.
+///
+/// The API layer depends on this rather than on [`Client`] directly so tests can
+/// substitute canned responses.
+pub trait Transport: Sync {
+    fn send(&self, request: &Request) -> Result<Response, Error>;
+
+    /// Like `send`, for running alongside other requests with
+    /// [`task::join_bounded`](crate::task::join_bounded): the future is
+    /// pending while the server is, instead of blocking the thread. By
+    /// default it just sends the request once polled.
+    fn send_async<'a>(&'a self, request: &'a Request) -> Pending<'a, Result<Response, Error>> {
+        Box::pin(async move { self.send(request) })
+    }
+}
+
+pub trait Stream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Stream for T {}
+
+/// A kept-alive connection, with its socket for switching between blocking
+/// and non-blocking I/O.
+struct Connection {
+    stream: Box<dyn Stream>,
+    socket: TcpStream,
+}
+
+/// A connection handed over to another protocol by [`Client::upgrade`].
+pub struct Upgraded {
+    pub stream: Box<dyn Stream>,
+    /// The underlying socket, for changing its timeouts.
+    pub socket: TcpStream,
+    /// Bytes the server sent right after the response head.
+    pub pending: Vec<u8>,
+}
+
+/// An HTTP/1.1 client that keeps idle keep-alive connections for reuse, so a
+/// burst of API calls to the same host pays for the TCP/TLS handshake once.
+pub struct Client {
+    idle: Mutex<Vec<(Target, Connection)>>,
+    tls: Arc<ClientConfig>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Client::new()
+    }
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Client {
+            idle: Mutex::new(Vec::new()),
+            tls: default_tls_config(),
+        }
+    }
+
+    /// A client that only completes TLS handshakes whose chain contains a
+    /// certificate with one of the given SPKI SHA-256 hashes.
+    pub fn with_pins(pins: Vec<[u8; 32]>) -> Result<Self, Error> {
+        Ok(Client {
+            idle: Mutex::new(Vec::new()),
+            tls: pinned_tls_config(pins)?,
+        })
+    }
+
+    /// A client with the TLS pins the user configured (`SLK_TLS_PINS` or
+    /// `tls_pins` in the config file), or an unpinned one if there are none.
+    pub fn configured() -> Result<Self, SlkError> {
+        let pins = crate::config::load_tls_pins()?;
+        if pins.is_empty() {
+            return Ok(Client::new());
+        }
+        Ok(Client::with_pins(pins)?)
+    }
+
+    fn checkout(&self, target: &Target) -> Option<Connection> {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        let pos = idle.iter().rposition(|(t, _)| t == target)?;
+        Some(idle.remove(pos).1)
+    }
+
+    fn checkin(&self, target: Target, conn: Connection) {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        if idle.iter().filter(|(t, _)| *t == target).count() < MAX_IDLE_PER_HOST {
+            idle.push((target, conn));
+        }
+    }
+
+    /// Connects to `target`. Resolving the host and connecting block even
+    /// for `send_async`; keeping connections for reuse makes that rare.
+    fn open(&self, target: &Target) -> Result<Connection, Error> {
+        let tcp = connect(target)?;
+        let socket = tcp.try_clone()?;
+        Ok(Connection { stream: self.wrap(target, tcp)?, socket })
+    }
+
+    /// Starts TLS over `tcp` when the target needs it.
+    fn wrap(&self, target: &Target, tcp: TcpStream) -> Result<Box<dyn Stream>, Error> {
+        if !target.tls {
+            return Ok(Box::new(tcp));
+        }
+        let server_name = ServerName::try_from(target.host.clone())
+            .map_err(|_| Error::InvalidRequest(format!("invalid host name: {}", target.host)))?;
+        let conn = ClientConnection::new(Arc::clone(&self.tls), server_name)
+            .map_err(|e| Error::Protocol(format!("failed to start TLS: {}", e)))?;
+        Ok(Box::new(StreamOwned::new(conn, tcp)))
+    }
+
+    /// Sends `request` on a connection of its own and returns the response
+    /// head with the connection, for protocols that take over after an
+    /// `Upgrade` (WebSocket). The response body is left unread.
+    pub fn upgrade(&self, request: &Request) -> Result<(Response, Upgraded), Error> {
+        let target = parse_url(&request.url)?;
+        let tcp = connect(&target)?;
+        let socket = tcp.try_clone()?;
+        let mut conn = BufReader::new(self.wrap(&target, tcp)?);
+        write_request(conn.get_mut(), &target, request)?;
+        let (status, headers) = read_head(&mut conn)?;
+        let pending = conn.buffer().to_vec();
+        let upgraded = Upgraded { stream: conn.into_inner(), socket, pending };
+        Ok((Response { status, headers, body: Vec::new() }, upgraded))
+    }
+
+    /// Sends a request, reusing an idle connection to the same host when one exists.
+    pub fn send(&self, request: &Request) -> Result<Response, Error> {
+        task::block_on(self.send_on(request, false))
+    }
+
+    /// Sends a request as `send` does; with `nonblocking`, waiting on the
+    /// socket leaves the future pending instead of blocking the thread.
+    async fn send_on(&self, request: &Request, nonblocking: bool) -> Result<Response, Error> {
+        let target = parse_url(&request.url)?;
+
+        if let Some(mut conn) = self.checkout(&target) {
+            match exchange(&mut conn, &target, request, nonblocking).await {
+                Ok(response) => {
+                    if is_reusable(&response) {
+                        self.checkin(target, conn);
+                    }
+                    return Ok(response);
+                }
+                // The server closed the idle connection while we held it, before
+                // the request reached it; fall through and retry once on a fresh one.
+                Err(Reuse::Stale(_)) => {}
+                // Past that point the server may have acted on the request, so
+                // only reads are safe to send again.
+                Err(Reuse::Failed(Error::Io(_))) if request.method.is_idempotent() => {}
+                Err(Reuse::Failed(e)) => return Err(e),
+            }
+        }
+
+        match self.send_fresh(&target, request, nonblocking).await {
+            // Reads are safe to repeat, so give a dropped connection one more try.
+            Err(e) if e.is_connection_dropped() && request.method.is_idempotent() => {
+                self.send_fresh(&target, request, nonblocking).await
+            }
+            result => result,
+        }
+    }
+
+    async fn send_fresh(
+        &self,
+        target: &Target,
+        request: &Request,
+        nonblocking: bool,
+    ) -> Result<Response, Error> {
+        let mut conn = self.open(target)?;
+        let response = exchange(&mut conn, target, request, nonblocking).await.map_err(Reuse::into_error)?;
+        if is_reusable(&response) {
+            self.checkin(target.clone(), conn);
+        }
+        Ok(response)
+    }
+}
+
+impl Transport for Client {
+    fn send(&self, request: &Request) -> Result<Response, Error> {
+        Client::send(self, request)
+    }
+
+    fn send_async<'a>(&'a self, request: &'a Request) -> Pending<'a, Result<Response, Error>> {
+        Box::pin(self.send_on(request, true))
+    }
+}
+
+/// An in-memory [`Transport`] that answers Slack API calls with canned JSON.
+#[cfg(test)]
+#[derive(Default)]
+pub struct FakeTransport {
+    responses: Vec<(String, String)>,
+    redirects: Vec<(String, String)>,
+    pub requests: std::sync::Mutex<Vec<Request>>,
+}
+
+#[cfg(test)]
+impl FakeTransport {
+    pub fn new() -> Self {
+        FakeTransport::default()
+    }
+
+    /// Answers every call to the given API method (e.g. `users.info`) with `body`.
+    /// The method may name a query parameter too (`conversations.history?cursor=p2`)
+    /// to answer only the calls that have it; the first matching response wins.
+    pub fn respond(mut self, method: &str, body: &str) -> Self {
+        self.responses.push((method.to_string(), body.to_string()));
+        self
+    }
+
+    /// Answers the calls `respond` would match with a redirect to `location`,
+    /// ahead of any response.
+    pub fn redirect(mut self, method: &str, location: &str) -> Self {
+        self.redirects.push((method.to_string(), location.to_string()));
+        self
+    }
+}
+
+#[cfg(test)]
+impl Transport for FakeTransport {
+    fn send(&self, request: &Request) -> Result<Response, Error> {
+        self.requests.lock().unwrap().push(request.clone());
+        let (path, query) = request.url.split_once('?').unwrap_or((&request.url, ""));
+        let answers = |(method, _): &&(String, String)| {
+            let (method, param) = method.split_once('?').unwrap_or((method, ""));
+            path.ends_with(&format!("/{}", method))
+                && (param.is_empty() || query.split('&').any(|p| p == param))
+        };
+        if let Some((_, location)) = self.redirects.iter().find(answers) {
+            return Ok(Response {
+                status: 302,
+                headers: vec![("Location".to_string(), location.clone())],
+                body: Vec::new(),
+            });
+        }
+        let body = self.responses.iter().find(answers).map(|(_, body)| body.clone());
+        if let Some(max) = request.max_body.filter(|max| body.as_ref().is_some_and(|b| b.len() as u64 > *max)) {
+            return Err(Error::BodyTooLarge(max));
+        }
+        Ok(match body {
+            Some(body) => Response {
+                status: 200,
+                headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+                body: body.into_bytes(),
+            },
+            None => Response {
+                status: 404,
+                headers: Vec::new(),
+                body: br#"{"ok":false,"error":"unknown_method"}"#.to_vec(),
+            },
+        })
+    }
+}
+
+/// How a request on a connection failed.
+enum Reuse {
+    /// Writing failed or the connection closed before any response arrived:
+    /// on a kept-alive connection, the signs of one the server had already
+    /// dropped.
+    Stale(Error),
+    Failed(Error),
+}
+
+impl Reuse {
+    fn into_error(self) -> Error {
+        match self {
+            Reuse::Stale(e) | Reuse::Failed(e) => e,
+        }
+    }
+}
+
+/// How much of a response `exchange` reads at a time.
+const READ_CHUNK: usize = 16 * 1024;
+
+/// Sends `request` on `conn` and reads the response. With `nonblocking`,
+/// the socket is switched to non-blocking I/O and the future is pending
+/// whenever it would block.
+async fn exchange(
+    conn: &mut Connection,
+    target: &Target,
+    request: &Request,
+    nonblocking: bool,
+) -> Result<Response, Reuse> {
+    conn.socket.set_nonblocking(nonblocking).map_err(|e| Reuse::Failed(e.into()))?;
+    let result = exchange_on(conn.stream.as_mut(), target, request).await;
+    if nonblocking && result.is_ok() {
+        conn.socket.set_nonblocking(false).map_err(|e| Reuse::Failed(e.into()))?;
+    }
+    result
+}
+
+async fn exchange_on(stream: &mut dyn Stream, target: &Target, request: &Request) -> Result<Response, Reuse> {
+    let mut out = Vec::new();
+    write_request(&mut out, target, request).map_err(Reuse::Failed)?;
+    let mut written = 0;
+    while written < out.len() {
+        match io(|| stream.write(&out[written..])).await {
+            Ok(0) => return Err(Reuse::Stale(std::io::Error::from(std::io::ErrorKind::WriteZero).into())),
+            Ok(n) => written += n,
+            Err(e) => return Err(Reuse::Stale(e.into())),
+        }
+    }
+    io(|| stream.flush()).await.map_err(|e| Reuse::Stale(e.into()))?;
+
+    let mut received = Vec::new();
+    let mut chunk = vec![0u8; READ_CHUNK];
+    loop {
+        let n = match io(|| stream.read(&mut chunk)).await {
+            Ok(n) => n,
+            // Servers that close without a TLS close_notify still delimit the body by EOF.
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => 0,
+            Err(e) => {
+                let e = Error::Io(e);
+                return Err(if received.is_empty() && e.is_connection_dropped() {
+                    Reuse::Stale(e)
+                } else {
+                    Reuse::Failed(e)
+                });
+            }
+        };
+        if n == 0 && received.is_empty() {
+            return Err(Reuse::Stale(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "connection closed before response was complete",
+            ))));
+        }
+        received.extend_from_slice(&chunk[..n]);
+        if let Some(max) = request.max_body.filter(|max| exceeds(&received, *max)) {
+            return Err(Reuse::Failed(Error::BodyTooLarge(max)));
+        }
+        if let Some(response) = parse_received(&received, n == 0) {
+            return response.map_err(Reuse::Failed);
+        }
+    }
+}
+
+/// Runs a socket operation, leaving the future pending while it would
+/// block (as it does on a non-blocking socket) for up to `IO_TIMEOUT`.
+async fn io<T>(mut op: impl FnMut() -> std::io::Result<T>) -> std::io::Result<T> {
+    let start = std::time::Instant::now();
+    std::future::poll_fn(|_| match op() {
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+            if start.elapsed() >= IO_TIMEOUT {
+                return Poll::Ready(Err(std::io::ErrorKind::TimedOut.into()));
+            }
+            task::note_waiting_on_io();
+            Poll::Pending
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+            task::note_progress();
+            Poll::Pending
+        }
+        result => {
+            task::note_progress();
+            Poll::Ready(result)
+        }
+    })
+    .await
+}
+
+/// The response in `received`, once it is all there: `None` while more is
+/// to come. `closed` tells that the server closed the connection, which
+/// ends a body without a length.
+fn parse_received(received: &[u8], closed: bool) -> Option<Result<Response, Error>> {
+    if !closed && !may_be_complete(received) {
+        return None;
+    }
+    match read_response(&mut &received[..]) {
+        Ok(response) if closed || response.status == 204 || response.status == 304 => Some(Ok(response)),
+        Ok(response) if is_delimited(&response) => Some(Ok(response)),
+        Ok(_) => None,
+        Err(Error::Io(e)) if !closed && e.kind() == std::io::ErrorKind::UnexpectedEof => None,
+        Err(e) => Some(Err(e)),
+    }
+}
+
+/// A cheap check before parsing `received` in full: whether the head is
+/// complete and the body may be.
+fn may_be_complete(received: &[u8]) -> bool {
+    let Some((head_end, length)) = received_head(received) else {
+        return false;
+    };
+    match length {
+        // An interim response's head may end before the real one arrives.
+        Some(length) => (received.len() - head_end) as u64 >= length,
+        // A chunked body ends with an empty chunk and a blank line: checking
+        // for that, rather than for any line end, keeps a body that arrives
+        // a chunk at a time from being parsed again after every read.
+        None => received[head_end..].ends_with(b"\r\n\r\n"),
+    }
+}
+
+/// Whether the response in `received` has, or says it will have, a body
+/// bigger than `max` bytes. Chunk headers count toward it, which only makes
+/// the check a little early.
+fn exceeds(received: &[u8], max: u64) -> bool {
+    let Some((head_end, length)) = received_head(received) else {
+        return false;
+    };
+    length.is_some_and(|length| length > max) || (received.len() - head_end) as u64 > max
+}
+
+/// Where the head in `received` ends and the Content-Length it gives, once
+/// the whole head is there.
+fn received_head(received: &[u8]) -> Option<(usize, Option<u64>)> {
+    let head_end = received.windows(4).position(|w| w == b"\r\n\r\n").map(|i| i + 4)?;
+    let head = String::from_utf8_lossy(&received[..head_end]);
+    let length = head.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim().eq_ignore_ascii_case("Content-Length").then(|| value.trim().parse::<u64>().ok())?
+    });
+    Some((head_end, length))
+}
+
+/// Whether the response says where its body ends, without the connection closing.
+fn is_delimited(response: &Response) -> bool {
+    response.header("Content-Length").is_some()
+        || response
+            .header("Transfer-Encoding")
+            .is_some_and(|v| v.eq_ignore_ascii_case("chunked"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url_https() {
+        assert_eq!(
+            parse_url("https://slack.com/api/users.info?user=U1").unwrap(),
+            Target {
+                tls: true,
+                host: "slack.com".to_string(),
+                port: 443,
+                path: "/api/users.info?user=U1".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_url_http_with_port() {
+        assert_eq!(
+            parse_url("http://127.0.0.1:8080").unwrap(),
+            Target {
+                tls: false,
+                host: "127.0.0.1".to_string(),
+                port: 8080,
+                path: "/".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_url_rejects_other_schemes() {
+        assert!(parse_url("ftp://slack.com/").is_err());
+        assert!(parse_url("https://:443/").is_err());
+    }
+
+    #[test]
+    fn test_resolve_url() {
+        let base = "https://files.slack.com/files-pri/T1-F1/a.txt?x=1";
+        assert_eq!(resolve_url(base, "https://example.com/b").unwrap(), "https://example.com/b");
+        assert_eq!(resolve_url(base, "//example.com/b").unwrap(), "https://example.com/b");
+        assert_eq!(resolve_url(base, "/download/b").unwrap(), "https://files.slack.com/download/b");
+        assert_eq!(resolve_url(base, "b.txt").unwrap(), "https://files.slack.com/files-pri/T1-F1/b.txt");
+        assert_eq!(resolve_url("http://127.0.0.1:8080/a", "/b").unwrap(), "http://127.0.0.1:8080/b");
+    }
+
+    #[test]
+    fn test_form_encode() {
+        assert_eq!(
+            form_encode(&[("code", "a b&c"), ("redirect_uri", "https://127.0.0.1:9876")]),
+            "code=a%20b%26c&redirect_uri=https%3A%2F%2F127.0.0.1%3A9876"
+        );
+    }
+
+    #[test]
+    fn test_write_request() {
+        let target = parse_url("https://slack.com/api/oauth.v2.access").unwrap();
+        let request = Request::post_form("https://slack.com/api/oauth.v2.access", &[("a", "1")]);
+        let mut out = Vec::new();
+        write_request(&mut out, &target, &request).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("POST /api/oauth.v2.access HTTP/1.1\r\nHost: slack.com\r\n"));
+        assert!(text.contains("Content-Type: application/x-www-form-urlencoded\r\n"));
+        assert!(text.ends_with("Content-Length: 3\r\n\r\na=1"));
+    }
+
+    #[test]
+    fn test_read_response_content_length() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 11\r\n\r\n{\"ok\":true}";
+        let response = read_response(&mut &raw[..]).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("content-type"), Some("application/json"));
+        assert_eq!(response.text().unwrap(), r#"{"ok":true}"#);
+    }
+
+    #[test]
+    fn test_read_response_chunked() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\n{\"ok\r\n7;ext=1\r\n\":true}\r\n0\r\n\r\n";
+        let response = read_response(&mut &raw[..]).unwrap();
+        assert_eq!(response.text().unwrap(), r#"{"ok":true}"#);
+    }
+
+    #[test]
+    fn test_read_response_until_eof() {
+        let raw = b"HTTP/1.1 200 OK\r\n\r\nhello";
+        let response = read_response(&mut &raw[..]).unwrap();
+        assert_eq!(response.text().unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_read_response_skips_interim_response() {
+        let raw = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 429 Too Many Requests\r\nRetry-After: 3\r\nContent-Length: 0\r\n\r\n";
+        let response = read_response(&mut &raw[..]).unwrap();
+        assert_eq!(response.status, 429);
+        assert_eq!(response.header("Retry-After"), Some("3"));
+    }
+
+    #[test]
+    fn test_read_response_truncated() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\nshort";
+        assert!(matches!(read_response(&mut &raw[..]), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn test_read_response_invalid_status() {
+        let raw = b"garbage\r\n\r\n";
+        assert!(matches!(read_response(&mut &raw[..]), Err(Error::Protocol(_))));
+    }
+
+    #[test]
+    fn test_is_reusable() {
+        let response = |headers: &[(&str, &str)]| Response {
+            status: 200,
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            body: Vec::new(),
+        };
+        assert!(is_reusable(&response(&[("Content-Length", "2")])));
+        assert!(is_reusable(&response(&[("Transfer-Encoding", "chunked")])));
+        assert!(!is_reusable(&response(&[])));
+        assert!(!is_reusable(&response(&[
+            ("Content-Length", "2"),
+            ("Connection", "close"),
+        ])));
+    }
+
+    #[test]
+    fn test_client_reuses_connection() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            // Accept exactly one connection and answer two requests on it.
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            for body in ["one", "two"] {
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let reply = format!("HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\n{}", body);
+                reader.get_mut().write_all(reply.as_bytes()).unwrap();
+            }
+        });
+
+        let client = Client::new();
+        let url = format!("http://127.0.0.1:{}/", port);
+        let first = client.send(&Request::get(&url)).unwrap();
+        let second = client.send(&Request::get(&url)).unwrap();
+        assert_eq!(first.text().unwrap(), "one");
+        assert_eq!(second.text().unwrap(), "two");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_response_describe() {
+        let response = Response {
+            status: 503,
+            headers: vec![
+                ("x-slack-req-id".to_string(), "abc123".to_string()),
+                ("Retry-After".to_string(), "5".to_string()),
+                ("Content-Type".to_string(), "text/html".to_string()),
+            ],
+            body: b"  Service Unavailable\n".to_vec(),
+        };
+        assert_eq!(
+            response.describe(),
+            "HTTP 503, Retry-After: 5, x-slack-req-id: abc123: Service Unavailable"
+        );
+    }
+
+    #[test]
+    fn test_response_describe_truncates_body() {
+        let response = Response {
+            status: 500,
+            headers: Vec::new(),
+            body: "x".repeat(500).into_bytes(),
+        };
+        let described = response.describe();
+        assert!(described.ends_with("..."));
+        assert_eq!(described.len(), "HTTP 500: ".len() + DESCRIBE_BODY_LIMIT + 3);
+    }
+
+    #[test]
+    fn test_is_connection_dropped() {
+        use std::io::ErrorKind;
+        let io = |kind| Error::Io(std::io::Error::new(kind, "x"));
+        assert!(io(ErrorKind::ConnectionReset).is_connection_dropped());
+        assert!(io(ErrorKind::UnexpectedEof).is_connection_dropped());
+        assert!(!io(ErrorKind::TimedOut).is_connection_dropped());
+        assert!(!Error::Protocol("x".to_string()).is_connection_dropped());
+    }
+
+    /// Serves one connection per entry in `replies`; `None` closes the
+    /// connection without answering.
+    fn serve(replies: Vec<Option<&'static str>>) -> (u16, std::thread::JoinHandle<usize>) {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let mut served = 0;
+            for reply in replies {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                }
+                if let Some(reply) = reply {
+                    reader.get_mut().write_all(reply.as_bytes()).unwrap();
+                }
+                served += 1;
+            }
+            served
+        });
+        (port, handle)
+    }
+
+    #[test]
+    fn test_get_retried_once_after_dropped_connection() {
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+        let (port, server) = serve(vec![None, Some(ok)]);
+        let response = Client::new()
+            .send(&Request::get(&format!("http://127.0.0.1:{}/", port)))
+            .unwrap();
+        assert_eq!(response.text().unwrap(), "ok");
+        assert_eq!(server.join().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_post_not_retried_after_dropped_connection() {
+        let (port, server) = serve(vec![None]);
+        let request = Request::post_form(&format!("http://127.0.0.1:{}/", port), &[("a", "1")]);
+        let err = Client::new().send(&request).unwrap_err();
+        assert!(err.is_connection_dropped());
+        assert_eq!(server.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_max_body_stops_reading_an_oversized_response() {
+        // The declared length is enough to give up before the body arrives.
+        let declared = "HTTP/1.1 200 OK\r\nContent-Length: 1000000\r\n\r\npartial";
+        let chunk = format!("c8\r\n{}\r\n", "x".repeat(200));
+        let chunked = format!("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{}0\r\n\r\n", chunk).leak();
+        let (port, server) = serve(vec![Some(declared), Some(chunked), Some(chunked)]);
+        let request = Request::get(&format!("http://127.0.0.1:{}/", port));
+        let err = Client::new().send(&request.clone().max_body(100)).unwrap_err();
+        assert!(matches!(err, Error::BodyTooLarge(100)));
+        let err = Client::new().send(&request.clone().max_body(100)).unwrap_err();
+        assert_eq!(err.to_string(), "response body is larger than 100 bytes");
+        assert_eq!(Client::new().send(&request.max_body(300)).unwrap().body.len(), 200);
+        assert_eq!(server.join().unwrap(), 3);
+    }
+
+    /// Accepts one connection and answers each request on it with the next of
+    /// `replies`, closing it after the last; the listener is handed back to
+    /// check for further connections.
+    fn serve_reused(replies: Vec<&'static str>) -> (u16, std::thread::JoinHandle<std::net::TcpListener>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            for reply in replies {
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.strip_prefix("Content-Length: ") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                reader.read_exact(&mut vec![0; length]).unwrap();
+                reader.get_mut().write_all(reply.as_bytes()).unwrap();
+            }
+            listener
+        });
+        (port, handle)
+    }
+
+    #[test]
+    fn test_post_not_resent_after_failing_mid_response_on_reused_connection() {
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let cut = "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n{\"ok\"";
+        let (port, server) = serve_reused(vec![ok, cut]);
+        let client = Client::new();
+        let url = format!("http://127.0.0.1:{}/api/chat.postMessage", port);
+        let request = Request::post_form(&url, &[("text", "hi")]);
+        assert_eq!(client.send(&request).unwrap().text().unwrap(), "ok");
+        assert!(client.send(&request).unwrap_err().is_connection_dropped());
+
+        let listener = server.join().unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let resent = listener.accept();
+        assert_eq!(resent.map(|_| ()).unwrap_err().kind(), std::io::ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn test_post_retried_on_stale_reused_connection() {
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let (port, server) = serve(vec![Some(ok), Some(ok)]);
+        let client = Client::new();
+        let request = Request::post_form(&format!("http://127.0.0.1:{}/", port), &[("a", "1")]);
+        client.send(&request).unwrap();
+        // Let the server close the first connection while it sits idle.
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(client.send(&request).unwrap().text().unwrap(), "ok");
+        assert_eq!(server.join().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_send_async_overlaps_requests_on_one_thread() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // Only answers once both requests are in, the second first: sent one
+        // after another, the first would wait forever.
+        let server = std::thread::spawn(move || {
+            let mut streams = Vec::new();
+            for _ in 0..2 {
+                let mut reader = BufReader::new(listener.accept().unwrap().0);
+                let mut path = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(rest) = line.strip_prefix("GET ") {
+                        path = rest.split(' ').next().unwrap().to_string();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                streams.push((reader, path));
+            }
+            for (reader, path) in streams.iter_mut().rev() {
+                let reply = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", path.len(), path);
+                reader.get_mut().write_all(reply.as_bytes()).unwrap();
+            }
+        });
+
+        let client = Client::new();
+        let requests = ["/a", "/b"].map(|path| Request::get(&format!("http://127.0.0.1:{}{}", port, path)));
+        let responses = task::join_bounded(requests.iter().map(|request| client.send_async(request)), 2);
+        let texts: Vec<String> = responses.into_iter().map(|r| r.unwrap().text().unwrap()).collect();
+        assert_eq!(texts, ["/a", "/b"]);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_parse_received_waits_for_the_whole_response() {
+        let full = b"HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\n{\"ok\":true}";
+        assert!(parse_received(&full[..20], false).is_none());
+        assert!(parse_received(&full[..full.len() - 1], false).is_none());
+        assert_eq!(parse_received(full, false).unwrap().unwrap().text().unwrap(), r#"{"ok":true}"#);
+        assert!(parse_received(&full[..full.len() - 1], true).unwrap().is_err());
+
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n";
+        assert!(!may_be_complete(chunked));
+        assert!(parse_received(chunked, false).is_none());
+        let chunked = [&chunked[..], b"0\r\n\r\n"].concat();
+        assert!(may_be_complete(&chunked));
+        assert_eq!(parse_received(&chunked, false).unwrap().unwrap().text().unwrap(), "ok");
+
+        let until_closed = b"HTTP/1.1 200 OK\r\n\r\nhello\r\n";
+        assert!(parse_received(until_closed, false).is_none());
+        assert_eq!(parse_received(until_closed, true).unwrap().unwrap().text().unwrap(), "hello\r\n");
+    }
+
+    #[test]
+    fn test_spki_sha256_matches_public_key() {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["slack.com".to_string()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+        use rcgen::PublicKeyData;
+        let expected =
+            ring::digest::digest(&ring::digest::SHA256, &key_pair.subject_public_key_info());
+        assert_eq!(spki_sha256(cert.der()).unwrap().as_slice(), expected.as_ref());
+    }
+
+    #[test]
+    fn test_spki_sha256_rejects_garbage() {
+        assert_eq!(spki_sha256(&CertificateDer::from(vec![1, 2, 3])), None);
+    }
+
+    #[test]
+    fn test_with_pins_builds() {
+        assert!(Client::with_pins(vec![[0u8; 32]]).is_ok());
+    }
+
+    #[test]
+    fn test_send_async_over_tls_reuses_connection() {
+        use rustls::pki_types::PrivateKeyDer;
+        use std::net::TcpListener;
+
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["127.0.0.1".to_string()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+        let server_config = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert.der().clone()], PrivateKeyDer::Pkcs8(key_pair.serialize_der().into()))
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let tcp = listener.accept().unwrap().0;
+            let tls = rustls::ServerConnection::new(Arc::new(server_config)).unwrap();
+            let mut reader = BufReader::new(StreamOwned::new(tls, tcp));
+            for body in ["one", "two"] {
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let reply = format!("HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\n{}", body);
+                reader.get_mut().write_all(reply.as_bytes()).unwrap();
+                reader.get_mut().flush().unwrap();
+            }
+        });
+
+        let mut roots = RootCertStore::empty();
+        roots.add(cert.der().clone()).unwrap();
+        let config = ClientConfig::builder().with_root_certificates(roots).with_no_client_auth();
+        let client = Client { idle: Mutex::new(Vec::new()), tls: Arc::new(config) };
+        let request = Request::get(&format!("https://127.0.0.1:{}/", port));
+        for body in ["one", "two"] {
+            let response = task::join_bounded([client.send_async(&request)], 4).pop().unwrap();
+            assert_eq!(response.unwrap().text().unwrap(), body);
+        }
+        server.join().unwrap();
+    }
+}
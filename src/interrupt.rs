@@ -0,0 +1,55 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set while a cancellable wait is running; Ctrl-C then ends the wait
+/// instead of killing the process.
+static CATCHING: AtomicBool = AtomicBool::new(false);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+fn install_handler() {
+    static INSTALLED: std::sync::Once = std::sync::Once::new();
+    INSTALLED.call_once(|| {
+        let _ = ctrlc::set_handler(|| {
+            if CATCHING.load(Ordering::SeqCst) {
+                INTERRUPTED.store(true, Ordering::SeqCst);
+            } else {
+                std::process::exit(130);
+            }
+        });
+    });
+}
+
+/// Catches Ctrl-C until dropped; check [`interrupted`] to see if it was pressed.
+pub struct Guard;
+
+impl Guard {
+    pub fn start() -> Self {
+        install_handler();
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        CATCHING.store(true, Ordering::SeqCst);
+        Guard
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        CATCHING.store(false, Ordering::SeqCst);
+    }
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Sleeps for `duration` in short steps, returning early (with `false`) if
+/// Ctrl-C is pressed.
+pub fn sleep(duration: std::time::Duration) -> bool {
+    let deadline = std::time::Instant::now() + duration;
+    while !interrupted() {
+        let left = deadline.saturating_duration_since(std::time::Instant::now());
+        if left.is_zero() {
+            return true;
+        }
+        std::thread::sleep(left.min(std::time::Duration::from_millis(100)));
+    }
+    false
+}
@@ -1,9 +1,18 @@
-use crate::error::SlkError;
+//! A JSON parser and serializer: [`parse`] into a [`JsonValue`] tree, or
+//! [`parse_borrowed`] and [`stream_array`] for big responses, and `to_string`
+//! or [`JsonValue::pretty`] back to text.
+
+use crate::error::{ErrorKind, SlkError};
+use std::borrow::Cow;
+use std::collections::HashMap;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum JsonValue {
     Null,
     Bool(bool),
+    /// A number written without a fraction or exponent that fits in an
+    /// `i64`, kept exact: IDs, counts and Unix times don't survive `f64`.
+    Int(i64),
     Number(f64),
     String(String),
     Array(Vec<JsonValue>),
@@ -11,6 +20,39 @@ pub enum JsonValue {
 }
 
 impl JsonValue {
+    /// An object with `members` in order, their values converted with `into`:
+    /// `JsonValue::object([("id", "C1".into()), ("count", 3u64.into())])`.
+    pub fn object<K: Into<String>>(members: impl IntoIterator<Item = (K, JsonValue)>) -> JsonValue {
+        JsonValue::Object(members.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    /// Sets `key` in an object, replacing its value if it has one and
+    /// appending it otherwise. Anything but an object becomes an empty one first.
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<JsonValue>) -> &mut JsonValue {
+        let (key, value) = (key.into(), value.into());
+        if !matches!(self, JsonValue::Object(_)) {
+            *self = JsonValue::Object(Vec::new());
+        }
+        if let JsonValue::Object(pairs) = self {
+            match pairs.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, old)) => *old = value,
+                None => pairs.push((key, value)),
+            }
+        }
+        self
+    }
+
+    /// Appends to an array. Anything but an array becomes an empty one first.
+    pub fn push(&mut self, value: impl Into<JsonValue>) -> &mut JsonValue {
+        if !matches!(self, JsonValue::Array(_)) {
+            *self = JsonValue::Array(Vec::new());
+        }
+        if let JsonValue::Array(items) = self {
+            items.push(value.into());
+        }
+        self
+    }
+
     pub fn get(&self, key: &str) -> Option<&JsonValue> {
         match self {
             JsonValue::Object(pairs) => pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v),
@@ -18,6 +60,16 @@ impl JsonValue {
         }
     }
 
+    /// An index over an object's members for code that looks up many keys in
+    /// the same big object: `get` scans the members, this hashes once. A
+    /// repeated key resolves to its first value, as with `get`.
+    pub fn index(&self) -> Index<'_> {
+        match self {
+            JsonValue::Object(pairs) => Index::new(pairs.iter().map(|(k, v)| (k.as_str(), v))),
+            _ => Index::new(std::iter::empty()),
+        }
+    }
+
     pub fn as_str(&self) -> Option<&str> {
         match self {
             JsonValue::String(s) => Some(s),
@@ -32,6 +84,20 @@ impl JsonValue {
         }
     }
 
+    /// An integer; a float only if it is a whole number.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            JsonValue::Int(n) => Some(*n),
+            JsonValue::Number(n) if n.fract() == 0.0 && n.abs() < 9.2e18 => Some(*n as i64),
+            _ => None,
+        }
+    }
+
+    /// A non-negative integer, as [`JsonValue::as_i64`].
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_i64().and_then(|n| u64::try_from(n).ok())
+    }
+
     pub fn as_array(&self) -> Option<&Vec<JsonValue>> {
         match self {
             JsonValue::Array(a) => Some(a),
@@ -40,8 +106,325 @@ impl JsonValue {
     }
 }
 
+impl From<&str> for JsonValue {
+    fn from(s: &str) -> Self {
+        JsonValue::String(s.to_string())
+    }
+}
+
+impl From<String> for JsonValue {
+    fn from(s: String) -> Self {
+        JsonValue::String(s)
+    }
+}
+
+impl From<bool> for JsonValue {
+    fn from(b: bool) -> Self {
+        JsonValue::Bool(b)
+    }
+}
+
+impl From<i64> for JsonValue {
+    fn from(n: i64) -> Self {
+        JsonValue::Int(n)
+    }
+}
+
+/// Counts and Unix times; beyond `i64::MAX` they become floats.
+impl From<u64> for JsonValue {
+    fn from(n: u64) -> Self {
+        i64::try_from(n).map_or(JsonValue::Number(n as f64), JsonValue::Int)
+    }
+}
+
+impl From<usize> for JsonValue {
+    fn from(n: usize) -> Self {
+        JsonValue::from(n as u64)
+    }
+}
+
+impl From<f64> for JsonValue {
+    fn from(n: f64) -> Self {
+        JsonValue::Number(n)
+    }
+}
+
+impl<T: Into<JsonValue>> From<Option<T>> for JsonValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(JsonValue::Null, Into::into)
+    }
+}
+
+impl<T: Into<JsonValue>> From<Vec<T>> for JsonValue {
+    fn from(items: Vec<T>) -> Self {
+        JsonValue::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+/// See [`JsonValue::index`]. A non-object has no members.
+pub struct Index<'a, N = JsonValue> {
+    members: HashMap<&'a str, &'a N>,
+}
+
+impl<'a, N> Index<'a, N> {
+    fn new(pairs: impl ExactSizeIterator<Item = (&'a str, &'a N)>) -> Self {
+        let mut members = HashMap::with_capacity(pairs.len());
+        for (key, value) in pairs {
+            members.entry(key).or_insert(value);
+        }
+        Index { members }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&'a N> {
+        self.members.get(key).copied()
+    }
+}
+
+/// A parsed document that borrows its strings from the input wherever they
+/// contain no escapes, so reading a big response doesn't allocate a `String`
+/// for every key and value. Convert it with `into_owned` to keep it.
+#[derive(Debug, PartialEq, Clone)]
+pub enum JsonRef<'a> {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Number(f64),
+    String(Cow<'a, str>),
+    Array(Vec<JsonRef<'a>>),
+    Object(Vec<(Cow<'a, str>, JsonRef<'a>)>),
+}
+
+impl JsonRef<'_> {
+    pub fn into_owned(self) -> JsonValue {
+        match self {
+            JsonRef::Null => JsonValue::Null,
+            JsonRef::Bool(b) => JsonValue::Bool(b),
+            JsonRef::Int(n) => JsonValue::Int(n),
+            JsonRef::Number(n) => JsonValue::Number(n),
+            JsonRef::String(s) => JsonValue::String(s.into_owned()),
+            JsonRef::Array(items) => JsonValue::Array(items.into_iter().map(JsonRef::into_owned).collect()),
+            JsonRef::Object(pairs) => {
+                JsonValue::Object(pairs.into_iter().map(|(k, v)| (k.into_owned(), v.into_owned())).collect())
+            }
+        }
+    }
+}
+
+/// Read access shared by [`JsonValue`] and [`JsonRef`], so code that only
+/// looks values up works on either.
+pub trait Node: Sized {
+    fn get(&self, key: &str) -> Option<&Self>;
+    fn as_str(&self) -> Option<&str>;
+    fn as_bool(&self) -> Option<bool>;
+    fn as_i64(&self) -> Option<i64>;
+    fn as_u64(&self) -> Option<u64>;
+    fn as_slice(&self) -> Option<&[Self]>;
+    /// See [`JsonValue::index`].
+    fn index(&self) -> Index<'_, Self>;
+}
+
+impl Node for JsonValue {
+    fn get(&self, key: &str) -> Option<&Self> {
+        JsonValue::get(self, key)
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        JsonValue::as_str(self)
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        JsonValue::as_bool(self)
+    }
+
+    fn as_i64(&self) -> Option<i64> {
+        JsonValue::as_i64(self)
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        JsonValue::as_u64(self)
+    }
+
+    fn as_slice(&self) -> Option<&[Self]> {
+        self.as_array().map(Vec::as_slice)
+    }
+
+    fn index(&self) -> Index<'_, Self> {
+        JsonValue::index(self)
+    }
+}
+
+impl Node for JsonRef<'_> {
+    fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            JsonRef::Object(pairs) => pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonRef::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonRef::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    fn as_i64(&self) -> Option<i64> {
+        match self {
+            JsonRef::Int(n) => Some(*n),
+            JsonRef::Number(n) if n.fract() == 0.0 && n.abs() < 9.2e18 => Some(*n as i64),
+            _ => None,
+        }
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        self.as_i64().and_then(|n| u64::try_from(n).ok())
+    }
+
+    fn as_slice(&self) -> Option<&[Self]> {
+        match self {
+            JsonRef::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    fn index(&self) -> Index<'_, Self> {
+        match self {
+            JsonRef::Object(pairs) => Index::new(pairs.iter().map(|(k, v)| (k.as_ref(), v))),
+            _ => Index::new(std::iter::empty()),
+        }
+    }
+}
+
+fn write_escaped(f: &mut std::fmt::Formatter<'_>, s: &str) -> std::fmt::Result {
+    f.write_str("\"")?;
+    for ch in s.chars() {
+        match ch {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+/// Serializes compactly (no whitespace), so `parse(&v.to_string())` round-trips.
+impl std::fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonValue::Null => f.write_str("null"),
+            JsonValue::Bool(b) => write!(f, "{}", b),
+            JsonValue::Int(n) => write!(f, "{}", n),
+            // A whole float keeps its `.0` so it parses back as a float.
+            JsonValue::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{:.1}", n),
+            JsonValue::Number(n) if n.is_finite() => write!(f, "{}", n),
+            JsonValue::Number(_) => f.write_str("null"),
+            JsonValue::String(s) => write_escaped(f, s),
+            JsonValue::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            JsonValue::Object(pairs) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_escaped(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+impl JsonValue {
+    /// Serializes with two-space indentation, one member or element per line.
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0);
+        out
+    }
+
+    fn write_pretty(&self, out: &mut String, depth: usize) {
+        let indent = |out: &mut String, depth: usize| out.push_str(&"  ".repeat(depth));
+        match self {
+            JsonValue::Array(items) if !items.is_empty() => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    indent(out, depth + 1);
+                    item.write_pretty(out, depth + 1);
+                    out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                indent(out, depth);
+                out.push(']');
+            }
+            JsonValue::Object(pairs) if !pairs.is_empty() => {
+                out.push_str("{\n");
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    indent(out, depth + 1);
+                    out.push_str(&format!("{}: ", JsonValue::String(key.clone())));
+                    value.write_pretty(out, depth + 1);
+                    out.push_str(if i + 1 < pairs.len() { ",\n" } else { "\n" });
+                }
+                indent(out, depth);
+                out.push('}');
+            }
+            _ => out.push_str(&self.to_string()),
+        }
+    }
+}
+
+/// How much a document may contain before parsing gives up, so a hostile or
+/// broken response fails with an error instead of exhausting the stack or
+/// memory.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Arrays and objects nested inside each other.
+    pub max_depth: usize,
+    /// Size of the input in bytes.
+    pub max_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits { max_depth: 128, max_bytes: 256 * 1024 * 1024 }
+    }
+}
+
 pub fn parse(input: &str) -> Result<JsonValue, SlkError> {
-    let mut parser = Parser::new(input);
+    parse_with(input, Limits::default())
+}
+
+pub fn parse_with(input: &str, limits: Limits) -> Result<JsonValue, SlkError> {
+    parse_borrowed_with(input, limits).map(JsonRef::into_owned)
+}
+
+/// Parses without copying strings that can be borrowed from `input`.
+pub fn parse_borrowed(input: &str) -> Result<JsonRef<'_>, SlkError> {
+    parse_borrowed_with(input, Limits::default())
+}
+
+fn parse_borrowed_with(input: &str, limits: Limits) -> Result<JsonRef<'_>, SlkError> {
+    check_size(input, limits)?;
+    let mut parser = Parser::new(input, limits.max_depth);
     let value = parser.parse_value()?;
     parser.skip_whitespace();
     if parser.pos < parser.input.len() {
@@ -50,25 +433,211 @@ pub fn parse(input: &str) -> Result<JsonValue, SlkError> {
     Ok(value)
 }
 
+fn check_size(input: &str, limits: Limits) -> Result<(), SlkError> {
+    if input.len() > limits.max_bytes {
+        return Err(SlkError::from(format!(
+            "JSON parse error: the input is {} bytes, more than the limit of {}",
+            input.len(),
+            limits.max_bytes
+        )));
+    }
+    Ok(())
+}
+
+/// Reads one array member of a JSON object an element at a time, so a big
+/// response (a full `users.list`, an export's messages) is handled one
+/// element after another instead of as a whole tree. Iterate over the
+/// elements, then call `finish` for the object's other members (`ok`,
+/// `response_metadata`).
+pub struct ArrayStream<'a> {
+    parser: Parser<'a>,
+    /// The object's other members read so far.
+    rest: Vec<(String, JsonValue)>,
+    /// Whether the object has the array.
+    found: bool,
+    state: StreamState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StreamState {
+    /// Inside the array; `first` until an element has been read.
+    Elements { first: bool },
+    /// Past the array, or the object had no such array.
+    After,
+    /// The object has been read to its end.
+    Done,
+    /// Reading an element failed; the stream ends there.
+    Failed,
+}
+
+/// Starts reading `input`, a JSON object, up to the start of the array in its
+/// `key` member. The members before it are parsed as usual.
+pub fn stream_array<'a>(input: &'a str, key: &str) -> Result<ArrayStream<'a>, SlkError> {
+    let limits = Limits::default();
+    check_size(input, limits)?;
+    let mut stream = ArrayStream {
+        parser: Parser::new(input, limits.max_depth),
+        rest: Vec::new(),
+        found: false,
+        state: StreamState::After,
+    };
+    let parser = &mut stream.parser;
+    parser.skip_whitespace();
+    parser.expect(b'{')?;
+    parser.descend()?;
+    parser.skip_whitespace();
+    if parser.peek_matches(b'}') {
+        parser.pos += 1;
+        stream.state = StreamState::Done;
+        return Ok(stream);
+    }
+    loop {
+        parser.skip_whitespace();
+        let name = parser.parse_string()?.into_owned();
+        parser.skip_whitespace();
+        parser.expect(b':')?;
+        parser.skip_whitespace();
+        if name == key && parser.peek_matches(b'[') {
+            parser.pos += 1;
+            parser.descend()?;
+            stream.found = true;
+            stream.state = StreamState::Elements { first: true };
+            return Ok(stream);
+        }
+        let value = parser.parse_value()?.into_owned();
+        stream.rest.push((name, value));
+        parser.skip_whitespace();
+        match parser.advance()? {
+            b',' => continue,
+            b'}' => {
+                stream.state = StreamState::Done;
+                return Ok(stream);
+            }
+            _ => return Err(parser.error("expected ',' or '}' in object")),
+        }
+    }
+}
+
+impl ArrayStream<'_> {
+    /// Whether the object has the array: without it, there are no elements.
+    pub fn found(&self) -> bool {
+        self.found
+    }
+
+    fn next_element(&mut self) -> Result<Option<JsonValue>, SlkError> {
+        let StreamState::Elements { first } = self.state else {
+            return Ok(None);
+        };
+        let parser = &mut self.parser;
+        parser.skip_whitespace();
+        let end = if first {
+            parser.peek_matches(b']').then(|| parser.pos += 1).is_some()
+        } else {
+            match parser.advance()? {
+                b']' => true,
+                b',' => false,
+                _ => return Err(parser.error("expected ',' or ']' in array")),
+            }
+        };
+        if end {
+            parser.depth -= 1;
+            self.state = StreamState::After;
+            return Ok(None);
+        }
+        let value = parser.parse_value()?.into_owned();
+        self.state = StreamState::Elements { first: false };
+        Ok(Some(value))
+    }
+
+    /// Reads the rest of the document and returns the object without the
+    /// streamed array. Elements that weren't read are skipped.
+    pub fn finish(mut self) -> Result<JsonValue, SlkError> {
+        for element in self.by_ref() {
+            element?;
+        }
+        let parser = &mut self.parser;
+        while self.state == StreamState::After {
+            parser.skip_whitespace();
+            match parser.advance()? {
+                b'}' => self.state = StreamState::Done,
+                b',' => {
+                    parser.skip_whitespace();
+                    let name = parser.parse_string()?.into_owned();
+                    parser.skip_whitespace();
+                    parser.expect(b':')?;
+                    let value = parser.parse_value()?.into_owned();
+                    self.rest.push((name, value));
+                }
+                _ => return Err(parser.error("expected ',' or '}' in object")),
+            }
+        }
+        parser.skip_whitespace();
+        if parser.pos < parser.input.len() {
+            return Err(parser.error("unexpected trailing content"));
+        }
+        Ok(JsonValue::Object(self.rest))
+    }
+}
+
+impl Iterator for ArrayStream<'_> {
+    type Item = Result<JsonValue, SlkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_element() {
+            Ok(value) => value.map(Ok),
+            Err(e) => {
+                self.state = StreamState::Failed;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
 struct Parser<'a> {
+    text: &'a str,
     input: &'a [u8],
     pos: usize,
+    /// Arrays and objects left open at `pos`.
+    depth: usize,
+    max_depth: usize,
 }
 
 impl<'a> Parser<'a> {
-    fn new(input: &'a str) -> Self {
+    fn new(input: &'a str, max_depth: usize) -> Self {
         Parser {
+            text: input,
             input: input.as_bytes(),
             pos: 0,
+            depth: 0,
+            max_depth,
+        }
+    }
+
+    /// Enters an array or object, failing once they nest too deeply.
+    fn descend(&mut self) -> Result<(), SlkError> {
+        if self.depth >= self.max_depth {
+            return Err(self.error(&format!("nested more than {} levels deep", self.max_depth)));
         }
+        self.depth += 1;
+        Ok(())
     }
 
-    fn parse_value(&mut self) -> Result<JsonValue, SlkError> {
+    fn parse_value(&mut self) -> Result<JsonRef<'a>, SlkError> {
         self.skip_whitespace();
         match self.peek()? {
-            b'"' => self.parse_string().map(JsonValue::String),
-            b'{' => self.parse_object(),
-            b'[' => self.parse_array(),
+            b'"' => self.parse_string().map(JsonRef::String),
+            b'{' => {
+                self.descend()?;
+                let value = self.parse_object()?;
+                self.depth -= 1;
+                Ok(value)
+            }
+            b'[' => {
+                self.descend()?;
+                let value = self.parse_array()?;
+                self.depth -= 1;
+                Ok(value)
+            }
             b't' | b'f' => self.parse_bool(),
             b'n' => self.parse_null(),
             b'-' | b'0'..=b'9' => self.parse_number(),
@@ -76,13 +645,23 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_string(&mut self) -> Result<String, SlkError> {
+    fn parse_string(&mut self) -> Result<Cow<'a, str>, SlkError> {
         self.expect(b'"')?;
-        let mut s = String::new();
+        // Most strings have no escapes and can be borrowed as they are.
+        let start = self.pos;
+        while self.pos < self.input.len() && !matches!(self.input[self.pos], b'"' | b'\\') {
+            self.pos += 1;
+        }
+        if self.peek_matches(b'"') {
+            self.pos += 1;
+            // Quotes are ASCII, so they never split a UTF-8 sequence.
+            return Ok(Cow::Borrowed(&self.text[start..self.pos - 1]));
+        }
+        let mut s = self.text[start..self.pos].to_string();
         loop {
             let ch = self.advance()?;
             match ch {
-                b'"' => return Ok(s),
+                b'"' => return Ok(Cow::Owned(s)),
                 b'\\' => {
                     let escaped = self.advance()?;
                     match escaped {
@@ -92,6 +671,8 @@ impl<'a> Parser<'a> {
                         b'n' => s.push('\n'),
                         b'r' => s.push('\r'),
                         b't' => s.push('\t'),
+                        b'b' => s.push('\u{8}'),
+                        b'f' => s.push('\u{c}'),
                         b'u' => {
                             let cp = self.parse_unicode_escape()?;
                             if (0xD800..=0xDBFF).contains(&cp) {
@@ -112,7 +693,18 @@ impl<'a> Parser<'a> {
                         _ => return Err(self.error(&format!("invalid escape: \\{}", escaped as char))),
                     }
                 }
-                _ => s.push(ch as char),
+                _ if ch.is_ascii() => s.push(ch as char),
+                _ => {
+                    // Copy a multi-byte UTF-8 run verbatim. Quotes and backslashes
+                    // are ASCII, so they never occur inside such a sequence.
+                    let start = self.pos - 1;
+                    while self.pos < self.input.len() && !self.input[self.pos].is_ascii() {
+                        self.pos += 1;
+                    }
+                    let run = std::str::from_utf8(&self.input[start..self.pos])
+                        .map_err(|_| self.error("invalid UTF-8 in string"))?;
+                    s.push_str(run);
+                }
             }
         }
     }
@@ -132,17 +724,20 @@ impl<'a> Parser<'a> {
         Ok(val)
     }
 
-    fn parse_number(&mut self) -> Result<JsonValue, SlkError> {
+    fn parse_number(&mut self) -> Result<JsonRef<'a>, SlkError> {
         let start = self.pos;
         if self.peek_matches(b'-') {
             self.pos += 1;
         }
         self.consume_digits()?;
+        let mut integral = true;
         if self.peek_matches(b'.') {
+            integral = false;
             self.pos += 1;
             self.consume_digits()?;
         }
         if self.pos < self.input.len() && (self.input[self.pos] == b'e' || self.input[self.pos] == b'E') {
+            integral = false;
             self.pos += 1;
             if self.pos < self.input.len() && (self.input[self.pos] == b'+' || self.input[self.pos] == b'-') {
                 self.pos += 1;
@@ -151,10 +746,14 @@ impl<'a> Parser<'a> {
         }
         let num_str = std::str::from_utf8(&self.input[start..self.pos])
             .map_err(|_| self.error("invalid UTF-8 in number"))?;
+        // Integers too big for an `i64` fall back to a float.
+        if integral && let Ok(n) = num_str.parse::<i64>() {
+            return Ok(JsonRef::Int(n));
+        }
         let n: f64 = num_str
             .parse()
             .map_err(|_| self.error(&format!("invalid number: {}", num_str)))?;
-        Ok(JsonValue::Number(n))
+        Ok(JsonRef::Number(n))
     }
 
     fn consume_digits(&mut self) -> Result<(), SlkError> {
@@ -167,13 +766,13 @@ impl<'a> Parser<'a> {
         Ok(())
     }
 
-    fn parse_object(&mut self) -> Result<JsonValue, SlkError> {
+    fn parse_object(&mut self) -> Result<JsonRef<'a>, SlkError> {
         self.expect(b'{')?;
         self.skip_whitespace();
         let mut pairs = Vec::new();
         if self.peek_matches(b'}') {
             self.pos += 1;
-            return Ok(JsonValue::Object(pairs));
+            return Ok(JsonRef::Object(pairs));
         }
         loop {
             self.skip_whitespace();
@@ -185,20 +784,20 @@ impl<'a> Parser<'a> {
             self.skip_whitespace();
             let ch = self.advance()?;
             match ch {
-                b'}' => return Ok(JsonValue::Object(pairs)),
+                b'}' => return Ok(JsonRef::Object(pairs)),
                 b',' => continue,
                 _ => return Err(self.error("expected ',' or '}' in object")),
             }
         }
     }
 
-    fn parse_array(&mut self) -> Result<JsonValue, SlkError> {
+    fn parse_array(&mut self) -> Result<JsonRef<'a>, SlkError> {
         self.expect(b'[')?;
         self.skip_whitespace();
         let mut items = Vec::new();
         if self.peek_matches(b']') {
             self.pos += 1;
-            return Ok(JsonValue::Array(items));
+            return Ok(JsonRef::Array(items));
         }
         loop {
             let value = self.parse_value()?;
@@ -206,29 +805,29 @@ impl<'a> Parser<'a> {
             self.skip_whitespace();
             let ch = self.advance()?;
             match ch {
-                b']' => return Ok(JsonValue::Array(items)),
+                b']' => return Ok(JsonRef::Array(items)),
                 b',' => continue,
                 _ => return Err(self.error("expected ',' or ']' in array")),
             }
         }
     }
 
-    fn parse_bool(&mut self) -> Result<JsonValue, SlkError> {
+    fn parse_bool(&mut self) -> Result<JsonRef<'a>, SlkError> {
         if self.starts_with(b"true") {
             self.pos += 4;
-            Ok(JsonValue::Bool(true))
+            Ok(JsonRef::Bool(true))
         } else if self.starts_with(b"false") {
             self.pos += 5;
-            Ok(JsonValue::Bool(false))
+            Ok(JsonRef::Bool(false))
         } else {
             Err(self.error("expected 'true' or 'false'"))
         }
     }
 
-    fn parse_null(&mut self) -> Result<JsonValue, SlkError> {
+    fn parse_null(&mut self) -> Result<JsonRef<'a>, SlkError> {
         if self.starts_with(b"null") {
             self.pos += 4;
-            Ok(JsonValue::Null)
+            Ok(JsonRef::Null)
         } else {
             Err(self.error("expected 'null'"))
         }
@@ -274,9 +873,7 @@ impl<'a> Parser<'a> {
     }
 
     fn error(&self, msg: &str) -> SlkError {
-        SlkError {
-            message: format!("JSON parse error at position {}: {}", self.pos, msg),
-        }
+        SlkError::new(ErrorKind::Parse, format!("JSON parse error at position {}: {}", self.pos, msg))
     }
 }
 
@@ -333,12 +930,27 @@ mod tests {
 
     #[test]
     fn test_parse_number_integer() {
-        assert_eq!(parse("42").unwrap(), JsonValue::Number(42.0));
-        assert_eq!(parse("-7").unwrap(), JsonValue::Number(-7.0));
-        assert_eq!(parse("0").unwrap(), JsonValue::Number(0.0));
+        assert_eq!(parse("42").unwrap(), JsonValue::Int(42));
+        assert_eq!(parse("-7").unwrap(), JsonValue::Int(-7));
+        assert_eq!(parse("0").unwrap(), JsonValue::Int(0));
+        assert_eq!(parse("2.0").unwrap(), JsonValue::Number(2.0));
     }
 
     #[test]
+    fn test_parse_large_integer() {
+        // 2^53 + 1 isn't representable as an f64.
+        let val = parse("9007199254740993").unwrap();
+        assert_eq!(val, JsonValue::Int(9007199254740993));
+        assert_eq!(val.to_string(), "9007199254740993");
+        assert_eq!(val.as_u64(), Some(9007199254740993));
+        assert_eq!(parse("-5").unwrap().as_u64(), None);
+        assert_eq!(parse("1e3").unwrap().as_i64(), Some(1000));
+        assert_eq!(parse("1.5").unwrap().as_i64(), None);
+        assert_eq!(parse("99999999999999999999").unwrap(), JsonValue::Number(1e20));
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
     fn test_parse_number_decimal() {
         assert_eq!(parse("3.14").unwrap(), JsonValue::Number(3.14));
         assert_eq!(parse("-0.5").unwrap(), JsonValue::Number(-0.5));
@@ -374,9 +986,9 @@ mod tests {
         assert_eq!(
             parse("[1, 2, 3]").unwrap(),
             JsonValue::Array(vec![
-                JsonValue::Number(1.0),
-                JsonValue::Number(2.0),
-                JsonValue::Number(3.0),
+                JsonValue::Int(1),
+                JsonValue::Int(2),
+                JsonValue::Int(3),
             ])
         );
     }
@@ -439,7 +1051,7 @@ mod tests {
         let val = parse("  { \"a\" : 1 }  ").unwrap();
         assert_eq!(
             val,
-            JsonValue::Object(vec![("a".to_string(), JsonValue::Number(1.0))])
+            JsonValue::Object(vec![("a".to_string(), JsonValue::Int(1))])
         );
     }
 
@@ -449,9 +1061,121 @@ mod tests {
         assert_eq!(val.get("b"), None);
     }
 
+    #[test]
+    fn test_builder() {
+        let mut val = JsonValue::object([("channel", "C1".into()), ("text", "hi".into())]);
+        val.set("text", "hello").set("unfurl_links", false).set("count", 3u64);
+        val.set("thread_ts", None::<&str>);
+        let mut blocks = JsonValue::Null;
+        blocks.push(JsonValue::object([("type", "divider".into())]));
+        val.set("blocks", blocks);
+        assert_eq!(
+            val.to_string(),
+            r#"{"channel":"C1","text":"hello","unfurl_links":false,"count":3,"thread_ts":null,"blocks":[{"type":"divider"}]}"#
+        );
+        assert_eq!(JsonValue::from(vec!["a", "b"]).to_string(), r#"["a","b"]"#);
+        assert_eq!(JsonValue::from(u64::MAX), JsonValue::Number(u64::MAX as f64));
+    }
+
+    #[test]
+    fn test_index() {
+        let val = parse(r#"{"a": 1, "b": {"c": true}, "a": 2}"#).unwrap();
+        let index = val.index();
+        assert_eq!(index.get("a"), Some(&JsonValue::Int(1)));
+        assert_eq!(index.get("b").and_then(|b| b.get("c")), Some(&JsonValue::Bool(true)));
+        assert_eq!(index.get("z"), None);
+        assert_eq!(parse("[1]").unwrap().index().get("0"), None);
+
+        let input = r#"{"a": "x", "a": "y"}"#;
+        let borrowed = parse_borrowed(input).unwrap();
+        assert_eq!(Node::index(&borrowed).get("a").and_then(|a| a.as_str()), Some("x"));
+    }
+
     #[test]
     fn test_get_returns_none_for_non_object() {
         let val = parse("42").unwrap();
         assert_eq!(val.get("key"), None);
     }
+
+    #[test]
+    fn test_to_string_compact() {
+        let val = parse(r#"{ "ok": true, "n": [1, 2.5, -3], "s": "a\"b\\c\n", "x": null }"#).unwrap();
+        assert_eq!(
+            val.to_string(),
+            r#"{"ok":true,"n":[1,2.5,-3],"s":"a\"b\\c\n","x":null}"#
+        );
+    }
+
+    #[test]
+    fn test_pretty() {
+        let val = parse(r#"{"a":1,"b":[true,{}],"c":[]}"#).unwrap();
+        assert_eq!(
+            val.pretty(),
+            "{\n  \"a\": 1,\n  \"b\": [\n    true,\n    {}\n  ],\n  \"c\": []\n}"
+        );
+        assert_eq!(parse(&val.pretty()).unwrap(), val);
+    }
+
+    #[test]
+    fn test_to_string_round_trips() {
+        let input = r#"{"text":"caf\u00e9 \u0001","nested":{"a":[]},"e":1e20,"f":2.0,"n":-3}"#;
+        let val = parse(input).unwrap();
+        assert_eq!(parse(&val.to_string()).unwrap(), val);
+    }
+
+    #[test]
+    fn test_limits() {
+        let deep = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        let err = parse(&deep).unwrap_err();
+        assert!(err.message.contains("nested more than 128 levels deep"), "{}", err.message);
+        let limits = Limits { max_depth: 2, ..Limits::default() };
+        assert!(parse_with(r#"{"a": [1]}"#, limits).is_ok());
+        assert!(parse_with(r#"{"a": [[1]]}"#, limits).is_err());
+        let limits = Limits { max_bytes: 8, ..Limits::default() };
+        assert!(parse_with("[1, 2, 3]", limits).unwrap_err().message.contains("more than the limit of 8"));
+        assert!(parse_with("[1, 2]", limits).is_ok());
+    }
+
+    #[test]
+    fn test_stream_array() {
+        let input = r#"{"ok": true, "members": [{"id": "U1"}, {"id": "U2"}], "response_metadata": {"next_cursor": "x"}}"#;
+        let mut members = stream_array(input, "members").unwrap();
+        assert_eq!(members.next().unwrap().unwrap().get("id").unwrap().as_str(), Some("U1"));
+        assert!(members.found());
+        // The unread element is skipped.
+        let rest = members.finish().unwrap();
+        assert_eq!(rest.to_string(), r#"{"ok":true,"response_metadata":{"next_cursor":"x"}}"#);
+
+        let members = stream_array(r#"{"ok": true, "members": []}"#, "members").unwrap();
+        assert_eq!(members.count(), 0);
+        let mut members = stream_array(r#"{"ok": false, "error": "invalid_auth"}"#, "members").unwrap();
+        assert!(members.next().is_none());
+        assert!(!members.found());
+        assert_eq!(members.finish().unwrap().get("error").unwrap().as_str(), Some("invalid_auth"));
+
+        let mut members = stream_array(r#"{"members": [1 2]}"#, "members").unwrap();
+        assert_eq!(members.next().unwrap().unwrap(), JsonValue::Int(1));
+        assert!(members.next().unwrap().is_err());
+        assert!(members.next().is_none());
+        assert!(stream_array(r#"{"members": [1]} x"#, "members").unwrap().finish().is_err());
+        assert!(stream_array("[1]", "members").is_err());
+    }
+
+    #[test]
+    fn test_parse_borrowed() {
+        let input = r#"{"text": "café", "escaped": "a\"b", "n": [1, null]}"#;
+        let value = parse_borrowed(input).unwrap();
+        assert!(matches!(value.get("text"), Some(JsonRef::String(Cow::Borrowed("café")))));
+        assert!(matches!(value.get("escaped"), Some(JsonRef::String(Cow::Owned(s))) if s == "a\"b"));
+        assert_eq!(value.get("n").and_then(|v| v.as_slice()).map(<[_]>::len), Some(2));
+        assert_eq!(value.into_owned(), parse(input).unwrap());
+    }
+
+    #[test]
+    fn test_parse_non_ascii_string() {
+        assert_eq!(
+            parse(r#""こんにちは, café 👍""#).unwrap(),
+            JsonValue::String("こんにちは, café 👍".to_string())
+        );
+    }
 }
@@ -0,0 +1,266 @@
+use crate::error::SlkError;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Service name the secret is filed under in the keyring.
+const SERVICE: &str = "slk";
+
+/// The command-line front end of the platform keyring, if one is installed:
+/// `secret-tool` for the Secret Service on Linux, `security` for the macOS
+/// Keychain, and Windows PowerShell for the Windows Credential Manager.
+fn tool() -> Option<&'static str> {
+    let name = if cfg!(target_os = "macos") {
+        "security"
+    } else if cfg!(unix) {
+        "secret-tool"
+    } else if cfg!(windows) {
+        POWERSHELL
+    } else {
+        return None;
+    };
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .any(|dir| is_executable(&dir.join(name)))
+        .then_some(name)
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// Whether a supported keyring is available on this machine.
+pub fn is_available() -> bool {
+    tool().is_some()
+}
+
+fn unavailable() -> SlkError {
+    SlkError::from(if cfg!(unix) {
+        "no keyring available: install secret-tool (libsecret) or set credential_store to \"file\""
+    } else if cfg!(windows) {
+        "no keyring available: powershell.exe is not on PATH; set credential_store to \"file\""
+    } else {
+        "the keyring backend is not supported on this platform; set credential_store to \"file\""
+    })
+}
+
+/// Windows has no keyring command that can read a secret back, so the
+/// Credential Manager is reached through the `PasswordVault` API from the
+/// Windows PowerShell that ships with the system.
+const POWERSHELL: &str = "powershell.exe";
+
+/// A PowerShell script that loads the vault as `$vault` and then runs `body`.
+/// Secrets cross the pipe as base64, clear of the console code page.
+fn vault_script(body: &str) -> String {
+    format!(
+        "$ErrorActionPreference = 'Stop'; \
+         [void][Windows.Security.Credentials.PasswordVault, \
+         Windows.Security.Credentials, ContentType = WindowsRuntime]; \
+         $vault = New-Object Windows.Security.Credentials.PasswordVault; {}",
+        body
+    )
+}
+
+/// The exit code of a vault script that finds no entry. `Retrieve` throws
+/// `ERROR_NOT_FOUND` (0x80070490), wrapped in PowerShell's own exception;
+/// anything else it throws exits with 1.
+const VAULT_NOT_FOUND: i32 = 3;
+
+/// `s` as a single-quoted PowerShell string.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn run_vault(body: &str, stdin: Option<&str>) -> Result<std::process::Output, SlkError> {
+    let script = vault_script(body);
+    run(POWERSHELL, &["-NoProfile", "-NonInteractive", "-Command", &script], stdin)
+}
+
+fn run(tool: &str, args: &[&str], stdin: Option<&str>) -> Result<std::process::Output, SlkError> {
+    let mut child = Command::new(tool)
+        .args(args)
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| SlkError::from(format!("failed to run {}: {}", tool, e)))?;
+    if let Some(input) = stdin
+        && let Some(mut pipe) = child.stdin.take()
+    {
+        pipe.write_all(input.as_bytes())
+            .map_err(|e| SlkError::from(format!("failed to write to {}: {}", tool, e)))?;
+    }
+    child
+        .wait_with_output()
+        .map_err(|e| SlkError::from(format!("failed to run {}: {}", tool, e)))
+}
+
+fn failure(tool: &str, action: &str, output: &std::process::Output) -> SlkError {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    SlkError::from(format!(
+        "{} failed to {} the keyring entry: {}",
+        tool,
+        action,
+        stderr.trim()
+    ))
+}
+
+/// Reads the secret stored for `account`, or `None` if there is none.
+pub fn get(account: &str) -> Result<Option<String>, SlkError> {
+    let tool = tool().ok_or_else(unavailable)?;
+    let output = match tool {
+        "security" => run(tool, &["find-generic-password", "-s", SERVICE, "-a", account, "-w"], None)?,
+        POWERSHELL => run_vault(
+            &format!(
+                "try {{ $entry = $vault.Retrieve({}, {}) }} catch {{ \
+                 for ($e = $_.Exception; $e; $e = $e.InnerException) {{ \
+                 if ($e.HResult -eq -2147023728) {{ exit {} }} }}; throw }}; \
+                 $entry.RetrievePassword(); \
+                 $bytes = [Text.Encoding]::UTF8.GetBytes($entry.Password); \
+                 [Console]::Out.Write([Convert]::ToBase64String($bytes))",
+                quote(SERVICE),
+                quote(account),
+                VAULT_NOT_FOUND
+            ),
+            None,
+        )?,
+        _ => run(tool, &["lookup", "service", SERVICE, "account", account], None)?,
+    };
+    if !output.status.success() {
+        return if is_missing(tool, &output) { Ok(None) } else { Err(failure(tool, "read", &output)) };
+    }
+    let secret = String::from_utf8_lossy(&output.stdout).trim_end().to_string();
+    if secret.is_empty() {
+        return Ok(None);
+    }
+    if tool == POWERSHELL {
+        let decoded = crate::base64::decode(&secret).ok().and_then(|bytes| String::from_utf8(bytes).ok());
+        return decoded
+            .map(Some)
+            .ok_or_else(|| SlkError::from("the Windows Credential Manager entry is not valid base64 UTF-8"));
+    }
+    Ok(Some(secret))
+}
+
+/// Whether a failed lookup only found no entry, rather than failing to reach
+/// the keyring: `security` exits with `errSecItemNotFound` (44), the vault
+/// script with [`VAULT_NOT_FOUND`], and `secret-tool` with 1 and nothing on
+/// stderr.
+fn is_missing(tool: &str, output: &std::process::Output) -> bool {
+    match tool {
+        "security" => output.status.code() == Some(44),
+        POWERSHELL => output.status.code() == Some(VAULT_NOT_FOUND),
+        _ => output.status.code() == Some(1) && output.stderr.trim_ascii().is_empty(),
+    }
+}
+
+/// Stores `secret` for `account`, replacing any existing entry. The secret is
+/// passed on stdin so it never appears in the process list.
+pub fn set(account: &str, secret: &str) -> Result<(), SlkError> {
+    let tool = tool().ok_or_else(unavailable)?;
+    let output = if tool == "security" {
+        let command = format!(
+            "add-generic-password -U -s {} -a {} -X {}\n",
+            SERVICE,
+            account,
+            hex_encode(secret.as_bytes())
+        );
+        run(tool, &["-i"], Some(&command))?
+    } else if tool == POWERSHELL {
+        let body = format!(
+            "$encoded = [Console]::In.ReadToEnd().Trim(); \
+             $secret = [Text.Encoding]::UTF8.GetString([Convert]::FromBase64String($encoded)); \
+             $vault.Add((New-Object Windows.Security.Credentials.PasswordCredential({}, {}, $secret)))",
+            quote(SERVICE),
+            quote(account)
+        );
+        run_vault(&body, Some(&crate::base64::encode(secret.as_bytes())))?
+    } else {
+        let label = format!("{} {}", SERVICE, account);
+        run(
+            tool,
+            &["store", "--label", &label, "service", SERVICE, "account", account],
+            Some(secret),
+        )?
+    };
+    if !output.status.success() {
+        return Err(failure(tool, "store", &output));
+    }
+    Ok(())
+}
+
+/// Deletes the entry for `account`, reporting whether one existed.
+pub fn delete(account: &str) -> Result<bool, SlkError> {
+    if get(account)?.is_none() {
+        return Ok(false);
+    }
+    let tool = tool().ok_or_else(unavailable)?;
+    let output = if tool == "security" {
+        run(tool, &["delete-generic-password", "-s", SERVICE, "-a", account], None)?
+    } else if tool == POWERSHELL {
+        run_vault(&format!("$vault.Remove($vault.Retrieve({}, {}))", quote(SERVICE), quote(account)), None)?
+    } else {
+        run(tool, &["clear", "service", SERVICE, "account", account], None)?
+    };
+    if !output.status.success() {
+        return Err(failure(tool, "delete", &output));
+    }
+    Ok(true)
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_encode() {
+        assert_eq!(hex_encode(b"{\"token\":\"x\"}"), "7b22746f6b656e223a2278227d");
+        assert_eq!(hex_encode(b""), "");
+    }
+
+    #[test]
+    fn test_vault_script_quotes_names() {
+        assert_eq!(quote("it's"), "'it''s'");
+        let script = vault_script("$vault.Remove($vault.Retrieve('slk', 'credentials'))");
+        assert!(script.starts_with("$ErrorActionPreference = 'Stop'; [void]"), "{}", script);
+        assert!(script.ends_with("PasswordVault; $vault.Remove($vault.Retrieve('slk', 'credentials'))"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_missing() {
+        use std::os::unix::process::ExitStatusExt;
+        let output = |code: i32, stderr: &str| std::process::Output {
+            status: std::process::ExitStatus::from_raw(code << 8),
+            stdout: Vec::new(),
+            stderr: stderr.as_bytes().to_vec(),
+        };
+        assert!(is_missing("security", &output(44, "The specified item could not be found in the keychain.")));
+        assert!(!is_missing("security", &output(51, "User interaction is not allowed.")));
+        assert!(is_missing(POWERSHELL, &output(VAULT_NOT_FOUND, "")));
+        assert!(!is_missing(POWERSHELL, &output(1, "Class not registered")));
+        assert!(is_missing("secret-tool", &output(1, "")));
+        assert!(!is_missing("secret-tool", &output(1, "Cannot autolaunch D-Bus without X11 $DISPLAY")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_executable() {
+        assert!(is_executable(Path::new("/bin/sh")));
+        assert!(!is_executable(Path::new("/etc/passwd")));
+        assert!(!is_executable(Path::new("/nonexistent/slk")));
+    }
+}
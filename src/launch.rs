@@ -0,0 +1,70 @@
+//! Opening links with the desktop's handler for them: `open` on macOS,
+//! the URL protocol handler on Windows and `xdg-open` elsewhere.
+
+use crate::error::SlkError;
+use std::process::{Command, Stdio};
+
+/// The program and arguments that open `url` on `os` (as in
+/// `std::env::consts::OS`). `url` stays a single argument that no shell parses.
+fn command(os: &str, url: &str) -> (&'static str, Vec<String>) {
+    match os {
+        "macos" => ("open", vec![url.to_string()]),
+        // Not `cmd /C start`: cmd.exe would split the URL at `&`, `|` and `^`.
+        "windows" => ("rundll32", vec!["url.dll,FileProtocolHandler".to_string(), url.to_string()]),
+        _ => ("xdg-open", vec![url.to_string()]),
+    }
+}
+
+/// Whether an application is registered for `scheme` links (`slack`). Only
+/// Linux can tell without opening one; elsewhere this is `true` and opening
+/// the link fails instead.
+pub fn has_handler(scheme: &str) -> bool {
+    if cfg!(target_os = "macos") || cfg!(windows) {
+        return true;
+    }
+    let output = Command::new("xdg-mime")
+        .args(["query", "default", &format!("x-scheme-handler/{}", scheme)])
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(output) => output.status.success() && !String::from_utf8_lossy(&output.stdout).trim().is_empty(),
+        // Without xdg-mime, let xdg-open decide.
+        Err(_) => true,
+    }
+}
+
+/// Opens `url` with the application registered for it. `xdg-open` may not
+/// return until a browser it started exits, so it isn't waited for.
+pub fn open(url: &str) -> Result<(), SlkError> {
+    let (program, args) = command(std::env::consts::OS, url);
+    let mut child = Command::new(program)
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| SlkError::from(format!("could not run {}: {}", program, e)))?;
+    if program == "xdg-open" {
+        return Ok(());
+    }
+    let status = child.wait().map_err(|e| SlkError::from(format!("{} failed: {}", program, e)))?;
+    if !status.success() {
+        return Err(SlkError::from(format!("{} could not open {}", program, url)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_passes_url_as_one_argument() {
+        let url = "slack://channel?team=T1&id=C1|x^y";
+        assert_eq!(command("macos", url), ("open", vec![url.to_string()]));
+        assert_eq!(command("linux", url), ("xdg-open", vec![url.to_string()]));
+        let (program, args) = command("windows", url);
+        assert_eq!(program, "rundll32");
+        assert_eq!(args, ["url.dll,FileProtocolHandler", url]);
+    }
+}
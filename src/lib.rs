@@ -0,0 +1,79 @@
+//! slk reads and posts Slack messages from the terminal. The library exposes
+//! the pieces other tools can build on:
+//!
+//! - [`client`]: read channels and threads and post messages with a token
+//! - [`message`]: the messages, users and conversations in API responses
+//! - [`response`]: typed responses of the Web API methods
+//! - [`json`]: the JSON parser and serializer used for the API
+//! - [`url`]: Slack message links
+//! - [`config`]: the config file and stored credentials
+//!
+//! The other modules implement the `slk` command itself and are not meant
+//! to be used directly.
+
+pub mod client;
+pub mod config;
+pub mod error;
+pub mod json;
+pub mod message;
+pub mod response;
+pub mod url;
+
+#[doc(hidden)]
+pub mod app;
+#[doc(hidden)]
+pub mod archive;
+#[doc(hidden)]
+pub mod base64;
+#[doc(hidden)]
+pub mod cache;
+#[doc(hidden)]
+pub mod cli;
+#[doc(hidden)]
+pub mod clipboard;
+#[doc(hidden)]
+pub mod completions;
+#[doc(hidden)]
+pub mod compose;
+#[doc(hidden)]
+pub mod hook;
+#[doc(hidden)]
+pub mod http;
+#[doc(hidden)]
+pub mod interrupt;
+#[doc(hidden)]
+pub mod keyring;
+#[doc(hidden)]
+pub mod launch;
+#[doc(hidden)]
+pub mod log;
+#[doc(hidden)]
+pub mod notify;
+#[doc(hidden)]
+pub mod oauth;
+#[doc(hidden)]
+pub mod query;
+#[doc(hidden)]
+pub mod report;
+#[doc(hidden)]
+pub mod resolve;
+#[doc(hidden)]
+pub mod rtm;
+#[doc(hidden)]
+pub mod search;
+#[doc(hidden)]
+pub mod slack_api;
+#[doc(hidden)]
+pub mod socket_mode;
+#[doc(hidden)]
+pub mod stats;
+#[doc(hidden)]
+pub mod task;
+#[doc(hidden)]
+pub mod toml;
+#[doc(hidden)]
+pub mod trace;
+#[doc(hidden)]
+pub mod websocket;
+#[doc(hidden)]
+pub mod wrap;
@@ -0,0 +1,70 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How much goes to stderr. Warnings are always shown; `-v` adds progress
+/// and `-vv` (or `--trace`) adds debugging details such as each API request.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+    Warn = 0,
+    Info = 1,
+    Debug = 2,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Level::Warn as u8);
+
+/// Sets the level from the number of `-v` flags given.
+pub fn set_verbosity(count: usize) {
+    VERBOSITY.store(count.min(Level::Debug as usize) as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= VERBOSITY.load(Ordering::Relaxed)
+}
+
+pub fn write(level: Level, args: fmt::Arguments<'_>) {
+    if !enabled(level) {
+        return;
+    }
+    match level {
+        Level::Warn => eprintln!("warning: {}", args),
+        Level::Info | Level::Debug => eprintln!("{}", args),
+    }
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Warn, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Info, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Debug, format_args!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbosity_levels() {
+        set_verbosity(1);
+        assert!(enabled(Level::Warn));
+        assert!(enabled(Level::Info));
+        assert!(!enabled(Level::Debug));
+        set_verbosity(5);
+        assert!(enabled(Level::Debug));
+        set_verbosity(0);
+        assert!(!enabled(Level::Info));
+    }
+}
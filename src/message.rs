@@ -1,14 +1,103 @@
-use crate::error::SlkError;
-use crate::json::JsonValue;
+//! Slack messages, users and conversations, extracted from API responses,
+//! and the formatting of Slack timestamps.
 
-#[derive(Debug, PartialEq)]
+use crate::error::{ErrorKind, SlkError};
+use crate::json::{self, Node};
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct SlackMessage {
     pub user: String,
     pub text: String,
     pub ts: String,
+    /// The thread the message starts or is a reply in, if any.
+    pub thread_ts: Option<String>,
+    /// How many replies there are, on a thread's parent.
+    pub reply_count: u64,
+    /// When the last reply was posted, on a thread's parent.
+    pub latest_reply: Option<String>,
+    /// What kind of message it is when not an ordinary one
+    /// (`channel_join`, `bot_message`, ...).
+    pub subtype: Option<String>,
+    /// The text of the thread's parent, on a reply also sent to the channel.
+    pub root_text: Option<String>,
+    pub reactions: Vec<Reaction>,
+}
+
+/// An emoji reaction on a message.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Reaction {
+    /// The emoji's name, without colons (`tada`).
+    pub name: String,
+    pub count: u64,
+    /// Who reacted; Slack may list fewer people than `count`.
+    pub users: Vec<String>,
+}
+
+impl SlackMessage {
+    /// Whether Slack posted the message about the conversation itself:
+    /// someone joining or leaving, a topic, purpose or name change, a pin.
+    pub fn is_system(&self) -> bool {
+        self.subtype.as_deref().is_some_and(|subtype| {
+            subtype.starts_with("channel_")
+                || subtype.starts_with("group_")
+                || ["pinned_item", "unpinned_item", "bot_add", "bot_remove", "reminder_add"].contains(&subtype)
+        })
+    }
+
+    /// Whether the message is a reply that was also sent to the channel.
+    pub fn is_broadcast(&self) -> bool {
+        self.subtype.as_deref() == Some("thread_broadcast")
+    }
+
+    /// Whether the message is a reply in a thread that wasn't also sent to
+    /// the channel.
+    pub fn is_thread_reply(&self) -> bool {
+        self.thread_ts.as_ref().is_some_and(|thread_ts| *thread_ts != self.ts) && !self.is_broadcast()
+    }
+
+    /// Whether the message starts a thread that has replies.
+    pub fn has_replies(&self) -> bool {
+        self.reply_count > 0 && self.thread_ts.as_deref().is_none_or(|thread_ts| thread_ts == self.ts)
+    }
 }
 
-pub fn format_unix_ts(ts_str: &str) -> String {
+/// Fails with the API's error (and scope details, when given) unless `ok` is true.
+pub fn check_ok(response: &impl Node) -> Result<(), SlkError> {
+    let ok = response
+        .get("ok")
+        .and_then(|v| v.as_bool())
+        .ok_or(SlkError::new(ErrorKind::Parse, "missing 'ok' field in response"))?;
+
+    if !ok {
+        let error = response
+            .get("error")
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown error");
+        let needed = response.get("needed").and_then(|v| v.as_str());
+        let provided = response.get("provided").and_then(|v| v.as_str());
+        let mut msg = format!("Slack API error: {}", error);
+        if let Some(needed) = needed {
+            msg.push_str(&format!("\n  needed scope: {}", needed));
+        }
+        if let Some(provided) = provided {
+            msg.push_str(&format!("\n  provided scopes: {}", provided));
+        }
+        return Err(SlkError::new(ErrorKind::from_api_code(error), msg));
+    }
+    Ok(())
+}
+
+/// The timestamp layout used unless `time_format` says otherwise.
+pub const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// Formats a Slack `ts` (UTC) with a strftime-style pattern. Supported:
+/// `%Y %y %m %d %e %H %I %M %S %p %b %a %j %s %%`; anything else is copied as is.
+pub fn format_unix_ts_with(ts_str: &str, pattern: &str) -> String {
     let secs: i64 = match ts_str.split('.').next() {
         Some(s) => s.parse().unwrap_or(0),
         None => 0,
@@ -18,9 +107,10 @@ pub fn format_unix_ts(ts_str: &str) -> String {
     let hours = time_of_day / 3600;
     let minutes = (time_of_day % 3600) / 60;
     let seconds = time_of_day % 60;
+    let days = secs.div_euclid(86400);
 
     // Howard Hinnant's civil_from_days algorithm
-    let z = secs.div_euclid(86400) + 719468;
+    let z = days + 719468;
     let era = if z >= 0 { z } else { z - 146096 } / 146097;
     let doe = z - era * 146097;
     let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
@@ -31,66 +121,146 @@ pub fn format_unix_ts(ts_str: &str) -> String {
     let m = if mp < 10 { mp + 3 } else { mp - 9 };
     let y = if m <= 2 { y + 1 } else { y };
 
-    format!(
-        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
-        y, m, d, hours, minutes, seconds
-    )
+    // doy counts from March 1st; shift it to January 1st.
+    let leap = (y % 4 == 0 && y % 100 != 0) || y % 400 == 0;
+    let day_of_year = if m >= 3 { doy + 60 + leap as i64 } else { doy - 305 };
+    // 1970-01-01 was a Thursday.
+    let weekday = (days + 4).rem_euclid(7);
+
+    let mut out = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(&format!("{:04}", y)),
+            Some('y') => out.push_str(&format!("{:02}", y.rem_euclid(100))),
+            Some('m') => out.push_str(&format!("{:02}", m)),
+            Some('d') => out.push_str(&format!("{:02}", d)),
+            Some('e') => out.push_str(&format!("{:2}", d)),
+            Some('H') => out.push_str(&format!("{:02}", hours)),
+            Some('I') => out.push_str(&format!("{:02}", (hours + 11) % 12 + 1)),
+            Some('M') => out.push_str(&format!("{:02}", minutes)),
+            Some('S') => out.push_str(&format!("{:02}", seconds)),
+            Some('p') => out.push_str(if hours < 12 { "AM" } else { "PM" }),
+            Some('b') => out.push_str(MONTH_NAMES[(m - 1) as usize]),
+            Some('a') => out.push_str(WEEKDAY_NAMES[weekday as usize]),
+            Some('j') => out.push_str(&format!("{:03}", day_of_year)),
+            Some('s') => out.push_str(&secs.to_string()),
+            Some('%') => out.push('%'),
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+            }
+            None => out.push('%'),
+        }
+    }
+    out
 }
 
-pub fn extract_messages(response: &JsonValue) -> Result<Vec<SlackMessage>, SlkError> {
-    let ok = response
-        .get("ok")
-        .and_then(|v| v.as_bool())
-        .ok_or(SlkError::from("missing 'ok' field in response"))?;
+/// A Slack timestamp as (seconds, microseconds), for ordering.
+pub fn ts_key(ts: &str) -> (u64, u64) {
+    let (secs, micros) = ts.split_once('.').unwrap_or((ts, "0"));
+    (secs.parse::<u64>().unwrap_or(0), micros.parse::<u64>().unwrap_or(0))
+}
 
-    if !ok {
-        let error = response
-            .get("error")
-            .and_then(|v| v.as_str())
-            .unwrap_or("unknown error");
-        let needed = response.get("needed").and_then(|v| v.as_str());
-        let provided = response.get("provided").and_then(|v| v.as_str());
-        let mut msg = format!("Slack API error: {}", error);
-        if let Some(needed) = needed {
-            msg.push_str(&format!("\n  needed scope: {}", needed));
-        }
-        if let Some(provided) = provided {
-            msg.push_str(&format!("\n  provided scopes: {}", provided));
-        }
-        return Err(SlkError::from(msg));
+/// Unix time at the start (UTC) of a `YYYY-MM-DD` date.
+pub fn parse_date(date: &str) -> Result<u64, SlkError> {
+    let invalid = || SlkError::from(format!("invalid date '{}': expected YYYY-MM-DD", date));
+    let parts: Vec<&str> = date.split('-').collect();
+    let [y, m, d] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    let (Ok(y), Ok(m), Ok(d)) = (y.parse::<i64>(), m.parse::<i64>(), d.parse::<i64>()) else {
+        return Err(invalid());
+    };
+    if y < 1970 || !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return Err(invalid());
+    }
+    // Howard Hinnant's days_from_civil algorithm
+    let (year, y) = (y, if m <= 2 { y - 1 } else { y });
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    let secs = (days * 86400) as u64;
+    // Catch days past the end of the month (2026-02-30).
+    if format_unix_ts_with(&secs.to_string(), "%Y-%m-%d") != format!("{:04}-{:02}-{:02}", year, m, d) {
+        return Err(invalid());
     }
+    Ok(secs)
+}
+
+pub fn extract_messages(response: &impl Node) -> Result<Vec<SlackMessage>, SlkError> {
+    check_ok(response)?;
 
     let messages = response
         .get("messages")
-        .and_then(|v| v.as_array())
+        .and_then(|v| v.as_slice())
         .ok_or(SlkError::from("missing 'messages' array in response"))?;
 
-    let mut result = Vec::new();
-    for msg in messages {
-        let user = msg
-            .get("user")
-            .and_then(|v| v.as_str())
-            .or_else(|| msg.get("username").and_then(|v| v.as_str()))
-            .or_else(|| msg.get("bot_id").and_then(|v| v.as_str()))
-            .unwrap_or("unknown")
-            .to_string();
-
-        let text = msg
-            .get("text")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
+    Ok(messages.iter().map(extract_message).collect())
+}
 
-        let ts = msg
-            .get("ts")
-            .and_then(|v| v.as_str())
-            .unwrap_or("0")
-            .to_string();
+/// The cursor for the next page of a paginated response, if there is one.
+pub fn extract_next_cursor(response: &impl Node) -> Option<String> {
+    response
+        .get("response_metadata")
+        .and_then(|m| m.get("next_cursor"))
+        .and_then(|v| v.as_str())
+        .filter(|cursor| !cursor.is_empty())
+        .map(String::from)
+}
 
-        result.push(SlackMessage { user, text, ts });
+/// One message object, from an API response or a message event.
+pub fn extract_message(msg: &impl Node) -> SlackMessage {
+    let user = msg
+        .get("user")
+        .and_then(|v| v.as_str())
+        .or_else(|| msg.get("username").and_then(|v| v.as_str()))
+        .or_else(|| msg.get("bot_id").and_then(|v| v.as_str()))
+        .unwrap_or("unknown")
+        .to_string();
+
+    let text = msg
+        .get("text")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+
+    let ts = msg
+        .get("ts")
+        .and_then(|v| v.as_str())
+        .unwrap_or("0")
+        .to_string();
+
+    let string = |key: &str| msg.get(key).and_then(|v| v.as_str()).map(String::from);
+    SlackMessage {
+        user,
+        text,
+        ts,
+        thread_ts: string("thread_ts"),
+        reply_count: msg.get("reply_count").and_then(|v| v.as_u64()).unwrap_or(0),
+        latest_reply: string("latest_reply"),
+        subtype: string("subtype"),
+        root_text: msg.get("root").and_then(|root| root.get("text")?.as_str()).map(String::from),
+        reactions: msg
+            .get("reactions")
+            .and_then(|v| v.as_slice())
+            .map_or(Vec::new(), |reactions| reactions.iter().map(extract_reaction).collect()),
     }
+}
 
-    Ok(result)
+fn extract_reaction(reaction: &impl Node) -> Reaction {
+    let users = reaction.get("users").and_then(|v| v.as_slice()).unwrap_or(&[]);
+    Reaction {
+        name: reaction.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+        count: reaction.get("count").and_then(|v| v.as_u64()).unwrap_or(users.len() as u64),
+        users: users.iter().filter_map(|u| u.as_str()).map(String::from).collect(),
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -99,32 +269,12 @@ pub struct SlackConversation {
     pub name: String,
 }
 
-pub fn extract_conversations(response: &JsonValue) -> Result<Vec<SlackConversation>, SlkError> {
-    let ok = response
-        .get("ok")
-        .and_then(|v| v.as_bool())
-        .ok_or(SlkError::from("missing 'ok' field in response"))?;
-
-    if !ok {
-        let error = response
-            .get("error")
-            .and_then(|v| v.as_str())
-            .unwrap_or("unknown error");
-        let needed = response.get("needed").and_then(|v| v.as_str());
-        let provided = response.get("provided").and_then(|v| v.as_str());
-        let mut msg = format!("Slack API error: {}", error);
-        if let Some(needed) = needed {
-            msg.push_str(&format!("\n  needed scope: {}", needed));
-        }
-        if let Some(provided) = provided {
-            msg.push_str(&format!("\n  provided scopes: {}", provided));
-        }
-        return Err(SlkError::from(msg));
-    }
+pub fn extract_conversations(response: &impl Node) -> Result<Vec<SlackConversation>, SlkError> {
+    check_ok(response)?;
 
     let channels = response
         .get("channels")
-        .and_then(|v| v.as_array())
+        .and_then(|v| v.as_slice())
         .ok_or(SlkError::from("missing 'channels' array in response"))?;
 
     let mut result = Vec::new();
@@ -145,55 +295,84 @@ pub fn extract_conversations(response: &JsonValue) -> Result<Vec<SlackConversati
     Ok(result)
 }
 
-pub fn resolve_user_name(response: &JsonValue) -> Result<String, SlkError> {
-    let ok = response
-        .get("ok")
-        .and_then(|v| v.as_bool())
-        .ok_or(SlkError::from("missing 'ok' field in response"))?;
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SlackUser {
+    pub id: String,
+    /// The handle (`@kanta`).
+    pub name: String,
+    pub display_name: String,
+    pub real_name: String,
+    /// The job title on the profile.
+    pub title: String,
+    /// Only there when the token has the `users:read.email` scope.
+    pub email: String,
+    /// The time zone, as a tz database name (`Asia/Tokyo`).
+    pub tz: String,
+    /// The time zone's name for people (`Japan Standard Time`).
+    pub tz_label: String,
+    /// The time zone's offset from UTC, in seconds.
+    pub tz_offset: i64,
+}
 
-    if !ok {
-        let error = response
-            .get("error")
-            .and_then(|v| v.as_str())
-            .unwrap_or("unknown error");
-        let needed = response.get("needed").and_then(|v| v.as_str());
-        let provided = response.get("provided").and_then(|v| v.as_str());
-        let mut msg = format!("Slack API error: {}", error);
-        if let Some(needed) = needed {
-            msg.push_str(&format!("\n  needed scope: {}", needed));
-        }
-        if let Some(provided) = provided {
-            msg.push_str(&format!("\n  provided scopes: {}", provided));
-        }
-        return Err(SlkError::from(msg));
+impl SlackUser {
+    /// The name messages are shown with.
+    pub fn shown_name(&self) -> &str {
+        [&self.display_name, &self.real_name, &self.name]
+            .into_iter()
+            .find(|n| !n.is_empty())
+            .map_or("", |n| n.as_str())
     }
+}
 
-    let user = response
-        .get("user")
-        .ok_or(SlkError::from("missing 'user' field in response"))?;
-
-    let profile = user.get("profile");
-    if let Some(profile) = profile {
-        if let Some(display_name) = profile.get("display_name").and_then(|v| v.as_str()) {
-            if !display_name.is_empty() {
-                return Ok(display_name.to_string());
-            }
-        }
+/// One user object, from `users.list`, `users.info` or an `slk export`.
+pub fn extract_user<N: Node>(user: &N) -> SlackUser {
+    // Members and their profiles carry dozens of keys, so index each once.
+    let user = user.index();
+    let profile = user.get("profile").map(Node::index);
+    let field = |value: Option<&N>| value.and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let profile = |key: &str| field(profile.as_ref().and_then(|p| p.get(key)));
+    SlackUser {
+        id: field(user.get("id")),
+        name: field(user.get("name")),
+        display_name: profile("display_name"),
+        real_name: field(user.get("real_name")),
+        title: profile("title"),
+        email: profile("email"),
+        tz: field(user.get("tz")),
+        tz_label: field(user.get("tz_label")),
+        tz_offset: user.get("tz_offset").and_then(|v| v.as_i64()).unwrap_or(0),
     }
+}
 
-    if let Some(real_name) = user.get("real_name").and_then(|v| v.as_str()) {
-        if !real_name.is_empty() {
-            return Ok(real_name.to_string());
+/// Members from a raw `users.list` page, leaving out deleted accounts, and
+/// the cursor of the next page, if there is one.
+pub fn extract_users(raw_json: &str) -> Result<(Vec<SlackUser>, Option<String>), SlkError> {
+    // A big workspace's list runs to megabytes: read it a member at a time.
+    let mut members = json::stream_array(raw_json, "members")?;
+    let mut users = Vec::new();
+    for member in &mut members {
+        let member = member?;
+        if member.get("deleted").and_then(|v| v.as_bool()) != Some(true) {
+            users.push(extract_user(&member));
         }
     }
-
-    if let Some(name) = user.get("name").and_then(|v| v.as_str()) {
-        if !name.is_empty() {
-            return Ok(name.to_string());
-        }
+    let found = members.found();
+    let rest = members.finish()?;
+    check_ok(&rest)?;
+    if !found {
+        return Err(SlkError::from("missing 'members' array in response"));
     }
+    Ok((users, extract_next_cursor(&rest)))
+}
 
-    Err(SlkError::from("no user name found in response"))
+/// Who a token belongs to, as reported by `auth.test`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AuthIdentity {
+    pub user_id: String,
+    pub user: String,
+    pub team_id: String,
+    pub team: String,
+    pub url: String,
 }
 
 #[cfg(test)]
@@ -201,13 +380,34 @@ mod tests {
     use super::*;
     use crate::json;
 
+    #[test]
+    fn test_format_unix_ts_with() {
+        let ts = "1770689887.565249";
+        assert_eq!(format_unix_ts_with(ts, DEFAULT_TIME_FORMAT), "2026-02-10 02:18:07");
+        assert_eq!(format_unix_ts_with(ts, "%a %b %e %I:%M %p"), "Tue Feb 10 02:18 AM");
+        assert_eq!(format_unix_ts_with(ts, "%y/%m/%d day %j, %s%%"), "26/02/10 day 041, 1770689887%");
+        assert_eq!(format_unix_ts_with("951825600", "%Y-%m-%d %j %H"), "2000-02-29 060 12");
+        assert_eq!(format_unix_ts_with("978307199", "%j %a %q"), "366 Sun %q");
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("2026-02-10").unwrap(), 1770681600);
+        assert_eq!(parse_date("2000-02-29").unwrap(), 951782400);
+        assert_eq!(parse_date("1970-01-01").unwrap(), 0);
+        assert!(parse_date("2026-02-30").is_err());
+        assert!(parse_date("2026-13-01").is_err());
+        assert!(parse_date("yesterday").is_err());
+    }
+
     #[test]
     fn test_extract_messages() {
         let input = r#"{
             "ok": true,
             "messages": [
                 {"user": "U081R4ZS5E2", "text": "Hello, this is a thread", "ts": "1770689887.565249"},
-                {"user": "U092X3AB7F1", "text": "Great thread!", "ts": "1770689900.000100"}
+                {"user": "U092X3AB7F1", "text": "Great thread!", "ts": "1770689900.000100",
+                 "thread_ts": "1770689900.000100", "reply_count": 12, "latest_reply": "1770697100.000200"}
             ],
             "has_more": false
         }"#;
@@ -221,16 +421,48 @@ mod tests {
                 user: "U081R4ZS5E2".to_string(),
                 text: "Hello, this is a thread".to_string(),
                 ts: "1770689887.565249".to_string(),
+                ..SlackMessage::default()
             }
         );
+        assert!(!messages[0].has_replies());
         assert_eq!(
             messages[1],
             SlackMessage {
                 user: "U092X3AB7F1".to_string(),
                 text: "Great thread!".to_string(),
                 ts: "1770689900.000100".to_string(),
+                thread_ts: Some("1770689900.000100".to_string()),
+                reply_count: 12,
+                latest_reply: Some("1770697100.000200".to_string()),
+                ..SlackMessage::default()
             }
         );
+        assert!(messages[1].has_replies());
+        let system =
+            |subtype: &str| SlackMessage { subtype: Some(subtype.to_string()), ..SlackMessage::default() };
+        assert!(system("channel_join").is_system());
+        assert!(system("group_topic").is_system());
+        assert!(system("pinned_item").is_system());
+        assert!(!system("bot_message").is_system());
+        assert!(!system("thread_broadcast").is_system());
+        assert!(!messages[0].is_system());
+
+        let broadcast = extract_message(
+            &json::parse(
+                r#"{"user": "U1", "text": "fixed", "ts": "1770690000.000100", "subtype": "thread_broadcast",
+                    "thread_ts": "1770689887.565249", "root": {"text": "Deploy is failing"}}"#,
+            )
+            .unwrap(),
+        );
+        assert!(broadcast.is_broadcast());
+        assert!(!broadcast.has_replies());
+        assert_eq!(broadcast.root_text.as_deref(), Some("Deploy is failing"));
+
+        let reacted = r#"{"ts": "1.0", "reactions": [{"name": "tada", "count": 3, "users": ["U1", "U2"]}]}"#;
+        let reacted = extract_message(&json::parse(reacted).unwrap());
+        let users = vec!["U1".to_string(), "U2".to_string()];
+        let tada = Reaction { name: "tada".to_string(), count: 3, users };
+        assert_eq!(reacted.reactions, [tada]);
     }
 
     #[test]
@@ -282,79 +514,6 @@ mod tests {
         assert_eq!(messages[0].text, "");
     }
 
-    #[test]
-    fn test_resolve_user_name_display_name() {
-        let input = r#"{
-            "ok": true,
-            "user": {
-                "name": "kanta",
-                "real_name": "Kanta Otomaeru",
-                "profile": {
-                    "display_name": "kanta"
-                }
-            }
-        }"#;
-        let json_val = json::parse(input).unwrap();
-        assert_eq!(resolve_user_name(&json_val).unwrap(), "kanta");
-    }
-
-    #[test]
-    fn test_resolve_user_name_fallback_to_real_name() {
-        let input = r#"{
-            "ok": true,
-            "user": {
-                "name": "kanta",
-                "real_name": "Kanta Otomaeru",
-                "profile": {
-                    "display_name": ""
-                }
-            }
-        }"#;
-        let json_val = json::parse(input).unwrap();
-        assert_eq!(resolve_user_name(&json_val).unwrap(), "Kanta Otomaeru");
-    }
-
-    #[test]
-    fn test_resolve_user_name_fallback_to_name() {
-        let input = r#"{
-            "ok": true,
-            "user": {
-                "name": "kanta",
-                "profile": {
-                    "display_name": ""
-                }
-            }
-        }"#;
-        let json_val = json::parse(input).unwrap();
-        assert_eq!(resolve_user_name(&json_val).unwrap(), "kanta");
-    }
-
-    #[test]
-    fn test_resolve_user_name_api_error() {
-        let input = r#"{"ok": false, "error": "user_not_found"}"#;
-        let json_val = json::parse(input).unwrap();
-        let result = resolve_user_name(&json_val);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().message.contains("user_not_found"));
-    }
-
-    #[test]
-    fn test_resolve_user_name_missing_scope() {
-        let input = r#"{
-            "ok": false,
-            "error": "missing_scope",
-            "needed": "users:read",
-            "provided": "channels:history"
-        }"#;
-        let json_val = json::parse(input).unwrap();
-        let result = resolve_user_name(&json_val);
-        assert!(result.is_err());
-        let msg = result.unwrap_err().message;
-        assert!(msg.contains("missing_scope"));
-        assert!(msg.contains("users:read"));
-        assert!(msg.contains("channels:history"));
-    }
-
     #[test]
     fn test_completely_unknown_user() {
         let input = r#"{
@@ -0,0 +1,159 @@
+use crate::message::SlackMessage;
+use crate::warn;
+use std::cell::Cell;
+use std::process::{Command, Stdio};
+
+/// How much of a message a notification shows.
+const SNIPPET_CHARS: usize = 100;
+
+/// Raises a desktop notification for new messages that mention the user or
+/// contain one of their keywords, through `notify-send` on Linux and
+/// `osascript` on macOS. Messages from before it was created (the backlog
+/// `slk watch` starts with) never notify.
+pub struct Notifier {
+    /// The logged-in user, whose own messages never notify.
+    user_id: String,
+    keywords: Vec<String>,
+    /// Unix time the notifier was created.
+    since: u64,
+    /// Set once the notifier program has failed, so it is only reported once.
+    failed: Cell<bool>,
+}
+
+impl Notifier {
+    pub fn new(user_id: String, keywords: Vec<String>) -> Self {
+        let keywords = keywords.into_iter().filter(|k| !k.trim().is_empty()).collect();
+        Notifier { user_id, keywords, since: crate::config::now_unix(), failed: Cell::new(false) }
+    }
+
+    /// Whether `message` should raise a notification: it is new, someone else
+    /// wrote it, and it mentions the user (directly or through `@here` or
+    /// `@channel`) or a keyword.
+    pub fn wants(&self, message: &SlackMessage) -> bool {
+        let secs = message.ts.split('.').next().and_then(|s| s.parse::<u64>().ok());
+        secs.is_some_and(|secs| secs >= self.since)
+            && message.user != self.user_id
+            && (mentions(&message.text, &self.user_id)
+                || self.keywords.iter().any(|k| contains_word(&message.text, k)))
+    }
+
+    /// Shows `message` with the channel it is from (`#general`) as the title.
+    pub fn notify(&self, message: &SlackMessage, channel: &str, author: &str) {
+        if self.failed.get() {
+            return;
+        }
+        let body = format!("{}: {}", author, snippet(&message.text, SNIPPET_CHARS));
+        let (program, args) = command(channel, &body);
+        let status = Command::new(program)
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        let problem = match status {
+            Ok(s) if s.success() => return,
+            Ok(s) => format!("{} exited with {}", program, s),
+            Err(e) => format!("could not run {}: {}", program, e),
+        };
+        self.failed.set(true);
+        warn!("desktop notifications are off: {}", problem);
+    }
+}
+
+/// Whether `text` mentions `user_id`, directly or through `@here`,
+/// `@channel` or `@everyone`.
+pub fn mentions(text: &str, user_id: &str) -> bool {
+    text.contains(&format!("<@{}>", user_id))
+        || text.contains(&format!("<@{}|", user_id))
+        || ["<!here", "<!channel", "<!everyone"].iter().any(|m| text.contains(m))
+}
+
+/// Whether `word` appears in `text` on its own rather than inside a longer
+/// word, ignoring case: "deploy" matches "Deploy done" but not "redeployed".
+fn contains_word(text: &str, word: &str) -> bool {
+    let text = text.to_lowercase();
+    let word = word.trim().to_lowercase();
+    let is_word_char = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    text.match_indices(&word).any(|(i, _)| {
+        !is_word_char(text[..i].chars().next_back()) && !is_word_char(text[i + word.len()..].chars().next())
+    })
+}
+
+/// The message on one line, cut short with an ellipsis if it is longer than
+/// `max_chars`.
+pub fn snippet(text: &str, max_chars: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+/// The program and arguments that show a notification on this platform.
+fn command(title: &str, body: &str) -> (&'static str, Vec<String>) {
+    if cfg!(target_os = "macos") {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let script = format!("display notification {} with title {}", quote(body), quote(title));
+        ("osascript", vec!["-e".to_string(), script])
+    } else {
+        ("notify-send", vec!["--app-name=slk".to_string(), title.to_string(), body.to_string()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(user: &str, text: &str) -> SlackMessage {
+        SlackMessage {
+            user: user.to_string(),
+            text: text.to_string(),
+            ts: "1700000100.000200".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_wants() {
+        let mut notifier = Notifier::new("U1".to_string(), vec!["deploy".to_string(), " ".to_string()]);
+        notifier.since = 1700000000;
+        assert!(notifier.wants(&message("U2", "<@U1> can you look?")));
+        assert!(notifier.wants(&message("U2", "ping <@U1|kanta>")));
+        assert!(notifier.wants(&message("U2", "<!here> standup")));
+        assert!(notifier.wants(&message("U2", "Deploy finished")));
+        assert!(!notifier.wants(&message("U2", "redeployed yesterday")));
+        assert!(!notifier.wants(&message("U2", "<@U10> hello")));
+        assert!(!notifier.wants(&message("U1", "<!here> deploy")));
+        assert!(!notifier.wants(&message("U2", "no keywords here")));
+        notifier.since = 1800000000;
+        assert!(!notifier.wants(&message("U2", "<@U1> an old message")));
+    }
+
+    #[test]
+    fn test_contains_word() {
+        assert!(contains_word("the release is OUT", "release"));
+        assert!(contains_word("on-call: me", "on-call"));
+        assert!(contains_word("prod is down again", "prod is down"));
+        assert!(!contains_word("production", "prod"));
+        assert!(contains_word("production, then prod", "prod"));
+    }
+
+    #[test]
+    fn test_snippet() {
+        assert_eq!(snippet("line one\n  line two", SNIPPET_CHARS), "line one line two");
+        let long = "あ".repeat(150);
+        assert_eq!(snippet(&long, SNIPPET_CHARS), format!("{}…", "あ".repeat(100)));
+    }
+
+    #[test]
+    fn test_command() {
+        let (program, args) = command("#general", "@kanta: say \"hi\"");
+        if cfg!(target_os = "macos") {
+            assert_eq!(program, "osascript");
+            assert_eq!(args[1], r##"display notification "@kanta: say \"hi\"" with title "#general""##);
+        } else {
+            assert_eq!(program, "notify-send");
+            assert_eq!(args[1..], ["#general", "@kanta: say \"hi\""]);
+        }
+    }
+}
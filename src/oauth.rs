@@ -1,13 +1,21 @@
+use crate::config::Credentials;
 use crate::error::SlkError;
-use std::io::{Read, Write};
-use std::net::TcpListener;
+use crate::http::{Request, Transport};
+use crate::info;
+use crate::json::JsonValue;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
 use std::process::Command;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use rustls::pki_types::PrivateKeyDer;
 use rustls::ServerConfig;
 
 const REDIRECT_URI: &str = "https://127.0.0.1:9876";
+const USER_SCOPES: &str = "channels:history,channels:read,channels:write,groups:history,groups:read,groups:write,mpim:read,im:read,users:read,chat:write,files:read";
+const BOT_SCOPES: &str =
+    "channels:history,channels:read,groups:history,groups:read,mpim:read,im:read,users:read,chat:write";
 
 fn generate_state() -> Result<String, SlkError> {
     let mut buf = [0u8; 16];
@@ -29,6 +37,17 @@ fn extract_callback_params(request: &str) -> Result<(String, String), SlkError>
         .nth(1)
         .ok_or(SlkError::from("no query string in callback"))?;
 
+    let (code, state) = parse_callback_query(query);
+    let code = code.ok_or(SlkError::from(
+        "no 'code' parameter in callback. Authorization may have been denied.",
+    ))?;
+    let state = state.ok_or(SlkError::from("no 'state' parameter in callback"))?;
+
+    Ok((code, state))
+}
+
+fn parse_callback_query(query: &str) -> (Option<String>, Option<String>) {
+    let query = query.split('#').next().unwrap_or(query);
     let mut code = None;
     let mut state = None;
 
@@ -43,15 +62,40 @@ fn extract_callback_params(request: &str) -> Result<(String, String), SlkError>
             state = Some(value.to_string());
         }
     }
+    (code, state)
+}
 
+/// Parses what the user pasted in the headless flow: either the full redirect
+/// URL (whose `state` is returned for checking) or just the bare code.
+fn parse_pasted_callback(input: &str) -> Result<(String, Option<String>), SlkError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(SlkError::from("no redirect URL or code was entered"));
+    }
+    let Some((_, query)) = input.split_once('?') else {
+        if input.contains('=') || input.contains('/') {
+            return Err(SlkError::from("no query string in pasted URL"));
+        }
+        return Ok((input.to_string(), None));
+    };
+    let (code, state) = parse_callback_query(query);
     let code = code.ok_or(SlkError::from(
-        "no 'code' parameter in callback. Authorization may have been denied.",
+        "no 'code' parameter in pasted URL. Authorization may have been denied.",
     ))?;
-    let state = state.ok_or(SlkError::from("no 'state' parameter in callback"))?;
-
     Ok((code, state))
 }
 
+/// Prompts on stderr and reads the pasted redirect URL (or code) from stdin.
+fn read_pasted_callback() -> Result<(String, Option<String>), SlkError> {
+    eprint!("Paste the redirect URL (or just the code): ");
+    let _ = std::io::stderr().flush();
+    let mut line = String::new();
+    std::io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| SlkError::from(format!("failed to read from stdin: {}", e)))?;
+    parse_pasted_callback(&line)
+}
+
 fn build_tls_config() -> Result<ServerConfig, SlkError> {
     let key_pair = rcgen::KeyPair::generate()
         .map_err(|e| SlkError::from(format!("failed to generate key pair: {}", e)))?;
@@ -70,15 +114,62 @@ fn build_tls_config() -> Result<ServerConfig, SlkError> {
     Ok(config)
 }
 
-fn wait_for_callback(tls_config: Arc<ServerConfig>) -> Result<String, SlkError> {
+/// Polls a non-blocking listener until a connection arrives, the deadline
+/// passes, or `cancelled` returns true.
+fn accept_until(
+    listener: &TcpListener,
+    deadline: Option<Instant>,
+    cancelled: &dyn Fn() -> bool,
+) -> Result<TcpStream, SlkError> {
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream
+                    .set_nonblocking(false)
+                    .map_err(|e| SlkError::from(format!("failed to accept connection: {}", e)))?;
+                return Ok(stream);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => return Err(SlkError::from(format!("failed to accept connection: {}", e))),
+        }
+        if cancelled() {
+            return Err(SlkError::from(
+                "login cancelled. Run `slk login` to try again.",
+            ));
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return Err(SlkError::from(
+                "timed out waiting for the browser to redirect back. Run `slk login` to try again, \
+                 raise login_timeout, or use `slk login --no-browser`.",
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+fn wait_for_callback(
+    tls_config: Arc<ServerConfig>,
+    timeout: Option<Duration>,
+) -> Result<String, SlkError> {
     let listener = TcpListener::bind("127.0.0.1:9876")
         .map_err(|e| SlkError::from(format!("failed to bind port 9876: {}", e)))?;
-    eprintln!("Waiting for callback on https://127.0.0.1:9876 ...");
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| SlkError::from(format!("failed to configure listener: {}", e)))?;
+    match timeout {
+        Some(t) => eprintln!(
+            "Waiting for callback on https://127.0.0.1:9876 (up to {}s, Ctrl-C to cancel) ...",
+            t.as_secs()
+        ),
+        None => eprintln!("Waiting for callback on https://127.0.0.1:9876 (Ctrl-C to cancel) ..."),
+    }
 
+    let _guard = crate::interrupt::Guard::start();
+    let deadline = timeout.map(|t| Instant::now() + t);
     loop {
-        let (tcp_stream, _) = listener
-            .accept()
-            .map_err(|e| SlkError::from(format!("failed to accept connection: {}", e)))?;
+        let tcp_stream = accept_until(&listener, deadline, &crate::interrupt::interrupted)?;
+        // Don't let a half-open connection stall the login.
+        let _ = tcp_stream.set_read_timeout(Some(Duration::from_secs(10)));
         let tls_conn = rustls::ServerConnection::new(Arc::clone(&tls_config))
             .map_err(|e| SlkError::from(format!("failed to create TLS connection: {}", e)))?;
         let mut stream = rustls::StreamOwned::new(tls_conn, tcp_stream);
@@ -104,38 +195,39 @@ fn wait_for_callback(tls_config: Arc<ServerConfig>) -> Result<String, SlkError>
 }
 
 fn exchange_code(
+    transport: &dyn Transport,
+    base_url: &str,
     client_id: &str,
     client_secret: &str,
     code: &str,
-) -> Result<String, SlkError> {
-    let output = Command::new("curl")
-        .args([
-            "-s",
-            "-X",
-            "POST",
-            "-d",
-            &format!(
-                "client_id={}&client_secret={}&code={}&redirect_uri={}",
-                client_id, client_secret, code, REDIRECT_URI
-            ),
-            "https://slack.com/api/oauth.v2.access",
-        ])
-        .output()
-        .map_err(|e| SlkError::from(format!("failed to execute curl: {}", e)))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(SlkError::from(format!(
-            "curl failed (exit {}): {}",
-            output.status, stderr
-        )));
-    }
-
-    let body = String::from_utf8(output.stdout)
-        .map_err(|e| SlkError::from(format!("invalid UTF-8 in response: {}", e)))?;
-
-    let json_val = crate::json::parse(&body)?;
+) -> Result<Credentials, SlkError> {
+    let request = Request::post_form(
+        &format!("{}/oauth.v2.access", base_url),
+        &[
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("code", code),
+            ("redirect_uri", REDIRECT_URI),
+        ],
+    );
+    info!("Exchanging the authorization code for a token");
+    let response = transport.send(&request)?;
+    let body = response.text()?;
+
+    let json_val = crate::json::parse(&body).map_err(|e| {
+        SlkError::from(format!(
+            "oauth.v2.access failed: {} ({})",
+            e.message,
+            response.describe()
+        ))
+    })?;
+
+    credentials_from_response(&json_val, "oauth.v2.access")
+}
 
+/// Reads a user token out of an `oauth.v2.access` response. A code exchange
+/// nests it under `authed_user`; a refresh returns it at the top level.
+fn credentials_from_response(json_val: &JsonValue, context: &str) -> Result<Credentials, SlkError> {
     let ok = json_val
         .get("ok")
         .and_then(|v| v.as_bool())
@@ -146,49 +238,131 @@ fn exchange_code(
             .get("error")
             .and_then(|v| v.as_str())
             .unwrap_or("unknown error");
-        return Err(SlkError::from(format!(
-            "oauth.v2.access failed: {}",
-            error
-        )));
+        return Err(SlkError::from(format!("{} failed: {}", context, error)));
     }
 
-    let token = json_val
-        .get("authed_user")
-        .and_then(|u| u.get("access_token"))
+    let grant = json_val.get("authed_user").unwrap_or(json_val);
+    let token = grant
+        .get("access_token")
         .and_then(|v| v.as_str())
         .ok_or(SlkError::from(
             "missing authed_user.access_token in response",
         ))?;
+    let refresh_token = grant
+        .get("refresh_token")
+        .and_then(|v| v.as_str())
+        .map(String::from);
+    let expires_at = grant.get("expires_in").and_then(|v| v.as_u64()).map(|secs| crate::config::now_unix() + secs);
+
+    // With bot scopes requested, the bot token is the top-level access_token.
+    let bot_token = json_val
+        .get("authed_user")
+        .and(json_val.get("access_token"))
+        .and_then(|v| v.as_str())
+        .map(String::from);
+
+    Ok(Credentials {
+        token: token.to_string(),
+        refresh_token,
+        expires_at,
+        bot_token,
+        identity: None,
+    })
+}
+
+/// Exchanges a refresh token for a new access token (token rotation).
+pub fn refresh_credentials(
+    transport: &dyn Transport,
+    base_url: &str,
+    client_id: &str,
+    client_secret: &str,
+    refresh_token: &str,
+) -> Result<Credentials, SlkError> {
+    let request = Request::post_form(
+        &format!("{}/oauth.v2.access", base_url),
+        &[
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+        ],
+    );
+    info!("Refreshing the access token");
+    let response = transport.send(&request)?;
+    let json_val = crate::json::parse(&response.text()?).map_err(|e| {
+        SlkError::from(format!(
+            "token refresh failed: {} ({})",
+            e.message,
+            response.describe()
+        ))
+    })?;
+    let mut creds = credentials_from_response(&json_val, "token refresh")?;
+    // Slack may omit the refresh token when it is unchanged.
+    if creds.refresh_token.is_none() {
+        creds.refresh_token = Some(refresh_token.to_string());
+    }
+    Ok(creds)
+}
 
-    Ok(token.to_string())
+/// The authorize page lives on the web host, next to (not under) the API base.
+fn authorize_base(base_url: &str) -> &str {
+    base_url.strip_suffix("/api").unwrap_or(base_url)
 }
 
-pub fn run_oauth_flow(client_id: &str, client_secret: &str) -> Result<String, SlkError> {
+pub fn run_oauth_flow(
+    transport: &dyn Transport,
+    base_url: &str,
+    client_id: &str,
+    client_secret: &str,
+    bot: bool,
+    no_browser: bool,
+    timeout: Option<Duration>,
+) -> Result<Credentials, SlkError> {
     let state = generate_state()?;
-    let tls_config = Arc::new(build_tls_config()?);
 
+    let bot_scope = if bot {
+        format!("&scope={}", BOT_SCOPES)
+    } else {
+        String::new()
+    };
     let auth_url = format!(
-        "https://slack.com/oauth/v2/authorize?client_id={}&user_scope=channels:history,channels:read,groups:history,groups:read,mpim:read,im:read,users:read&redirect_uri={}&state={}",
+        "{}/oauth/v2/authorize?client_id={}{}&user_scope={}&redirect_uri={}&state={}",
+        authorize_base(base_url),
         client_id,
+        bot_scope,
+        USER_SCOPES,
         REDIRECT_URI.replace(':', "%3A").replace('/', "%2F"),
         state
     );
 
-    eprintln!("Opening browser for authorization...");
-    eprintln!("If prompted about the certificate, click 'Advanced' and 'Proceed'.");
-    eprintln!("If the browser doesn't open, visit:\n  {}", auth_url);
-    let _ = Command::new("xdg-open").arg(&auth_url).spawn();
-
-    let request = wait_for_callback(tls_config)?;
-    let (code, callback_state) = extract_callback_params(&request)?;
-
-    if callback_state != state {
+    let (code, callback_state) = if no_browser {
+        eprintln!("Open this URL in a browser on any machine and approve access:\n  {}", auth_url);
+        eprintln!(
+            "The browser will then fail to load {} — that is expected.",
+            REDIRECT_URI
+        );
+        eprintln!("Copy the full URL from its address bar.");
+        read_pasted_callback()?
+    } else {
+        let tls_config = Arc::new(build_tls_config()?);
+        eprintln!("Opening browser for authorization...");
+        eprintln!("If prompted about the certificate, click 'Advanced' and 'Proceed'.");
+        eprintln!("If the browser doesn't open, visit:\n  {}", auth_url);
+        let _ = Command::new("xdg-open").arg(&auth_url).spawn();
+
+        let request = wait_for_callback(tls_config, timeout)?;
+        let (code, callback_state) = extract_callback_params(&request)?;
+        (code, Some(callback_state))
+    };
+
+    // A bare pasted code carries no state to check.
+    if callback_state.is_some_and(|s| s != state) {
         return Err(SlkError::from(
             "state mismatch: possible CSRF attack. Please try again.",
         ));
     }
 
-    exchange_code(client_id, client_secret, &code)
+    exchange_code(transport, base_url, client_id, client_secret, &code)
 }
 
 #[cfg(test)]
@@ -236,6 +410,55 @@ mod tests {
         assert!(extract_callback_params("").is_err());
     }
 
+    #[test]
+    fn test_parse_pasted_callback_url() {
+        let (code, state) =
+            parse_pasted_callback("https://127.0.0.1:9876/?code=abc123&state=deadbeef\n").unwrap();
+        assert_eq!(code, "abc123");
+        assert_eq!(state.as_deref(), Some("deadbeef"));
+    }
+
+    #[test]
+    fn test_parse_pasted_callback_bare_code() {
+        let (code, state) = parse_pasted_callback("  1234.5678.abcdef \n").unwrap();
+        assert_eq!(code, "1234.5678.abcdef");
+        assert_eq!(state, None);
+    }
+
+    #[test]
+    fn test_parse_pasted_callback_denied() {
+        let err = parse_pasted_callback("https://127.0.0.1:9876/?error=access_denied&state=x")
+            .unwrap_err();
+        assert!(err.message.contains("code"));
+        assert!(parse_pasted_callback("").is_err());
+    }
+
+    #[test]
+    fn test_accept_until_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let deadline = Some(Instant::now() + Duration::from_millis(50));
+        let err = accept_until(&listener, deadline, &|| false).unwrap_err();
+        assert!(err.message.contains("timed out"));
+    }
+
+    #[test]
+    fn test_accept_until_cancelled() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let err = accept_until(&listener, None, &|| true).unwrap_err();
+        assert!(err.message.contains("cancelled"));
+    }
+
+    #[test]
+    fn test_accept_until_returns_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let deadline = Some(Instant::now() + Duration::from_secs(5));
+        assert!(accept_until(&listener, deadline, &|| false).is_ok());
+    }
+
     #[test]
     fn test_generate_state_length_and_hex() {
         let state = generate_state().unwrap();
@@ -249,4 +472,64 @@ mod tests {
         let s2 = generate_state().unwrap();
         assert_ne!(s1, s2);
     }
+
+    #[test]
+    fn test_authorize_base() {
+        assert_eq!(authorize_base("https://slack.com/api"), "https://slack.com");
+        assert_eq!(authorize_base("https://slack-gov.com/api"), "https://slack-gov.com");
+        assert_eq!(authorize_base("http://127.0.0.1:8080"), "http://127.0.0.1:8080");
+    }
+
+    #[test]
+    fn test_credentials_from_code_exchange() {
+        let json_val = crate::json::parse(
+            r#"{"ok": true, "access_token": "xoxb-bot", "authed_user": {"id": "U1", "access_token": "xoxp-user"}}"#,
+        )
+        .unwrap();
+        let creds = credentials_from_response(&json_val, "oauth.v2.access").unwrap();
+        assert_eq!(creds.token, "xoxp-user");
+        assert_eq!(creds.bot_token.as_deref(), Some("xoxb-bot"));
+        assert_eq!(creds.refresh_token, None);
+        assert_eq!(creds.expires_at, None);
+    }
+
+    #[test]
+    fn test_credentials_from_rotating_exchange() {
+        let json_val = crate::json::parse(
+            r#"{"ok": true, "authed_user": {"access_token": "xoxe.xoxp-1", "refresh_token": "xoxe-1-r", "expires_in": 43200}}"#,
+        )
+        .unwrap();
+        let creds = credentials_from_response(&json_val, "oauth.v2.access").unwrap();
+        assert_eq!(creds.token, "xoxe.xoxp-1");
+        assert_eq!(creds.refresh_token.as_deref(), Some("xoxe-1-r"));
+        assert!(creds.expires_at.unwrap() >= crate::config::now_unix() + 43000);
+    }
+
+    #[test]
+    fn test_refresh_credentials() {
+        let transport = crate::http::FakeTransport::new().respond(
+            "oauth.v2.access",
+            r#"{"ok": true, "token_type": "user", "access_token": "xoxe.xoxp-2", "expires_in": 43200}"#,
+        );
+        let creds =
+            refresh_credentials(&transport, "https://slack.com/api", "id", "secret", "xoxe-1-r")
+                .unwrap();
+        assert_eq!(creds.token, "xoxe.xoxp-2");
+        assert_eq!(creds.refresh_token.as_deref(), Some("xoxe-1-r"));
+        assert_eq!(creds.bot_token, None);
+
+        let requests = transport.requests.lock().unwrap();
+        let body = String::from_utf8(requests[0].body.clone()).unwrap();
+        assert!(body.contains("grant_type=refresh_token"));
+        assert!(body.contains("refresh_token=xoxe-1-r"));
+    }
+
+    #[test]
+    fn test_refresh_credentials_error() {
+        let transport = crate::http::FakeTransport::new()
+            .respond("oauth.v2.access", r#"{"ok": false, "error": "invalid_refresh_token"}"#);
+        let err = refresh_credentials(&transport, "https://slack.com/api", "id", "secret", "bad")
+            .unwrap_err();
+        assert!(err.message.contains("invalid_refresh_token"));
+    }
 }
@@ -0,0 +1,177 @@
+//! `--query`: a small subset of jq's paths for pulling fields out of JSON
+//! output without jq. A query is a chain of steps:
+//!
+//! - `.name` or `name` (first step only): an object's field, `null` if missing
+//! - `["name"]`: a field whose name has dots or brackets in it
+//! - `[n]`: an array's nth item, counting from the end when negative
+//! - `[]`: every item of an array (or value of an object)
+//!
+//! so `messages[].text` is the text of each message and `.` the whole
+//! document. Strings are printed without quotes, one result per line, and
+//! anything else as compact JSON.
+
+use crate::error::SlkError;
+use crate::json::{self, JsonValue};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Field(String),
+    Index(i64),
+    Each,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    text: String,
+    steps: Vec<Step>,
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Self, SlkError> {
+        let invalid = |why: &str| SlkError::from(format!("invalid --query '{}': {}", text, why));
+        let mut steps = Vec::new();
+        let mut rest = text.trim();
+        if rest == "." {
+            rest = "";
+        }
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']').ok_or_else(|| invalid("missing ']'"))?;
+                let inner = after[..end].trim();
+                steps.push(if inner.is_empty() {
+                    Step::Each
+                } else if inner.starts_with('"') {
+                    let JsonValue::String(name) = json::parse(inner).map_err(|_| invalid("bad quoted name"))? else {
+                        return Err(invalid("bad quoted name"));
+                    };
+                    Step::Field(name)
+                } else {
+                    Step::Index(inner.parse().map_err(|_| invalid(&format!("'{}' is not an index", inner)))?)
+                });
+                rest = &after[end + 1..];
+                continue;
+            }
+            let name = match rest.strip_prefix('.') {
+                Some(name) => name,
+                None if steps.is_empty() => rest,
+                None => return Err(invalid(&format!("expected '.' or '[' before '{}'", rest))),
+            };
+            let end = name.find(['.', '[']).unwrap_or(name.len());
+            if end == 0 {
+                return Err(invalid("missing field name after '.'"));
+            }
+            steps.push(Step::Field(name[..end].to_string()));
+            rest = &name[end..];
+        }
+        Ok(Query { text: text.to_string(), steps })
+    }
+
+    /// The values the query picks out of `value`, in order.
+    pub fn eval<'a>(&self, value: &'a JsonValue) -> Result<Vec<&'a JsonValue>, SlkError> {
+        const NULL: &JsonValue = &JsonValue::Null;
+        let mut values = vec![value];
+        for step in &self.steps {
+            let mut next = Vec::new();
+            for value in values {
+                match (step, value) {
+                    (Step::Field(name), JsonValue::Object(_)) => next.push(value.get(name).unwrap_or(NULL)),
+                    (Step::Index(i), JsonValue::Array(items)) => {
+                        let i = if *i < 0 { items.len() as i64 + i } else { *i };
+                        next.push(usize::try_from(i).ok().and_then(|i| items.get(i)).unwrap_or(NULL));
+                    }
+                    (Step::Each, JsonValue::Array(items)) => next.extend(items),
+                    (Step::Each, JsonValue::Object(pairs)) => next.extend(pairs.iter().map(|(_, v)| v)),
+                    (_, JsonValue::Null) => next.push(NULL),
+                    (step, value) => {
+                        let what = match step {
+                            Step::Field(name) => format!("field '{}'", name),
+                            Step::Index(i) => format!("index {}", i),
+                            Step::Each => "items".to_string(),
+                        };
+                        return Err(SlkError::from(format!(
+                            "--query '{}': cannot take {} of {}",
+                            self.text,
+                            what,
+                            kind(value)
+                        )));
+                    }
+                }
+            }
+            values = next;
+        }
+        Ok(values)
+    }
+
+    /// Runs the query over a command's output: one JSON document, or JSON
+    /// Lines as `slk watch --format json` prints them.
+    pub fn apply(&self, output: &str) -> Result<String, SlkError> {
+        let documents = match json::parse(output) {
+            Ok(document) => vec![document],
+            Err(_) => output
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(json::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| SlkError::from("--query needs JSON output; add --format json"))?,
+        };
+        let mut lines = Vec::new();
+        for document in &documents {
+            lines.extend(self.eval(document)?.into_iter().map(|value| match value {
+                JsonValue::String(s) => s.clone(),
+                value => value.to_string(),
+            }));
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+fn kind(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "a boolean",
+        JsonValue::Int(_) | JsonValue::Number(_) => "a number",
+        JsonValue::String(_) => "a string",
+        JsonValue::Array(_) => "an array",
+        JsonValue::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HISTORY: &str = r#"{"ok": true, "messages": [
+        {"user": "U1", "text": "hi", "ts": "1770689887.565249", "reply_count": 2},
+        {"user": "U2", "text": "lunch?", "ts": "1770689900.000100", "a.b": {"c": 1}}
+    ]}"#;
+
+    fn run(query: &str, output: &str) -> Result<String, SlkError> {
+        Query::parse(query)?.apply(output)
+    }
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(run("messages[].text", HISTORY).unwrap(), "hi\nlunch?");
+        assert_eq!(run(".messages[0].user", HISTORY).unwrap(), "U1");
+        assert_eq!(run("messages[-1].ts", HISTORY).unwrap(), "1770689900.000100");
+        assert_eq!(run("messages[].reply_count", HISTORY).unwrap(), "2\nnull");
+        assert_eq!(run(r#"messages[1]["a.b"]"#, HISTORY).unwrap(), r#"{"c":1}"#);
+        assert_eq!(run("messages[5].text", HISTORY).unwrap(), "null");
+        assert_eq!(run(".", r#"[1, "a"]"#).unwrap(), r#"[1,"a"]"#);
+        assert_eq!(run("[]", r#"{"user": "U1", "n": 2}"#).unwrap(), "U1\n2");
+        assert_eq!(run(".user", "{\"user\": \"U1\"}\n{\"user\": \"U2\"}\n").unwrap(), "U1\nU2");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Query::parse("messages[").is_err());
+        assert!(Query::parse("messages[x]").is_err());
+        assert!(Query::parse("messages..text").is_err());
+        assert!(Query::parse("messages[]text").is_err());
+        assert_eq!(
+            run("messages.text", HISTORY).unwrap_err().message,
+            "--query 'messages.text': cannot take field 'text' of an array"
+        );
+        assert_eq!(run("ok", "2026-02-10 hi").unwrap_err().message, "--query needs JSON output; add --format json");
+    }
+}
@@ -0,0 +1,208 @@
+use crate::json::JsonValue;
+use crate::message::{self, format_unix_ts_with};
+use std::collections::HashMap;
+
+/// How much of the parent message the report's title shows.
+const TITLE_CHARS: usize = 80;
+
+/// A thread to write up with `slk thread --report`.
+pub struct Thread<'a> {
+    /// `#general`, or the channel ID when the name isn't known.
+    pub channel: &'a str,
+    /// A link to the thread, when the workspace is known.
+    pub permalink: Option<String>,
+    /// The parent message first, then the replies, as the API returned them.
+    pub messages: &'a [JsonValue],
+    pub user_names: &'a HashMap<String, String>,
+    pub time_format: &'a str,
+}
+
+/// The thread as a Markdown document for postmortems: a summary, who took
+/// part, every message in order, and the files and links that were shared.
+pub fn markdown(thread: &Thread) -> String {
+    let messages: Vec<message::SlackMessage> = thread.messages.iter().map(message::extract_message).collect();
+    let time = |ts: &str| format_unix_ts_with(ts, thread.time_format);
+    let who = |user: &str| thread.user_names.get(user).map_or(user.to_string(), |name| format!("@{}", name));
+    let Some(parent) = messages.first() else {
+        return format!("# Thread in {}\n\nThe thread has no messages.\n", thread.channel);
+    };
+
+    let first_line = to_markdown(&parent.text, thread.user_names);
+    let first_line = first_line.lines().next().unwrap_or("").trim();
+    let title = match first_line.char_indices().nth(TITLE_CHARS) {
+        Some((end, _)) => format!("{}…", &first_line[..end]),
+        None => first_line.to_string(),
+    };
+    let mut out = format!("# {}\n\n", if title.is_empty() { "Thread" } else { &title });
+    out.push_str(&format!("- **Channel:** {}\n", thread.channel));
+    out.push_str(&format!("- **Started:** {} by {}\n", time(&parent.ts), who(&parent.user)));
+    if let Some(last) = messages.last().filter(|_| messages.len() > 1) {
+        out.push_str(&format!("- **Last reply:** {}\n", time(&last.ts)));
+    }
+    out.push_str(&format!("- **Replies:** {}\n", messages.len() - 1));
+    if let Some(permalink) = &thread.permalink {
+        out.push_str(&format!("- **Link:** <{}>\n", permalink));
+    }
+    out.push_str("\nTimes are in UTC.\n");
+
+    let mut participants: Vec<(&str, usize)> = Vec::new();
+    for m in &messages {
+        match participants.iter_mut().find(|(user, _)| *user == m.user) {
+            Some((_, count)) => *count += 1,
+            None => participants.push((&m.user, 1)),
+        }
+    }
+    out.push_str("\n## Participants\n\n");
+    for (user, count) in &participants {
+        let plural = if *count == 1 { "" } else { "s" };
+        out.push_str(&format!("- {} ({} message{})\n", who(user), count, plural));
+    }
+
+    out.push_str("\n## Timeline\n\n");
+    for m in &messages {
+        let text = to_markdown(&m.text, thread.user_names).replace('\n', "\n  ");
+        out.push_str(&format!("- **{}** {}: {}\n", time(&m.ts), who(&m.user), text));
+    }
+
+    let files: Vec<String> = thread
+        .messages
+        .iter()
+        .zip(&messages)
+        .flat_map(|(raw, m)| {
+            let files = raw.get("files").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+            files.into_iter().map(move |f| {
+                let field = |key: &str| f.get(key).and_then(|v| v.as_str()).map(String::from);
+                let name = field("name").or_else(|| field("title")).unwrap_or_else(|| "file".to_string());
+                let shared = format!("shared by {} at {}", who(&m.user), time(&m.ts));
+                match field("permalink").or_else(|| field("url_private")) {
+                    Some(url) => format!("- [{}]({}) — {}\n", name, url, shared),
+                    None => format!("- {} — {}\n", name, shared),
+                }
+            })
+        })
+        .collect();
+    if !files.is_empty() {
+        out.push_str("\n## Files\n\n");
+        out.extend(files);
+    }
+
+    let mut links: Vec<&str> = Vec::new();
+    for m in &messages {
+        for link in links_in(&m.text) {
+            if !links.contains(&link) {
+                links.push(link);
+            }
+        }
+    }
+    if !links.is_empty() {
+        out.push_str("\n## Links\n\n");
+        for link in links {
+            out.push_str(&format!("- <{}>\n", link));
+        }
+    }
+    out
+}
+
+/// The users a message mentions (`<@U…>`), so their names can be looked up.
+pub fn mentioned_users(text: &str) -> Vec<&str> {
+    text.split("<@")
+        .skip(1)
+        .filter_map(|rest| rest.split(['>', '|']).next())
+        .filter(|id| crate::resolve::is_user_id(id))
+        .collect()
+}
+
+/// The web links in a message (`<https://…>` or `<https://…|label>`).
+fn links_in(text: &str) -> Vec<&str> {
+    text.split('<')
+        .skip(1)
+        .filter_map(|rest| rest.split(['>', '|']).next())
+        .filter(|target| target.starts_with("http://") || target.starts_with("https://"))
+        .collect()
+}
+
+/// Turns Slack's message markup into Markdown: mentions become `@name`,
+/// channel references `#name`, links `[label](url)`, and the escaped `&`,
+/// `<` and `>` plain characters again.
+fn to_markdown(text: &str, user_names: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        out.push_str(&unescape(&rest[..start]));
+        let inner = &rest[start + 1..start + len];
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target, Some(unescape(label))),
+            None => (inner, None),
+        };
+        let shown = if let Some(id) = target.strip_prefix('@') {
+            let name = user_names.get(id).cloned().or(label).unwrap_or_else(|| id.to_string());
+            format!("@{}", name.trim_start_matches('@'))
+        } else if let Some(id) = target.strip_prefix('#') {
+            format!("#{}", label.unwrap_or_else(|| id.to_string()))
+        } else if let Some(special) = target.strip_prefix('!') {
+            label.unwrap_or_else(|| format!("@{}", special))
+        } else {
+            match label {
+                Some(label) => format!("[{}]({})", label, target),
+                None => format!("<{}>", target),
+            }
+        };
+        out.push_str(&shown);
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(&unescape(rest));
+    out
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn test_to_markdown() {
+        let names = HashMap::from([("U1".to_string(), "kanta".to_string())]);
+        assert_eq!(
+            to_markdown("<@U1> see <https://example.com/pr/1|the fix> in <#C1|ops>, &lt;3", &names),
+            "@kanta see [the fix](https://example.com/pr/1) in #ops, <3"
+        );
+        assert_eq!(to_markdown("<!here> <@U2> <https://example.com>", &names), "@here @U2 <https://example.com>");
+        assert_eq!(to_markdown("a < b", &names), "a < b");
+        assert_eq!(mentioned_users("<@U1> and <@U2|taro>, not <#C1>"), ["U1", "U2"]);
+    }
+
+    #[test]
+    fn test_markdown() {
+        let messages = json::parse(
+            r#"[
+                {"user": "U1", "text": "incident: prod is down", "ts": "1770689887.565249"},
+                {"user": "U2", "text": "looking\nat <https://example.com/dash>", "ts": "1770689900.000100"},
+                {"user": "U1", "text": "log attached", "ts": "1770689990.000100",
+                 "files": [{"name": "log.txt", "permalink": "https://myteam.slack.com/files/U1/F1/log.txt"}]}
+            ]"#,
+        )
+        .unwrap();
+        let names = HashMap::from([("U1".to_string(), "kanta".to_string())]);
+        let thread = Thread {
+            channel: "#ops",
+            permalink: Some("https://myteam.slack.com/archives/C1/p1770689887565249".to_string()),
+            messages: messages.as_array().unwrap(),
+            user_names: &names,
+            time_format: "%H:%M:%S",
+        };
+        let report = markdown(&thread);
+        assert!(report.starts_with("# incident: prod is down\n\n- **Channel:** #ops\n- **Started:** 02:18:07 by @kanta\n"));
+        assert!(report.contains("- **Replies:** 2\n"));
+        assert!(report.contains("## Participants\n\n- @kanta (2 messages)\n- U2 (1 message)\n"));
+        assert!(report.contains("- **02:18:20** U2: looking\n  at <https://example.com/dash>\n"));
+        assert!(report.contains("- [log.txt](https://myteam.slack.com/files/U1/F1/log.txt) — shared by @kanta at 02:19:50\n"));
+        assert!(report.ends_with("## Links\n\n- <https://example.com/dash>\n"));
+    }
+}
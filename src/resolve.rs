@@ -0,0 +1,258 @@
+use crate::error::SlkError;
+use crate::message::{SlackConversation, SlackUser};
+
+/// How many near-matches an "unknown channel" or "unknown user" error lists.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Whether `s` is already a conversation ID (`C…`, `G…` or `D…`). Channel
+/// names are always lowercase, so they never look like one.
+pub fn is_channel_id(s: &str) -> bool {
+    s.len() > 1
+        && s.starts_with(['C', 'G', 'D'])
+        && s.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+/// The ID of the channel called `name` (with or without a leading `#`), or
+/// `None` if no channel has that name. Several channels sharing a name is an
+/// error listing them.
+pub fn find_channel(channels: &[SlackConversation], name: &str) -> Result<Option<String>, SlkError> {
+    let name = name.strip_prefix('#').unwrap_or(name);
+    let found: Vec<&SlackConversation> = channels.iter().filter(|c| c.name == name).collect();
+    match found.as_slice() {
+        [] => Ok(None),
+        [channel] => Ok(Some(channel.id.clone())),
+        _ => {
+            let ids: Vec<&str> = found.iter().map(|c| c.id.as_str()).collect();
+            Err(SlkError::from(format!(
+                "channel name '#{}' is ambiguous: it matches {}. Use the channel ID instead",
+                name,
+                ids.join(", ")
+            )))
+        }
+    }
+}
+
+/// The error for a channel name that matches nothing, suggesting names that
+/// contain it or are a small typo away.
+pub fn unknown_channel(channels: &[SlackConversation], name: &str) -> SlkError {
+    let name = name.strip_prefix('#').unwrap_or(name);
+    let mut near: Vec<(usize, &str)> = channels
+        .iter()
+        .filter_map(|c| {
+            let distance = edit_distance(name, &c.name);
+            (c.name.contains(name) || distance <= 2).then_some((distance, c.name.as_str()))
+        })
+        .collect();
+    near.sort();
+    near.dedup();
+    let mut message = format!("unknown channel '#{}'", name);
+    if !near.is_empty() {
+        let names: Vec<String> = near
+            .iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, n)| format!("#{}", n))
+            .collect();
+        message.push_str(&format!(". Did you mean: {}?", names.join(", ")));
+    }
+    SlkError::from(message)
+}
+
+/// Whether `s` is already a user ID (`U…` or, on Enterprise Grid, `W…`).
+pub fn is_user_id(s: &str) -> bool {
+    s.len() > 1
+        && s.starts_with(['U', 'W'])
+        && s.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+/// Whether a user was given by email address rather than by name.
+pub fn is_email(s: &str) -> bool {
+    s.find('@').is_some_and(|at| at > 0 && s[at + 1..].contains('.'))
+}
+
+/// The ID of the user whose handle (`@kanta`, with or without the `@`) or,
+/// failing that, display or real name is `name`, ignoring case. `None` if
+/// nobody matches; several people sharing a name is an error listing them.
+pub fn find_user(users: &[SlackUser], name: &str) -> Result<Option<String>, SlkError> {
+    let name = name.strip_prefix('@').unwrap_or(name);
+    let by = |field: fn(&SlackUser) -> &str| -> Vec<&SlackUser> {
+        users.iter().filter(|u| field(u).eq_ignore_ascii_case(name)).collect()
+    };
+    let mut found = by(|u| &u.name);
+    if found.is_empty() {
+        found = by(|u| &u.display_name);
+    }
+    if found.is_empty() {
+        found = by(|u| &u.real_name);
+    }
+    found.dedup_by(|a, b| a.id == b.id);
+    match found.as_slice() {
+        [] => Ok(None),
+        [user] => Ok(Some(user.id.clone())),
+        _ => {
+            let who: Vec<String> = found.iter().map(|u| describe_user(u)).collect();
+            Err(SlkError::from(format!(
+                "user name '{}' is ambiguous: it matches {}. Use the handle or user ID instead",
+                name,
+                who.join(", ")
+            )))
+        }
+    }
+}
+
+fn describe_user(user: &SlackUser) -> String {
+    if user.name.is_empty() {
+        format!("{} ({})", user.shown_name(), user.id)
+    } else {
+        format!("@{} ({})", user.name, user.id)
+    }
+}
+
+/// The error for a user name that matches nobody, suggesting close handles
+/// and display names.
+pub fn unknown_user(users: &[SlackUser], name: &str) -> SlkError {
+    let name = name.strip_prefix('@').unwrap_or(name);
+    let lower = name.to_lowercase();
+    let mut near: Vec<(usize, String)> = users
+        .iter()
+        .filter_map(|u| {
+            [&u.name, &u.display_name]
+                .into_iter()
+                .filter(|n| !n.is_empty())
+                .map(|n| (edit_distance(&lower, &n.to_lowercase()), n))
+                .min()
+                .filter(|(distance, n)| *distance <= 2 || n.to_lowercase().contains(&lower))
+                .map(|(distance, _)| (distance, describe_user(u)))
+        })
+        .collect();
+    near.sort();
+    near.dedup();
+    let mut message = format!("unknown user '{}'", name);
+    if !near.is_empty() {
+        let who: Vec<String> = near.into_iter().take(MAX_SUGGESTIONS).map(|(_, w)| w).collect();
+        message.push_str(&format!(". Did you mean: {}?", who.join(", ")));
+    }
+    SlkError::from(message)
+}
+
+/// Levenshtein distance between two strings, by characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels(pairs: &[(&str, &str)]) -> Vec<SlackConversation> {
+        pairs
+            .iter()
+            .map(|(id, name)| SlackConversation { id: id.to_string(), name: name.to_string() })
+            .collect()
+    }
+
+    #[test]
+    fn test_is_channel_id() {
+        assert!(is_channel_id("C081VT5GLQH"));
+        assert!(is_channel_id("G0123"));
+        assert!(!is_channel_id("general"));
+        assert!(!is_channel_id("#general"));
+        assert!(!is_channel_id("C"));
+        assert!(!is_channel_id("Cafe"));
+    }
+
+    #[test]
+    fn test_find_channel() {
+        let list = channels(&[("C1", "general"), ("C2", "random"), ("C3", "dup"), ("C4", "dup")]);
+        assert_eq!(find_channel(&list, "general").unwrap().as_deref(), Some("C1"));
+        assert_eq!(find_channel(&list, "#random").unwrap().as_deref(), Some("C2"));
+        assert_eq!(find_channel(&list, "gen").unwrap(), None);
+        let err = find_channel(&list, "dup").unwrap_err();
+        assert!(err.message.contains("ambiguous: it matches C3, C4"));
+    }
+
+    #[test]
+    fn test_unknown_channel_suggestions() {
+        let list = channels(&[("C1", "general"), ("C2", "dev-general"), ("C3", "random")]);
+        assert_eq!(
+            unknown_channel(&list, "#genral").message,
+            "unknown channel '#genral'. Did you mean: #general?"
+        );
+        assert_eq!(
+            unknown_channel(&list, "general-x").message,
+            "unknown channel '#general-x'. Did you mean: #general?"
+        );
+        assert_eq!(
+            unknown_channel(&list, "eneral").message,
+            "unknown channel '#eneral'. Did you mean: #general, #dev-general?"
+        );
+        assert_eq!(unknown_channel(&list, "zzz").message, "unknown channel '#zzz'");
+    }
+
+    fn user(id: &str, name: &str, display_name: &str) -> SlackUser {
+        SlackUser {
+            id: id.to_string(),
+            name: name.to_string(),
+            display_name: display_name.to_string(),
+            ..SlackUser::default()
+        }
+    }
+
+    #[test]
+    fn test_is_user_id_and_email() {
+        assert!(is_user_id("U081R4ZS5E2"));
+        assert!(is_user_id("W012"));
+        assert!(!is_user_id("@kanta"));
+        assert!(!is_user_id("Ursula"));
+        assert!(is_email("kanta@example.com"));
+        assert!(!is_email("@kanta"));
+        assert!(!is_email("kanta@localhost"));
+    }
+
+    #[test]
+    fn test_find_user() {
+        let users = vec![
+            user("U1", "kanta", "Kanta O"),
+            user("U2", "taro", "Taro"),
+            user("U3", "taro2", "Taro"),
+        ];
+        assert_eq!(find_user(&users, "@kanta").unwrap().as_deref(), Some("U1"));
+        assert_eq!(find_user(&users, "KANTA O").unwrap().as_deref(), Some("U1"));
+        // A handle wins over someone else's display name.
+        assert_eq!(find_user(&users, "taro").unwrap().as_deref(), Some("U2"));
+        assert_eq!(find_user(&users, "hanako").unwrap(), None);
+
+        let users = vec![user("U2", "taro", "Taro"), user("U3", "taro2", "taro")];
+        assert_eq!(find_user(&users, "Taro").unwrap().as_deref(), Some("U2"));
+        let users = vec![user("U2", "t1", "Taro"), user("U3", "t2", "taro")];
+        let err = find_user(&users, "taro").unwrap_err();
+        assert!(err.message.contains("ambiguous: it matches @t1 (U2), @t2 (U3)"));
+    }
+
+    #[test]
+    fn test_unknown_user_suggestions() {
+        let users = vec![user("U1", "kanta", "Kanta O"), user("U2", "taro", "")];
+        assert_eq!(
+            unknown_user(&users, "@kamta").message,
+            "unknown user 'kamta'. Did you mean: @kanta (U1)?"
+        );
+        assert_eq!(unknown_user(&users, "zzz").message, "unknown user 'zzz'");
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+}
@@ -0,0 +1,399 @@
+//! Typed responses of the Web API methods slk calls. [`parse`] checks `ok`
+//! and maps the body onto the method's struct, so a field Slack renamed or
+//! dropped fails with the method and field named rather than later, deep in
+//! a command.
+
+use crate::error::{ErrorKind, SlkError};
+use crate::json::{self, Node};
+use crate::message::{self, AuthIdentity, SlackConversation, SlackMessage, SlackUser};
+
+/// A response body that can be read from an API method's JSON.
+pub trait FromResponse: Sized {
+    /// The API method, for error messages.
+    const METHOD: &'static str;
+
+    /// Reads a successful (`"ok": true`) response.
+    fn from_response(response: &impl Node) -> Result<Self, SlkError>;
+}
+
+/// Parses a raw response body as `T`. Fails with Slack's error when `ok` is
+/// false, and with the method and the offending field when the body doesn't
+/// have the expected shape.
+pub fn parse<T: FromResponse>(raw_json: &str) -> Result<T, SlkError> {
+    read(&json::parse_borrowed(raw_json)?)
+}
+
+/// Reads an already parsed response, such as a page of
+/// [`Pages`](crate::slack_api::Pages), as `T`, the way [`parse`] does.
+pub fn read<T: FromResponse>(response: &impl Node) -> Result<T, SlkError> {
+    message::check_ok(response)?;
+    T::from_response(response)
+        .map_err(|e| SlkError::new(ErrorKind::Parse, format!("unexpected {} response: {}", T::METHOD, e)))
+}
+
+/// The `key` array of a response.
+fn array<'a, N: Node>(response: &'a N, key: &str) -> Result<&'a [N], SlkError> {
+    let value = response.get(key).ok_or_else(|| SlkError::from(format!("missing '{}'", key)))?;
+    value.as_slice().ok_or_else(|| SlkError::from(format!("'{}' is not an array", key)))
+}
+
+/// The `key` string of a response.
+fn string<N: Node>(response: &N, key: &str) -> Result<String, SlkError> {
+    let value = response.get(key).ok_or_else(|| SlkError::from(format!("missing '{}'", key)))?;
+    value
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| SlkError::from(format!("'{}' is not a string", key)))
+}
+
+/// The `key` object of a response.
+fn object<'a, N: Node>(response: &'a N, key: &str) -> Result<&'a N, SlkError> {
+    response.get(key).ok_or_else(|| SlkError::from(format!("missing '{}'", key)))
+}
+
+/// `conversations.history`: a page of a channel's messages, newest first.
+#[derive(Debug, PartialEq)]
+pub struct ConversationsHistoryResponse {
+    pub messages: Vec<SlackMessage>,
+    pub has_more: bool,
+    /// The cursor for the next page, if there is one.
+    pub next_cursor: Option<String>,
+}
+
+impl FromResponse for ConversationsHistoryResponse {
+    const METHOD: &'static str = "conversations.history";
+
+    fn from_response(response: &impl Node) -> Result<Self, SlkError> {
+        Ok(ConversationsHistoryResponse {
+            messages: array(response, "messages")?.iter().map(message::extract_message).collect(),
+            has_more: response.get("has_more").and_then(|v| v.as_bool()).unwrap_or(false),
+            next_cursor: message::extract_next_cursor(response),
+        })
+    }
+}
+
+/// `conversations.replies`: a page of a thread, parent first.
+#[derive(Debug, PartialEq)]
+pub struct ConversationsRepliesResponse {
+    pub messages: Vec<SlackMessage>,
+    pub has_more: bool,
+    pub next_cursor: Option<String>,
+}
+
+impl FromResponse for ConversationsRepliesResponse {
+    const METHOD: &'static str = "conversations.replies";
+
+    fn from_response(response: &impl Node) -> Result<Self, SlkError> {
+        Ok(ConversationsRepliesResponse {
+            messages: array(response, "messages")?.iter().map(message::extract_message).collect(),
+            has_more: response.get("has_more").and_then(|v| v.as_bool()).unwrap_or(false),
+            next_cursor: message::extract_next_cursor(response),
+        })
+    }
+}
+
+/// `conversations.list` or `users.conversations`.
+#[derive(Debug, PartialEq)]
+pub struct ConversationsListResponse {
+    pub channels: Vec<SlackConversation>,
+    pub next_cursor: Option<String>,
+}
+
+impl FromResponse for ConversationsListResponse {
+    const METHOD: &'static str = "conversations.list";
+
+    fn from_response(response: &impl Node) -> Result<Self, SlkError> {
+        let channels = array(response, "channels")?
+            .iter()
+            .map(|c| {
+                Ok(SlackConversation {
+                    id: string(c, "id")?,
+                    name: c.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                })
+            })
+            .collect::<Result<_, SlkError>>()?;
+        Ok(ConversationsListResponse { channels, next_cursor: message::extract_next_cursor(response) })
+    }
+}
+
+/// `conversations.info` (with `include_num_members`): what the header of
+/// `slk history` says about a conversation.
+#[derive(Debug, PartialEq)]
+pub struct ConversationsInfoResponse {
+    pub id: String,
+    /// Empty for a DM.
+    pub name: String,
+    /// The other person in a DM.
+    pub user: Option<String>,
+    pub num_members: Option<u64>,
+    pub topic: String,
+}
+
+impl FromResponse for ConversationsInfoResponse {
+    const METHOD: &'static str = "conversations.info";
+
+    fn from_response(response: &impl Node) -> Result<Self, SlkError> {
+        let channel = object(response, "channel")?;
+        let topic = channel.get("topic").and_then(|t| t.get("value")).and_then(|v| v.as_str());
+        Ok(ConversationsInfoResponse {
+            id: string(channel, "id")?,
+            name: channel.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            user: channel.get("user").and_then(|v| v.as_str()).map(String::from),
+            num_members: channel.get("num_members").and_then(|v| v.as_u64()),
+            topic: topic.unwrap_or("").to_string(),
+        })
+    }
+}
+
+/// `users.info`.
+#[derive(Debug, PartialEq)]
+pub struct UserInfoResponse {
+    pub user: SlackUser,
+}
+
+impl FromResponse for UserInfoResponse {
+    const METHOD: &'static str = "users.info";
+
+    fn from_response(response: &impl Node) -> Result<Self, SlkError> {
+        Ok(UserInfoResponse { user: message::extract_user(object(response, "user")?) })
+    }
+}
+
+/// `users.lookupByEmail`.
+#[derive(Debug, PartialEq)]
+pub struct UserLookupResponse {
+    pub user: SlackUser,
+}
+
+impl FromResponse for UserLookupResponse {
+    const METHOD: &'static str = "users.lookupByEmail";
+
+    fn from_response(response: &impl Node) -> Result<Self, SlkError> {
+        Ok(UserLookupResponse { user: message::extract_user(object(response, "user")?) })
+    }
+}
+
+/// `auth.test`: who the token belongs to.
+impl FromResponse for AuthIdentity {
+    const METHOD: &'static str = "auth.test";
+
+    fn from_response(response: &impl Node) -> Result<Self, SlkError> {
+        Ok(AuthIdentity {
+            user_id: string(response, "user_id")?,
+            user: string(response, "user")?,
+            team_id: string(response, "team_id")?,
+            team: string(response, "team")?,
+            url: string(response, "url")?,
+        })
+    }
+}
+
+/// `chat.postMessage`: where the new message went.
+#[derive(Debug, PartialEq)]
+pub struct ChatPostMessageResponse {
+    pub channel: String,
+    pub ts: String,
+}
+
+impl FromResponse for ChatPostMessageResponse {
+    const METHOD: &'static str = "chat.postMessage";
+
+    fn from_response(response: &impl Node) -> Result<Self, SlkError> {
+        Ok(ChatPostMessageResponse { channel: string(response, "channel")?, ts: string(response, "ts")? })
+    }
+}
+
+/// `chat.getPermalink`: a link to a message.
+#[derive(Debug, PartialEq)]
+pub struct ChatGetPermalinkResponse {
+    pub permalink: String,
+}
+
+impl FromResponse for ChatGetPermalinkResponse {
+    const METHOD: &'static str = "chat.getPermalink";
+
+    fn from_response(response: &impl Node) -> Result<Self, SlkError> {
+        Ok(ChatGetPermalinkResponse { permalink: string(response, "permalink")? })
+    }
+}
+
+/// `apps.connections.open`: the Socket Mode WebSocket URL.
+#[derive(Debug, PartialEq)]
+pub struct AppsConnectionsOpenResponse {
+    pub url: String,
+}
+
+impl FromResponse for AppsConnectionsOpenResponse {
+    const METHOD: &'static str = "apps.connections.open";
+
+    fn from_response(response: &impl Node) -> Result<Self, SlkError> {
+        Ok(AppsConnectionsOpenResponse { url: string(response, "url")? })
+    }
+}
+
+/// `rtm.connect`: the RTM WebSocket URL.
+#[derive(Debug, PartialEq)]
+pub struct RtmConnectResponse {
+    pub url: String,
+}
+
+impl FromResponse for RtmConnectResponse {
+    const METHOD: &'static str = "rtm.connect";
+
+    fn from_response(response: &impl Node) -> Result<Self, SlkError> {
+        Ok(RtmConnectResponse { url: string(response, "url")? })
+    }
+}
+
+/// A response whose body doesn't matter, such as `conversations.mark` or
+/// `auth.revoke`'s: only `ok` is checked.
+#[derive(Debug, PartialEq)]
+pub struct Empty;
+
+impl FromResponse for Empty {
+    const METHOD: &'static str = "";
+
+    fn from_response(_: &impl Node) -> Result<Self, SlkError> {
+        Ok(Empty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let response: ConversationsHistoryResponse = parse(
+            r#"{"ok": true, "messages": [{"user": "U1", "text": "hi", "ts": "1770689887.565249"}],
+                "has_more": true, "response_metadata": {"next_cursor": "bmV4dA=="}}"#,
+        )
+        .unwrap();
+        assert_eq!(response.messages[0].text, "hi");
+        assert!(response.has_more);
+        assert_eq!(response.next_cursor.as_deref(), Some("bmV4dA=="));
+
+        let err = parse::<ConversationsHistoryResponse>(r#"{"ok": true, "messages": {}}"#).unwrap_err();
+        assert_eq!(err.message, "unexpected conversations.history response: 'messages' is not an array");
+        let err = parse::<ConversationsRepliesResponse>(r#"{"ok": true}"#).unwrap_err();
+        assert_eq!(err.message, "unexpected conversations.replies response: missing 'messages'");
+        assert_eq!(err.kind, ErrorKind::Parse);
+    }
+
+    #[test]
+    fn test_api_errors() {
+        let err = parse::<UserInfoResponse>(r#"{"ok": false, "error": "user_not_found"}"#).unwrap_err();
+        assert!(err.message.contains("user_not_found"));
+        let err = parse::<UserInfoResponse>(
+            r#"{"ok": false, "error": "missing_scope", "needed": "users:read", "provided": "channels:history"}"#,
+        )
+        .unwrap_err();
+        assert!(err.message.contains("missing_scope"));
+        assert!(err.message.contains("users:read"));
+        assert!(err.message.contains("channels:history"));
+        assert_eq!(err.kind, ErrorKind::MissingScope);
+        let err = parse::<Empty>(r#"{"ok": false, "error": "token_revoked"}"#).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Auth);
+        assert!(!err.is_retryable());
+        assert!(parse::<Empty>(r#"{"ok": false, "error": "internal_error"}"#).unwrap_err().is_retryable());
+        let err = parse::<Empty>(r#"{"ok": false, "error": "channel_not_found"}"#).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Api { code: "channel_not_found".to_string() });
+        assert_eq!(err.kind.exit_code(), 8);
+        assert!(parse::<Empty>(r#"{"ok": false, "error": "not_in_channel"}"#).is_err());
+        assert_eq!(parse::<Empty>(r#"{"ok": true}"#).unwrap(), Empty);
+    }
+
+    #[test]
+    fn test_user_info() {
+        let name = |user: &str| {
+            let response: UserInfoResponse = parse(&format!(r#"{{"ok": true, "user": {}}}"#, user)).unwrap();
+            response.user.shown_name().to_string()
+        };
+        assert_eq!(
+            name(r#"{"name": "kanta", "real_name": "Kanta Otomaeru", "profile": {"display_name": "kanta"}}"#),
+            "kanta"
+        );
+        assert_eq!(
+            name(r#"{"name": "kanta", "real_name": "Kanta Otomaeru", "profile": {"display_name": ""}}"#),
+            "Kanta Otomaeru"
+        );
+        assert_eq!(name(r#"{"name": "kanta", "profile": {"display_name": ""}}"#), "kanta");
+        let err = parse::<UserLookupResponse>(r#"{"ok": true}"#).unwrap_err();
+        assert_eq!(err.message, "unexpected users.lookupByEmail response: missing 'user'");
+    }
+
+    #[test]
+    fn test_conversations_list() {
+        let response: ConversationsListResponse = parse(
+            r#"{"ok": true, "channels": [{"id": "C081VT5GLQH", "name": "general"}, {"id": "C092X3AB7F1"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            response.channels,
+            [
+                SlackConversation { id: "C081VT5GLQH".to_string(), name: "general".to_string() },
+                SlackConversation { id: "C092X3AB7F1".to_string(), name: String::new() },
+            ]
+        );
+        assert_eq!(response.next_cursor, None);
+        let err = parse::<ConversationsListResponse>(r#"{"ok": true, "channels": [{"name": "general"}]}"#)
+            .unwrap_err();
+        assert_eq!(err.message, "unexpected conversations.list response: missing 'id'");
+    }
+
+    #[test]
+    fn test_conversations_info() {
+        let info: ConversationsInfoResponse = parse(
+            r#"{"ok": true, "channel": {"id": "C1", "name": "general", "num_members": 42,
+                "topic": {"value": "Company-wide announcements", "creator": "U1"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            info,
+            ConversationsInfoResponse {
+                id: "C1".to_string(),
+                name: "general".to_string(),
+                user: None,
+                num_members: Some(42),
+                topic: "Company-wide announcements".to_string(),
+            }
+        );
+        let info: ConversationsInfoResponse =
+            parse(r#"{"ok": true, "channel": {"id": "D1", "is_im": true, "user": "U2"}}"#).unwrap();
+        assert_eq!((info.name.as_str(), info.user.as_deref(), info.num_members), ("", Some("U2"), None));
+    }
+
+    #[test]
+    fn test_auth_test() {
+        let identity: AuthIdentity = parse(
+            r#"{"ok": true, "url": "https://myteam.slack.com/", "team": "My Team", "user": "kanta",
+                "team_id": "T012AB3C4", "user_id": "U081R4ZS5E2"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            identity,
+            AuthIdentity {
+                user_id: "U081R4ZS5E2".to_string(),
+                user: "kanta".to_string(),
+                team_id: "T012AB3C4".to_string(),
+                team: "My Team".to_string(),
+                url: "https://myteam.slack.com/".to_string(),
+            }
+        );
+        let err = parse::<AuthIdentity>(r#"{"ok": false, "error": "invalid_auth"}"#).unwrap_err();
+        assert!(err.message.contains("invalid_auth"));
+        let err = parse::<AuthIdentity>(r#"{"ok": true, "user_id": 7}"#).unwrap_err();
+        assert_eq!(err.message, "unexpected auth.test response: 'user_id' is not a string");
+    }
+
+    #[test]
+    fn test_post_message_and_socket_url() {
+        let posted: ChatPostMessageResponse =
+            parse(r#"{"ok": true, "channel": "C1", "ts": "1770689900.000100"}"#).unwrap();
+        assert_eq!(posted, ChatPostMessageResponse { channel: "C1".to_string(), ts: "1770689900.000100".to_string() });
+        let socket: AppsConnectionsOpenResponse = parse(r#"{"ok": true, "url": "wss://example.com/link"}"#).unwrap();
+        assert_eq!(socket.url, "wss://example.com/link");
+        let rtm: RtmConnectResponse = parse(r#"{"ok": true, "url": "wss://example.com/rtm"}"#).unwrap();
+        assert_eq!(rtm.url, "wss://example.com/rtm");
+    }
+}
@@ -0,0 +1,84 @@
+//! Slack's legacy RTM API, for `slk watch --events`: the only stream that
+//! carries typing indicators and presence changes, which Socket Mode never
+//! delivers. Only tokens of classic Slack apps may open it.
+
+use crate::error::SlkError;
+use crate::http;
+use crate::json::{self, JsonValue};
+use crate::response::{self, RtmConnectResponse};
+use crate::slack_api::SlackClient;
+use crate::socket_mode::Activity;
+use crate::websocket::{self, WebSocket};
+
+/// Asks Slack for an RTM connection URL with the user token and opens it.
+pub fn connect(client: &http::Client, api: &SlackClient) -> Result<WebSocket, SlkError> {
+    let url = response::parse::<RtmConnectResponse>(&api.rtm_connect()?)?.url;
+    websocket::connect(client, &url)
+}
+
+/// The message that asks for the presence changes of `users`, replacing any
+/// earlier list: RTM only sends those it is asked for.
+pub fn presence_sub<'a>(users: impl IntoIterator<Item = &'a str>) -> String {
+    let ids = users.into_iter().map(JsonValue::from).collect();
+    JsonValue::object([("type", "presence_sub".into()), ("ids", JsonValue::Array(ids))]).to_string()
+}
+
+/// A typing indicator or presence change from an RTM message. Everything
+/// else RTM sends (messages, reactions, ...) comes over Socket Mode too and
+/// is left to it.
+pub fn parse_event(text: &str) -> Result<Option<Activity>, SlkError> {
+    let event = json::parse(text)?;
+    let field = |name: &str| event.get(name).and_then(|f| f.as_str()).map(String::from);
+    let (Some(kind), Some(user)) = (field("type"), field("user")) else {
+        return Ok(None);
+    };
+    let (channel, action) = match kind.as_str() {
+        "user_typing" => match field("channel") {
+            Some(channel) => (Some(channel), "is typing".to_string()),
+            None => return Ok(None),
+        },
+        "presence_change" => match field("presence") {
+            Some(presence) => (None, format!("is {}", presence)),
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(Activity { kind, channel, user, action }))
+}
+
+/// Whether Slack is about to close the connection (`goodbye`).
+pub fn is_goodbye(text: &str) -> bool {
+    json::parse(text).is_ok_and(|event| event.get("type").and_then(|t| t.as_str()) == Some("goodbye"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_event() {
+        let typing = parse_event(r#"{"type": "user_typing", "channel": "C1", "user": "U2"}"#).unwrap().unwrap();
+        assert_eq!(
+            typing,
+            Activity {
+                kind: "user_typing".to_string(),
+                channel: Some("C1".to_string()),
+                user: "U2".to_string(),
+                action: "is typing".to_string(),
+            }
+        );
+        let presence = parse_event(r#"{"type": "presence_change", "user": "U2", "presence": "away"}"#).unwrap();
+        let presence = presence.unwrap();
+        assert_eq!((presence.channel, presence.action.as_str()), (None, "is away"));
+        let message = r#"{"type": "message", "channel": "C1", "user": "U2", "text": "hi", "ts": "1.0"}"#;
+        assert_eq!(parse_event(message).unwrap(), None);
+        assert_eq!(parse_event(r#"{"type": "hello"}"#).unwrap(), None);
+        assert!(is_goodbye(r#"{"type": "goodbye"}"#));
+        assert!(!is_goodbye(r#"{"type": "hello"}"#));
+    }
+
+    #[test]
+    fn test_presence_sub() {
+        assert_eq!(presence_sub(["U1", "U2"]), r#"{"type":"presence_sub","ids":["U1","U2"]}"#);
+    }
+}
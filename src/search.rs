@@ -0,0 +1,116 @@
+use crate::archive::Archive;
+use crate::error::SlkError;
+use crate::message::{self, SlackMessage};
+
+/// What `slk search --local` looks for: messages containing every word of
+/// the query, ignoring case, that pass the channel, author and date filters.
+#[derive(Debug, Default)]
+pub struct Query {
+    terms: Vec<String>,
+    /// Channel IDs to search; every archived channel when empty.
+    pub channels: Vec<String>,
+    /// Only messages by this user ID.
+    pub from: Option<String>,
+    /// Only messages at or after this Unix time.
+    pub since: Option<u64>,
+    /// Only messages before this Unix time.
+    pub until: Option<u64>,
+}
+
+impl Query {
+    pub fn new(text: &str) -> Self {
+        Query { terms: text.split_whitespace().map(str::to_lowercase).collect(), ..Query::default() }
+    }
+
+    /// Whether the query has nothing to look for: no words and no filters.
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+            && self.channels.is_empty()
+            && self.from.is_none()
+            && self.since.is_none()
+            && self.until.is_none()
+    }
+
+    fn matches(&self, m: &SlackMessage) -> bool {
+        let secs = crate::message::ts_key(&m.ts).0;
+        let text = m.text.to_lowercase();
+        self.from.as_ref().is_none_or(|from| m.user == *from)
+            && self.since.is_none_or(|since| secs >= since)
+            && self.until.is_none_or(|until| secs < until)
+            && self.terms.iter().all(|term| text.contains(term.as_str()))
+    }
+}
+
+/// The archived messages matching `query`, oldest first, each with the ID of
+/// its channel.
+pub fn search(archive: &Archive, query: &Query) -> Result<Vec<(String, SlackMessage)>, SlkError> {
+    let channels = if query.channels.is_empty() {
+        archive.channels().into_iter().map(|c| c.id).collect()
+    } else {
+        query.channels.clone()
+    };
+    let mut found = Vec::new();
+    for channel in channels {
+        for m in archive.messages(&channel)? {
+            let m = message::extract_message(&m);
+            if query.matches(&m) {
+                found.push((channel.clone(), m));
+            }
+        }
+    }
+    found.sort_by_key(|(_, m)| crate::message::ts_key(&m.ts));
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::ArchivedChannel;
+    use crate::json;
+
+    fn message(user: &str, text: &str, ts: &str) -> json::JsonValue {
+        json::parse(&format!(r#"{{"user":"{}","text":"{}","ts":"{}"}}"#, user, text, ts)).unwrap()
+    }
+
+    #[test]
+    fn test_search() {
+        let dir = std::env::temp_dir().join(format!("slk-search-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let archive = Archive::at(dir.clone());
+        for (id, name) in [("C1", "general"), ("C2", "ops")] {
+            let channel = ArchivedChannel { id: id.to_string(), name: name.to_string(), synced_at: 0, latest: None };
+            archive.record_sync(channel).unwrap();
+        }
+        archive
+            .store_messages(
+                "C1",
+                vec![
+                    message("U1", "Deploy failed again", "1770681600.000100"),
+                    message("U2", "lunch?", "1770681700.000100"),
+                ],
+            )
+            .unwrap();
+        archive
+            .store_messages("C2", vec![message("U2", "deploy FAILED on prod", "1770600000.000100")])
+            .unwrap();
+
+        let texts = |query: &Query| -> Vec<(String, String)> {
+            search(&archive, query).unwrap().into_iter().map(|(c, m)| (c, m.text)).collect()
+        };
+        let query = Query::new("failed deploy");
+        assert_eq!(
+            texts(&query),
+            [
+                ("C2".to_string(), "deploy FAILED on prod".to_string()),
+                ("C1".to_string(), "Deploy failed again".to_string())
+            ]
+        );
+        assert_eq!(texts(&Query { channels: vec!["C1".to_string()], ..Query::new("deploy") }).len(), 1);
+        assert_eq!(texts(&Query { from: Some("U2".to_string()), ..Query::new("") }).len(), 2);
+        assert_eq!(texts(&Query { since: Some(1770681600), ..Query::new("deploy") }).len(), 1);
+        assert_eq!(texts(&Query { until: Some(1770681600), ..Query::new("deploy") }).len(), 1);
+        assert!(texts(&Query::new("deploy rollback")).is_empty());
+        assert!(Query::new(" ").is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,90 +1,724 @@
-use crate::error::SlkError;
-use std::process::Command;
+use crate::error::{ErrorKind, SlkError};
+use crate::http::{Request, Response, Transport};
+use crate::json::{self, JsonValue};
+use crate::message;
+use crate::task::{self, Pending};
+use crate::{info, warn};
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
 
-pub fn build_api_url(channel_id: &str, ts: &str) -> String {
+const MAX_RATE_LIMIT_WAIT_SECS: u64 = 120;
+const DEFAULT_RETRY_AFTER_SECS: u64 = 30;
+const DEFAULT_MAX_RETRIES: u32 = 3;
+/// How many calls `SlackClient::concurrently` has in flight at once.
+const MAX_IN_FLIGHT: usize = 4;
+
+/// How transient failures (network errors, 5xx, `internal_error`) are retried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: DEFAULT_MAX_RETRIES,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Reads the retry count from `SLK_MAX_RETRIES`, falling back to the default.
+    pub fn from_env() -> Self {
+        let mut policy = RetryPolicy::default();
+        if let Some(n) = std::env::var("SLK_MAX_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            policy.max_retries = n;
+        }
+        policy
+    }
+
+    /// Upper bound of the backoff window for the given attempt (0-based).
+    fn backoff_ceiling(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt).unwrap_or(u32::MAX);
+        self.base_delay
+            .saturating_mul(factor)
+            .min(self.max_delay)
+    }
+
+    /// "Full jitter" backoff: a random delay between zero and the ceiling.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self.backoff_ceiling(attempt).as_millis() as u64;
+        if ceiling == 0 {
+            return Duration::ZERO;
+        }
+        let random = RandomState::new().build_hasher().finish();
+        Duration::from_millis(random % (ceiling + 1))
+    }
+}
+
+enum FetchError {
+    /// Worth retrying: the request may succeed if sent again.
+    Transient(SlkError),
+    Fatal(SlkError),
+}
+
+pub fn build_api_url(base_url: &str, channel_id: &str, ts: &str) -> String {
     format!(
-        "https://slack.com/api/conversations.replies?channel={}&ts={}",
-        channel_id, ts
+        "{}/conversations.replies?channel={}&ts={}",
+        base_url, channel_id, ts
     )
 }
 
-pub fn fetch_user_info(user_id: &str, token: &str) -> Result<String, SlkError> {
-    let url = format!("https://slack.com/api/users.info?user={}", user_id);
-    let output = Command::new("curl")
-        .args(["-s", "-H", &format!("Authorization: Bearer {}", token), &url])
-        .output()
-        .map_err(|e| SlkError::from(format!("failed to execute curl: {}", e)))?;
+/// Sends `request`, alongside the other calls when run by
+/// [`SlackClient::concurrently`].
+async fn send(transport: &dyn Transport, request: &Request) -> Result<Response, FetchError> {
+    let result = if task::is_multiplexing() {
+        transport.send_async(request).await
+    } else {
+        transport.send(request)
+    };
+    result.map_err(|e| match SlkError::from(e) {
+        // A write that failed mid-flight may still have been applied, so only
+        // reads are safe to send again.
+        e if e.is_retryable() && request.method.is_idempotent() => FetchError::Transient(e),
+        e => FetchError::Fatal(e),
+    })
+}
+
+fn api_error_code(response: &Response) -> Option<String> {
+    let body = std::str::from_utf8(&response.body).ok()?;
+    crate::json::parse(body)
+        .ok()
+        .and_then(|v| v.get("error").and_then(|e| e.as_str()).map(String::from))
+}
+
+/// A failure on Slack's side, worth retrying with backoff. Rate limiting is
+/// retryable too, but waits as long as Slack asks instead.
+fn is_transient_failure(response: &Response) -> bool {
+    response.status >= 500
+        || api_error_code(response).is_some_and(|code| ErrorKind::from_api_code(&code) == ErrorKind::Network)
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(SlkError::from(format!(
-            "curl failed (exit {}): {}",
-            output.status, stderr
-        )));
+/// Returns how long Slack asked us to wait, if the response is a rate-limit rejection.
+fn rate_limit_delay(response: &Response) -> Option<u64> {
+    let limited =
+        response.status == 429 || api_error_code(response).as_deref() == Some("ratelimited");
+    if !limited {
+        return None;
     }
+    let secs = response
+        .header("Retry-After")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_RETRY_AFTER_SECS);
+    Some(secs)
+}
 
-    String::from_utf8(output.stdout)
-        .map_err(|e| SlkError::from(format!("invalid UTF-8 in response: {}", e)))
+/// Returns the body of a final (non-retried) response, or an error carrying the
+/// HTTP status, diagnostic headers, and a body excerpt when it is unusable.
+fn checked_body(response: &Response) -> Result<String, SlkError> {
+    if !(200..300).contains(&response.status) {
+        let kind = if response.status >= 500 { ErrorKind::Network } else { ErrorKind::Other };
+        return Err(SlkError::new(kind, format!("Slack API request failed ({})", response.describe())));
+    }
+    let body = response.text()?;
+    if let Err(e) = crate::json::parse(&body) {
+        return Err(SlkError::new(e.kind, format!("{} ({})", e.message, response.describe())));
+    }
+    Ok(body)
 }
 
-pub fn fetch_conversations_list(token: &str) -> Result<String, SlkError> {
-    let url = "https://slack.com/api/conversations.list?limit=200&exclude_archived=true";
-    let output = Command::new("curl")
-        .args(["-s", "-H", &format!("Authorization: Bearer {}", token), url])
-        .output()
-        .map_err(|e| SlkError::from(format!("failed to execute curl: {}", e)))?;
+/// What `chat_post` sends besides the text.
+#[derive(Debug, Default)]
+pub struct PostOptions<'a> {
+    /// Block Kit blocks, as a JSON array; the text is then what
+    /// notifications show, and may be empty.
+    pub blocks: Option<&'a str>,
+    /// The thread to reply in.
+    pub thread_ts: Option<&'a str>,
+    /// Whether a reply is also sent to the channel.
+    pub broadcast: bool,
+}
+
+/// The Slack Web API as one token sees it: where the API is, how requests
+/// are sent, and how transient failures are retried. Each method calls the
+/// API method of the same name and returns the JSON body.
+#[derive(Clone)]
+pub struct SlackClient<'a> {
+    transport: &'a dyn Transport,
+    base_url: String,
+    token: String,
+    retry: RetryPolicy,
+}
+
+impl<'a> SlackClient<'a> {
+    /// A client for `token`, retrying as `SLK_MAX_RETRIES` says.
+    pub fn new(transport: &'a dyn Transport, base_url: &str, token: &str) -> Self {
+        SlackClient {
+            transport,
+            base_url: base_url.to_string(),
+            token: token.to_string(),
+            retry: RetryPolicy::from_env(),
+        }
+    }
+
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        SlackClient { retry, ..self }
+    }
+
+    /// The same API and transport with another token, such as a refreshed
+    /// one or the app-level token.
+    pub fn with_token(&self, token: &str) -> Self {
+        SlackClient {
+            transport: self.transport,
+            base_url: self.base_url.clone(),
+            token: token.to_string(),
+            retry: self.retry.clone(),
+        }
+    }
+
+    pub fn transport(&self) -> &'a dyn Transport {
+        self.transport
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Runs the future `call` makes for each of `items`, up to `MAX_IN_FLIGHT`
+    /// at a time, and returns the results in the items' order. The calls
+    /// share this thread: the `_async` methods wait on Slack by yielding to
+    /// the others, so independent requests (the authors of a page of
+    /// messages, the channels of a watch) wait together rather than one
+    /// after another.
+    pub fn concurrently<'s, T, F: Future>(
+        &'s self,
+        items: &'s [T],
+        call: impl Fn(&'s Self, &'s T) -> F,
+    ) -> Vec<F::Output> {
+        task::join_bounded(items.iter().map(|item| call(self, item)), MAX_IN_FLIGHT)
+    }
+
+    fn get(&self, url: &str) -> Result<String, SlkError> {
+        task::block_on(self.get_async(url))
+    }
+
+    async fn get_async(&self, url: &str) -> Result<String, SlkError> {
+        let request = Request::get(url).header("Authorization", &format!("Bearer {}", self.token));
+        api_call(self.transport, &self.retry, &request).await
+    }
+
+    fn post(&self, method: &str, params: &[(&str, &str)]) -> Result<String, SlkError> {
+        let url = format!("{}/{}", self.base_url, method);
+        let request =
+            Request::post_form(&url, params).header("Authorization", &format!("Bearer {}", self.token));
+        task::block_on(api_call(self.transport, &self.retry, &request))
+    }
+
+    /// Calls any Web API method with form parameters, for the methods
+    /// without one of their own here.
+    pub fn call(&self, method: &str, params: &[(&str, &str)]) -> Result<String, SlkError> {
+        self.post(method, params)
+    }
+
+    pub fn auth_test(&self) -> Result<String, SlkError> {
+        self.get(&format!("{}/auth.test", self.base_url))
+    }
+
+    pub fn auth_revoke(&self) -> Result<String, SlkError> {
+        self.get(&format!("{}/auth.revoke", self.base_url))
+    }
+
+    pub fn users_info(&self, user_id: &str) -> Result<String, SlkError> {
+        task::block_on(self.users_info_async(user_id))
+    }
+
+    pub async fn users_info_async(&self, user_id: &str) -> Result<String, SlkError> {
+        self.get_async(&format!("{}/users.info?user={}", self.base_url, user_id)).await
+    }
+
+    /// A page of `users.list`: the first for no `cursor`.
+    pub fn users_list(&self, cursor: Option<&str>) -> Result<String, SlkError> {
+        let mut url = format!("{}/users.list?limit=1000", self.base_url);
+        if let Some(cursor) = cursor {
+            url.push_str(&format!("&cursor={}", crate::http::url_encode(cursor)));
+        }
+        self.get(&url)
+    }
+
+    pub fn users_lookup_by_email(&self, email: &str) -> Result<String, SlkError> {
+        self.get(&format!(
+            "{}/users.lookupByEmail?email={}",
+            self.base_url,
+            crate::http::url_encode(email)
+        ))
+    }
+
+    /// The first page of the public and private channels the token can see.
+    pub fn conversations_list(&self) -> Result<String, SlkError> {
+        task::block_on(self.conversations_list_page_async(None))
+    }
+
+    async fn conversations_list_page_async(&self, cursor: Option<&str>) -> Result<String, SlkError> {
+        let mut url = format!(
+            "{}/conversations.list?limit=200&exclude_archived=true&types=public_channel,private_channel",
+            self.base_url
+        );
+        if let Some(cursor) = cursor {
+            url.push_str(&format!("&cursor={}", crate::http::url_encode(cursor)));
+        }
+        self.get_async(&url).await
+    }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(SlkError::from(format!(
-            "curl failed (exit {}): {}",
-            output.status, stderr
-        )));
+    /// A conversation's details, with its member count.
+    pub fn conversations_info(&self, channel_id: &str) -> Result<String, SlkError> {
+        self.get(&format!(
+            "{}/conversations.info?channel={}&include_num_members=true",
+            self.base_url, channel_id
+        ))
     }
 
-    String::from_utf8(output.stdout)
-        .map_err(|e| SlkError::from(format!("invalid UTF-8 in response: {}", e)))
+    async fn users_conversations_page_async(&self, cursor: Option<&str>) -> Result<String, SlkError> {
+        let mut url = format!(
+            "{}/users.conversations?limit=200&exclude_archived=true&types=public_channel,private_channel",
+            self.base_url
+        );
+        if let Some(cursor) = cursor {
+            url.push_str(&format!("&cursor={}", crate::http::url_encode(cursor)));
+        }
+        self.get_async(&url).await
+    }
+
+    pub fn conversations_history(&self, channel_id: &str, limit: usize) -> Result<String, SlkError> {
+        task::block_on(self.conversations_history_async(channel_id, limit))
+    }
+
+    pub async fn conversations_history_async(
+        &self,
+        channel_id: &str,
+        limit: usize,
+    ) -> Result<String, SlkError> {
+        self.get_async(&format!(
+            "{}/conversations.history?channel={}&limit={}",
+            self.base_url, channel_id, limit
+        ))
+        .await
+    }
+
+    /// One page of a channel's history (after `oldest`, if given), newest
+    /// first; `cursor` comes from the previous page's
+    /// `response_metadata.next_cursor`.
+    pub fn conversations_history_page(
+        &self,
+        channel_id: &str,
+        oldest: Option<&str>,
+        cursor: Option<&str>,
+    ) -> Result<String, SlkError> {
+        task::block_on(self.conversations_history_page_async(channel_id, oldest, cursor))
+    }
+
+    pub async fn conversations_history_page_async(
+        &self,
+        channel_id: &str,
+        oldest: Option<&str>,
+        cursor: Option<&str>,
+    ) -> Result<String, SlkError> {
+        let mut url = format!("{}/conversations.history?channel={}&limit=200", self.base_url, channel_id);
+        if let Some(oldest) = oldest {
+            url.push_str(&format!("&oldest={}", oldest));
+        }
+        if let Some(cursor) = cursor {
+            url.push_str(&format!("&cursor={}", crate::http::url_encode(cursor)));
+        }
+        self.get_async(&url).await
+    }
+
+    /// The `limit` messages up to and including the one at `latest`, newest
+    /// first.
+    pub fn conversations_history_until(
+        &self,
+        channel_id: &str,
+        latest: &str,
+        limit: usize,
+    ) -> Result<String, SlkError> {
+        self.get(&format!(
+            "{}/conversations.history?channel={}&latest={}&inclusive=true&limit={}",
+            self.base_url, channel_id, latest, limit
+        ))
+    }
+
+    async fn conversations_history_between_async(
+        &self,
+        channel_id: &str,
+        oldest: &str,
+        latest: &str,
+        cursor: Option<&str>,
+    ) -> Result<String, SlkError> {
+        let mut url = format!(
+            "{}/conversations.history?channel={}&oldest={}&latest={}&limit=200",
+            self.base_url, channel_id, oldest, latest
+        );
+        if let Some(cursor) = cursor {
+            url.push_str(&format!("&cursor={}", crate::http::url_encode(cursor)));
+        }
+        self.get_async(&url).await
+    }
+
+    /// Messages posted after `oldest`, newest first.
+    pub fn conversations_history_since(&self, channel_id: &str, oldest: &str) -> Result<String, SlkError> {
+        task::block_on(self.conversations_history_since_async(channel_id, oldest))
+    }
+
+    pub async fn conversations_history_since_async(
+        &self,
+        channel_id: &str,
+        oldest: &str,
+    ) -> Result<String, SlkError> {
+        self.get_async(&format!(
+            "{}/conversations.history?channel={}&oldest={}&limit=200",
+            self.base_url, channel_id, oldest
+        ))
+        .await
+    }
+
+    pub fn conversations_replies(&self, channel_id: &str, ts: &str) -> Result<String, SlkError> {
+        task::block_on(self.conversations_replies_async(channel_id, ts))
+    }
+
+    pub async fn conversations_replies_async(&self, channel_id: &str, ts: &str) -> Result<String, SlkError> {
+        self.get_async(&build_api_url(&self.base_url, channel_id, ts)).await
+    }
+
+    /// Replies in a thread posted after `oldest`.
+    pub fn conversations_replies_since(&self, channel_id: &str, ts: &str, oldest: &str) -> Result<String, SlkError> {
+        task::block_on(self.conversations_replies_since_async(channel_id, ts, oldest))
+    }
+
+    pub async fn conversations_replies_since_async(
+        &self,
+        channel_id: &str,
+        ts: &str,
+        oldest: &str,
+    ) -> Result<String, SlkError> {
+        self.get_async(&format!("{}&oldest={}", build_api_url(&self.base_url, channel_id, ts), oldest)).await
+    }
+
+    /// One page of a thread's replies, parent first; `cursor` as for
+    /// `conversations_history_page`.
+    pub fn conversations_replies_page(
+        &self,
+        channel_id: &str,
+        ts: &str,
+        cursor: Option<&str>,
+    ) -> Result<String, SlkError> {
+        task::block_on(self.conversations_replies_page_async(channel_id, ts, cursor))
+    }
+
+    pub async fn conversations_replies_page_async(
+        &self,
+        channel_id: &str,
+        ts: &str,
+        cursor: Option<&str>,
+    ) -> Result<String, SlkError> {
+        let mut url = format!("{}&limit=200", build_api_url(&self.base_url, channel_id, ts));
+        if let Some(cursor) = cursor {
+            url.push_str(&format!("&cursor={}", crate::http::url_encode(cursor)));
+        }
+        self.get_async(&url).await
+    }
+
+    /// Every page of a channel's history after `oldest` (or of all of it),
+    /// newest first, fetched as the iterator is advanced.
+    pub fn history_pages(&self, channel_id: &str, oldest: Option<&str>) -> Pages<'_> {
+        let (channel_id, oldest) = (channel_id.to_string(), oldest.map(String::from));
+        Pages::new(move |cursor| {
+            let (channel_id, oldest) = (channel_id.clone(), oldest.clone());
+            Box::pin(async move {
+                self.conversations_history_page_async(&channel_id, oldest.as_deref(), cursor.as_deref()).await
+            })
+        })
+    }
+
+    /// Every page of the messages posted after `oldest` and before `latest`,
+    /// newest first, fetched as the iterator is advanced.
+    pub fn history_pages_between(&self, channel_id: &str, oldest: &str, latest: &str) -> Pages<'_> {
+        let (channel_id, oldest, latest) = (channel_id.to_string(), oldest.to_string(), latest.to_string());
+        Pages::new(move |cursor| {
+            let (channel_id, oldest, latest) = (channel_id.clone(), oldest.clone(), latest.clone());
+            Box::pin(async move {
+                self.conversations_history_between_async(&channel_id, &oldest, &latest, cursor.as_deref()).await
+            })
+        })
+    }
+
+    /// Every page of a thread, parent first, fetched as the iterator is
+    /// advanced.
+    pub fn replies_pages(&self, channel_id: &str, ts: &str) -> Pages<'_> {
+        let (channel_id, ts) = (channel_id.to_string(), ts.to_string());
+        Pages::new(move |cursor| {
+            let (channel_id, ts) = (channel_id.clone(), ts.clone());
+            Box::pin(async move {
+                self.conversations_replies_page_async(&channel_id, &ts, cursor.as_deref()).await
+            })
+        })
+    }
+
+    /// Every page of `conversations.list`, fetched as the iterator is
+    /// advanced.
+    pub fn conversations_pages(&self) -> Pages<'_> {
+        Pages::new(move |cursor| {
+            Box::pin(async move { self.conversations_list_page_async(cursor.as_deref()).await })
+        })
+    }
+
+    /// Every page of the public and private channels the token's user is a
+    /// member of (`users.conversations`), fetched as the iterator is advanced.
+    pub fn users_conversations_pages(&self) -> Pages<'_> {
+        Pages::new(move |cursor| {
+            Box::pin(async move { self.users_conversations_page_async(cursor.as_deref()).await })
+        })
+    }
+
+    /// Posts `text` to a channel, or as a reply when `thread_ts` is given.
+    pub fn chat_post_message(&self, channel_id: &str, text: &str, thread_ts: Option<&str>) -> Result<String, SlkError> {
+        self.chat_post(channel_id, text, &PostOptions { thread_ts, ..PostOptions::default() })
+    }
+
+    /// `chat_post_message` with blocks, or a reply also sent to the channel.
+    pub fn chat_post(&self, channel_id: &str, text: &str, options: &PostOptions) -> Result<String, SlkError> {
+        let mut params = vec![("channel", channel_id)];
+        if let Some(blocks) = options.blocks {
+            params.push(("blocks", blocks));
+        }
+        if !text.is_empty() || options.blocks.is_none() {
+            params.push(("text", text));
+        }
+        if let Some(ts) = options.thread_ts {
+            params.push(("thread_ts", ts));
+            if options.broadcast {
+                params.push(("reply_broadcast", "true"));
+            }
+        }
+        self.post("chat.postMessage", &params)
+    }
+
+    /// A link to the message at `ts` that opens it in Slack.
+    pub fn chat_get_permalink(&self, channel_id: &str, ts: &str) -> Result<String, SlkError> {
+        self.get(&format!(
+            "{}/chat.getPermalink?channel={}&message_ts={}",
+            self.base_url, channel_id, ts
+        ))
+    }
+
+    /// Moves the user's read marker in a channel to `ts`.
+    pub fn conversations_mark(&self, channel_id: &str, ts: &str) -> Result<String, SlkError> {
+        self.post("conversations.mark", &[("channel", channel_id), ("ts", ts)])
+    }
+
+    /// Asks for a Socket Mode WebSocket URL. Needs an app-level (`xapp-`) token.
+    pub fn apps_connections_open(&self) -> Result<String, SlkError> {
+        self.post("apps.connections.open", &[])
+    }
+
+    /// Asks for an RTM WebSocket URL, with presence changes only for the
+    /// users subscribed to. Only classic apps' tokens may.
+    pub fn rtm_connect(&self) -> Result<String, SlkError> {
+        self.post("rtm.connect", &[("presence_sub", "true")])
+    }
+
+    /// Downloads a file Slack hosts (a file's `url_private_download`),
+    /// following redirects. The token only goes to Slack hosts over https.
+    /// Gives `None` for a file bigger than `max_bytes`, without reading the
+    /// rest of it.
+    pub fn download_file(&self, url: &str, max_bytes: u64) -> Result<Option<Vec<u8>>, SlkError> {
+        let mut url = url.to_string();
+        for _ in 0..=MAX_REDIRECTS {
+            let mut request = Request::get(&url).max_body(max_bytes);
+            if self.trusts(&crate::http::parse_url(&url)?) {
+                request = request.header("Authorization", &format!("Bearer {}", self.token));
+            }
+            let response = match self.transport.send(&request) {
+                Err(crate::http::Error::BodyTooLarge(_)) => return Ok(None),
+                result => result?,
+            };
+            if let (300..=399, Some(location)) = (response.status, response.header("Location")) {
+                url = crate::http::resolve_url(&url, location)?;
+                continue;
+            }
+            if !(200..300).contains(&response.status) {
+                return Err(SlkError::from(format!("download failed ({})", response.describe())));
+            }
+            if response.header("Content-Type").is_some_and(|t| t.starts_with("text/html")) {
+                return Err(SlkError::from(
+                    "Slack sent a web page instead of the file; the token may lack the files:read scope",
+                ));
+            }
+            return Ok(Some(response.body));
+        }
+        Err(SlkError::from(format!("download failed: more than {} redirects", MAX_REDIRECTS)))
+    }
+
+    /// Whether the token may be sent to `target`: over https, to Slack or to
+    /// the API host this client talks to.
+    fn trusts(&self, target: &crate::http::Target) -> bool {
+        let host = target.host.to_ascii_lowercase();
+        let api_host = crate::http::parse_url(&self.base_url).map(|api| api.host.to_ascii_lowercase());
+        target.tls && (host.ends_with(".slack.com") || api_host.is_ok_and(|api| api == host))
+    }
+}
+
+/// Fetches the page at a cursor (the first page for none).
+type FetchPage<'c> = Box<dyn FnMut(Option<String>) -> Pending<'c, Result<String, SlkError>> + 'c>;
+
+/// The pages of a paginated API call, each fetched when the iterator gets to
+/// it with the previous page's `next_cursor`, so a caller can stop early
+/// without fetching the rest. Each page is a checked (`"ok": true`)
+/// response; iteration ends after the last page or the first error.
+pub struct Pages<'c> {
+    fetch: FetchPage<'c>,
+    cursor: Option<String>,
+    done: bool,
 }
 
-pub fn fetch_conversation_history(channel_id: &str, token: &str) -> Result<String, SlkError> {
-    let url = format!(
-        "https://slack.com/api/conversations.history?channel={}&limit=200",
-        channel_id
-    );
-    let output = Command::new("curl")
-        .args(["-s", "-H", &format!("Authorization: Bearer {}", token), &url])
-        .output()
-        .map_err(|e| SlkError::from(format!("failed to execute curl: {}", e)))?;
+impl<'c> Pages<'c> {
+    /// Pages from `fetch`, which gets the cursor of the page to fetch (none
+    /// for the first) and returns the raw response.
+    pub fn new(fetch: impl FnMut(Option<String>) -> Pending<'c, Result<String, SlkError>> + 'c) -> Self {
+        Pages { fetch: Box::new(fetch), cursor: None, done: false }
+    }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(SlkError::from(format!(
-            "curl failed (exit {}): {}",
-            output.status, stderr
-        )));
+    /// The next page, as `next` returns it, without blocking the other
+    /// calls of a [`SlackClient::concurrently`].
+    pub async fn next_page(&mut self) -> Option<Result<JsonValue, SlkError>> {
+        if self.done {
+            return None;
+        }
+        let page = (self.fetch)(self.cursor.take()).await.and_then(|raw_json| {
+            let response = json::parse(&raw_json)?;
+            message::check_ok(&response)?;
+            Ok(response)
+        });
+        self.cursor = page.as_ref().ok().and_then(message::extract_next_cursor);
+        self.done = self.cursor.is_none();
+        Some(page)
     }
 
-    String::from_utf8(output.stdout)
-        .map_err(|e| SlkError::from(format!("invalid UTF-8 in response: {}", e)))
+    /// The messages on the pages, one at a time.
+    pub fn messages(self) -> Messages<'c> {
+        Messages { pages: self, page: Vec::new().into_iter() }
+    }
 }
 
-pub fn fetch_thread_replies(channel_id: &str, ts: &str, token: &str) -> Result<String, SlkError> {
-    let url = build_api_url(channel_id, ts);
-    let output = Command::new("curl")
-        .args(["-s", "-H", &format!("Authorization: Bearer {}", token), &url])
-        .output()
-        .map_err(|e| SlkError::from(format!("failed to execute curl: {}", e)))?;
+impl Iterator for Pages<'_> {
+    type Item = Result<JsonValue, SlkError>;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(SlkError::from(format!(
-            "curl failed (exit {}): {}",
-            output.status, stderr
-        )));
+    fn next(&mut self) -> Option<Self::Item> {
+        task::block_on(self.next_page())
     }
+}
 
-    String::from_utf8(output.stdout)
-        .map_err(|e| SlkError::from(format!("invalid UTF-8 in response: {}", e)))
+/// The messages of [`Pages`], as the API returned them.
+pub struct Messages<'c> {
+    pages: Pages<'c>,
+    page: std::vec::IntoIter<JsonValue>,
+}
+
+impl Iterator for Messages<'_> {
+    type Item = Result<JsonValue, SlkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(message) = self.page.next() {
+                return Some(Ok(message));
+            }
+            let messages = match self.pages.next()? {
+                Ok(JsonValue::Object(pairs)) => pairs.into_iter().find(|(key, _)| key == "messages"),
+                Ok(_) => None,
+                Err(e) => return Some(Err(e)),
+            };
+            match messages {
+                Some((_, JsonValue::Array(messages))) => self.page = messages.into_iter(),
+                _ => {
+                    self.pages.done = true;
+                    return Some(Err(SlkError::from("missing 'messages' array in response")));
+                }
+            }
+        }
+    }
+}
+
+/// How many redirects `download_file` follows.
+const MAX_REDIRECTS: usize = 5;
+
+/// Sends an API request, retrying transient failures of reads and any
+/// rate-limited request, and returns the JSON body.
+async fn api_call(
+    transport: &dyn Transport,
+    policy: &RetryPolicy,
+    request: &Request,
+) -> Result<String, SlkError> {
+    let mut attempt = 0;
+    let mut waited = 0;
+    loop {
+        let response = match send(transport, request).await {
+            Ok(response) => response,
+            Err(FetchError::Transient(e)) if attempt < policy.max_retries => {
+                let delay = policy.backoff(attempt);
+                info!("{}; retrying in {:.1}s...", e, delay.as_secs_f64());
+                task::sleep(delay).await;
+                attempt += 1;
+                continue;
+            }
+            Err(FetchError::Transient(e)) | Err(FetchError::Fatal(e)) => return Err(e),
+        };
+
+        if is_transient_failure(&response) {
+            if attempt < policy.max_retries && request.method.is_idempotent() {
+                let delay = policy.backoff(attempt);
+                info!(
+                    "Slack API request failed (HTTP {}); retrying in {:.1}s...",
+                    response.status,
+                    delay.as_secs_f64()
+                );
+                task::sleep(delay).await;
+                attempt += 1;
+                continue;
+            }
+            if response.status >= 500 {
+                return Err(SlkError::new(ErrorKind::Network, format!(
+                    "Slack API request failed after {} retries ({})",
+                    attempt,
+                    response.describe()
+                )));
+            }
+        }
+
+        let Some(delay) = rate_limit_delay(&response) else {
+            return checked_body(&response);
+        };
+        if waited + delay > MAX_RATE_LIMIT_WAIT_SECS {
+            return Err(SlkError::new(ErrorKind::RateLimited, format!(
+                "rate limited by Slack; gave up after waiting {}s ({})",
+                waited,
+                response.describe()
+            )));
+        }
+        warn!("rate limited by Slack; retrying in {}s...", delay);
+        task::sleep(Duration::from_secs(delay)).await;
+        waited += delay;
+    }
 }
 
 #[cfg(test)]
@@ -94,7 +728,7 @@ mod tests {
     #[test]
     fn test_build_api_url() {
         assert_eq!(
-            build_api_url("C081VT5GLQH", "1770689887.565249"),
+            build_api_url("https://slack.com/api", "C081VT5GLQH", "1770689887.565249"),
             "https://slack.com/api/conversations.replies?channel=C081VT5GLQH&ts=1770689887.565249"
         );
     }
@@ -140,4 +774,233 @@ mod tests {
         assert!(result.is_err());
         assert!(result.unwrap_err().message.contains("invalid_auth"));
     }
+
+    #[test]
+    fn test_rate_limit_delay_from_429() {
+        let response = Response {
+            status: 429,
+            headers: vec![("Retry-After".to_string(), "7".to_string())],
+            body: Vec::new(),
+        };
+        assert_eq!(rate_limit_delay(&response), Some(7));
+    }
+
+    #[test]
+    fn test_rate_limit_delay_from_error_payload() {
+        let response = Response {
+            status: 200,
+            headers: vec![],
+            body: r#"{"ok": false, "error": "ratelimited"}"#.as_bytes().to_vec(),
+        };
+        assert_eq!(rate_limit_delay(&response), Some(DEFAULT_RETRY_AFTER_SECS));
+    }
+
+    #[test]
+    fn test_rate_limit_delay_none_for_success() {
+        let response = Response {
+            status: 200,
+            headers: vec![],
+            body: r#"{"ok": true}"#.as_bytes().to_vec(),
+        };
+        assert_eq!(rate_limit_delay(&response), None);
+    }
+
+    #[test]
+    fn test_is_transient_failure() {
+        let server_error = Response {
+            status: 503,
+            headers: vec![],
+            body: Vec::new(),
+        };
+        let internal_error = Response {
+            status: 200,
+            headers: vec![],
+            body: r#"{"ok": false, "error": "internal_error"}"#.as_bytes().to_vec(),
+        };
+        let auth_error = Response {
+            status: 200,
+            headers: vec![],
+            body: r#"{"ok": false, "error": "invalid_auth"}"#.as_bytes().to_vec(),
+        };
+        assert!(is_transient_failure(&server_error));
+        assert!(is_transient_failure(&internal_error));
+        assert!(!is_transient_failure(&auth_error));
+    }
+
+    #[test]
+    fn test_backoff_ceiling_grows_and_caps() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+        };
+        assert_eq!(policy.backoff_ceiling(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff_ceiling(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff_ceiling(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff_ceiling(3), Duration::from_millis(500));
+        assert_eq!(policy.backoff_ceiling(40), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_backoff_within_ceiling() {
+        let policy = RetryPolicy::default();
+        for attempt in 0..5 {
+            assert!(policy.backoff(attempt) <= policy.backoff_ceiling(attempt));
+        }
+    }
+
+    #[test]
+    fn test_api_error_includes_http_status() {
+        let transport = crate::http::FakeTransport::new();
+        let api = SlackClient::new(&transport, "https://slack.com/api", "xoxp-test");
+        let err = api.users_info("U1").unwrap_err();
+        assert!(err.message.contains("HTTP 404"));
+        assert!(err.message.contains("unknown_method"));
+    }
+
+    #[test]
+    fn test_non_json_body_includes_http_context() {
+        let transport = crate::http::FakeTransport::new().respond("users.info", "<html>oops</html>");
+        let api = SlackClient::new(&transport, "https://slack.com/api", "xoxp-test");
+        let err = api.users_info("U1").unwrap_err();
+        assert!(err.message.contains("JSON parse error"));
+        assert!(err.message.contains("HTTP 200"));
+        assert!(err.message.contains("<html>oops</html>"));
+    }
+
+    #[test]
+    fn test_client_sends_its_token() {
+        let transport = crate::http::FakeTransport::new()
+            .respond("auth.test", r#"{"ok": true}"#)
+            .respond("apps.connections.open", r#"{"ok": true, "url": "wss://example.com"}"#);
+        let api = SlackClient::new(&transport, "https://slack.com/api", "xoxp-user");
+        api.auth_test().unwrap();
+        api.with_token("xapp-app").apps_connections_open().unwrap();
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests[0].url, "https://slack.com/api/auth.test");
+        assert!(requests[0].headers.contains(&("Authorization".to_string(), "Bearer xoxp-user".to_string())));
+        assert_eq!(requests[1].url, "https://slack.com/api/apps.connections.open");
+        assert!(requests[1].headers.contains(&("Authorization".to_string(), "Bearer xapp-app".to_string())));
+    }
+
+    #[test]
+    fn test_download_file_sends_token_only_to_slack_over_https() {
+        let transport = crate::http::FakeTransport::new()
+            .redirect("cross.txt", "https://cdn.example.com/x/elsewhere.txt")
+            .redirect("plain.txt", "http://files.slack.com/x/cleartext.txt")
+            .redirect("relative.txt", "/y/moved.txt")
+            .respond("elsewhere.txt", "1")
+            .respond("cleartext.txt", "2")
+            .respond("moved.txt", "3")
+            .respond("other.txt", "4");
+        let api = SlackClient::new(&transport, "https://slack.com/api", "xoxp-test");
+        let download = |url: &str| api.download_file(url, 1024).unwrap().unwrap();
+        assert_eq!(download("https://files.slack.com/x/cross.txt"), b"1");
+        assert_eq!(download("https://files.slack.com/x/plain.txt"), b"2");
+        assert_eq!(download("https://FILES.Slack.com/x/relative.txt"), b"3");
+        assert_eq!(download("https://example.com/x/other.txt"), b"4");
+
+        let requests = transport.requests.lock().unwrap();
+        let sent: Vec<(&str, bool)> = requests
+            .iter()
+            .map(|r| (r.url.as_str(), r.headers.iter().any(|(name, _)| name == "Authorization")))
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                ("https://files.slack.com/x/cross.txt", true),
+                ("https://cdn.example.com/x/elsewhere.txt", false),
+                ("https://files.slack.com/x/plain.txt", true),
+                ("http://files.slack.com/x/cleartext.txt", false),
+                ("https://FILES.Slack.com/x/relative.txt", true),
+                ("https://FILES.Slack.com/y/moved.txt", true),
+                ("https://example.com/x/other.txt", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_concurrently_keeps_order() {
+        let transport = crate::http::FakeTransport::new().respond("users.info", r#"{"ok": true, "user": {"id": "U1"}}"#);
+        let api = SlackClient::new(&transport, "https://slack.com/api", "xoxp-user");
+        let ids: Vec<String> = (0..10).map(|i| format!("U{}", i)).collect();
+        let results =
+            api.concurrently(&ids, |api, id| async move { api.users_info_async(id).await.map(|_| id.clone()) });
+        assert_eq!(results.into_iter().collect::<Result<Vec<_>, _>>().unwrap(), ids);
+        assert_eq!(transport.requests.lock().unwrap().len(), 10);
+    }
+
+    /// Answers each request once polled a second time, as if Slack took a
+    /// while, and logs when it starts and ends on which thread.
+    #[derive(Default)]
+    struct SlowTransport {
+        log: std::sync::Mutex<Vec<(String, std::thread::ThreadId)>>,
+    }
+
+    impl Transport for SlowTransport {
+        fn send(&self, _: &Request) -> Result<Response, crate::http::Error> {
+            unreachable!("only sent concurrently")
+        }
+
+        fn send_async<'a>(&'a self, request: &'a Request) -> Pending<'a, Result<Response, crate::http::Error>> {
+            Box::pin(async move {
+                let user = request.url.rsplit('=').next().unwrap();
+                let log = |event: &str| {
+                    self.log.lock().unwrap().push((format!("{} {}", event, user), std::thread::current().id()))
+                };
+                log("start");
+                let mut waited = false;
+                std::future::poll_fn(|_| {
+                    if std::mem::replace(&mut waited, true) {
+                        return std::task::Poll::Ready(());
+                    }
+                    task::note_waiting_on_io();
+                    std::task::Poll::Pending
+                })
+                .await;
+                log("end");
+                Ok(Response { status: 200, headers: Vec::new(), body: br#"{"ok": true}"#.to_vec() })
+            })
+        }
+    }
+
+    #[test]
+    fn test_concurrently_overlaps_calls_on_one_thread() {
+        let transport = SlowTransport::default();
+        let api = SlackClient::new(&transport, "https://slack.com/api", "xoxp-user");
+        let ids: Vec<String> = (0..6).map(|i| format!("U{}", i)).collect();
+        let results = api.concurrently(&ids, |api, id| api.users_info_async(id));
+        assert!(results.iter().all(Result::is_ok));
+        let log = transport.log.into_inner().unwrap();
+        let events: Vec<&str> = log.iter().map(|(event, _)| event.as_str()).collect();
+        assert_eq!(events[..MAX_IN_FLIGHT + 1], ["start U0", "start U1", "start U2", "start U3", "end U0"]);
+        assert_eq!(events.len(), 12);
+        assert!(log.iter().all(|(_, thread)| *thread == std::thread::current().id()));
+    }
+
+    #[test]
+    fn test_pages_fetch_lazily() {
+        let transport = crate::http::FakeTransport::new()
+            .respond(
+                "conversations.history?cursor=p2",
+                r#"{"ok": true, "messages": [{"user": "U1", "text": "c", "ts": "1.0"}]}"#,
+            )
+            .respond(
+                "conversations.history",
+                r#"{"ok": true, "messages": [{"user": "U1", "text": "b", "ts": "3.0"}, {"user": "U1", "text": "a", "ts": "2.0"}],
+                    "response_metadata": {"next_cursor": "p2"}}"#,
+            );
+        let api = SlackClient::new(&transport, "https://slack.com/api", "xoxp-user");
+        let texts = |messages: Messages| -> Vec<String> {
+            messages.map(|m| crate::message::extract_message(&m.unwrap()).text).collect()
+        };
+        assert_eq!(texts(api.history_pages("C1", None).messages()), ["b", "a", "c"]);
+        assert_eq!(transport.requests.lock().unwrap().len(), 2);
+
+        let first: Vec<JsonValue> = api.history_pages("C1", Some("1.5")).messages().take(2).map(Result::unwrap).collect();
+        assert_eq!(first.len(), 2);
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[2].url.ends_with("channel=C1&limit=200&oldest=1.5"));
+    }
 }
@@ -0,0 +1,152 @@
+use crate::error::SlkError;
+use crate::http;
+use crate::json::{self, JsonValue};
+use crate::message::{self, SlackMessage};
+use crate::response::{self, AppsConnectionsOpenResponse};
+use crate::slack_api::SlackClient;
+use crate::websocket::{self, WebSocket};
+
+/// Message events that change or remove an existing message rather than
+/// post a new one.
+const EDIT_SUBTYPES: [&str; 3] = ["message_changed", "message_deleted", "message_replied"];
+
+/// What is needed to open Socket Mode connections.
+pub struct SocketMode<'a> {
+    pub client: &'a http::Client,
+    /// App-level token (`xapp-`).
+    pub app_token: String,
+}
+
+impl SocketMode<'_> {
+    /// Asks Slack for a connection URL and opens it.
+    pub fn connect(&self, api: &SlackClient) -> Result<WebSocket, SlkError> {
+        let raw = api.with_token(&self.app_token).apps_connections_open()?;
+        let url = response::parse::<AppsConnectionsOpenResponse>(&raw)?.url;
+        websocket::connect(self.client, &url)
+    }
+}
+
+/// One message received over a Socket Mode connection.
+#[derive(Debug, PartialEq)]
+pub struct Envelope {
+    /// Set when Slack expects the envelope to be acknowledged.
+    pub envelope_id: Option<String>,
+    /// `hello`, `events_api`, `disconnect`, ...
+    pub kind: String,
+    /// A newly posted message and the channel it was posted in.
+    pub message: Option<(String, SlackMessage)>,
+    pub activity: Option<Activity>,
+}
+
+/// Something someone did other than post a message, for `slk watch --events`.
+#[derive(Debug, PartialEq)]
+pub struct Activity {
+    /// The event type, e.g. `user_typing`.
+    pub kind: String,
+    /// Where it happened; presence changes aren't tied to a channel.
+    pub channel: Option<String>,
+    pub user: String,
+    /// What the user did, to follow their name: "is typing", "reacted :+1:".
+    pub action: String,
+}
+
+fn parse_activity(event: &JsonValue) -> Option<Activity> {
+    let field = |v: &JsonValue, name: &str| v.get(name).and_then(|f| f.as_str()).map(String::from);
+    let kind = field(event, "type")?;
+    let reaction = || field(event, "reaction").unwrap_or_default();
+    let (channel, action) = match kind.as_str() {
+        "reaction_added" => (event.get("item").and_then(|i| field(i, "channel")), format!("reacted :{}:", reaction())),
+        "reaction_removed" => {
+            (event.get("item").and_then(|i| field(i, "channel")), format!("removed :{}:", reaction()))
+        }
+        "member_joined_channel" => (field(event, "channel"), "joined".to_string()),
+        "member_left_channel" => (field(event, "channel"), "left".to_string()),
+        _ => return None,
+    };
+    // A reaction to a file outside any channel has none to show it in.
+    Some(Activity { user: field(event, "user")?, kind, channel: Some(channel?), action })
+}
+
+pub fn parse_envelope(text: &str) -> Result<Envelope, SlkError> {
+    let value = json::parse(text)?;
+    let field = |v: &JsonValue, name: &str| v.get(name).and_then(|f| f.as_str()).map(String::from);
+    let message = value
+        .get("payload")
+        .and_then(|p| p.get("event"))
+        .filter(|e| field(e, "type").as_deref() == Some("message"))
+        .filter(|e| field(e, "subtype").is_none_or(|s| !EDIT_SUBTYPES.contains(&s.as_str())))
+        .and_then(|e| Some((field(e, "channel")?, message::extract_message(e))));
+    Ok(Envelope {
+        envelope_id: field(&value, "envelope_id"),
+        kind: field(&value, "type").unwrap_or_default(),
+        message,
+        activity: value.get("payload").and_then(|p| p.get("event")).and_then(parse_activity),
+    })
+}
+
+/// The acknowledgement for an envelope.
+pub fn ack(envelope_id: &str) -> String {
+    JsonValue::object([("envelope_id", envelope_id.into())]).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_envelope() {
+        let envelope = parse_envelope(
+            r#"{"envelope_id": "e1", "type": "events_api", "accepts_response_payload": false,
+                "payload": {"event": {"type": "message", "channel": "C1", "user": "U1",
+                                      "text": "hi", "ts": "1770689900.000100"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(envelope.envelope_id.as_deref(), Some("e1"));
+        assert_eq!(envelope.kind, "events_api");
+        let (channel, message) = envelope.message.unwrap();
+        assert_eq!(channel, "C1");
+        assert_eq!(message.text, "hi");
+
+        let edit = parse_envelope(
+            r#"{"envelope_id": "e2", "type": "events_api",
+                "payload": {"event": {"type": "message", "subtype": "message_changed", "channel": "C1"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(edit.message, None);
+
+        let hello = parse_envelope(r#"{"type": "hello", "num_connections": 1}"#).unwrap();
+        assert_eq!((hello.envelope_id, hello.kind.as_str()), (None, "hello"));
+        assert_eq!(hello.activity, None);
+    }
+
+    #[test]
+    fn test_parse_activity() {
+        let reaction = parse_envelope(
+            r#"{"envelope_id": "e3", "type": "events_api",
+                "payload": {"event": {"type": "reaction_added", "user": "U1", "reaction": "tada",
+                                      "item": {"type": "message", "channel": "C1", "ts": "1.0"}}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            reaction.activity,
+            Some(Activity {
+                kind: "reaction_added".to_string(),
+                channel: Some("C1".to_string()),
+                user: "U1".to_string(),
+                action: "reacted :tada:".to_string(),
+            })
+        );
+        let file_reaction = parse_envelope(
+            r#"{"envelope_id": "e4", "type": "events_api",
+                "payload": {"event": {"type": "reaction_added", "user": "U1", "reaction": "tada",
+                                      "item": {"type": "file", "file": "F1"}}}}"#,
+        )
+        .unwrap();
+        assert_eq!(file_reaction.activity, None);
+    }
+
+    #[test]
+    fn test_ack() {
+        assert_eq!(ack("e1"), r#"{"envelope_id":"e1"}"#);
+    }
+}
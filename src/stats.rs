@@ -0,0 +1,1046 @@
+//! `slk stats`: how busy a channel was over a range of days, worked out from
+//! its messages and shown as a table, JSON or CSV.
+
+use crate::config::OutputFormat;
+use crate::error::SlkError;
+use crate::json::JsonValue;
+use crate::message::{self, SlackMessage};
+use crate::notify;
+use std::collections::HashMap;
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+/// How many of the busiest hours the table lists.
+const BUSIEST_HOURS: usize = 5;
+
+/// How many places each `--leaderboard` table has.
+const LEADERBOARD_PLACES: usize = 10;
+
+/// How much of each unanswered message `--response-time` and `--unanswered`
+/// quote.
+const UNANSWERED_CHARS: usize = 60;
+
+/// What `slk stats` shows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Report {
+    /// Messages per day, per person and by hour.
+    Summary,
+    /// `--leaderboard`: who posted and reacted the most.
+    Leaderboard,
+    /// `--response-time`: how long messages waited for a reply.
+    ResponseTime,
+    /// `--series`: messages per day or per hour, for plotting.
+    Series(Interval),
+    /// `--unanswered`: messages nobody replied to.
+    Unanswered,
+    /// `--keywords`: messages mentioning each of these words, per day.
+    Keywords(Vec<String>),
+}
+
+/// The buckets of a `--series`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interval {
+    Day,
+    Hour,
+}
+
+impl Interval {
+    pub fn parse(s: &str) -> Result<Self, SlkError> {
+        match s {
+            "day" => Ok(Interval::Day),
+            "hour" => Ok(Interval::Hour),
+            _ => Err(SlkError::from(format!("invalid series '{}': expected 'day' or 'hour'", s))),
+        }
+    }
+
+    fn secs(self) -> u64 {
+        match self {
+            Interval::Day => DAY_SECS,
+            Interval::Hour => 3600,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Interval::Day => "day",
+            Interval::Hour => "hour",
+        }
+    }
+
+    /// The bucket starting at `secs`, as `2026-02-10` or `2026-02-10 13:00` (UTC).
+    fn label(self, secs: u64) -> String {
+        match self {
+            Interval::Day => day_of(secs),
+            Interval::Hour => message::format_unix_ts_with(&secs.to_string(), "%Y-%m-%d %H:%M"),
+        }
+    }
+}
+
+impl Report {
+    /// Whether the report looks at the replies in threads, which cost an
+    /// API call per thread to fetch.
+    pub fn needs_replies(&self) -> bool {
+        *self == Report::ResponseTime
+    }
+}
+
+/// What `slk stats` works from: a channel's messages from `since` up to
+/// `until`, and when the report needs them, the replies in their threads.
+pub struct History<'a> {
+    /// The channel's own messages, in any order. Messages outside the range,
+    /// and replies in threads, are left out of the counts.
+    pub messages: &'a [SlackMessage],
+    /// Replies by their parent's ts, oldest first, leaving out the parent.
+    pub replies: &'a HashMap<String, Vec<SlackMessage>>,
+    pub since: u64,
+    pub until: u64,
+}
+
+/// The `report` on `history` as `format`, naming people by `user_names`
+/// where known.
+pub fn report(
+    history: &History,
+    report: Report,
+    format: OutputFormat,
+    user_names: &HashMap<String, String>,
+) -> String {
+    let History { messages, since, until, .. } = *history;
+    match report {
+        Report::Summary => Stats::new(messages, since, until).render(format, user_names),
+        Report::Leaderboard => Leaderboard::new(messages, since, until).render(format, user_names),
+        Report::ResponseTime => ResponseTimes::new(history).render(format, user_names),
+        Report::Series(interval) => Series::new(messages, since, until, interval).render(format),
+        Report::Unanswered => Unanswered::new(messages, since, until).render(format, user_names),
+        Report::Keywords(keywords) => Keywords::new(messages, since, until, keywords).render(format),
+    }
+}
+
+/// Reads `--keywords`: comma-separated words, in any case.
+pub fn parse_keywords(value: &str) -> Result<Vec<String>, SlkError> {
+    let mut keywords: Vec<String> = Vec::new();
+    for keyword in value.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+        if !keywords.iter().any(|k| k.eq_ignore_ascii_case(keyword)) {
+            keywords.push(keyword.to_string());
+        }
+    }
+    if keywords.is_empty() {
+        return Err(SlkError::from("--keywords needs a comma-separated list of words"));
+    }
+    Ok(keywords)
+}
+
+/// Reads `--since` or `--until`: a `YYYY-MM-DD` day (UTC), which starts the
+/// range, or with `end`, ends it after that day; or a time that long before
+/// `now`, such as `7d`, `12h` or `2w`.
+pub fn parse_time(value: &str, now: u64, end: bool) -> Result<u64, SlkError> {
+    let unit = match value.chars().last() {
+        Some('h') => 60 * 60,
+        Some('d') => DAY_SECS,
+        Some('w') => 7 * DAY_SECS,
+        _ => return Ok(message::parse_date(value)? + if end { DAY_SECS } else { 0 }),
+    };
+    match value[..value.len() - 1].parse::<u64>() {
+        Ok(n) => Ok(now.saturating_sub(n * unit)),
+        Err(_) => Err(SlkError::from(format!(
+            "invalid time '{}': expected YYYY-MM-DD or an age such as 7d, 12h or 2w",
+            value
+        ))),
+    }
+}
+
+/// A channel's activity from `since` up to `until` (Unix seconds).
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub since: u64,
+    pub until: u64,
+    /// Messages posted to the channel itself: not replies in threads, nor
+    /// joins, topic changes and other system messages.
+    pub messages: usize,
+    /// How many of the messages have threads under them, and how many
+    /// replies those threads have.
+    pub threads: usize,
+    pub replies: u64,
+    /// Messages per day (`YYYY-MM-DD`, UTC), quiet days included.
+    pub days: Vec<(String, usize)>,
+    /// Messages per user ID, most first.
+    pub users: Vec<(String, usize)>,
+    /// Messages per hour of the day (UTC).
+    pub hours: [usize; 24],
+}
+
+impl Stats {
+    /// The stats of those of `messages` posted from `since` up to `until`.
+    pub fn new(messages: &[SlackMessage], since: u64, until: u64) -> Stats {
+        let first_day = since - since % DAY_SECS;
+        let days = (first_day..until.max(first_day + 1)).step_by(DAY_SECS as usize);
+        let mut stats = Stats {
+            since,
+            until,
+            messages: 0,
+            threads: 0,
+            replies: 0,
+            days: days.map(|day| (day_of(day), 0)).collect(),
+            users: Vec::new(),
+            hours: [0; 24],
+        };
+        for m in messages.iter().filter(|m| counts(m, since, until)) {
+            let secs = message::ts_key(&m.ts).0;
+            stats.messages += 1;
+            if m.has_replies() {
+                stats.threads += 1;
+                stats.replies += m.reply_count;
+            }
+            stats.days[((secs - first_day) / DAY_SECS) as usize].1 += 1;
+            tally(&mut stats.users, &m.user, 1);
+            stats.hours[(secs % DAY_SECS / 3600) as usize] += 1;
+        }
+        rank(&mut stats.users);
+        stats
+    }
+
+    /// The busiest hours that had any messages, busiest first.
+    fn busiest_hours(&self) -> Vec<(usize, usize)> {
+        let mut hours: Vec<(usize, usize)> =
+            self.hours.iter().copied().enumerate().filter(|(_, n)| *n > 0).collect();
+        hours.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+        hours.truncate(BUSIEST_HOURS);
+        hours
+    }
+
+    /// The stats as a table, a JSON object, or CSV rows of `kind,key,name,count`,
+    /// naming people by `user_names` where known.
+    pub fn render(&self, format: OutputFormat, user_names: &HashMap<String, String>) -> String {
+        match format {
+            OutputFormat::Text => self.table(user_names),
+            OutputFormat::Json => self.json(user_names).to_string(),
+            OutputFormat::Csv => self.csv(user_names),
+        }
+    }
+
+    fn table(&self, user_names: &HashMap<String, String>) -> String {
+        let who = |user: &str| user_names.get(user).map_or(user.to_string(), |name| format!("@{}", name));
+        let last_day = day_of(self.until.saturating_sub(1).max(self.since));
+        let mut out =
+            format!("Messages: {} from {} to {} (UTC)\n", self.messages, day_of(self.since), last_day);
+        out.push_str(&format!("Threads:  {}, with {} replies\n", self.threads, self.replies));
+        out.push_str(&table("Day", "Messages", &self.days));
+        let users: Vec<(String, usize)> = self.users.iter().map(|(user, n)| (who(user), *n)).collect();
+        out.push_str(&table("User", "Messages", &users));
+        let hours: Vec<(String, usize)> =
+            self.busiest_hours().iter().map(|(hour, n)| (format!("{:02}:00", hour), *n)).collect();
+        out.push_str(&table("Busiest hour", "Messages", &hours));
+        out.trim_end().to_string()
+    }
+
+    fn json(&self, user_names: &HashMap<String, String>) -> JsonValue {
+        let days = self
+            .days
+            .iter()
+            .map(|(day, n)| JsonValue::object([("date", day.as_str().into()), ("messages", (*n).into())]));
+        let users = self.users.iter().map(|(user, n)| {
+            JsonValue::object([
+                ("user", user.as_str().into()),
+                ("user_name", user_names.get(user).cloned().into()),
+                ("messages", (*n).into()),
+            ])
+        });
+        let hours = self
+            .hours
+            .iter()
+            .enumerate()
+            .map(|(hour, n)| JsonValue::object([("hour", hour.into()), ("messages", (*n).into())]));
+        JsonValue::object([
+            ("since", self.since.into()),
+            ("until", self.until.into()),
+            ("messages", self.messages.into()),
+            ("threads", self.threads.into()),
+            ("replies", self.replies.into()),
+            ("days", days.collect::<Vec<_>>().into()),
+            ("users", users.collect::<Vec<_>>().into()),
+            ("hours", hours.collect::<Vec<_>>().into()),
+        ])
+    }
+
+    fn csv(&self, user_names: &HashMap<String, String>) -> String {
+        let mut rows = vec![
+            row("messages", "", "", self.messages),
+            row("threads", "", "", self.threads),
+            row("replies", "", "", self.replies as usize),
+        ];
+        rows.extend(self.days.iter().map(|(day, n)| row("day", day, "", *n)));
+        rows.extend(self.users.iter().map(|(user, n)| row("user", user, user_name(user_names, user), *n)));
+        rows.extend(self.hours.iter().enumerate().map(|(hour, n)| row("hour", &hour.to_string(), "", *n)));
+        csv(&["kind", "key", "name", "count"], &rows)
+    }
+}
+
+/// `slk stats --leaderboard`: who posted and reacted the most, and with what.
+#[derive(Debug, PartialEq)]
+pub struct Leaderboard {
+    /// Messages per user ID, most first.
+    pub posters: Vec<(String, usize)>,
+    /// Reactions per emoji name, most used first.
+    pub reactions: Vec<(String, usize)>,
+    /// Reactions added per user ID, and received per author, most first.
+    pub given: Vec<(String, usize)>,
+    pub received: Vec<(String, usize)>,
+}
+
+impl Leaderboard {
+    /// The top places among those of `messages` posted from `since` up to
+    /// `until`.
+    pub fn new(messages: &[SlackMessage], since: u64, until: u64) -> Leaderboard {
+        let mut board =
+            Leaderboard { posters: Vec::new(), reactions: Vec::new(), given: Vec::new(), received: Vec::new() };
+        for m in messages.iter().filter(|m| counts(m, since, until)) {
+            tally(&mut board.posters, &m.user, 1);
+            for reaction in &m.reactions {
+                tally(&mut board.reactions, &reaction.name, reaction.count as usize);
+                tally(&mut board.received, &m.user, reaction.count as usize);
+                for user in &reaction.users {
+                    tally(&mut board.given, user, 1);
+                }
+            }
+        }
+        for counts in [&mut board.posters, &mut board.reactions, &mut board.given, &mut board.received] {
+            rank(counts);
+            counts.truncate(LEADERBOARD_PLACES);
+        }
+        board
+    }
+
+    /// The leaderboard as tables, a JSON object, or CSV rows of
+    /// `kind,key,name,count` (kinds `poster`, `reaction`, `given` and
+    /// `received`), naming people by `user_names` where known.
+    pub fn render(&self, format: OutputFormat, user_names: &HashMap<String, String>) -> String {
+        let people = [
+            ("poster", "posters", &self.posters),
+            ("given", "given", &self.given),
+            ("received", "received", &self.received),
+        ];
+        match format {
+            OutputFormat::Text => {
+                let who =
+                    |user: &str| user_names.get(user).map_or(user.to_string(), |name| format!("@{}", name));
+                let named = |counts: &[(String, usize)]| {
+                    counts.iter().map(|(user, n)| (who(user), *n)).collect::<Vec<_>>()
+                };
+                let emoji: Vec<(String, usize)> =
+                    self.reactions.iter().map(|(name, n)| (format!(":{}:", name), *n)).collect();
+                let tables = [
+                    table("Top poster", "Messages", &named(&self.posters)),
+                    table("Top reaction", "Times", &emoji),
+                    table("Most reactions given", "Reactions", &named(&self.given)),
+                    table("Most reactions received", "Reactions", &named(&self.received)),
+                ];
+                match tables.concat().trim() {
+                    "" => "No messages in this range.".to_string(),
+                    tables => tables.to_string(),
+                }
+            }
+            OutputFormat::Json => {
+                let people = people.map(|(_, key, counts)| {
+                    let count = if key == "posters" { "messages" } else { "reactions" };
+                    let items = counts.iter().map(|(user, n)| {
+                        JsonValue::object([
+                            ("user", user.as_str().into()),
+                            ("user_name", user_names.get(user).cloned().into()),
+                            (count, (*n).into()),
+                        ])
+                    });
+                    (key, items.collect::<Vec<_>>().into())
+                });
+                let reactions = self.reactions.iter().map(|(name, n)| {
+                    JsonValue::object([("name", name.as_str().into()), ("count", (*n).into())])
+                });
+                JsonValue::object(
+                    people.into_iter().chain([("reactions", reactions.collect::<Vec<_>>().into())]),
+                )
+                .to_string()
+            }
+            OutputFormat::Csv => {
+                let mut rows: Vec<[String; 4]> = Vec::new();
+                for (kind, _, counts) in people {
+                    rows.extend(
+                        counts.iter().map(|(user, n)| row(kind, user, user_name(user_names, user), *n)),
+                    );
+                }
+                rows.extend(self.reactions.iter().map(|(name, n)| row("reaction", name, "", *n)));
+                csv(&["kind", "key", "name", "count"], &rows)
+            }
+        }
+    }
+}
+
+/// `slk stats --response-time`: how long messages waited for their first
+/// reply from someone other than their author, for support channels.
+#[derive(Debug, PartialEq)]
+pub struct ResponseTimes {
+    pub since: u64,
+    pub until: u64,
+    /// Seconds each answered message waited, shortest first.
+    pub waits: Vec<u64>,
+    /// The messages nobody else has replied to, oldest first.
+    pub unanswered: Vec<(String, String, String)>,
+}
+
+impl ResponseTimes {
+    pub fn new(history: &History) -> ResponseTimes {
+        let mut times = ResponseTimes {
+            since: history.since,
+            until: history.until,
+            waits: Vec::new(),
+            unanswered: Vec::new(),
+        };
+        let no_replies = Vec::new();
+        for m in history.messages.iter().filter(|m| counts(m, history.since, history.until)) {
+            let replies = history.replies.get(&m.ts).unwrap_or(&no_replies);
+            match replies.iter().find(|r| r.user != m.user) {
+                Some(reply) => {
+                    times.waits.push(message::ts_key(&reply.ts).0.saturating_sub(message::ts_key(&m.ts).0))
+                }
+                None => times.unanswered.push((m.ts.clone(), m.user.clone(), m.text.clone())),
+            }
+        }
+        times.waits.sort_unstable();
+        times.unanswered.sort_by_key(|(ts, _, _)| message::ts_key(ts));
+        times
+    }
+
+    /// The wait that `percent` of answered messages waited at most (nearest
+    /// rank), when any were answered.
+    pub fn percentile(&self, percent: usize) -> Option<u64> {
+        let rank = (self.waits.len() * percent).div_ceil(100).max(1);
+        self.waits.get(rank - 1).copied()
+    }
+
+    /// The times as a summary and a table of unanswered messages, a JSON
+    /// object, or CSV rows of `kind,key,name,count`: `messages`, `answered`,
+    /// `median_secs` and `p95_secs`, then an `unanswered` row (keyed by ts)
+    /// for each message nobody answered.
+    pub fn render(&self, format: OutputFormat, user_names: &HashMap<String, String>) -> String {
+        let (median, p95) = (self.percentile(50), self.percentile(95));
+        let answered = self.waits.len();
+        let messages = answered + self.unanswered.len();
+        match format {
+            OutputFormat::Text => {
+                let wait = |secs: Option<u64>| secs.map_or("-".to_string(), describe_wait);
+                let last_day = day_of(self.until.saturating_sub(1).max(self.since));
+                let mut out = format!(
+                    "Messages:        {} from {} to {} (UTC)\n",
+                    messages,
+                    day_of(self.since),
+                    last_day
+                );
+                out.push_str(&format!("Answered:        {}\n", answered));
+                out.push_str(&format!("Median response: {}\n", wait(median)));
+                out.push_str(&format!("95th percentile: {}\n", wait(p95)));
+                out.push_str(&format!("Unanswered:      {}\n", self.unanswered.len()));
+                out.push_str(&unanswered_lines(&self.unanswered, user_names));
+                out.trim_end().to_string()
+            }
+            OutputFormat::Json => {
+                let unanswered = unanswered_json(&self.unanswered, user_names);
+                JsonValue::object([
+                    ("since", self.since.into()),
+                    ("until", self.until.into()),
+                    ("messages", messages.into()),
+                    ("answered", answered.into()),
+                    ("median_secs", median.into()),
+                    ("p95_secs", p95.into()),
+                    ("unanswered", unanswered.into()),
+                ])
+                .to_string()
+            }
+            OutputFormat::Csv => {
+                let mut rows = vec![row("messages", "", "", messages), row("answered", "", "", answered)];
+                rows.extend(median.map(|secs| row("median_secs", "", "", secs as usize)));
+                rows.extend(p95.map(|secs| row("p95_secs", "", "", secs as usize)));
+                rows.extend(unanswered_rows(&self.unanswered, user_names));
+                csv(&["kind", "key", "name", "count"], &rows)
+            }
+        }
+    }
+}
+
+/// `slk stats --unanswered`: the messages that started no thread, for
+/// triaging questions nobody has picked up.
+#[derive(Debug, PartialEq)]
+pub struct Unanswered {
+    pub since: u64,
+    pub until: u64,
+    pub messages: usize,
+    /// (ts, user, text) of the messages without replies, oldest first.
+    pub unanswered: Vec<(String, String, String)>,
+}
+
+impl Unanswered {
+    pub fn new(messages: &[SlackMessage], since: u64, until: u64) -> Unanswered {
+        let counted: Vec<&SlackMessage> = messages.iter().filter(|m| counts(m, since, until)).collect();
+        let mut unanswered: Vec<(String, String, String)> = counted
+            .iter()
+            .filter(|m| m.reply_count == 0 && !m.is_broadcast())
+            .map(|m| (m.ts.clone(), m.user.clone(), m.text.clone()))
+            .collect();
+        unanswered.sort_by_key(|(ts, _, _)| message::ts_key(ts));
+        Unanswered { since, until, messages: counted.len(), unanswered }
+    }
+
+    /// The messages as a count and a list, a JSON object, or CSV rows of
+    /// `kind,key,name,count`: `messages`, then an `unanswered` row (keyed by
+    /// ts) for each.
+    pub fn render(&self, format: OutputFormat, user_names: &HashMap<String, String>) -> String {
+        match format {
+            OutputFormat::Text => {
+                let last_day = day_of(self.until.saturating_sub(1).max(self.since));
+                let mut out = format!(
+                    "Unanswered: {} of {} messages from {} to {} (UTC)\n",
+                    self.unanswered.len(),
+                    self.messages,
+                    day_of(self.since),
+                    last_day
+                );
+                out.push_str(&unanswered_lines(&self.unanswered, user_names));
+                out.trim_end().to_string()
+            }
+            OutputFormat::Json => JsonValue::object([
+                ("since", self.since.into()),
+                ("until", self.until.into()),
+                ("messages", self.messages.into()),
+                ("unanswered", unanswered_json(&self.unanswered, user_names).into()),
+            ])
+            .to_string(),
+            OutputFormat::Csv => {
+                let mut rows = vec![row("messages", "", "", self.messages)];
+                rows.extend(unanswered_rows(&self.unanswered, user_names));
+                csv(&["kind", "key", "name", "count"], &rows)
+            }
+        }
+    }
+}
+
+/// `slk stats --keywords`: how many messages mentioned each keyword on each
+/// day of the range, quiet days included.
+#[derive(Debug, PartialEq)]
+pub struct Keywords {
+    pub since: u64,
+    pub until: u64,
+    pub keywords: Vec<String>,
+    /// Per day (`YYYY-MM-DD`, UTC), the messages mentioning each keyword, in
+    /// the order of `keywords`.
+    pub days: Vec<(String, Vec<usize>)>,
+}
+
+impl Keywords {
+    /// A message counts for a keyword when its text contains it, ignoring
+    /// case, so `deploy` also counts `Deployed` and `redeploy`.
+    pub fn new(messages: &[SlackMessage], since: u64, until: u64, keywords: Vec<String>) -> Keywords {
+        let first_day = since - since % DAY_SECS;
+        let days = (first_day..until.max(first_day + 1)).step_by(DAY_SECS as usize);
+        let days = days.map(|day| (day_of(day), vec![0; keywords.len()])).collect();
+        let mut report = Keywords { since, until, keywords, days };
+        let lowercase: Vec<String> = report.keywords.iter().map(|k| k.to_lowercase()).collect();
+        for m in messages.iter().filter(|m| counts(m, since, until)) {
+            let text = m.text.to_lowercase();
+            let day = &mut report.days[((message::ts_key(&m.ts).0 - first_day) / DAY_SECS) as usize].1;
+            for (i, keyword) in lowercase.iter().enumerate() {
+                day[i] += usize::from(text.contains(keyword.as_str()));
+            }
+        }
+        report
+    }
+
+    /// The counts as a table with a column per keyword and a total row, a
+    /// JSON object with a `days` array, or CSV rows of `day,<keyword>,…`.
+    pub fn render(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Text => {
+                let widths: Vec<usize> = self.keywords.iter().map(|k| k.chars().count().max(5)).collect();
+                let line = |label: &str, counts: &[usize]| {
+                    let cells = counts.iter().zip(&widths).map(|(n, w)| format!("{:>w$}", n, w = w));
+                    std::iter::once(format!("{:10}", label)).chain(cells).collect::<Vec<_>>().join("  ")
+                };
+                let header = std::iter::once(format!("{:10}", "Day"))
+                    .chain(self.keywords.iter().zip(&widths).map(|(k, w)| format!("{:>w$}", k, w = w)));
+                let mut lines = vec![header.collect::<Vec<_>>().join("  ")];
+                lines.extend(self.days.iter().map(|(day, counts)| line(day, counts)));
+                lines.push(line("Total", &self.totals()));
+                lines.join("\n")
+            }
+            OutputFormat::Json => {
+                let counts = |counts: &[usize]| {
+                    let fields = self.keywords.iter().zip(counts).map(|(k, n)| (k.clone(), (*n).into()));
+                    JsonValue::Object(fields.collect())
+                };
+                let days = self
+                    .days
+                    .iter()
+                    .map(|(day, n)| JsonValue::object([("day", day.as_str().into()), ("counts", counts(n))]));
+                JsonValue::object([
+                    ("since", self.since.into()),
+                    ("until", self.until.into()),
+                    (
+                        "keywords",
+                        self.keywords.iter().map(|k| k.as_str().into()).collect::<Vec<JsonValue>>().into(),
+                    ),
+                    ("totals", counts(&self.totals())),
+                    ("days", days.collect::<Vec<_>>().into()),
+                ])
+                .to_string()
+            }
+            OutputFormat::Csv => {
+                let header: Vec<&str> =
+                    std::iter::once("day").chain(self.keywords.iter().map(String::as_str)).collect();
+                let rows: Vec<Vec<String>> = self
+                    .days
+                    .iter()
+                    .map(|(day, counts)| {
+                        std::iter::once(day.clone()).chain(counts.iter().map(usize::to_string)).collect()
+                    })
+                    .collect();
+                csv(&header, &rows)
+            }
+        }
+    }
+
+    /// The messages mentioning each keyword over the whole range.
+    fn totals(&self) -> Vec<usize> {
+        (0..self.keywords.len()).map(|i| self.days.iter().map(|(_, counts)| counts[i]).sum()).collect()
+    }
+}
+
+/// Unanswered messages as indented `2026-02-10 02:16 @kanta text…` lines.
+fn unanswered_lines(unanswered: &[(String, String, String)], user_names: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    for (ts, user, text) in unanswered {
+        let time = message::format_unix_ts_with(ts, "%Y-%m-%d %H:%M");
+        let who = user_names.get(user).map_or(user.to_string(), |name| format!("@{}", name));
+        out.push_str(&format!("  {} {} {}\n", time, who, notify::snippet(text, UNANSWERED_CHARS)));
+    }
+    out
+}
+
+fn unanswered_json(
+    unanswered: &[(String, String, String)],
+    user_names: &HashMap<String, String>,
+) -> Vec<JsonValue> {
+    let item = |(ts, user, text): &(String, String, String)| {
+        JsonValue::object([
+            ("ts", ts.as_str().into()),
+            ("user", user.as_str().into()),
+            ("user_name", user_names.get(user).cloned().into()),
+            ("text", text.as_str().into()),
+        ])
+    };
+    unanswered.iter().map(item).collect()
+}
+
+fn unanswered_rows<'a>(
+    unanswered: &'a [(String, String, String)],
+    user_names: &'a HashMap<String, String>,
+) -> impl Iterator<Item = [String; 4]> + 'a {
+    unanswered.iter().map(|(ts, user, _)| row("unanswered", ts, user_name(user_names, user), 1))
+}
+
+/// `slk stats --series`: the number of messages in each day or hour of the
+/// range, quiet ones included, oldest first.
+#[derive(Debug, PartialEq)]
+pub struct Series {
+    pub interval: Interval,
+    pub since: u64,
+    pub until: u64,
+    /// Messages per bucket, by the bucket's start (Unix time, UTC).
+    pub counts: Vec<(u64, usize)>,
+}
+
+impl Series {
+    pub fn new(messages: &[SlackMessage], since: u64, until: u64, interval: Interval) -> Series {
+        let step = interval.secs();
+        let first = since - since % step;
+        let buckets = (first..until.max(first + 1)).step_by(step as usize);
+        let mut series = Series { interval, since, until, counts: buckets.map(|start| (start, 0)).collect() };
+        for m in messages.iter().filter(|m| counts(m, since, until)) {
+            series.counts[((message::ts_key(&m.ts).0 - first) / step) as usize].1 += 1;
+        }
+        series
+    }
+
+    /// The series as a table, a JSON object with a `series` array, or CSV
+    /// rows of `day,messages` (`hour,messages`).
+    pub fn render(&self, format: OutputFormat) -> String {
+        let name = self.interval.name();
+        match format {
+            OutputFormat::Text => {
+                let mut title = name.to_string();
+                title[..1].make_ascii_uppercase();
+                let rows: Vec<(String, usize)> =
+                    self.counts.iter().map(|(start, n)| (self.interval.label(*start), *n)).collect();
+                table(&format!("{} (UTC)", title), "Messages", &rows).trim().to_string()
+            }
+            OutputFormat::Json => {
+                let points = self.counts.iter().map(|(start, n)| {
+                    JsonValue::object([
+                        ("start", (*start).into()),
+                        (name, self.interval.label(*start).as_str().into()),
+                        ("messages", (*n).into()),
+                    ])
+                });
+                JsonValue::object([
+                    ("interval", name.into()),
+                    ("since", self.since.into()),
+                    ("until", self.until.into()),
+                    ("series", points.collect::<Vec<_>>().into()),
+                ])
+                .to_string()
+            }
+            OutputFormat::Csv => {
+                let rows: Vec<[String; 2]> =
+                    self.counts.iter().map(|(start, n)| [self.interval.label(*start), n.to_string()]).collect();
+                csv(&[name, "messages"], &rows)
+            }
+        }
+    }
+}
+
+/// A wait as `45s`, `12m`, `3h 5m` or `2d 4h`.
+fn describe_wait(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        3600..DAY_SECS => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {}h", secs / DAY_SECS, secs % DAY_SECS / 3600),
+    }
+}
+
+/// Whether `m` counts towards the stats from `since` up to `until`: it was
+/// posted to the channel itself in that time, and isn't a system message.
+fn counts(m: &SlackMessage, since: u64, until: u64) -> bool {
+    let secs = message::ts_key(&m.ts).0;
+    (since..until).contains(&secs) && !m.is_thread_reply() && !m.is_system()
+}
+
+/// Adds `n` to the count of `key`.
+fn tally(counts: &mut Vec<(String, usize)>, key: &str, n: usize) {
+    match counts.iter_mut().find(|(k, _)| k == key) {
+        Some((_, count)) => *count += n,
+        None => counts.push((key.to_string(), n)),
+    }
+}
+
+/// Sorts counts most first; ties keep the order they were first counted in.
+fn rank(counts: &mut [(String, usize)]) {
+    counts.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+}
+
+fn user_name<'a>(user_names: &'a HashMap<String, String>, user: &str) -> &'a str {
+    user_names.get(user).map_or("", String::as_str)
+}
+
+/// A `kind,key,name,count` CSV row.
+fn row(kind: &str, key: &str, name: &str, count: usize) -> [String; 4] {
+    [kind.to_string(), key.to_string(), name.to_string(), count.to_string()]
+}
+
+/// `YYYY-MM-DD` (UTC) of a Unix time.
+fn day_of(secs: u64) -> String {
+    message::format_unix_ts_with(&secs.to_string(), "%Y-%m-%d")
+}
+
+/// A two-column table of counts, headed `title` and `column`, after a blank
+/// line; nothing when there are no rows.
+fn table(title: &str, column: &str, rows: &[(String, usize)]) -> String {
+    if rows.is_empty() {
+        return String::new();
+    }
+    let width = rows.iter().map(|(label, _)| label.chars().count()).chain([title.len()]).max().unwrap_or(0);
+    let mut out = format!("\n{:width$}  {}\n", title, column, width = width);
+    for (label, n) in rows {
+        out.push_str(&format!("{:width$}  {:>count$}\n", label, n, width = width, count = column.len()));
+    }
+    out
+}
+
+/// CSV with a header row, quoting fields that need it.
+pub fn csv<R: AsRef<[String]>>(header: &[&str], rows: &[R]) -> String {
+    let field = |f: &str| {
+        if f.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", f.replace('"', "\"\""))
+        } else {
+            f.to_string()
+        }
+    };
+    let mut lines = vec![header.join(",")];
+    lines.extend(rows.iter().map(|row| row.as_ref().iter().map(|f| field(f)).collect::<Vec<_>>().join(",")));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(user: &str, ts: &str) -> SlackMessage {
+        SlackMessage { user: user.to_string(), ts: ts.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let now = 1770689887;
+        assert_eq!(parse_time("7d", now, false).unwrap(), now - 7 * DAY_SECS);
+        assert_eq!(parse_time("12h", now, true).unwrap(), now - 12 * 3600);
+        assert_eq!(parse_time("2w", now, false).unwrap(), now - 14 * DAY_SECS);
+        assert_eq!(parse_time("2026-02-10", now, false).unwrap(), 1770681600);
+        assert_eq!(parse_time("2026-02-10", now, true).unwrap(), 1770681600 + DAY_SECS);
+        assert!(parse_time("xd", now, false).is_err());
+        assert!(parse_time("last week", now, false).is_err());
+    }
+
+    #[test]
+    fn test_stats() {
+        // 2026-02-09 00:00 to 2026-02-11 00:00 UTC.
+        let (since, until) = (1770595200, 1770768000);
+        let messages = vec![
+            message("U1", "1770600000.000100"),
+            SlackMessage {
+                thread_ts: Some("1770689887.000100".to_string()),
+                reply_count: 3,
+                ..message("U2", "1770689887.000100")
+            },
+            message("U1", "1770690000.000100"),
+            SlackMessage {
+                thread_ts: Some("1770689887.000100".to_string()),
+                ..message("U3", "1770690100.000100")
+            },
+            SlackMessage { subtype: Some("channel_join".to_string()), ..message("U4", "1770690200.000100") },
+            message("U1", "1770500000.000100"),
+        ];
+        let stats = Stats::new(&messages, since, until);
+        assert_eq!(stats.messages, 3);
+        assert_eq!((stats.threads, stats.replies), (1, 3));
+        assert_eq!(stats.days, [("2026-02-09".to_string(), 1), ("2026-02-10".to_string(), 2)]);
+        assert_eq!(stats.users, [("U1".to_string(), 2), ("U2".to_string(), 1)]);
+        assert_eq!((stats.hours[1], stats.hours[2]), (1, 2));
+
+        let names = HashMap::from([("U1".to_string(), "kanta".to_string())]);
+        assert_eq!(
+            stats.render(OutputFormat::Text, &names),
+            "Messages: 3 from 2026-02-09 to 2026-02-10 (UTC)\n\
+             Threads:  1, with 3 replies\n\
+             \n\
+             Day         Messages\n\
+             2026-02-09         1\n\
+             2026-02-10         2\n\
+             \n\
+             User    Messages\n\
+             @kanta         2\n\
+             U2             1\n\
+             \n\
+             Busiest hour  Messages\n\
+             02:00                2\n\
+             01:00                1"
+        );
+        let csv = stats.render(OutputFormat::Csv, &names);
+        assert!(
+            csv.starts_with("kind,key,name,count\nmessages,,,3\nthreads,,,1\nreplies,,,3\nday,2026-02-09,,1\n")
+        );
+        assert!(csv.contains("\nuser,U1,kanta,2\nuser,U2,,1\nhour,0,,0\n"));
+        let json = stats.render(OutputFormat::Json, &names);
+        assert!(
+            json.starts_with(r#"{"since":1770595200,"until":1770768000,"messages":3,"threads":1,"replies":3,"#)
+        );
+        assert!(json.contains(r#""users":[{"user":"U1","user_name":"kanta","messages":2},"#));
+    }
+
+    #[test]
+    fn test_leaderboard() {
+        let reaction = |name: &str, users: &[&str]| message::Reaction {
+            name: name.to_string(),
+            count: users.len() as u64,
+            users: users.iter().map(|u| u.to_string()).collect(),
+        };
+        let messages = vec![
+            SlackMessage {
+                reactions: vec![reaction("tada", &["U2", "U3"])],
+                ..message("U1", "1770600000.000100")
+            },
+            SlackMessage {
+                reactions: vec![reaction("tada", &["U1"]), reaction("eyes", &["U1", "U3"])],
+                ..message("U2", "1770600100.000100")
+            },
+            message("U2", "1770600200.000100"),
+            SlackMessage { reactions: vec![reaction("100", &["U1"])], ..message("U3", "1770500000.000100") },
+        ];
+        let board = Leaderboard::new(&messages, 1770595200, 1770768000);
+        let counts =
+            |pairs: &[(&str, usize)]| pairs.iter().map(|(k, n)| (k.to_string(), *n)).collect::<Vec<_>>();
+        assert_eq!(board.posters, counts(&[("U2", 2), ("U1", 1)]));
+        assert_eq!(board.reactions, counts(&[("tada", 3), ("eyes", 2)]));
+        assert_eq!(board.given, counts(&[("U3", 2), ("U1", 2), ("U2", 1)]));
+        assert_eq!(board.received, counts(&[("U2", 3), ("U1", 2)]));
+
+        let names = HashMap::from([("U1".to_string(), "kanta".to_string())]);
+        let text = board.render(OutputFormat::Text, &names);
+        assert!(text.starts_with("Top poster  Messages\nU2                 2\n@kanta             1\n"));
+        assert!(text.contains("\nTop reaction  Times\n:tada:            3\n"));
+        assert!(text.contains("\nMost reactions received  Reactions\nU2                               3\n"));
+        let csv = board.render(OutputFormat::Csv, &names);
+        assert!(csv.starts_with("kind,key,name,count\nposter,U2,,2\nposter,U1,kanta,1\ngiven,U3,,2\n"));
+        assert!(csv.ends_with("\nreaction,tada,,3\nreaction,eyes,,2"));
+        let json = board.render(OutputFormat::Json, &names);
+        assert!(json.starts_with(r#"{"posters":[{"user":"U2","user_name":null,"messages":2},"#));
+        assert!(json.ends_with(r#""reactions":[{"name":"tada","count":3},{"name":"eyes","count":2}]}"#));
+        assert_eq!(
+            Leaderboard::new(&[], 0, 1).render(OutputFormat::Text, &names),
+            "No messages in this range."
+        );
+    }
+
+    #[test]
+    fn test_response_times() {
+        let messages = vec![
+            message("U1", "1770600000.000100"),
+            message("U2", "1770600100.000100"),
+            message("U3", "1770600200.000100"),
+            message("U1", "1770600300.000100"),
+            message("U1", "1770500000.000100"),
+        ];
+        let replies = HashMap::from([
+            ("1770600000.000100".to_string(), vec![message("U2", "1770600045.000100")]),
+            (
+                "1770600100.000100".to_string(),
+                vec![message("U2", "1770600200.000100"), message("U1", "1770611000.000100")],
+            ),
+            ("1770600200.000100".to_string(), vec![message("U3", "1770600300.000100")]),
+        ]);
+        let history = History { messages: &messages, replies: &replies, since: 1770595200, until: 1770768000 };
+        let times = ResponseTimes::new(&history);
+        assert_eq!(times.waits, [45, 10900]);
+        let unanswered: Vec<&str> = times.unanswered.iter().map(|(ts, _, _)| ts.as_str()).collect();
+        assert_eq!(unanswered, ["1770600200.000100", "1770600300.000100"]);
+        assert_eq!((times.percentile(50), times.percentile(95)), (Some(45), Some(10900)));
+
+        let names = HashMap::from([("U1".to_string(), "kanta".to_string())]);
+        let text = times.render(OutputFormat::Text, &names);
+        assert!(text.starts_with(
+            "Messages:        4 from 2026-02-09 to 2026-02-10 (UTC)
+Answered:        2
+"
+        ));
+        assert!(text.contains(
+            "
+Median response: 45s
+95th percentile: 3h 1m
+Unanswered:      2
+"
+        ));
+        let csv = times.render(OutputFormat::Csv, &names);
+        assert!(csv.ends_with(
+            "
+median_secs,,,45
+p95_secs,,,10900
+unanswered,1770600200.000100,,1
+\
+                               unanswered,1770600300.000100,kanta,1"
+        ));
+        let json = times.render(OutputFormat::Json, &names);
+        assert!(json.contains(r#""messages":4,"answered":2,"median_secs":45,"p95_secs":10900,"#));
+        assert_eq!(ResponseTimes::new(&History { messages: &[], ..history }).percentile(50), None);
+        assert_eq!(describe_wait(2 * DAY_SECS + 4 * 3600 + 59), "2d 4h");
+    }
+
+    #[test]
+    fn test_series() {
+        let messages = vec![
+            message("U1", "1770600000.000100"),
+            message("U2", "1770602000.000100"),
+            message("U1", "1770690000.000100"),
+            message("U1", "1770500000.000100"),
+        ];
+        let (since, until) = (1770595200, 1770768000);
+        let days = Series::new(&messages, since, until, Interval::Day);
+        assert_eq!(days.counts, [(1770595200, 2), (1770681600, 1)]);
+        assert_eq!(days.render(OutputFormat::Csv), "day,messages\n2026-02-09,2\n2026-02-10,1");
+        assert_eq!(
+            days.render(OutputFormat::Text),
+            "Day (UTC)   Messages\n2026-02-09         2\n2026-02-10         1"
+        );
+        let json = days.render(OutputFormat::Json);
+        assert!(json.starts_with(r#"{"interval":"day","since":1770595200,"until":1770768000,"series":[{"#));
+        assert!(json.ends_with(r#"{"start":1770681600,"day":"2026-02-10","messages":1}]}"#));
+
+        let hours = Series::new(&messages, since, until, Interval::Hour);
+        assert_eq!(hours.counts.len(), 48);
+        assert_eq!(&hours.counts[1..3], [(1770598800, 2), (1770602400, 0)]);
+        assert!(hours.render(OutputFormat::Csv).starts_with("hour,messages\n2026-02-09 00:00,0\n"));
+        assert!(Interval::parse("week").is_err());
+    }
+
+    #[test]
+    fn test_unanswered() {
+        let messages = vec![
+            message("U1", "1770600300.000100"),
+            SlackMessage {
+                thread_ts: Some("1770600000.000100".to_string()),
+                reply_count: 2,
+                ..message("U2", "1770600000.000100")
+            },
+            SlackMessage { text: "anyone seen this?".to_string(), ..message("U3", "1770600100.000100") },
+            SlackMessage {
+                thread_ts: Some("1770600000.000100".to_string()),
+                ..message("U1", "1770600050.000100")
+            },
+            SlackMessage { subtype: Some("channel_join".to_string()), ..message("U4", "1770600200.000100") },
+        ];
+        let unanswered = Unanswered::new(&messages, 1770595200, 1770768000);
+        assert_eq!(unanswered.messages, 3);
+        let ts: Vec<&str> = unanswered.unanswered.iter().map(|(ts, _, _)| ts.as_str()).collect();
+        assert_eq!(ts, ["1770600100.000100", "1770600300.000100"]);
+
+        let names = HashMap::from([("U3".to_string(), "mio".to_string())]);
+        let text = unanswered.render(OutputFormat::Text, &names);
+        assert!(text.starts_with("Unanswered: 2 of 3 messages from 2026-02-09 to 2026-02-10 (UTC)\n  "));
+        assert!(text.contains(" @mio anyone seen this?\n"));
+        assert_eq!(
+            unanswered.render(OutputFormat::Csv, &names),
+            "kind,key,name,count\nmessages,,,3\nunanswered,1770600100.000100,mio,1\n\
+             unanswered,1770600300.000100,,1"
+        );
+        let json = unanswered.render(OutputFormat::Json, &names);
+        assert!(json.contains(
+            r#""messages":3,"unanswered":[{"ts":"1770600100.000100","user":"U3","user_name":"mio","#
+        ));
+    }
+
+    #[test]
+    fn test_keywords() {
+        let said = |text: &str, ts: &str| SlackMessage { text: text.to_string(), ..message("U1", ts) };
+        let messages = vec![
+            said("Deploying v2 now", "1770600000.000100"),
+            said("deploy failed, rollback?", "1770600100.000100"),
+            said("rolled back", "1770690000.000100"),
+            said("redeploy went fine", "1770690100.000100"),
+            said("deploy", "1770500000.000100"),
+        ];
+        let keywords = parse_keywords("deploy, rollback,,Deploy").unwrap();
+        assert_eq!(keywords, ["deploy", "rollback"]);
+        assert!(parse_keywords(" , ").is_err());
+        let report = Keywords::new(&messages, 1770595200, 1770768000, keywords);
+        assert_eq!(report.days[0], ("2026-02-09".to_string(), vec![2, 1]));
+        assert_eq!(report.days[1], ("2026-02-10".to_string(), vec![1, 0]));
+        assert_eq!(report.render(OutputFormat::Csv), "day,deploy,rollback\n2026-02-09,2,1\n2026-02-10,1,0");
+        assert_eq!(
+            report.render(OutputFormat::Text),
+            "Day         deploy  rollback\n\
+             2026-02-09       2         1\n\
+             2026-02-10       1         0\n\
+             Total            3         1"
+        );
+        let json = report.render(OutputFormat::Json);
+        assert!(json.contains(r#""totals":{"deploy":3,"rollback":1},"days":[{"day":"2026-02-09","counts":{"#));
+    }
+
+    #[test]
+    fn test_csv() {
+        let rows = [["a,b".to_string(), "say \"hi\"".to_string()]];
+        assert_eq!(csv(&["x", "y"], &rows), "x,y\n\"a,b\",\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,186 @@
+//! A small single-threaded executor, so independent API calls can wait on
+//! Slack together without a thread each. Futures that would block on a
+//! socket return `Pending` instead; the executor polls the pending ones in
+//! turn and, when none of them got anywhere, sleeps a little before trying
+//! again (std has no portable way to wait on several sockets at once).
+//!
+//! Only reads and writes are multiplexed: resolving a host and connecting
+//! to it still block the thread, and so every other future, until they are
+//! done. And while every future waits, the executor wakes up every few
+//! milliseconds to poll them rather than sleeping until a socket is ready.
+
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// The shortest and longest pause between rounds in which every future is
+/// waiting on a socket; the pause doubles while nothing arrives.
+const MIN_IDLE: Duration = Duration::from_micros(100);
+const MAX_IDLE: Duration = Duration::from_millis(5);
+
+/// A boxed future, for trait methods that return one.
+pub type Pending<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+thread_local! {
+    /// Whether a [`join_bounded`] is running on this thread, so sockets
+    /// should be waited on by returning `Pending` rather than by blocking.
+    static MULTIPLEXING: Cell<bool> = const { Cell::new(false) };
+    /// What the futures polled in the current round reported.
+    static PROGRESSED: Cell<bool> = const { Cell::new(false) };
+    static WAITING_ON_IO: Cell<bool> = const { Cell::new(false) };
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Whether sockets should be waited on by returning `Pending`.
+pub fn is_multiplexing() -> bool {
+    MULTIPLEXING.get()
+}
+
+/// Tells the executor a future moved data, so the round wasn't idle.
+pub fn note_progress() {
+    PROGRESSED.set(true);
+}
+
+/// Tells the executor a future is pending until a socket is ready.
+pub fn note_waiting_on_io() {
+    WAITING_ON_IO.set(true);
+}
+
+/// Runs `future` to completion on this thread.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    run(std::iter::once(future), 1).pop().expect("one future gives one output")
+}
+
+/// Runs `futures` on this thread, up to `limit` of them at a time, and
+/// returns their outputs in order. The futures are only created as room
+/// frees up.
+pub fn join_bounded<F: Future>(futures: impl IntoIterator<Item = F>, limit: usize) -> Vec<F::Output> {
+    let _multiplexing = Multiplexing(MULTIPLEXING.replace(true));
+    run(futures, limit)
+}
+
+/// Restores the outer [`MULTIPLEXING`] when a [`join_bounded`] ends, even
+/// when one of its futures panics.
+struct Multiplexing(bool);
+
+impl Drop for Multiplexing {
+    fn drop(&mut self) {
+        MULTIPLEXING.set(self.0);
+    }
+}
+
+fn run<F: Future>(futures: impl IntoIterator<Item = F>, limit: usize) -> Vec<F::Output> {
+    let mut queue = futures.into_iter();
+    let mut outputs: Vec<Option<F::Output>> = Vec::new();
+    let mut active: Vec<(usize, Pin<Box<F>>)> = Vec::new();
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut idle = MIN_IDLE;
+    loop {
+        while active.len() < limit.max(1) {
+            let Some(future) = queue.next() else { break };
+            active.push((outputs.len(), Box::pin(future)));
+            outputs.push(None);
+        }
+        if active.is_empty() {
+            break;
+        }
+        PROGRESSED.set(false);
+        WAITING_ON_IO.set(false);
+        DEADLINE.set(None);
+        let before = active.len();
+        active.retain_mut(|(i, future)| match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => {
+                outputs[*i] = Some(output);
+                false
+            }
+            Poll::Pending => true,
+        });
+        if active.len() < before || PROGRESSED.get() {
+            idle = MIN_IDLE;
+            continue;
+        }
+        let until_deadline = DEADLINE.get().map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        let pause = match until_deadline {
+            Some(wait) if !WAITING_ON_IO.get() => wait,
+            Some(wait) => wait.min(idle),
+            None => idle,
+        };
+        std::thread::sleep(pause);
+        idle = (idle * 2).min(MAX_IDLE);
+    }
+    outputs.into_iter().map(|output| output.expect("every future ran to completion")).collect()
+}
+
+/// Waits for `duration` without holding up the other futures.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep { deadline: Instant::now() + duration }
+}
+
+pub struct Sleep {
+    deadline: Instant,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+        let earliest = DEADLINE.get().map_or(self.deadline, |d| d.min(self.deadline));
+        DEADLINE.set(Some(earliest));
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Pending `n` times, as if waiting on a socket, then `value`.
+    async fn after_polls(n: usize, value: usize, log: &RefCell<Vec<String>>) -> usize {
+        log.borrow_mut().push(format!("start {}", value));
+        let mut left = n;
+        std::future::poll_fn(|_| {
+            if left == 0 {
+                return Poll::Ready(());
+            }
+            left -= 1;
+            note_waiting_on_io();
+            Poll::Pending
+        })
+        .await;
+        log.borrow_mut().push(format!("end {}", value));
+        value
+    }
+
+    #[test]
+    fn test_join_bounded_interleaves_and_keeps_order() {
+        let log = RefCell::new(Vec::new());
+        let outputs = join_bounded([3, 1, 0].iter().enumerate().map(|(i, &n)| after_polls(n, i, &log)), 2);
+        assert_eq!(outputs, [0, 1, 2]);
+        // The second starts before the first ends; the third waits for room.
+        assert_eq!(log.into_inner(), ["start 0", "start 1", "end 1", "start 2", "end 2", "end 0"]);
+    }
+
+    #[test]
+    fn test_is_multiplexing_only_inside_join() {
+        assert!(!is_multiplexing());
+        assert_eq!(join_bounded([async { is_multiplexing() }], 4), [true]);
+        assert!(!block_on(async { is_multiplexing() }));
+        assert!(!is_multiplexing());
+        let panicked = std::panic::catch_unwind(|| join_bounded([async { panic!("in a future") }], 4));
+        assert!(panicked.is_err());
+        assert!(!is_multiplexing());
+    }
+
+    #[test]
+    fn test_sleep() {
+        let start = Instant::now();
+        block_on(sleep(Duration::from_millis(30)));
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+}
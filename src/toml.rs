@@ -0,0 +1,709 @@
+use crate::error::{ErrorKind, SlkError};
+use crate::json::JsonValue;
+
+/// Parses a TOML document into the same [`JsonValue`] tree the JSON parser
+/// produces, so config handling does not care which format it was read from.
+///
+/// Supports tables, arrays of tables, dotted keys, inline tables, all four
+/// string forms, integers (including hex/octal/binary), floats and booleans.
+/// Dates and times are rejected.
+pub fn parse(input: &str) -> Result<JsonValue, SlkError> {
+    let mut parser = Parser {
+        input: input.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    let mut root = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+
+    loop {
+        parser.skip_blank_lines();
+        let Some(ch) = parser.peek() else { break };
+        if ch == '[' {
+            parser.pos += 1;
+            let array = parser.peek() == Some('[');
+            if array {
+                parser.pos += 1;
+            }
+            parser.skip_spaces();
+            let path = parser.parse_key()?;
+            parser.skip_spaces();
+            parser.expect(']')?;
+            if array {
+                parser.expect(']')?;
+                push_array_table(&mut root, &path, &parser)?;
+            } else {
+                table_mut(&mut root, &path, &parser)?;
+            }
+            current = path;
+        } else {
+            let key = parser.parse_key()?;
+            parser.skip_spaces();
+            parser.expect('=')?;
+            parser.skip_spaces();
+            let value = parser.parse_value()?;
+            let (last, parents) = key.split_last().expect("keys are never empty");
+            let full: Vec<String> = current.iter().chain(parents).cloned().collect();
+            insert(table_mut(&mut root, &full, &parser)?, last, value, &parser)?;
+        }
+        parser.end_of_line()?;
+    }
+    Ok(JsonValue::Object(root))
+}
+
+/// Formats a key, quoting it unless it is a valid bare key.
+pub fn format_key(key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        key.to_string()
+    } else {
+        format_value(&JsonValue::String(key.to_string()))
+    }
+}
+
+/// Formats a value as a TOML literal. Strings use basic (double-quoted) form.
+pub fn format_value(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(s) => {
+            let mut out = String::from("\"");
+            for c in s.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\t' => out.push_str("\\t"),
+                    '\r' => out.push_str("\\r"),
+                    c if (c as u32) < 0x20 || c == '\u{7f}' => {
+                        out.push_str(&format!("\\u{:04X}", c as u32))
+                    }
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+            out
+        }
+        JsonValue::Array(items) => format!(
+            "[{}]",
+            items.iter().map(format_value).collect::<Vec<_>>().join(", ")
+        ),
+        JsonValue::Object(pairs) => format!(
+            "{{ {} }}",
+            pairs
+                .iter()
+                .map(|(k, v)| format!("{} = {}", format_key(k), format_value(v)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        JsonValue::Null => "\"\"".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// What a line of a TOML document declares, as far as [`set_value`] cares.
+enum LineKind {
+    Header(Vec<String>),
+    Key(Vec<String>),
+    Other,
+}
+
+fn classify_line(line: &str) -> LineKind {
+    let mut parser = Parser {
+        input: line.trim_start().chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    match parser.peek() {
+        Some('[') => {
+            parser.pos += 1;
+            if parser.peek() == Some('[') {
+                return LineKind::Other;
+            }
+            match parser.parse_key() {
+                Ok(path) if parser.expect(']').is_ok() => LineKind::Header(path),
+                _ => LineKind::Other,
+            }
+        }
+        Some('#') | None => LineKind::Other,
+        _ => match parser.parse_key() {
+            Ok(key) if parser.expect('=').is_ok() => LineKind::Key(key),
+            _ => LineKind::Other,
+        },
+    }
+}
+
+/// Sets `key` (in `[table]`, or at the top level) to `value`, editing the
+/// document line by line so comments and formatting elsewhere are kept.
+/// An existing entry is replaced in place, even if its value spans lines.
+pub fn set_value(
+    doc: &str,
+    table: Option<&str>,
+    key: &str,
+    value: &JsonValue,
+) -> Result<String, SlkError> {
+    let mut lines: Vec<String> = doc.lines().map(String::from).collect();
+    let entry = format!("{} = {}", format_key(key), format_value(value));
+    let wanted_table: Vec<String> = table.map(|t| vec![t.to_string()]).unwrap_or_default();
+
+    // The line an entry starting at `start` ends on (values may span lines).
+    let entry_end = |lines: &[String], start: usize| {
+        (start..lines.len())
+            .find(|&end| parse(&lines[start..=end].join("\n")).is_ok())
+            .ok_or(SlkError::from(format!("could not parse the entry on line {}", start + 1)))
+    };
+
+    // Scan for the existing entry, or the place to append one: after the
+    // table's last entry, else after its header (or before the first header).
+    let mut in_table = table.is_none();
+    let mut insert_at = None;
+    let mut found = None;
+    for (i, line) in lines.iter().enumerate() {
+        match classify_line(line) {
+            LineKind::Header(path) => {
+                if in_table && insert_at.is_none() {
+                    let mut at = i;
+                    while at > 0 && lines[at - 1].trim().is_empty() {
+                        at -= 1;
+                    }
+                    insert_at = Some(at);
+                }
+                in_table = path == wanted_table;
+                if in_table {
+                    insert_at = Some(i + 1);
+                }
+            }
+            LineKind::Key(k) if in_table => {
+                if k == [key] && found.is_none() {
+                    found = Some(i);
+                }
+                insert_at = Some(i);
+            }
+            _ => {}
+        }
+    }
+    if table.is_none() && insert_at.is_none() {
+        insert_at = Some(lines.len());
+    }
+
+    if let Some(start) = found {
+        let end = entry_end(&lines, start)?;
+        lines.splice(start..=end, [entry]);
+    } else if let Some(at) = insert_at {
+        let at = match classify_line(lines.get(at).map(String::as_str).unwrap_or("")) {
+            LineKind::Key(_) => entry_end(&lines, at)? + 1,
+            _ => at,
+        };
+        lines.insert(at, entry);
+    } else {
+        if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+            lines.push(String::new());
+        }
+        lines.push(format!("[{}]", format_key(table.unwrap_or_default())));
+        lines.push(entry);
+    }
+
+    let mut out = lines.join("\n");
+    out.push('\n');
+    parse(&out)?;
+    Ok(out)
+}
+
+/// Walks to (creating as needed) the table at `path`. A path through an
+/// array of tables descends into its most recent element.
+fn table_mut<'a>(
+    mut table: &'a mut Vec<(String, JsonValue)>,
+    path: &[String],
+    parser: &Parser,
+) -> Result<&'a mut Vec<(String, JsonValue)>, SlkError> {
+    for key in path {
+        let index = match table.iter().position(|(k, _)| k == key) {
+            Some(i) => i,
+            None => {
+                table.push((key.clone(), JsonValue::Object(Vec::new())));
+                table.len() - 1
+            }
+        };
+        table = match &mut table[index].1 {
+            JsonValue::Object(pairs) => pairs,
+            JsonValue::Array(items) => match items.last_mut() {
+                Some(JsonValue::Object(pairs)) => pairs,
+                _ => return Err(parser.error(&format!("'{}' is not a table", key))),
+            },
+            _ => return Err(parser.error(&format!("'{}' is not a table", key))),
+        };
+    }
+    Ok(table)
+}
+
+fn push_array_table(
+    root: &mut Vec<(String, JsonValue)>,
+    path: &[String],
+    parser: &Parser,
+) -> Result<(), SlkError> {
+    let (last, parents) = path.split_last().expect("keys are never empty");
+    let table = table_mut(root, parents, parser)?;
+    match table.iter_mut().find(|(k, _)| k == last) {
+        Some((_, JsonValue::Array(items))) => items.push(JsonValue::Object(Vec::new())),
+        Some(_) => return Err(parser.error(&format!("'{}' is not an array of tables", last))),
+        None => table.push((
+            last.clone(),
+            JsonValue::Array(vec![JsonValue::Object(Vec::new())]),
+        )),
+    }
+    Ok(())
+}
+
+fn insert(
+    table: &mut Vec<(String, JsonValue)>,
+    key: &str,
+    value: JsonValue,
+    parser: &Parser,
+) -> Result<(), SlkError> {
+    if table.iter().any(|(k, _)| k == key) {
+        return Err(parser.error(&format!("duplicate key '{}'", key)));
+    }
+    table.push((key.to_string(), value));
+    Ok(())
+}
+
+struct Parser {
+    input: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn advance(&mut self) -> Result<char, SlkError> {
+        let ch = self.peek().ok_or_else(|| self.error("unexpected end of input"))?;
+        self.pos += 1;
+        if ch == '\n' {
+            self.line += 1;
+        }
+        Ok(ch)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SlkError> {
+        match self.peek() {
+            Some(ch) if ch == expected => {
+                self.advance()?;
+                Ok(())
+            }
+            Some(ch) => Err(self.error(&format!("expected '{}', found '{}'", expected, ch))),
+            None => Err(self.error(&format!("expected '{}', found end of input", expected))),
+        }
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        (self.pos..)
+            .zip(s.chars())
+            .all(|(i, c)| self.input.get(i) == Some(&c))
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Skips whitespace, newlines and comments (between statements and
+    /// inside arrays).
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n') => self.line += 1,
+                Some('\r') => {}
+                _ => return,
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), SlkError> {
+        self.skip_spaces();
+        self.skip_comment();
+        if self.peek() == Some('\r') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => self.advance().map(|_| ()),
+            Some(ch) => Err(self.error(&format!("unexpected '{}' after value", ch))),
+        }
+    }
+
+    /// Parses a possibly dotted key (`a."b c".d`) into its segments.
+    fn parse_key(&mut self) -> Result<Vec<String>, SlkError> {
+        let mut segments = Vec::new();
+        loop {
+            self.skip_spaces();
+            let segment = match self.peek() {
+                Some('"') => self.parse_basic_string()?,
+                Some('\'') => self.parse_literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    {
+                        self.pos += 1;
+                    }
+                    if self.pos == start {
+                        return Err(self.error("expected a key"));
+                    }
+                    self.input[start..self.pos].iter().collect()
+                }
+            };
+            segments.push(segment);
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                return Ok(segments);
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, SlkError> {
+        match self.peek() {
+            Some('"') if self.starts_with("\"\"\"") => self.parse_multiline_basic().map(JsonValue::String),
+            Some('"') => self.parse_basic_string().map(JsonValue::String),
+            Some('\'') if self.starts_with("'''") => {
+                self.parse_multiline_literal().map(JsonValue::String)
+            }
+            Some('\'') => self.parse_literal_string().map(JsonValue::String),
+            Some('[') => self.parse_array(),
+            Some('{') => self.parse_inline_table(),
+            Some('t') if self.starts_with("true") => {
+                self.pos += 4;
+                Ok(JsonValue::Bool(true))
+            }
+            Some('f') if self.starts_with("false") => {
+                self.pos += 5;
+                Ok(JsonValue::Bool(false))
+            }
+            Some(c) if c.is_ascii_digit() || matches!(c, '+' | '-' | 'i' | 'n') => self.parse_number(),
+            Some(c) => Err(self.error(&format!("unexpected character '{}'", c))),
+            None => Err(self.error("expected a value")),
+        }
+    }
+
+    fn parse_escape(&mut self, out: &mut String) -> Result<(), SlkError> {
+        match self.advance()? {
+            '"' => out.push('"'),
+            '\\' => out.push('\\'),
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            'b' => out.push('\u{8}'),
+            'f' => out.push('\u{c}'),
+            'e' => out.push('\u{1b}'),
+            c @ ('u' | 'U') => {
+                let len = if c == 'u' { 4 } else { 8 };
+                let hex: String = (0..len).map(|_| self.advance()).collect::<Result<_, _>>()?;
+                let ch = u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| self.error(&format!("invalid unicode escape \\{}{}", c, hex)))?;
+                out.push(ch);
+            }
+            c => return Err(self.error(&format!("invalid escape \\{}", c))),
+        }
+        Ok(())
+    }
+
+    fn parse_basic_string(&mut self) -> Result<String, SlkError> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.advance()? {
+                '"' => return Ok(s),
+                '\\' => self.parse_escape(&mut s)?,
+                '\n' => return Err(self.error("newline in string")),
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> Result<String, SlkError> {
+        self.expect('\'')?;
+        let mut s = String::new();
+        loop {
+            match self.advance()? {
+                '\'' => return Ok(s),
+                '\n' => return Err(self.error("newline in string")),
+                c => s.push(c),
+            }
+        }
+    }
+
+    /// Skips the newline that may directly follow an opening `"""`/`'''`.
+    fn skip_leading_newline(&mut self) {
+        if self.starts_with("\r\n") {
+            self.pos += 2;
+            self.line += 1;
+        } else if self.peek() == Some('\n') {
+            self.pos += 1;
+            self.line += 1;
+        }
+    }
+
+    fn parse_multiline_basic(&mut self) -> Result<String, SlkError> {
+        self.pos += 3;
+        self.skip_leading_newline();
+        let mut s = String::new();
+        loop {
+            if self.starts_with("\"\"\"") {
+                self.pos += 3;
+                return Ok(s);
+            }
+            match self.advance()? {
+                '\\' if matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) => {
+                    // Line-ending backslash: trim the newline and leading whitespace.
+                    while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                        self.advance()?;
+                    }
+                }
+                '\\' => self.parse_escape(&mut s)?,
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn parse_multiline_literal(&mut self) -> Result<String, SlkError> {
+        self.pos += 3;
+        self.skip_leading_newline();
+        let mut s = String::new();
+        loop {
+            if self.starts_with("'''") {
+                self.pos += 3;
+                return Ok(s);
+            }
+            s.push(self.advance()?);
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, SlkError> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_' | ':'))
+        {
+            self.pos += 1;
+        }
+        let raw: String = self.input[start..self.pos].iter().collect();
+        if raw.contains(':') || raw[1..].contains('-') && !raw.contains(['e', 'E']) {
+            return Err(self.error(&format!("dates and times are not supported: {}", raw)));
+        }
+        let (sign, body) = match raw.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, raw.strip_prefix('+').unwrap_or(&raw)),
+        };
+        if body.starts_with('_') || body.ends_with('_') || body.contains("__") {
+            return Err(self.error(&format!("invalid number: {}", raw)));
+        }
+        let digits = body.replace('_', "");
+        let radix = match digits.get(..2) {
+            Some("0x") => Some(16),
+            Some("0o") => Some(8),
+            Some("0b") => Some(2),
+            _ => None,
+        };
+        let value = match (radix, digits.as_str()) {
+            (Some(radix), _) => i64::from_str_radix(&digits[2..], radix).map(|n| JsonValue::Int(sign * n)).ok(),
+            (None, "inf") => Some(JsonValue::Number(sign as f64 * f64::INFINITY)),
+            (None, "nan") => Some(JsonValue::Number(f64::NAN)),
+            (None, d) if d.len() > 1 && d.starts_with('0') && !d.starts_with("0.") && !d.starts_with("0e") => {
+                None
+            }
+            (None, d) if d.bytes().all(|c| c.is_ascii_digit()) => d.parse::<i64>().map(|n| JsonValue::Int(sign * n)).ok(),
+            (None, d) if d.starts_with(|c: char| c.is_ascii_digit()) => {
+                d.parse::<f64>().map(|n| JsonValue::Number(sign as f64 * n)).ok()
+            }
+            _ => None,
+        };
+        value.ok_or_else(|| self.error(&format!("invalid number: {}", raw)))
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, SlkError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank_lines();
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(JsonValue::Array(items));
+            }
+            items.push(self.parse_value()?);
+            self.skip_blank_lines();
+            match self.advance()? {
+                ',' => continue,
+                ']' => return Ok(JsonValue::Array(items)),
+                c => return Err(self.error(&format!("expected ',' or ']' in array, found '{}'", c))),
+            }
+        }
+    }
+
+    fn parse_inline_table(&mut self) -> Result<JsonValue, SlkError> {
+        self.expect('{')?;
+        let mut pairs = Vec::new();
+        self.skip_spaces();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(pairs));
+        }
+        loop {
+            let key = self.parse_key()?;
+            self.skip_spaces();
+            self.expect('=')?;
+            self.skip_spaces();
+            let value = self.parse_value()?;
+            let (last, parents) = key.split_last().expect("keys are never empty");
+            insert(table_mut(&mut pairs, parents, self)?, last, value, self)?;
+            self.skip_spaces();
+            match self.advance()? {
+                ',' => self.skip_spaces(),
+                '}' => return Ok(JsonValue::Object(pairs)),
+                c => return Err(self.error(&format!("expected ',' or '}}' in inline table, found '{}'", c))),
+            }
+        }
+    }
+
+    fn error(&self, msg: &str) -> SlkError {
+        SlkError::new(ErrorKind::Parse, format!("TOML parse error at line {}: {}", self.line, msg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let input = r#"
+# slk configuration
+client_id = "123.456"   # from the app page
+limit = 50
+color = 'never'
+tls_pins = [
+    "sha256/aaa",  # primary
+    "sha256/bbb",
+]
+
+[aliases]
+standup = "history C0123 --limit 20"
+"my alias".x = true
+"#;
+        let value = parse(input).unwrap();
+        assert_eq!(
+            value,
+            crate::json::parse(
+                r#"{"client_id": "123.456", "limit": 50, "color": "never",
+                    "tls_pins": ["sha256/aaa", "sha256/bbb"],
+                    "aliases": {"standup": "history C0123 --limit 20", "my alias": {"x": true}}}"#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_strings() {
+        let value = parse(
+            "a = \"tab\\there \\u00e9\"\nb = 'C:\\path'\nc = \"\"\"\nline1\nline2\"\"\"\nd = '''\nraw \\n'''\ne = \"\"\"one \\\n    two\"\"\"\n",
+        )
+        .unwrap();
+        assert_eq!(value.get("a").unwrap().as_str(), Some("tab\there é"));
+        assert_eq!(value.get("b").unwrap().as_str(), Some("C:\\path"));
+        assert_eq!(value.get("c").unwrap().as_str(), Some("line1\nline2"));
+        assert_eq!(value.get("d").unwrap().as_str(), Some("raw \\n"));
+        assert_eq!(value.get("e").unwrap().as_str(), Some("one two"));
+    }
+
+    #[test]
+    fn test_parse_numbers() {
+        let value = parse("a = 1_000\nb = -3.5e2\nc = 0xff\nd = +7\ne = 0.5").unwrap();
+        assert_eq!(value.get("a"), Some(&JsonValue::Int(1000)));
+        assert_eq!(value.get("b"), Some(&JsonValue::Number(-350.0)));
+        assert_eq!(value.get("c"), Some(&JsonValue::Int(255)));
+        assert_eq!(value.get("d"), Some(&JsonValue::Int(7)));
+        assert_eq!(value.get("e"), Some(&JsonValue::Number(0.5)));
+    }
+
+    #[test]
+    fn test_parse_tables() {
+        let value = parse(
+            "[a.b]\nc = 1\n[[items]]\nname = \"x\"\n[[items]]\nname = \"y\"\n[point]\npos = { x = 1, y.z = 2 }",
+        )
+        .unwrap();
+        assert_eq!(
+            value,
+            crate::json::parse(
+                r#"{"a": {"b": {"c": 1}}, "items": [{"name": "x"}, {"name": "y"}],
+                    "point": {"pos": {"x": 1, "y": {"z": 2}}}}"#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_format_value() {
+        assert_eq!(format_value(&JsonValue::String("a\"b\\c\n".to_string())), r#""a\"b\\c\n""#);
+        assert_eq!(format_value(&JsonValue::Int(50)), "50");
+        assert_eq!(
+            format_value(&crate::json::parse(r#"["x", true]"#).unwrap()),
+            r#"["x", true]"#
+        );
+        assert_eq!(format_key("standup"), "standup");
+        assert_eq!(format_key("my alias"), r#""my alias""#);
+    }
+
+    #[test]
+    fn test_set_value_replaces_in_place() {
+        let doc = "# creds\nclient_id = \"1\" # old\ntls_pins = [\n  \"a\",\n]\n\n[aliases]\nsu = \"history\"\n";
+        let doc = set_value(doc, None, "tls_pins", &crate::json::parse(r#"["b"]"#).unwrap()).unwrap();
+        let doc = set_value(&doc, None, "client_id", &JsonValue::String("2".to_string())).unwrap();
+        assert_eq!(
+            doc,
+            "# creds\nclient_id = \"2\"\ntls_pins = [\"b\"]\n\n[aliases]\nsu = \"history\"\n"
+        );
+    }
+
+    #[test]
+    fn test_set_value_inserts() {
+        let doc = "client_id = \"1\"\n\n[aliases]\nsu = \"history\"\n\n# trailing comment\n";
+        let doc = set_value(doc, None, "limit", &JsonValue::Int(20)).unwrap();
+        let doc = set_value(&doc, Some("aliases"), "gen", &JsonValue::String("history C1".to_string()))
+            .unwrap();
+        assert_eq!(
+            doc,
+            "client_id = \"1\"\nlimit = 20\n\n[aliases]\nsu = \"history\"\ngen = \"history C1\"\n\n# trailing comment\n"
+        );
+
+        let doc = set_value("", Some("aliases"), "my alias", &JsonValue::String("list".to_string()))
+            .unwrap();
+        assert_eq!(doc, "[aliases]\n\"my alias\" = \"list\"\n");
+        assert_eq!(set_value("", None, "limit", &JsonValue::Int(5)).unwrap(), "limit = 5\n");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (input, expected) in [
+            ("a = 1\na = 2", "line 2: duplicate key 'a'"),
+            ("a = ", "expected a value"),
+            ("a = \"open", "unexpected end of input"),
+            ("a = 1 b = 2", "unexpected 'b' after value"),
+            ("a = 1979-05-27", "dates and times are not supported"),
+            ("a = 012", "invalid number"),
+            ("a = 1\n[a]\nb = 2", "'a' is not a table"),
+        ] {
+            let err = parse(input).unwrap_err();
+            assert!(err.message.contains(expected), "{}: {}", input, err.message);
+        }
+    }
+}
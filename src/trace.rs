@@ -0,0 +1,185 @@
+use crate::debug;
+use crate::http::{self, Request, Response, Transport};
+use crate::task::Pending;
+use std::time::Instant;
+
+/// Parameters whose values are credentials and must never be printed.
+const SECRET_PARAMS: [&str; 5] = ["token", "client_secret", "code", "refresh_token", "password"];
+
+/// A [`Transport`] wrapper that logs each request and a summary of its
+/// response to stderr. Credentials are redacted and bodies are never printed.
+pub struct Tracing<'a> {
+    inner: &'a dyn Transport,
+}
+
+impl<'a> Tracing<'a> {
+    pub fn new(inner: &'a dyn Transport) -> Self {
+        Tracing { inner }
+    }
+}
+
+impl Transport for Tracing<'_> {
+    fn send(&self, request: &Request) -> Result<Response, http::Error> {
+        debug!("[trace] {}", describe_request(request));
+        let start = Instant::now();
+        let result = self.inner.send(request);
+        trace_result(&result, start);
+        result
+    }
+
+    fn send_async<'a>(&'a self, request: &'a Request) -> Pending<'a, Result<Response, http::Error>> {
+        Box::pin(async move {
+            debug!("[trace] {}", describe_request(request));
+            let start = Instant::now();
+            let result = self.inner.send_async(request).await;
+            trace_result(&result, start);
+            result
+        })
+    }
+}
+
+fn trace_result(result: &Result<Response, http::Error>, start: Instant) {
+    let elapsed = start.elapsed().as_millis();
+    match result {
+        Ok(response) => debug!("[trace] <- {} in {}ms", summarize_response(response), elapsed),
+        Err(e) => debug!("[trace] <- failed in {}ms: {}", elapsed, e),
+    }
+}
+
+fn looks_like_token(value: &str) -> bool {
+    ["xoxp-", "xoxb-", "xoxa-", "xoxe", "xoxs-", "xapp-"]
+        .iter()
+        .any(|prefix| value.starts_with(prefix))
+}
+
+/// Rewrites `k=v&...` so that secret values read `[REDACTED]`.
+pub fn redact_params(params: &str) -> String {
+    params
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((k, v)) if SECRET_PARAMS.contains(&k) || looks_like_token(v) => {
+                format!("{}=[REDACTED]", k)
+            }
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn describe_request(request: &Request) -> String {
+    let (path, query) = match request.url.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (request.url.as_str(), None),
+    };
+    let name = match path.split_once("/services/") {
+        // An incoming webhook's path is its secret; show only the host.
+        Some((base, _)) => format!("{}/services/…", base.split_once("://").map_or(base, |(_, host)| host)),
+        // Show just the API method name (e.g. "conversations.history") when possible.
+        None => path.rsplit('/').next().filter(|n| !n.is_empty()).unwrap_or(path).to_string(),
+    };
+
+    let mut out = format!("{} {}", request.method.as_str(), name);
+    if let Some(query) = query {
+        out.push(' ');
+        out.push_str(&redact_params(query));
+    }
+    let is_form = request.headers.iter().any(|(k, v)| {
+        k.eq_ignore_ascii_case("Content-Type") && v.starts_with("application/x-www-form-urlencoded")
+    });
+    if is_form && !request.body.is_empty() {
+        out.push_str(" body: ");
+        out.push_str(&redact_params(&String::from_utf8_lossy(&request.body)));
+    }
+    out
+}
+
+fn summarize_response(response: &Response) -> String {
+    let mut out = format!("{} ({} bytes", response.status, response.body.len());
+    let parsed = std::str::from_utf8(&response.body)
+        .ok()
+        .and_then(|body| crate::json::parse(body).ok());
+    if let Some(json) = parsed {
+        if let Some(ok) = json.get("ok").and_then(|v| v.as_bool()) {
+            out.push_str(&format!(", ok={}", ok));
+        }
+        if let Some(error) = json.get("error").and_then(|v| v.as_str()) {
+            out.push_str(&format!(", error={}", error));
+        }
+    }
+    if let Some(req_id) = response.header("x-slack-req-id") {
+        out.push_str(&format!(", x-slack-req-id={}", req_id));
+    }
+    out.push(')');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_params() {
+        assert_eq!(
+            redact_params("client_id=123&client_secret=shh&code=abc&redirect_uri=x"),
+            "client_id=123&client_secret=[REDACTED]&code=[REDACTED]&redirect_uri=x"
+        );
+        assert_eq!(
+            redact_params("channel=C1&foo=xoxp-1234"),
+            "channel=C1&foo=[REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_describe_request_hides_authorization() {
+        let request = Request::get("https://slack.com/api/conversations.history?channel=C1&limit=200")
+            .header("Authorization", "Bearer xoxp-secret");
+        let described = describe_request(&request);
+        assert_eq!(described, "GET conversations.history channel=C1&limit=200");
+        assert!(!described.contains("xoxp-secret"));
+    }
+
+    #[test]
+    fn test_describe_request_redacts_form_body() {
+        let request = Request::post_form(
+            "https://slack.com/api/oauth.v2.access",
+            &[("client_id", "1"), ("client_secret", "shh")],
+        );
+        assert_eq!(
+            describe_request(&request),
+            "POST oauth.v2.access body: client_id=1&client_secret=[REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_describe_request_hides_webhook_secret() {
+        let body = r#"{"text":"deployed"}"#;
+        let request = Request::post_json("https://hooks.slack.com/services/T1/B1/s3cr3tS3cr3t", body);
+        let described = describe_request(&request);
+        assert_eq!(described, "POST hooks.slack.com/services/…");
+        assert!(!described.contains("s3cr3t"));
+    }
+
+    #[test]
+    fn test_summarize_response() {
+        let response = Response {
+            status: 200,
+            headers: vec![("x-slack-req-id".to_string(), "r1".to_string())],
+            body: br#"{"ok":false,"error":"not_in_channel"}"#.to_vec(),
+        };
+        assert_eq!(
+            summarize_response(&response),
+            "200 (37 bytes, ok=false, error=not_in_channel, x-slack-req-id=r1)"
+        );
+    }
+
+    #[test]
+    fn test_tracing_passes_through() {
+        let fake = http::FakeTransport::new().respond("api.test", r#"{"ok":true}"#);
+        let tracing = Tracing::new(&fake);
+        let response = tracing
+            .send(&Request::get("https://slack.com/api/api.test"))
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(fake.requests.lock().unwrap().len(), 1);
+    }
+}
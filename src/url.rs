@@ -1,3 +1,6 @@
+//! Slack message links (`https://<workspace>.slack.com/archives/...`) and
+//! their parts.
+
 use crate::error::SlkError;
 
 #[derive(Debug, PartialEq)]
@@ -6,28 +9,114 @@ pub struct SlackThread {
     pub ts: String,
 }
 
+/// The thread a message link belongs to. A link to a reply (with a
+/// `thread_ts` parameter) gives the thread it is in, not the reply.
 pub fn parse_slack_url(url: &str) -> Result<SlackThread, SlkError> {
-    let segments: Vec<&str> = url.split('/').collect();
+    match parse_link(url)? {
+        SlackLink::Message { channel_id, ts, thread_ts } => {
+            Ok(SlackThread { channel_id, ts: thread_ts.unwrap_or(ts) })
+        }
+        SlackLink::Channel { .. } => Err(SlkError::from("missing timestamp after channel ID")),
+    }
+}
+
+/// What a Slack URL points at.
+#[derive(Debug, PartialEq)]
+pub enum SlackLink {
+    /// `/archives/C…`
+    Channel { channel_id: String },
+    /// `/archives/C…/p…`. `thread_ts` is set when the message is a reply,
+    /// from the link's `thread_ts` parameter.
+    Message { channel_id: String, ts: String, thread_ts: Option<String> },
+}
+
+/// Whether a command-line argument is a Slack link rather than a channel
+/// name or ID: a URL, or a workspace-relative `/archives/...` path.
+pub fn is_link(arg: &str) -> bool {
+    arg.starts_with("https://") || arg.starts_with("http://") || arg.starts_with("/archives/")
+}
 
+/// Works out whether a Slack URL is a channel or a message link. A `cid`
+/// parameter, as in links copied from replies, names the channel. Besides
+/// `/archives/` links (with or without the workspace host), this takes the
+/// web client's `app.slack.com/client/T…/C…[/thread/C…-<ts>]` addresses and
+/// `slack.com/app_redirect?channel=…` redirects, for channels, private
+/// groups and DMs alike.
+pub fn parse_link(url: &str) -> Result<SlackLink, SlkError> {
+    let url = url.split('#').next().unwrap_or(url);
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    if segments.last() == Some(&"app_redirect") {
+        let channel_id =
+            query_param(query, "channel").ok_or(SlkError::from("missing channel in app_redirect link"))?;
+        return Ok(SlackLink::Channel { channel_id: channel_id.to_string() });
+    }
+    if let Some(client_pos) = segments.iter().position(|&s| s == "client") {
+        let channel_id = segments
+            .get(client_pos + 2)
+            .ok_or(SlkError::from("missing channel ID after /client/<team>/"))?
+            .to_string();
+        return match segments.get(client_pos + 3..client_pos + 5) {
+            Some(["thread", thread]) => {
+                let ts = thread
+                    .split_once('-')
+                    .map(|(_, ts)| ts)
+                    .filter(|ts| ts.contains('.'))
+                    .ok_or(SlkError::from("expected /thread/<channel>-<ts> in the link"))?;
+                Ok(SlackLink::Message { channel_id, ts: ts.to_string(), thread_ts: None })
+            }
+            _ => Ok(SlackLink::Channel { channel_id }),
+        };
+    }
     let archives_pos = segments
         .iter()
         .position(|&s| s == "archives")
-        .ok_or(SlkError::from("URL must contain '/archives/'"))?;
-
+        .ok_or(SlkError::from("not a Slack channel or message URL (expected '/archives/')"))?;
     let channel_id = segments
         .get(archives_pos + 1)
         .ok_or(SlkError::from("missing channel ID after /archives/"))?;
+    let channel_id = query_param(query, "cid").unwrap_or(channel_id).to_string();
+    let Some(ts_segment) = segments.get(archives_pos + 2) else {
+        return Ok(SlackLink::Channel { channel_id });
+    };
+    let thread_ts = query_param(query, "thread_ts").map(String::from);
+    Ok(SlackLink::Message { channel_id, ts: convert_timestamp(ts_segment)?, thread_ts })
+}
 
-    let ts_segment = segments
-        .get(archives_pos + 2)
-        .ok_or(SlkError::from("missing timestamp after channel ID"))?;
+/// The non-empty value of `name` in a URL's query string.
+fn query_param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+        .filter(|value| !value.is_empty())
+}
 
-    let ts = convert_timestamp(ts_segment)?;
+/// The workspace host of a Slack URL, e.g. `myteam.slack.com`. Links on
+/// `slack.com` or `app.slack.com` don't name a workspace.
+pub fn workspace_host(url: &str) -> Option<&str> {
+    let rest = url.split_once("://")?.1;
+    let host = rest.split(['/', '?', '#']).next()?;
+    (!host.is_empty() && !["slack.com", "app.slack.com"].contains(&host)).then_some(host)
+}
 
-    Ok(SlackThread {
-        channel_id: channel_id.to_string(),
-        ts,
-    })
+/// The `slack://` link that opens a channel, or a message in it, in the
+/// Slack app.
+pub fn app_link(team_id: &str, channel_id: &str, ts: Option<&str>) -> String {
+    let mut link = format!("slack://channel?team={}&id={}", team_id, channel_id);
+    if let Some(ts) = ts {
+        link.push_str(&format!("&message={}", ts));
+    }
+    link
+}
+
+/// The `/archives/` link to a channel, or a message in it, on the workspace
+/// at `workspace_url` (`https://myteam.slack.com/`, as `auth.test` gives it).
+pub fn archive_link(workspace_url: &str, channel_id: &str, ts: Option<&str>) -> String {
+    let mut link = format!("{}/archives/{}", workspace_url.trim_end_matches('/'), channel_id);
+    if let Some(ts) = ts {
+        link.push_str(&format!("/p{}", ts.replace('.', "")));
+    }
+    link
 }
 
 fn convert_timestamp(raw: &str) -> Result<String, SlkError> {
@@ -47,6 +136,17 @@ fn convert_timestamp(raw: &str) -> Result<String, SlkError> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_workspace_host() {
+        assert_eq!(
+            workspace_host("https://myteam.slack.com/archives/C081VT5GLQH/p1770689887565249"),
+            Some("myteam.slack.com")
+        );
+        assert_eq!(workspace_host("https://myteam.slack.com/"), Some("myteam.slack.com"));
+        assert_eq!(workspace_host("myteam.slack.com"), None);
+        assert_eq!(workspace_host("https://app.slack.com/client/T012AB3C4/D0123ABCD"), None);
+    }
+
     #[test]
     fn test_parse_valid_url() {
         let result = parse_slack_url(
@@ -74,6 +174,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_reply_url() {
+        let result = parse_slack_url(
+            "https://myteam.slack.com/archives/C081VT5GLQH/p1770689900000100?thread_ts=1770689887.565249&cid=C081VT5GLQH",
+        );
+        assert_eq!(
+            result.unwrap(),
+            SlackThread {
+                channel_id: "C081VT5GLQH".to_string(),
+                ts: "1770689887.565249".to_string(),
+            }
+        );
+        let result = parse_slack_url("https://myteam.slack.com/archives/C081VT5GLQH/p1770689887565249?cid=C092X3AB7F1");
+        assert_eq!(result.unwrap().channel_id, "C092X3AB7F1");
+        let result = parse_slack_url("https://myteam.slack.com/archives/C081VT5GLQH/p1770689887565249?thread_ts=");
+        assert_eq!(result.unwrap().ts, "1770689887.565249");
+    }
+
     #[test]
     fn test_parse_url_missing_p_prefix() {
         let result = parse_slack_url(
@@ -94,6 +212,77 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_link() {
+        assert_eq!(
+            parse_link("https://myteam.slack.com/archives/C081VT5GLQH/").unwrap(),
+            SlackLink::Channel { channel_id: "C081VT5GLQH".to_string() }
+        );
+        assert_eq!(
+            parse_link("https://myteam.slack.com/archives/C081VT5GLQH/p1770689887565249").unwrap(),
+            SlackLink::Message {
+                channel_id: "C081VT5GLQH".to_string(),
+                ts: "1770689887.565249".to_string(),
+                thread_ts: None,
+            }
+        );
+        assert_eq!(
+            parse_link(
+                "https://myteam.slack.com/archives/C081VT5GLQH/p1770689900000100?thread_ts=1770689887.565249&cid=C081VT5GLQH"
+            )
+            .unwrap(),
+            SlackLink::Message {
+                channel_id: "C081VT5GLQH".to_string(),
+                ts: "1770689900.000100".to_string(),
+                thread_ts: Some("1770689887.565249".to_string()),
+            }
+        );
+        assert!(parse_link("https://myteam.slack.com/messages/C081VT5GLQH").is_err());
+        assert!(parse_link("https://myteam.slack.com/archives/").is_err());
+    }
+
+    #[test]
+    fn test_parse_link_conversation_kinds() {
+        for id in ["C081VT5GLQH", "G012ABC3DEF", "D0123ABCDEF"] {
+            let message = SlackLink::Message {
+                channel_id: id.to_string(),
+                ts: "1770689887.565249".to_string(),
+                thread_ts: None,
+            };
+            let channel = SlackLink::Channel { channel_id: id.to_string() };
+            let link = |url: String| parse_link(&url).unwrap();
+            assert_eq!(link(format!("https://myteam.slack.com/archives/{}/p1770689887565249", id)), message);
+            assert_eq!(link(format!("/archives/{}/p1770689887565249", id)), message);
+            let client_thread = format!("https://app.slack.com/client/T012AB3C4/{}/thread/{}-1770689887.565249", id, id);
+            assert_eq!(link(client_thread.clone()), message);
+            assert_eq!(link(format!("https://app.slack.com/client/T012AB3C4/{}", id)), channel);
+            assert_eq!(link(format!("https://slack.com/app_redirect?channel={}", id)), channel);
+            let thread = parse_slack_url(&client_thread).unwrap();
+            assert_eq!((thread.channel_id.as_str(), thread.ts.as_str()), (id, "1770689887.565249"));
+        }
+        assert!(parse_link("https://app.slack.com/client/T012AB3C4/").is_err());
+        assert!(parse_link("https://app.slack.com/client/T012AB3C4/D0123ABCDEF/thread/D0123ABCDEF").is_err());
+        assert!(parse_link("https://slack.com/app_redirect?team=T012AB3C4").is_err());
+        assert!(is_link("/archives/D0123ABCDEF/p1770689887565249"));
+        assert!(!is_link("#general"));
+    }
+
+    #[test]
+    fn test_app_and_archive_links() {
+        assert_eq!(app_link("T012AB3C4", "C081VT5GLQH", None), "slack://channel?team=T012AB3C4&id=C081VT5GLQH");
+        assert_eq!(
+            app_link("T012AB3C4", "C081VT5GLQH", Some("1770689887.565249")),
+            "slack://channel?team=T012AB3C4&id=C081VT5GLQH&message=1770689887.565249"
+        );
+        assert_eq!(
+            archive_link("https://myteam.slack.com/", "C081VT5GLQH", None),
+            "https://myteam.slack.com/archives/C081VT5GLQH"
+        );
+        let link = archive_link("https://myteam.slack.com/", "C081VT5GLQH", Some("1770689887.565249"));
+        assert_eq!(link, "https://myteam.slack.com/archives/C081VT5GLQH/p1770689887565249");
+        assert!(matches!(parse_link(&link).unwrap(), SlackLink::Message { ts, .. } if ts == "1770689887.565249"));
+    }
+
     #[test]
     fn test_convert_timestamp() {
         assert_eq!(
@@ -0,0 +1,289 @@
+use crate::error::SlkError;
+use crate::http::{self, Request, Stream};
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use ring::rand::{SecureRandom, SystemRandom};
+
+/// Appended to the client's key before hashing, per RFC 6455.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Refuse frames larger than this rather than buffering them.
+const MAX_FRAME_LEN: u64 = 16 * 1024 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    Text(String),
+    /// The server closed the connection (or it dropped).
+    Close,
+}
+
+#[derive(Debug, PartialEq)]
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// A client WebSocket connection. Pings are answered as they arrive.
+pub struct WebSocket {
+    stream: Box<dyn Stream>,
+    socket: TcpStream,
+    /// Received bytes not yet parsed into frames.
+    buf: Vec<u8>,
+    /// The opcode and payload so far of a fragmented message.
+    partial: Option<(u8, Vec<u8>)>,
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], SlkError> {
+    let mut bytes = [0u8; N];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| SlkError::from("failed to generate random bytes"))?;
+    Ok(bytes)
+}
+
+/// The `Sec-WebSocket-Accept` value the server must answer `key` with.
+pub fn accept_key(key: &str) -> String {
+    let digest = ring::digest::digest(
+        &ring::digest::SHA1_FOR_LEGACY_USE_ONLY,
+        format!("{}{}", key, ACCEPT_GUID).as_bytes(),
+    );
+    crate::base64::encode(digest.as_ref())
+}
+
+/// Opens a `ws://` or `wss://` URL.
+pub fn connect(client: &http::Client, url: &str) -> Result<WebSocket, SlkError> {
+    let http_url = if let Some(rest) = url.strip_prefix("wss://") {
+        format!("https://{}", rest)
+    } else if let Some(rest) = url.strip_prefix("ws://") {
+        format!("http://{}", rest)
+    } else {
+        return Err(SlkError::from(format!("not a WebSocket URL: {}", url)));
+    };
+    let key = crate::base64::encode(&random_bytes::<16>()?);
+    let request = Request::get(&http_url)
+        .header("Connection", "Upgrade")
+        .header("Upgrade", "websocket")
+        .header("Sec-WebSocket-Version", "13")
+        .header("Sec-WebSocket-Key", &key);
+    let (response, upgraded) = client.upgrade(&request)?;
+    if response.status != 101 {
+        return Err(SlkError::from(format!(
+            "WebSocket handshake failed ({})",
+            response.describe()
+        )));
+    }
+    if response.header("Sec-WebSocket-Accept") != Some(accept_key(&key).as_str()) {
+        return Err(SlkError::from("WebSocket handshake failed: bad Sec-WebSocket-Accept"));
+    }
+    Ok(WebSocket {
+        stream: upgraded.stream,
+        socket: upgraded.socket,
+        buf: upgraded.pending,
+        partial: None,
+    })
+}
+
+/// A masked client frame.
+fn encode_frame(opcode: u8, payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(0x80 | len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    frame
+}
+
+/// The first complete frame in `buf` and its length in bytes, or `None` if
+/// more bytes are needed.
+fn decode_frame(buf: &[u8]) -> Result<Option<(Frame, usize)>, SlkError> {
+    let [b0, b1, ..] = *buf else { return Ok(None) };
+    let masked = b1 & 0x80 != 0;
+    let (len, mut pos) = match b1 & 0x7F {
+        126 if buf.len() >= 4 => (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4),
+        127 if buf.len() >= 10 => (u64::from_be_bytes(buf[2..10].try_into().unwrap()), 10),
+        126 | 127 => return Ok(None),
+        len => (len as u64, 2),
+    };
+    if len > MAX_FRAME_LEN {
+        return Err(SlkError::from(format!("WebSocket frame too large ({} bytes)", len)));
+    }
+    let mask = if masked {
+        let Some(mask) = buf.get(pos..pos + 4) else { return Ok(None) };
+        pos += 4;
+        Some([mask[0], mask[1], mask[2], mask[3]])
+    } else {
+        None
+    };
+    let end = pos + len as usize;
+    let Some(payload) = buf.get(pos..end) else { return Ok(None) };
+    let payload = match mask {
+        Some(mask) => payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]).collect(),
+        None => payload.to_vec(),
+    };
+    Ok(Some((Frame { fin: b0 & 0x80 != 0, opcode: b0 & 0x0F, payload }, end)))
+}
+
+impl WebSocket {
+    /// How long [`WebSocket::read`] waits for data before returning `None`.
+    pub fn set_read_timeout(&self, timeout: Duration) -> Result<(), SlkError> {
+        self.socket
+            .set_read_timeout(Some(timeout))
+            .map_err(|e| SlkError::from(format!("failed to set WebSocket timeout: {}", e)))
+    }
+
+    fn send_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<(), SlkError> {
+        let frame = encode_frame(opcode, payload, random_bytes()?);
+        self.stream
+            .write_all(&frame)
+            .and_then(|()| self.stream.flush())
+            .map_err(|e| SlkError::from(format!("WebSocket write failed: {}", e)))
+    }
+
+    pub fn send_text(&mut self, text: &str) -> Result<(), SlkError> {
+        self.send_frame(OP_TEXT, text.as_bytes())
+    }
+
+    /// Sends a close frame; the connection is not usable afterwards.
+    pub fn close(&mut self) {
+        let _ = self.send_frame(OP_CLOSE, &[]);
+    }
+
+    /// The next message, or `None` if nothing arrived within the read timeout.
+    pub fn read(&mut self) -> Result<Option<Message>, SlkError> {
+        loop {
+            while let Some((frame, used)) = decode_frame(&self.buf)? {
+                self.buf.drain(..used);
+                if let Some(message) = self.handle(frame)? {
+                    return Ok(Some(message));
+                }
+            }
+            let mut chunk = [0u8; 16 * 1024];
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Ok(Some(Message::Close)),
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(None);
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(SlkError::from(format!("WebSocket read failed: {}", e))),
+            }
+        }
+    }
+
+    fn handle(&mut self, frame: Frame) -> Result<Option<Message>, SlkError> {
+        match frame.opcode {
+            OP_PING => self.send_frame(OP_PONG, &frame.payload)?,
+            OP_PONG => {}
+            OP_CLOSE => {
+                self.close();
+                return Ok(Some(Message::Close));
+            }
+            OP_TEXT | OP_BINARY if frame.fin => return Ok(Some(text(frame.payload)?)),
+            OP_TEXT | OP_BINARY => self.partial = Some((frame.opcode, frame.payload)),
+            OP_CONTINUATION => {
+                let Some((opcode, mut payload)) = self.partial.take() else {
+                    return Err(SlkError::from("WebSocket continuation frame without a message"));
+                };
+                payload.extend(frame.payload);
+                if frame.fin {
+                    return Ok(Some(text(payload)?));
+                }
+                self.partial = Some((opcode, payload));
+            }
+            opcode => return Err(SlkError::from(format!("unknown WebSocket opcode {}", opcode))),
+        }
+        Ok(None)
+    }
+}
+
+fn text(payload: Vec<u8>) -> Result<Message, SlkError> {
+    String::from_utf8(payload)
+        .map(Message::Text)
+        .map_err(|_| SlkError::from("WebSocket message is not valid UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_accept_key() {
+        // The example from RFC 6455, section 1.3.
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn test_frame_round_trip() {
+        let frame = encode_frame(OP_TEXT, b"Hello", [0x37, 0xfa, 0x21, 0x3d]);
+        // The masked "Hello" example from RFC 6455, section 5.7.
+        assert_eq!(frame, [0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58]);
+        let (decoded, used) = decode_frame(&frame).unwrap().unwrap();
+        assert_eq!(used, frame.len());
+        assert_eq!(decoded, Frame { fin: true, opcode: OP_TEXT, payload: b"Hello".to_vec() });
+        assert_eq!(decode_frame(&frame[..6]).unwrap(), None);
+
+        let long = vec![b'x'; 300];
+        let frame = encode_frame(OP_BINARY, &long, [1, 2, 3, 4]);
+        assert_eq!(&frame[1..4], &[0x80 | 126, 0x01, 0x2c]);
+        assert_eq!(decode_frame(&frame).unwrap().unwrap().0.payload, long);
+    }
+
+    #[test]
+    fn test_connect_and_read() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut key = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.strip_prefix("Sec-WebSocket-Key: ") {
+                    key = value.trim().to_string();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut out = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(&key)
+            )
+            .into_bytes();
+            // A ping, then "hello" split over two fragments.
+            out.extend([0x89, 0x01, b'!', 0x01, 0x03, b'h', b'e', b'l', 0x80, 0x02, b'l', b'o']);
+            stream.write_all(&out).unwrap();
+            let mut reply = vec![0u8; 7 + 12];
+            reader.read_exact(&mut reply).unwrap();
+            (decode_frame(&reply).unwrap().unwrap().0, decode_frame(&reply[7..]).unwrap().unwrap().0)
+        });
+
+        let mut ws = connect(&http::Client::new(), &format!("ws://127.0.0.1:{}/link", port)).unwrap();
+        assert_eq!(ws.read().unwrap(), Some(Message::Text("hello".to_string())));
+        ws.send_text("ack:ok").unwrap();
+        let (pong, ack) = server.join().unwrap();
+        assert_eq!(pong, Frame { fin: true, opcode: OP_PONG, payload: b"!".to_vec() });
+        assert_eq!(ack.payload, b"ack:ok");
+    }
+}
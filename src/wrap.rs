@@ -0,0 +1,146 @@
+//! Wrapping message text to the terminal's width, with continuation lines
+//! indented under the text rather than under the time and name before it.
+
+use std::io::IsTerminal;
+use std::process::{Command, Stdio};
+
+/// The width of the terminal stdout is on: `COLUMNS` if set, or what `stty`
+/// says. `None` when stdout isn't a terminal, or the width can't be told.
+pub fn terminal_width() -> Option<usize> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    if let Some(columns) = std::env::var("COLUMNS").ok().and_then(|c| c.trim().parse().ok()) {
+        return Some(columns).filter(|&c| c > 0);
+    }
+    let tty = std::fs::File::open("/dev/tty").ok()?;
+    let output = Command::new("stty").arg("size").stdin(tty).stderr(Stdio::null()).output().ok()?;
+    let size = String::from_utf8_lossy(&output.stdout);
+    size.split_whitespace().nth(1)?.parse().ok().filter(|&c| c > 0)
+}
+
+/// How many columns `c` takes up: 2 for East Asian wide characters and
+/// emoji, 0 for combining marks and zero-width characters.
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+pub fn display_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// Splits a line into the pieces it may be broken between: runs of spaces,
+/// words, and single wide characters, since Japanese and Chinese text has no
+/// spaces to break at.
+fn pieces(line: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let wide = |c: char| char_width(c) == 2;
+    let mut chars = line.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        let (end, breaks_after) = match chars.peek() {
+            None => (line.len(), true),
+            Some(&(end, next)) => (end, wide(c) || wide(next) || (c == ' ') != (next == ' ')),
+        };
+        if breaks_after {
+            pieces.push(&line[start..end]);
+            start = end;
+        }
+    }
+    pieces
+}
+
+/// Wraps one line of text into lines of at most `width` columns, breaking
+/// between words. A word too long for a line (a URL) gets a line of its own.
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut space = "";
+    for piece in pieces(line) {
+        if piece.starts_with(' ') {
+            space = piece;
+            continue;
+        }
+        let fits = display_width(&current) + display_width(space) + display_width(piece) <= width;
+        if !current.is_empty() && !fits {
+            lines.push(std::mem::take(&mut current));
+        } else {
+            current.push_str(space);
+        }
+        current.push_str(piece);
+        space = "";
+    }
+    lines.push(current);
+    lines
+}
+
+/// `text` wrapped to end by column `width` when it starts at column
+/// `indent`, with every line after the first indented to `indent`. Lines
+/// the text already breaks at are kept, and code blocks (between ```
+/// fences) are indented but not wrapped. Too narrow a space for the text
+/// leaves it as it is.
+pub fn wrap(text: &str, indent: usize, width: usize) -> String {
+    const MIN_WIDTH: usize = 20;
+    let Some(available) = width.checked_sub(indent).filter(|&w| w >= MIN_WIDTH) else {
+        return text.to_string();
+    };
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for line in text.split('\n') {
+        let fences = line.matches("```").count();
+        if in_code || fences > 0 {
+            lines.push(line.to_string());
+        } else {
+            lines.extend(wrap_line(line, available));
+        }
+        in_code ^= fences % 2 == 1;
+    }
+    lines.join(&format!("\n{}", " ".repeat(indent)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        let text = "the deploy finished but the canary is reporting elevated error rates";
+        assert_eq!(
+            wrap(text, 10, 40),
+            "the deploy finished but the\n          canary is reporting elevated\n          error rates"
+        );
+        assert_eq!(wrap("short", 10, 40), "short");
+        assert_eq!(wrap(text, 30, 40), text);
+        assert_eq!(wrap("see\nhttps://example.com/a/very/long/path/that/does/not/fit", 4, 30),
+            "see\n    https://example.com/a/very/long/path/that/does/not/fit");
+        assert_eq!(
+            wrap("code:\n```\nlet x = a_rather_long_expression + another_long_one;\n```", 2, 30),
+            "code:\n  ```\n  let x = a_rather_long_expression + another_long_one;\n  ```"
+        );
+    }
+
+    #[test]
+    fn test_wrap_wide_characters() {
+        assert_eq!(display_width("日本語abc"), 9);
+        assert_eq!(wrap("デプロイが完了しましたが、エラー率が上がっています", 0, 20),
+            "デプロイが完了しまし\nたが、エラー率が上が\nっています");
+        assert_eq!(pieces("ok 了解です"), ["ok", " ", "了", "解", "で", "す"]);
+    }
+}